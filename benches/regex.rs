@@ -0,0 +1,116 @@
+//! コンパイル時間と探索のスループットを測る
+//!
+//! 実行: cargo bench --bench regex
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use ryota2357_regex::{Cache, Construction, Nfa, NfaHook, Regex, RegexBuilder};
+
+const EN: &str = include_str!("data/en.txt");
+const JA: &str = include_str!("data/ja.txt");
+
+/// 探索の対象. コーパスを繰り返して十分な長さにする
+fn corpus(text: &str) -> String {
+    text.repeat(64)
+}
+
+/// NFAを書き換えないフック
+///
+/// NFAのフックがあると, 文字列そのものやビット並列で照合できるパターンでもNFAから作ったDFAで照合する.
+/// 構成法の違いを比べるために, 常にこの経路を通らせる.
+#[derive(Debug)]
+struct Identity;
+
+impl NfaHook for Identity {
+    fn rewrite(&self, nfa: Nfa) -> Nfa {
+        nfa
+    }
+}
+
+/// 既定の照合と, 各構成法でNFAから作ったDFAによる照合
+fn builders(pattern: &str) -> [(&'static str, RegexBuilder); 3] {
+    [
+        ("default", RegexBuilder::new(pattern)),
+        (
+            "thompson",
+            RegexBuilder::new(pattern)
+                .construction(Construction::Thompson)
+                .nfa_hook(Identity),
+        ),
+        (
+            "glushkov",
+            RegexBuilder::new(pattern)
+                .construction(Construction::Glushkov)
+                .nfa_hook(Identity),
+        ),
+    ]
+}
+
+/// (名前, パターン, コーパス)
+fn cases() -> Vec<(&'static str, String, &'static str)> {
+    vec![
+        ("literal", "automaton".to_string(), EN),
+        (
+            "alternation",
+            "Thompson|Glushkov|subset|lexer|parser".to_string(),
+            EN,
+        ),
+        ("star_heavy", "(a|b)*a(a|b)(a|b)(a|b)".to_string(), EN),
+        ("many_positions", format!("({})*b", "a|".repeat(65)), EN),
+        ("unicode", "(山田|佐藤)(太|一|次)郎".to_string(), JA),
+        ("unicode_star", "ｗｗ*（笑）".to_string(), JA),
+    ]
+}
+
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    for (name, pattern, _) in cases() {
+        for (variant, builder) in builders(&pattern) {
+            group.bench_with_input(BenchmarkId::new(name, variant), &builder, |b, builder| {
+                b.iter(|| builder.build().unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn find_iter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_iter");
+    for (name, pattern, text) in cases() {
+        let haystack: String = corpus(text);
+        group.throughput(Throughput::Bytes(haystack.len() as u64));
+        for (variant, builder) in builders(&pattern) {
+            let regex: Regex = builder.build().unwrap();
+            group.bench_with_input(BenchmarkId::new(name, variant), &haystack, |b, haystack| {
+                b.iter(|| regex.find_iter(haystack).count())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn is_match(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_match");
+    for (name, pattern, text) in cases() {
+        // 全体を走査させるため, マッチする行を除いた文字列を探す
+        let regex: Regex = Regex::new(&pattern).unwrap();
+        let haystack: String = corpus(text)
+            .lines()
+            .filter(|line| !regex.is_match(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        group.throughput(Throughput::Bytes(haystack.len() as u64));
+        group.bench_with_input(BenchmarkId::new(name, "new"), &haystack, |b, haystack| {
+            b.iter(|| regex.is_match(black_box(haystack)))
+        });
+        let mut cache: Cache = Cache::new();
+        group.bench_with_input(BenchmarkId::new(name, "cache"), &haystack, |b, haystack| {
+            b.iter(|| regex.is_match_with(&mut cache, black_box(haystack)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compile, find_iter, is_match);
+criterion_main!(benches);
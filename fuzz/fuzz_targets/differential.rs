@@ -0,0 +1,52 @@
+//! ランダムなパターンと文字列について, 照合結果を `regex` crate と比べる
+//!
+//! このライブラリは最左最長, `regex` crate は最左優先でマッチを選ぶため,
+//! `find` は開始位置と, 終了位置が最長であることを別々に確かめる.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use ryota2357_regex::Regex;
+use ryota2357_regex_fuzz::{Ast, Char, haystack};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    ast: Ast,
+    haystack: Vec<Char>,
+}
+
+fuzz_target!(|input: Input| {
+    let pattern: String = input.ast.to_pattern();
+    let text: String = haystack(&input.haystack);
+    let ours = Regex::new(&pattern).unwrap();
+    let Ok(theirs) = regex::Regex::new(&input.ast.to_regex_syntax()) else {
+        // 入れ子が深すぎるなど, `regex` crate の制限に引っかかった
+        return;
+    };
+    let full = regex::Regex::new(&format!("^(?:{})$", input.ast.to_regex_syntax())).unwrap();
+
+    assert_eq!(
+        ours.is_match(&text),
+        theirs.is_match(&text),
+        "{:?}",
+        pattern
+    );
+    assert_eq!(ours.matches(&text), full.is_match(&text), "{:?}", pattern);
+
+    let found = ours.find(&text);
+    assert_eq!(
+        found.map(|m| m.start()),
+        theirs.find(&text).map(|m| m.start()),
+        "{:?}",
+        pattern
+    );
+    if let Some(found) = found {
+        assert!(full.is_match(found.as_str()), "{:?}", pattern);
+        let longer = text[found.end()..]
+            .char_indices()
+            .map(|(i, c)| found.end() + i + c.len_utf8())
+            .find(|&end| full.is_match(&text[found.start()..end]));
+        assert_eq!(longer, None, "{:?}", pattern);
+    }
+});
@@ -0,0 +1,35 @@
+//! 構文木を書き出したパターンが構文解析でき, [`Regex::to_pattern`] で書き戻しても同じ言語を表すことを確かめる
+//!
+//! 任意のバイト列もパターンとして与え, 構文解析が panic しないことも確かめる.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use ryota2357_regex::Regex;
+use ryota2357_regex_fuzz::Ast;
+
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    ast: Ast,
+    raw: &'a str,
+}
+
+fuzz_target!(|input: Input<'_>| {
+    let _ = Regex::new(input.raw);
+
+    let pattern: String = input.ast.to_pattern();
+    let regex = Regex::new(&pattern).unwrap();
+    assert_eq!(regex.as_str(), pattern);
+    if let Some(rendered) = regex.to_pattern() {
+        let reparsed = Regex::new(&rendered).unwrap();
+        assert!(
+            regex.is_equivalent(&reparsed),
+            "{:?} -> {:?}",
+            pattern,
+            rendered
+        );
+    } else {
+        assert!(regex.matches_nothing(), "{:?}", pattern);
+    }
+});
@@ -0,0 +1,71 @@
+//! ファジング用の構文木の生成と, パターン文字列への書き出し
+
+use arbitrary::Arbitrary;
+
+/// パターンに使う文字. 照合結果に差が出やすいよう, 少数の文字とメタ文字に限る
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub enum Char {
+    A,
+    B,
+    Kanji,
+    Star,
+    Bar,
+    Paren,
+    Backslash,
+}
+
+impl Char {
+    pub fn to_char(self) -> char {
+        match self {
+            Char::A => 'a',
+            Char::B => 'b',
+            Char::Kanji => '山',
+            Char::Star => '*',
+            Char::Bar => '|',
+            Char::Paren => '(',
+            Char::Backslash => '\\',
+        }
+    }
+}
+
+/// 対応する範囲の構文木
+#[derive(Arbitrary, Clone, Debug)]
+pub enum Ast {
+    Char(Char),
+    Empty,
+    Star(Box<Ast>),
+    Union(Box<Ast>, Box<Ast>),
+    Concat(Box<Ast>, Box<Ast>),
+}
+
+impl Ast {
+    /// このライブラリの構文で書き出す
+    pub fn to_pattern(&self) -> String {
+        match self {
+            Ast::Char(c) => match c.to_char() {
+                chara @ ('*' | '|' | '(' | ')' | '\\') => format!("\\{}", chara),
+                chara => chara.to_string(),
+            },
+            Ast::Empty => "()".to_string(),
+            Ast::Star(ast) => format!("({})*", ast.to_pattern()),
+            Ast::Union(a1, a2) => format!("({}|{})", a1.to_pattern(), a2.to_pattern()),
+            Ast::Concat(a1, a2) => format!("{}{}", a1.to_pattern(), a2.to_pattern()),
+        }
+    }
+
+    /// `regex` crate の構文で書き出す
+    pub fn to_regex_syntax(&self) -> String {
+        match self {
+            Ast::Char(c) => regex::escape(&c.to_char().to_string()),
+            Ast::Empty => "(?:)".to_string(),
+            Ast::Star(ast) => format!("(?:{})*", ast.to_regex_syntax()),
+            Ast::Union(a1, a2) => format!("(?:{}|{})", a1.to_regex_syntax(), a2.to_regex_syntax()),
+            Ast::Concat(a1, a2) => format!("{}{}", a1.to_regex_syntax(), a2.to_regex_syntax()),
+        }
+    }
+}
+
+/// 照合する文字列. パターンと同じ文字のみからなる
+pub fn haystack(chars: &[Char]) -> String {
+    chars.iter().map(|c| c.to_char()).collect()
+}
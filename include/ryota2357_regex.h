@@ -0,0 +1,31 @@
+#ifndef RYOTA2357_REGEX_H
+#define RYOTA2357_REGEX_H
+
+/* `ffi` 機能を有効にしてビルドした ryota2357-regex の C インターフェース */
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Regex Regex;
+
+/* NUL終端のパターン文字列をコンパイルする. 不正なパターンやUTF-8でない場合はNULL */
+Regex *regex_new(const char *pattern);
+
+/* text から始まる len バイトの文字列の部分文字列にマッチするか. UTF-8でない場合は false */
+bool regex_is_match(const Regex *regex, const unsigned char *text, size_t len);
+
+/* 最も左にあるマッチを探し, 見つかれば *start, *end にそのバイトオフセットを書いて true を返す */
+bool regex_find(const Regex *regex, const unsigned char *text, size_t len, size_t *start, size_t *end);
+
+/* regex_new が返した Regex を解放する. NULLの場合は何もしない */
+void regex_free(Regex *regex);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
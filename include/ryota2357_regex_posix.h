@@ -0,0 +1,65 @@
+#ifndef RYOTA2357_REGEX_POSIX_H
+#define RYOTA2357_REGEX_POSIX_H
+
+/* `posix` 機能を有効にしてビルドした ryota2357-regex の, <regex.h> と同じ形のインターフェース.
+ * <regex.h> の代わりにインクルードする. パターンの構文はこの crate のもの (|, *, (), \) で, 最左最長でマッチを選ぶ */
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* regcomp の cflags */
+#define REG_EXTENDED 1
+#define REG_ICASE 2
+#define REG_NEWLINE 4
+#define REG_NOSUB 8
+
+/* regexec の eflags (このライブラリの構文には ^, $ がないため何も変わらない) */
+#define REG_NOTBOL 1
+#define REG_NOTEOL 2
+
+/* エラーコード */
+#define REG_NOMATCH 1
+#define REG_BADPAT 2
+#define REG_EESCAPE 5
+#define REG_EPAREN 8
+#define REG_BADRPT 13
+
+typedef struct {
+    /* キャプチャグループの数 (マッチ全体は含まない) */
+    size_t re_nsub;
+    void *re_regex;
+    int re_cflags;
+} regex_t;
+
+typedef ptrdiff_t regoff_t;
+
+typedef struct {
+    regoff_t rm_so;
+    regoff_t rm_eo;
+} regmatch_t;
+
+/* パターンをコンパイルして *preg に書き込む. 成功すれば 0, 失敗すればエラーコード */
+int ryota2357_regcomp(regex_t *preg, const char *pattern, int cflags);
+
+/* 最も左にあるマッチを探す. 見つかれば 0 で, REG_NOSUB でなければ pmatch の先頭 nmatch 個に位置を書き込む (関与しなかったグループは -1) */
+int ryota2357_regexec(const regex_t *preg, const char *string, size_t nmatch, regmatch_t *pmatch, int eflags);
+
+/* エラーコードの説明を errbuf に書き込み, 全体に必要なバイト数を返す */
+size_t ryota2357_regerror(int errcode, const regex_t *preg, char *errbuf, size_t errbuf_size);
+
+/* ryota2357_regcomp が確保したものを解放する */
+void ryota2357_regfree(regex_t *preg);
+
+#define regcomp ryota2357_regcomp
+#define regexec ryota2357_regexec
+#define regerror ryota2357_regerror
+#define regfree ryota2357_regfree
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,170 @@
+//! `ryota2357-regex` の `derive` 機能で使う `#[derive(FromRegex)]`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Fields, GenericArgument, LitInt, LitStr, PathArguments, Type};
+
+/// キャプチャグループを構造体のフィールドに対応させ, `FromRegex` を実装する
+///
+/// 構造体に `#[regex(pattern = "...")]` でパターンを指定する. 名前のあるフィールドは同じ名前のグループ (`(?<name>...)`) に,
+/// タプル構造体のフィールドは前から順にグループ 1, 2, ... に対応し, `#[regex(group = N)]` で対応するグループを指定できる (0 はマッチ全体).
+/// 対応するグループがパターンに無ければ, 最初に `FromRegex::regex` でパターンをコンパイルしたときに panic する.
+/// 各フィールドの型は `FromStr` を実装するか, そのような型の `Option` (グループが照合に関与しなかった場合は `None`) であること.
+#[proc_macro_derive(FromRegex, attributes(regex))]
+pub fn derive_from_regex(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse_macro_input!(input);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut pattern: Option<LitStr> = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("regex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("pattern") {
+                pattern = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `pattern = \"...\"`"))
+            }
+        })?;
+    }
+    let pattern: LitStr = pattern.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing #[regex(pattern = \"...\")]")
+    })?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "FromRegex can only be derived for structs",
+        ));
+    };
+    let ident = &input.ident;
+    let type_name: String = ident.to_string();
+    // 各フィールドに対応するグループの番号を, パターンをコンパイルしたときに求める式
+    let mut groups: Vec<TokenStream2> = Vec::new();
+    let mut values: Vec<TokenStream2> = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let mut group: Option<usize> = None;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("regex")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("group") {
+                    group = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `group = N`"))
+                }
+            })?;
+        }
+        let name: String = match &field.ident {
+            Some(ident) => ident.unraw().to_string(),
+            None => i.to_string(),
+        };
+        groups.push(match (group, &field.ident) {
+            (None, Some(_)) => quote! {
+                regex.capture_index(#name).unwrap_or_else(|| {
+                    panic!(
+                        "#[derive(FromRegex)] on `{}`: pattern {:?} has no group named `{}` for field `{}`",
+                        #type_name, #pattern, #name, #name
+                    )
+                })
+            },
+            (group, _) => {
+                let group: usize = group.unwrap_or(i + 1);
+                quote! {{
+                    assert!(
+                        #group < regex.captures_len(),
+                        "#[derive(FromRegex)] on `{}`: pattern {:?} has no group {} for field `{}` (it has {} groups)",
+                        #type_name, #pattern, #group, #name, regex.captures_len() - 1
+                    );
+                    #group
+                }}
+            }
+        });
+        let group: TokenStream2 = quote! { groups[#i] };
+        let value: TokenStream2 = if let Some(inner) = option_inner(&field.ty) {
+            quote! {
+                match caps.get(#group) {
+                    Some(m) => Some(
+                        m.as_str()
+                            .parse::<#inner>()
+                            .map_err(|err| ::ryota2357_regex::FromRegexError::parse(#name, err))?,
+                    ),
+                    None => None,
+                }
+            }
+        } else {
+            let ty: &Type = &field.ty;
+            quote! {
+                caps.get(#group)
+                    .ok_or(::ryota2357_regex::FromRegexError::MissingGroup(#group))?
+                    .as_str()
+                    .parse::<#ty>()
+                    .map_err(|err| ::ryota2357_regex::FromRegexError::parse(#name, err))?
+            }
+        };
+        values.push(value);
+    }
+    let construct: TokenStream2 = match &data.fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|f| &f.ident);
+            quote! { Self { #(#names: #values),* } }
+        }
+        Fields::Unnamed(_) => quote! { Self(#(#values),*) },
+        Fields::Unit => quote! { Self },
+    };
+
+    let len: usize = groups.len();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        const _: () = {
+            /// コンパイルしたパターンと, 各フィールドに対応するグループの番号
+            static STATE: ::std::sync::OnceLock<(::ryota2357_regex::Regex, [usize; #len])> =
+                ::std::sync::OnceLock::new();
+
+            fn state() -> &'static (::ryota2357_regex::Regex, [usize; #len]) {
+                STATE.get_or_init(|| {
+                    let regex: ::ryota2357_regex::Regex = ::ryota2357_regex::Regex::new(#pattern)
+                        .expect("invalid pattern in #[regex(pattern = ...)]");
+                    let groups: [usize; #len] = [#(#groups),*];
+                    (regex, groups)
+                })
+            }
+
+            impl #impl_generics ::ryota2357_regex::FromRegex for #ident #ty_generics #where_clause {
+                fn regex() -> &'static ::ryota2357_regex::Regex {
+                    &state().0
+                }
+
+                fn from_captures(
+                    caps: &::ryota2357_regex::Captures<'_>,
+                ) -> ::std::result::Result<Self, ::ryota2357_regex::FromRegexError> {
+                    let groups: &[usize; #len] = &state().1;
+                    Ok(#construct)
+                }
+            }
+        };
+    })
+}
+
+/// `Option<T>` の `T`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
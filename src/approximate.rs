@@ -0,0 +1,226 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+use crate::Regex;
+use crate::automaton::{DFA, DFAState};
+use crate::hash::HashMap;
+
+/// [`Regex::find_approximate`] が返す, 誤りを含むマッチ
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApproximateMatch<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+    distance: usize,
+}
+
+impl<'h> ApproximateMatch<'h> {
+    /// 開始位置 (バイトオフセット)
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 終了位置 (バイトオフセット)
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// 開始位置から終了位置までの範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.start..self.end]
+    }
+
+    /// マッチした部分と, パターンにマッチする文字列とのレーベンシュタイン距離の最小値
+    pub fn distance(&self) -> usize {
+        self.distance
+    }
+}
+
+/// 各状態に至る (誤りの数, マッチの開始位置) の最小値
+type Costs = HashMap<DFAState, (usize, usize)>;
+
+/// 誤りの数を重みとして, DFAの状態の上で最短経路を求める
+struct Scorer<'a> {
+    dfa: &'a DFA,
+    /// 受理状態へ到達できる状態
+    live: HashMap<DFAState, usize>,
+    max_distance: usize,
+}
+
+impl Scorer<'_> {
+    /// `cost` の方が小さければ更新する
+    fn relax(&self, costs: &mut Costs, state: DFAState, cost: (usize, usize)) -> bool {
+        if cost.0 > self.max_distance || !self.live.contains_key(&state) {
+            return false;
+        }
+        match costs.get(&state) {
+            Some(current) if *current <= cost => false,
+            _ => {
+                costs.insert(state, cost);
+                true
+            }
+        }
+    }
+
+    /// 削除 (文字を読まずに遷移する) を繰り返して到達できる状態を加える
+    fn deletions(&self, costs: &mut Costs) {
+        let mut heap: BinaryHeap<Reverse<((usize, usize), DFAState)>> =
+            costs.iter().map(|(q, cost)| Reverse((*cost, *q))).collect();
+        while let Some(Reverse((cost, state))) = heap.pop() {
+            if costs[&state] != cost {
+                continue;
+            }
+            let next: (usize, usize) = (cost.0 + 1, cost.1);
+            for successor in self.dfa.successors(state) {
+                if self.relax(costs, successor, next) {
+                    heap.push(Reverse((next, successor)));
+                }
+            }
+        }
+    }
+
+    /// `chara` を1文字読んだ後のコスト
+    fn read(&self, costs: &Costs, chara: char) -> Costs {
+        let mut next: Costs = Costs::default();
+        for (state, (errors, start)) in costs {
+            if let Some(to) = self.dfa.next_state(*state, chara) {
+                self.relax(&mut next, to, (*errors, *start));
+            }
+            // 挿入: 読んだ文字を読み飛ばす
+            self.relax(&mut next, *state, (errors + 1, *start));
+            // 置換: 読んだ文字を別の文字として遷移する
+            for successor in self.dfa.successors(*state) {
+                self.relax(&mut next, successor, (errors + 1, *start));
+            }
+        }
+        self.deletions(&mut next);
+        next
+    }
+
+    fn best_accept(&self, costs: &Costs) -> Option<(usize, usize)> {
+        costs
+            .iter()
+            .filter(|(state, _)| self.dfa.is_accept(**state))
+            .map(|(_, cost)| *cost)
+            .min()
+    }
+
+    /// 誤りの数が最小のマッチの (誤りの数, 開始位置, 終了位置).
+    /// 同じ数なら最も左から始まるもの, さらに同じなら最長のもの
+    fn scan(&self, text: &str, anchored: bool) -> Option<(usize, usize, usize)> {
+        let mut costs: Costs = Costs::default();
+        let mut best: Option<(usize, usize, usize)> = None;
+        let positions = text.char_indices().map(Some).chain([None]);
+        for next in positions {
+            let at: usize = next.map_or(text.len(), |(i, _)| i);
+            if at == 0 || !anchored {
+                self.relax(&mut costs, self.dfa.start, (0, at));
+                self.deletions(&mut costs);
+            }
+            if (next.is_none() || !anchored)
+                && let Some((errors, start)) = self.best_accept(&costs)
+            {
+                // 誤りが少ないもの, 開始位置が左のもの, 終了位置が右のものの順に優先する
+                let key = |(e, s, end): (usize, usize, usize)| (e, s, Reverse(end));
+                if best.is_none_or(|best| key((errors, start, at)) < key(best)) {
+                    best = Some((errors, start, at));
+                }
+            }
+            let Some((_, chara)) = next else {
+                break;
+            };
+            costs = self.read(&costs, chara);
+            if costs.is_empty() && anchored {
+                break;
+            }
+        }
+        best
+    }
+}
+
+impl Regex {
+    /// `text` と, マッチする文字列とのレーベンシュタイン距離の最小値. マッチする文字列が存在しなければ `None`
+    pub fn distance(&self, text: &str) -> Option<usize> {
+        let dfa = self.dfa();
+        let scorer = Scorer {
+            dfa: &dfa,
+            live: dfa.distances_to_accept(),
+            max_distance: usize::MAX,
+        };
+        scorer.scan(text, true).map(|(errors, _, _)| errors)
+    }
+
+    /// `text` の部分文字列のうち, マッチする文字列とのレーベンシュタイン距離が `max_distance` 以下で最小のもの
+    ///
+    /// 距離が同じなら最も左から始まるもの, さらに同じなら最長のものを返す. [`Regex::fuzzy`] と異なり距離も分かるので,
+    /// 複数の結果を距離で順位付けできる.
+    pub fn find_approximate<'h>(
+        &self,
+        text: &'h str,
+        max_distance: usize,
+    ) -> Option<ApproximateMatch<'h>> {
+        let dfa = self.dfa();
+        let scorer = Scorer {
+            dfa: &dfa,
+            live: dfa.distances_to_accept(),
+            max_distance,
+        };
+        let (distance, start, end) = scorer.scan(text, false)?;
+        Some(ApproximateMatch {
+            haystack: text,
+            start,
+            end,
+            distance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        assert_eq!(regex.distance("山田太郎"), Some(0));
+        assert_eq!(regex.distance("山田大郎"), Some(1));
+        assert_eq!(regex.distance("田太朗"), Some(2));
+        assert_eq!(regex.distance(""), Some(4));
+        assert_eq!(regex.distance("山田太郎様です"), Some(3));
+
+        let regex = Regex::new(r"a(bc)*").unwrap();
+        assert_eq!(regex.distance("abcbcbc"), Some(0));
+        assert_eq!(regex.distance("bcbcx"), Some(2));
+        assert_eq!(
+            Regex::new(r"a")
+                .unwrap()
+                .minus(&Regex::new(r"a").unwrap())
+                .distance("a"),
+            None
+        );
+    }
+
+    #[test]
+    fn find_approximate() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let text: &str = "OCR: 山円太朗 / 山田大郎 様";
+        let found = regex.find_approximate(text, 2).unwrap();
+        assert_eq!((found.as_str(), found.distance()), ("山田大郎", 1));
+        assert_eq!(&text[found.range()], "山田大郎");
+        assert!(regex.find_approximate(text, 0).is_none());
+
+        let found = regex.find_approximate("山田太郎と山田一郎", 1).unwrap();
+        assert_eq!((found.start(), found.end(), found.distance()), (0, 12, 0));
+        // 誤りの数が同じなら最も左のもの
+        let found = Regex::new(r"abc")
+            .unwrap()
+            .find_approximate("xbc abx", 1)
+            .unwrap();
+        assert_eq!((found.as_str(), found.distance()), ("xbc", 1));
+    }
+}
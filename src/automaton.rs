@@ -1,5 +1,23 @@
+mod bitparallel;
+mod counting;
 mod dfa;
+mod eliminate;
+mod fuzzy;
+mod glushkov;
+mod layout;
 mod nfa;
+mod ops;
+mod pikevm;
+#[cfg(feature = "rand")]
+mod sample;
+mod serialize;
+mod two_pass;
 
+pub(crate) use crate::automaton::bitparallel::*;
 pub(crate) use crate::automaton::dfa::*;
+pub(crate) use crate::automaton::glushkov::*;
+pub(crate) use crate::automaton::layout::*;
 pub(crate) use crate::automaton::nfa::*;
+pub(crate) use crate::automaton::pikevm::*;
+pub(crate) use crate::automaton::serialize::FORMAT_VERSION;
+pub(crate) use crate::automaton::two_pass::*;
@@ -0,0 +1,414 @@
+use std::collections::BTreeSet;
+
+use crate::automaton::{NFA, NFAState, Positions};
+use crate::hash::{HashMap, HashSet};
+use crate::parser::Node;
+
+/// 状態集合を1ワードで表すNFA (Shift-And法の一般化)
+///
+/// 構文木中の各文字を1つの位置とし (Glushkov構成), 位置 `i` に居ることを `i` ビット目で表す.
+/// 位置の数が64以下のパターンにのみ使える.
+#[derive(Clone, Debug)]
+pub(crate) struct BitNFA {
+    /// 空文字列を受理するか
+    nullable: bool,
+    /// 最初に読む文字の位置の集合
+    first: u64,
+    /// 最後に読む文字の位置の集合
+    last: u64,
+    /// follow[i]: 位置 `i` の次に読む文字の位置の集合
+    follow: Vec<u64>,
+    /// ASCII文字ごとの, その文字が置かれた位置の集合
+    ascii: Box<[u64; 128]>,
+    /// 非ASCII文字ごとの, その文字が置かれた位置の集合 (文字でソート済み)
+    others: Vec<(char, u64)>,
+}
+
+impl BitNFA {
+    pub(crate) const MAX_POSITIONS: usize = u64::BITS as usize;
+
+    /// 位置の数が [`BitNFA::MAX_POSITIONS`] を超える場合は `None`
+    pub(crate) fn from_node(node: &Node) -> Option<Self> {
+        let positions: Positions = Positions::from_node(node);
+        if positions.chars.len() > Self::MAX_POSITIONS {
+            return None;
+        }
+        let to_mask = |set: &BTreeSet<usize>| set.iter().fold(0u64, |mask, p| mask | 1 << p);
+
+        let mut ascii = Box::new([0u64; 128]);
+        let mut others: Vec<(char, u64)> = Vec::new();
+        for (i, chara) in positions.chars.iter().enumerate() {
+            if chara.is_ascii() {
+                ascii[*chara as usize] |= 1 << i;
+            } else {
+                match others.binary_search_by_key(chara, |(c, _)| *c) {
+                    Ok(j) => others[j].1 |= 1 << i,
+                    Err(j) => others.insert(j, (*chara, 1 << i)),
+                }
+            }
+        }
+
+        Some(BitNFA {
+            nullable: positions.nullable,
+            first: to_mask(&positions.first),
+            last: to_mask(&positions.last),
+            follow: positions.follow.iter().map(to_mask).collect(),
+            ascii,
+            others,
+        })
+    }
+
+    /// 最初に読むことのできる文字の集合
+    pub(crate) fn start_chars(&self) -> HashSet<char> {
+        let ascii = (0..128u8)
+            .filter(|b| self.ascii[*b as usize] & self.first != 0)
+            .map(char::from);
+        let others = self
+            .others
+            .iter()
+            .filter(|(_, mask)| mask & self.first != 0)
+            .map(|(c, _)| *c);
+        ascii.chain(others).collect()
+    }
+
+    /// 同じ言語を受理する (空遷移を持たない) NFA
+    ///
+    /// 状態 0 が開始状態, 状態 `i + 1` が位置 `i` に対応する.
+    pub(crate) fn to_nfa(&self) -> NFA {
+        let state = |position: u32| NFAState(position + 1);
+        let start: NFAState = NFAState(0);
+        let mut accepts: HashSet<NFAState> = bits(self.last).map(state).collect();
+        if self.nullable {
+            accepts.insert(start);
+        }
+        let mut nfa: NFA = NFA::new(start, accepts);
+        let chars = (0..128u8)
+            .map(|b| (char::from(b), self.ascii[b as usize]))
+            .chain(self.others.iter().cloned());
+        for (chara, mask) in chars {
+            for to in bits(self.first & mask) {
+                nfa = nfa.add_transition(start, chara, state(to));
+            }
+            for (from, follow) in self.follow.iter().enumerate() {
+                for to in bits(follow & mask) {
+                    nfa = nfa.add_transition(state(from as u32), chara, state(to));
+                }
+            }
+        }
+        nfa
+    }
+
+    pub(crate) fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    /// `text` 全体を受理するか
+    pub(crate) fn matches(&self, text: &str) -> bool {
+        let mut candidates: u64 = self.first;
+        let mut states: u64 = 0;
+        for chara in text.chars() {
+            states = candidates & self.mask(chara);
+            if states == 0 {
+                return false;
+            }
+            candidates = self.follow_of(states);
+        }
+        if text.is_empty() {
+            self.nullable
+        } else {
+            states & self.last != 0
+        }
+    }
+
+    /// `text` のいずれかの部分文字列を受理するか
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        if self.nullable {
+            return true;
+        }
+        let mut states: u64 = 0;
+        for chara in text.chars() {
+            // 各位置からマッチを開始するため, 毎回 first を候補に加える
+            states = (self.first | self.follow_of(states)) & self.mask(chara);
+            if states & self.last != 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// `text[start..]` の接頭辞のうち, 受理される最長のものの終了位置
+    pub(crate) fn longest_match_at(&self, text: &str, start: usize) -> Option<usize> {
+        let chars = text[start..]
+            .char_indices()
+            .map(|(i, chara)| (start + i + chara.len_utf8(), chara));
+        self.longest_match_in(start, chars)
+    }
+
+    /// `start` から続く文字 (その文字の終了位置と組にしたもの) を読み, 受理される最長の接頭辞の終了位置を返す
+    pub(crate) fn longest_match_in(
+        &self,
+        start: usize,
+        chars: impl Iterator<Item = (usize, char)>,
+    ) -> Option<usize> {
+        let mut end: Option<usize> = self.nullable.then_some(start);
+        let mut candidates: u64 = self.first;
+        for (char_end, chara) in chars {
+            let states: u64 = candidates & self.mask(chara);
+            if states == 0 {
+                break;
+            }
+            if states & self.last != 0 {
+                end = Some(char_end);
+            }
+            candidates = self.follow_of(states);
+        }
+        end
+    }
+
+    /// [`BitNFA::longest_match_at`] と同じ結果を, 状態集合の遷移を `lazy` に記録しながら求める
+    pub(crate) fn longest_match_at_lazy(
+        &self,
+        lazy: &mut LazyDFA,
+        text: &str,
+        start: usize,
+    ) -> Option<usize> {
+        let mut end: Option<usize> = self.nullable.then_some(start);
+        let mut id: u32 = LazyDFA::START;
+        for (i, chara) in text[start..].char_indices() {
+            id = lazy.next(self, id, chara);
+            if id == LazyDFA::DEAD {
+                break;
+            }
+            if lazy.states[id as usize] & self.last != 0 {
+                end = Some(start + i + chara.len_utf8());
+            }
+        }
+        end
+    }
+
+    fn mask(&self, chara: char) -> u64 {
+        if chara.is_ascii() {
+            self.ascii[chara as usize]
+        } else {
+            self.others
+                .binary_search_by_key(&chara, |(c, _)| *c)
+                .map(|i| self.others[i].1)
+                .unwrap_or(0)
+        }
+    }
+
+    fn follow_of(&self, mut states: u64) -> u64 {
+        let mut ret: u64 = 0;
+        while states != 0 {
+            ret |= self.follow[states.trailing_zeros() as usize];
+            states &= states - 1;
+        }
+        ret
+    }
+}
+
+/// [`BitNFA`] の状態集合を必要になった時点でDFAの状態として登録し, ASCII文字による遷移を記録する
+#[derive(Clone, Debug)]
+pub(crate) struct LazyDFA {
+    /// states[id]: 状態 `id` で居る位置の集合
+    states: Vec<u64>,
+    /// candidates[id]: 状態 `id` の次に読むことのできる位置の集合
+    candidates: Vec<u64>,
+    ids: HashMap<u64, u32>,
+    /// ascii[id][b]: 状態 `id` から文字 `b` で移る状態. 未計算なら [`LazyDFA::UNKNOWN`]
+    ascii: Vec<[u32; 128]>,
+}
+
+impl LazyDFA {
+    const START: u32 = 0;
+    const DEAD: u32 = u32::MAX;
+    const UNKNOWN: u32 = u32::MAX - 1;
+    /// 登録する状態数の上限. 超えたら記録を捨てて作り直す
+    const MAX_STATES: usize = 4096;
+
+    pub(crate) fn new() -> Self {
+        LazyDFA {
+            states: Vec::new(),
+            candidates: Vec::new(),
+            ids: HashMap::default(),
+            ascii: Vec::new(),
+        }
+    }
+
+    /// 登録済みの状態の数
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.states.clear();
+        self.candidates.clear();
+        self.ids.clear();
+        self.ascii.clear();
+    }
+
+    fn next(&mut self, nfa: &BitNFA, id: u32, chara: char) -> u32 {
+        if self.states.is_empty() {
+            // 開始状態: どの位置にも居らず, first の位置を読むことができる
+            self.register(0, nfa.first);
+        }
+        if chara.is_ascii() {
+            let next: u32 = self.ascii[id as usize][chara as usize];
+            if next != Self::UNKNOWN {
+                return next;
+            }
+        }
+        let states: u64 = self.candidates[id as usize] & nfa.mask(chara);
+        let next: u32 = if states == 0 {
+            Self::DEAD
+        } else {
+            match self.ids.get(&states) {
+                Some(next) => *next,
+                None => {
+                    if self.states.len() >= Self::MAX_STATES {
+                        // 記録を捨てると `id` は無効になるので, この遷移は記録しない
+                        self.clear();
+                        self.register(0, nfa.first);
+                        return self.register(states, nfa.follow_of(states));
+                    }
+                    self.register(states, nfa.follow_of(states))
+                }
+            }
+        };
+        if chara.is_ascii() {
+            self.ascii[id as usize][chara as usize] = next;
+        }
+        next
+    }
+
+    fn register(&mut self, states: u64, candidates: u64) -> u32 {
+        let id: u32 = self.states.len() as u32;
+        self.states.push(states);
+        self.candidates.push(candidates);
+        self.ascii.push([Self::UNKNOWN; 128]);
+        if states != 0 {
+            self.ids.insert(states, id);
+        }
+        id
+    }
+}
+
+/// 立っているビットの番号を列挙する
+fn bits(mut mask: u64) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        (mask != 0).then(|| {
+            let bit: u32 = mask.trailing_zeros();
+            mask &= mask - 1;
+            bit
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn bitnfa(pattern: &str) -> Option<BitNFA> {
+        let node: Node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        BitNFA::from_node(&node)
+    }
+
+    #[test]
+    fn from_node() {
+        // 位置: a=0, b=1, c=2
+        let nfa = bitnfa(r"a(b|)c*").unwrap();
+        assert!(!nfa.nullable);
+        assert_eq!(nfa.first, 0b001);
+        assert_eq!(nfa.last, 0b111);
+        assert_eq!(nfa.follow, vec![0b110, 0b100, 0b100]);
+    }
+
+    #[test]
+    fn matches() {
+        let nfa = bitnfa(r"(p(erl|ython|hp)|ruby)").unwrap();
+        assert!(nfa.matches("python"));
+        assert!(nfa.matches("ruby"));
+        assert!(!nfa.matches("pytho"));
+        assert!(!nfa.matches(""));
+
+        let nfa = bitnfa(r"ｗｗ*|\(笑\)").unwrap();
+        assert!(nfa.matches("ｗｗｗ"));
+        assert!(nfa.matches("(笑)"));
+        assert!(!nfa.matches("笑"));
+
+        let nfa = bitnfa(r"(a|)*").unwrap();
+        assert!(nfa.matches(""));
+        assert!(nfa.matches("aaa"));
+    }
+
+    #[test]
+    fn is_match() {
+        let nfa = bitnfa(r"山田(太|一)郎").unwrap();
+        assert!(nfa.is_match("私は山田山田太郎です"));
+        assert!(!nfa.is_match("私は山田次郎です"));
+    }
+
+    #[test]
+    fn longest_match_at() {
+        let nfa = bitnfa(r"ab*").unwrap();
+        assert_eq!(nfa.longest_match_at("xabbbc", 1), Some(5));
+        assert_eq!(nfa.longest_match_at("xabbbc", 0), None);
+    }
+
+    #[test]
+    fn longest_match_at_lazy() {
+        let nfa = bitnfa(r"(a|ab)*c|山b*").unwrap();
+        let mut lazy = LazyDFA::new();
+        for (text, start) in [
+            ("xababac", 1),
+            ("ababab", 0),
+            ("山bbb", 0),
+            ("c", 0),
+            ("x", 0),
+        ] {
+            assert_eq!(
+                nfa.longest_match_at_lazy(&mut lazy, text, start),
+                nfa.longest_match_at(text, start)
+            );
+        }
+        let len = lazy.len();
+        nfa.longest_match_at_lazy(&mut lazy, "ababac", 0);
+        assert_eq!(lazy.len(), len);
+    }
+
+    #[test]
+    fn start_chars() {
+        let nfa = bitnfa(r"a*(b|郎)c").unwrap();
+        assert_eq!(nfa.start_chars(), HashSet::from_iter(['a', 'b', '郎']));
+    }
+
+    #[test]
+    fn to_nfa() {
+        let nfa = bitnfa(r"a*|b").unwrap().to_nfa();
+
+        // -> 0 --a--> 1 --a--> 1
+        //     \--b--> 2
+        // accept: 0, 1, 2
+        assert_eq!(nfa.start, NFAState(0));
+        assert_eq!(
+            nfa.accepts,
+            HashSet::from_iter([NFAState(0), NFAState(1), NFAState(2)])
+        );
+        assert_eq!(
+            nfa.transition,
+            [
+                vec![(Some('a'), NFAState(1)), (Some('b'), NFAState(2))],
+                vec![(Some('a'), NFAState(1))]
+            ]
+        );
+    }
+
+    #[test]
+    fn too_many_positions() {
+        assert!(bitnfa(&"a".repeat(64)).is_some());
+        assert!(bitnfa(&"a".repeat(65)).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+/// 元のDFAの状態と, それより前に読み終えた繰り返しの回数の組
+type Counted = (DFAState, usize);
+
+impl DFA {
+    /// `self` の言語を `min` 回以上 `max` 回以下 (`None` なら上限なし) 繰り返した言語を受理するDFA
+    ///
+    /// 繰り返しを展開した構文木からNFAを作らず, 元のDFAの状態と読み終えた回数の組の集合を状態にする.
+    /// 上限が無い場合, 回数は `min` に達したら数えない.
+    pub(crate) fn repeat(&self, min: usize, max: Option<usize>) -> DFA {
+        if max == Some(0) {
+            return DFA {
+                start: DFAState(0),
+                accepts: HashSet::from_iter([DFAState(0)]),
+                transition: BTreeMap::new(),
+                otherwise: HashMap::default(),
+            };
+        }
+        // 空文字列にマッチするなら, 足りない回数は空文字列で埋められる
+        let min: usize = if self.is_accept(self.start) { 0 } else { min };
+        let saturate: usize = min.saturating_sub(1);
+        // `count` 回読み終えた後に, もう1回読み始められるか. 読み始めた場合の回数を返す
+        let next_count = |count: usize| -> Option<usize> {
+            match max {
+                Some(max) => (count + 1 < max).then_some(count + 1),
+                None => Some((count + 1).min(saturate)),
+            }
+        };
+        let live: HashSet<DFAState> = self.distances_to_accept().into_keys().collect();
+        let step = |set: &BTreeSet<Counted>, chara: char| -> BTreeSet<Counted> {
+            let mut next: BTreeSet<Counted> = BTreeSet::new();
+            for (state, count) in set {
+                if let Some(to) = self.next_state(*state, chara) {
+                    next.insert((to, *count));
+                }
+                // 1回分を読み終えていれば, 次の回の最初の文字として読む
+                if self.is_accept(*state)
+                    && let Some(count) = next_count(*count)
+                    && let Some(to) = self.next_state(self.start, chara)
+                {
+                    next.insert((to, count));
+                }
+            }
+            next.retain(|(state, _)| live.contains(state));
+            next
+        };
+        let accept = |set: &BTreeSet<Counted>| -> bool {
+            set.iter()
+                .any(|(state, count)| self.is_accept(*state) && count + 1 >= min)
+        };
+
+        let alphabet: BTreeSet<char> = self.alphabet();
+        let other: char = self.other_char();
+        // 開始状態は, 回数が 0 の区切りにいる点で, 1回目の途中で開始状態に戻った場合と区別する
+        let initial: BTreeSet<Counted> = [(self.start, 0)].into();
+        let mut ids: HashMap<BTreeSet<Counted>, DFAState> = HashMap::default();
+        let mut queue: VecDeque<(DFAState, BTreeSet<Counted>)> = VecDeque::new();
+        let mut accepts: HashSet<DFAState> = HashSet::default();
+        let mut transition: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
+        let mut otherwise: HashMap<DFAState, DFAState> = HashMap::default();
+        let start: DFAState = DFAState(0);
+        if min == 0 || accept(&initial) {
+            accepts.insert(start);
+        }
+        queue.push_back((start, initial));
+        let mut states: u32 = 1;
+        while let Some((from, set)) = queue.pop_front() {
+            for chara in alphabet.iter().chain([&other]) {
+                let next: BTreeSet<Counted> = step(&set, *chara);
+                if next.is_empty() {
+                    continue;
+                }
+                let to: DFAState = *ids.entry(next).or_insert_with_key(|next| {
+                    let id: DFAState = DFAState(states);
+                    states += 1;
+                    if accept(next) {
+                        accepts.insert(id);
+                    }
+                    queue.push_back((id, next.clone()));
+                    id
+                });
+                if *chara == other {
+                    otherwise.insert(from, to);
+                } else {
+                    transition.insert((from, *chara), to);
+                }
+            }
+        }
+        DFA {
+            start,
+            accepts,
+            transition,
+            otherwise,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::{Node, Parser};
+
+    fn node(pattern: &str) -> Node {
+        Parser::new(Lexer::new(pattern)).parse().unwrap()
+    }
+
+    /// 繰り返しを展開したパターン. `x{2,4}` は `(x)(x)((x)((x)|)|)` になる
+    fn unroll(pattern: &str, min: usize, max: Option<usize>) -> String {
+        let once: String = format!("({})", pattern);
+        let rest: String = match max {
+            None => format!("{}*", once),
+            Some(max) => (min..max).fold(String::new(), |rest, _| format!("({}{}|)", once, rest)),
+        };
+        once.repeat(min) + &rest
+    }
+
+    #[test]
+    fn repeat() {
+        let cases: [(&str, usize, Option<usize>); 9] = [
+            (r"a", 2, Some(4)),
+            (r"ab|c", 0, Some(3)),
+            (r"ab|c", 3, None),
+            (r"(ab)*c", 0, Some(2)),
+            (r"a*b|", 2, Some(3)),
+            (r"a|aa", 3, Some(5)),
+            (r"山(田|)", 1, None),
+            (r"a", 0, Some(0)),
+            (r"a|b", 0, None),
+        ];
+        for (pattern, min, max) in cases {
+            let dfa: DFA = DFA::from_nfa(NFA::from_node(node(pattern)));
+            let unrolled: DFA = DFA::from_nfa(NFA::from_node(node(&unroll(pattern, min, max))));
+            let counted: DFA = dfa.repeat(min, max);
+            assert!(
+                counted.product(&unrolled, |a, b| a != b).is_empty(),
+                "{:?} {} {:?}",
+                pattern,
+                min,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn linear_states() {
+        // 展開すると 10 * 100 個の位置になる
+        let digits: String = ('0'..='9').map(String::from).collect::<Vec<_>>().join("|");
+        let dfa: DFA = DFA::from_nfa(NFA::from_node(node(&digits)));
+        assert_eq!(dfa.repeat(1, Some(100)).minimize().states().len(), 101);
+    }
+}
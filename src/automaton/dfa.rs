@@ -1,9 +1,10 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 
 use crate::automaton::{NFA, NFAState};
+use crate::hash::{HashMap, HashSet};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
-pub struct DFAState(u32);
+pub struct DFAState(pub(crate) u32);
 
 struct Context {
     states: u32,
@@ -14,13 +15,14 @@ impl Context {
     fn new() -> Self {
         Context {
             states: 0,
-            statemap: HashMap::new(),
+            statemap: HashMap::default(),
         }
     }
 
     fn get_state(&mut self, states: &[NFAState]) -> DFAState {
         let mut sorted_states: Vec<NFAState> = states.to_vec();
         sorted_states.sort();
+        sorted_states.dedup();
         match self.statemap.get(&sorted_states) {
             Some(state) => *state,
             None => {
@@ -34,62 +36,126 @@ impl Context {
 }
 
 #[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug)]
 pub(crate) struct DFA {
     pub(crate) start: DFAState,
     pub(crate) accepts: HashSet<DFAState>,
-    transition: HashMap<(DFAState, char), DFAState>,
+    /// 状態ごとの遷移を範囲で引けるように, (遷移元, 文字) の順に並べる
+    pub(crate) transition: BTreeMap<(DFAState, char), DFAState>,
+    /// 遷移テーブルに無い文字による遷移先
+    pub(crate) otherwise: HashMap<DFAState, DFAState>,
 }
 
 impl DFA {
     pub(crate) fn next_state(&self, state: DFAState, chara: char) -> Option<DFAState> {
-        self.transition.get(&(state, chara)).cloned()
+        self.transition
+            .get(&(state, chara))
+            .or_else(|| self.otherwise.get(&state))
+            .cloned()
+    }
+
+    pub(crate) fn is_accept(&self, state: DFAState) -> bool {
+        self.accepts.contains(&state)
+    }
+
+    /// `text[start..]` の接頭辞のうち, 受理される最長のものの終了位置
+    pub(crate) fn longest_match_at(&self, text: &str, start: usize) -> Option<usize> {
+        let chars = text[start..]
+            .char_indices()
+            .map(|(i, chara)| (start + i + chara.len_utf8(), chara));
+        self.longest_match_in(start, chars)
+    }
+
+    /// `start` から続く文字 (その文字の終了位置と組にしたもの) を読み, 受理される最長の接頭辞の終了位置を返す
+    pub(crate) fn longest_match_in(
+        &self,
+        start: usize,
+        chars: impl Iterator<Item = (usize, char)>,
+    ) -> Option<usize> {
+        let mut current_state: DFAState = self.start;
+        let mut end: Option<usize> = self.is_accept(current_state).then_some(start);
+        for (char_end, chara) in chars {
+            match self.next_state(current_state, chara) {
+                Some(state) => current_state = state,
+                None => break,
+            }
+            if self.is_accept(current_state) {
+                end = Some(char_end);
+            }
+        }
+        end
+    }
+
+    /// `text[start..]` の接頭辞のうち, 受理される最短のものの終了位置. 受理状態に達した時点で読むのをやめる
+    pub(crate) fn shortest_match_at(&self, text: &str, start: usize) -> Option<usize> {
+        let mut current_state: DFAState = self.start;
+        if self.is_accept(current_state) {
+            return Some(start);
+        }
+        for (i, chara) in text[start..].char_indices() {
+            current_state = self.next_state(current_state, chara)?;
+            if self.is_accept(current_state) {
+                return Some(start + i + chara.len_utf8());
+            }
+        }
+        None
+    }
+
+    /// 開始状態から遷移可能な文字の集合
+    pub(crate) fn start_chars(&self) -> HashSet<char> {
+        self.transition
+            .keys()
+            .filter(|(from, _)| *from == self.start)
+            .map(|(_, chara)| *chara)
+            .collect()
     }
 
     pub(crate) fn from_nfa(nfa: NFA) -> Self {
+        Self::determinize(&nfa).0
+    }
+
+    /// 部分集合構成法でDFAを作り, DFAの各状態に対応するNFAの状態集合 (空遷移で閉じたもの) も返す
+    pub(crate) fn determinize(nfa: &NFA) -> (Self, HashMap<Vec<NFAState>, DFAState>) {
         let mut context: Context = Context::new();
 
         // start: DFASの開始状態 (DFAState)
         // start_states: NFAとしての開始状態集合 (Vec<NFAState>)
         let (start, start_states) = {
             let mut ret = vec![nfa.start];
-            let mut stack = nfa
-                .next_states(nfa.start, None)
-                .iter()
-                .cloned()
-                .collect::<Vec<_>>();
+            let mut stack = nfa.next_states(nfa.start, None).collect::<Vec<_>>();
             while let Some(state) = stack.pop() {
                 ret.push(state);
-                let next: HashSet<NFAState> = nfa.next_states(state, None);
-                stack.extend(next.iter().filter(|s| !ret.contains(s)).cloned());
+                let next = nfa.next_states(state, None);
+                stack.extend(next.filter(|s| !ret.contains(s)));
             }
             (context.get_state(&ret), ret)
         };
 
         // 遷移テーブル
-        let transition: HashMap<(DFAState, char), DFAState> = {
-            let mut ret: HashMap<(DFAState, char), DFAState> = HashMap::new();
+        let transition: BTreeMap<(DFAState, char), DFAState> = {
+            let mut ret: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
             let mut waiting: Vec<Vec<NFAState>> = vec![start_states];
-            let mut visited: HashSet<DFAState> = HashSet::new();
+            let mut visited: HashSet<DFAState> = HashSet::default();
             while let Some(look_states) = waiting.pop() {
                 visited.insert(context.get_state(&look_states));
 
                 // Collect states that can be transitioned from the current state (look_states).
                 // transition_map[char] = The set of states that can be transitioned by `char`.
-                let mut transition_map: HashMap<char, HashSet<NFAState>> = HashMap::new();
+                // 文字の順に状態を登録するので, 同じNFAからは常に同じ番号のDFAができる
+                let mut transition_map: BTreeMap<char, BTreeSet<NFAState>> = BTreeMap::new();
                 for look_state in &look_states {
-                    for chara in nfa.next_chars(*look_state).iter().filter_map(|c| *c) {
+                    for chara in nfa.next_chars(*look_state).flatten() {
                         let mut next_states: Vec<NFAState> = nfa
                             .next_states(*look_state, Some(chara))
-                            .into_iter()
                             .chain(nfa.next_states(*look_state, None))
                             .collect::<Vec<_>>();
                         let mut stack: Vec<NFAState> = next_states
                             .iter()
-                            .filter(|s| !nfa.next_states(**s, None).is_empty())
+                            .filter(|s| nfa.next_states(**s, None).next().is_some())
                             .cloned()
                             .collect::<Vec<_>>();
                         while let Some(state) = stack.pop() {
-                            let next: HashSet<NFAState> = nfa.next_states(state, None);
+                            let next: Vec<NFAState> = nfa.next_states(state, None).collect();
                             stack.extend(next.iter().filter(|s| !next_states.contains(s)).cloned());
                             next_states.extend(next);
                         }
@@ -98,10 +164,10 @@ impl DFA {
                 }
                 let from: DFAState = context.get_state(&look_states);
                 for (chara, next_states) in transition_map {
-                    let next_states_vec: Vec<NFAState> = next_states.iter().cloned().collect();
-                    let to: DFAState = context.get_state(&next_states_vec);
+                    let next_states: Vec<NFAState> = next_states.into_iter().collect();
+                    let to: DFAState = context.get_state(&next_states);
                     if !visited.contains(&to) {
-                        waiting.push(next_states.into_iter().collect());
+                        waiting.push(next_states);
                     }
                     ret.insert((from, chara), to);
                 }
@@ -111,26 +177,30 @@ impl DFA {
 
         // 受理状態 (HashSet<DFAState>)
         let accepts = {
-            let mut ret: HashSet<DFAState> = HashSet::new();
-            for (nfa_states, dfa_state) in context.statemap {
+            let mut ret: HashSet<DFAState> = HashSet::default();
+            for (nfa_states, dfa_state) in &context.statemap {
                 if nfa_states.iter().any(|s| nfa.accepts.contains(s)) {
-                    ret.insert(dfa_state);
+                    ret.insert(*dfa_state);
                 }
             }
             ret
         };
 
-        DFA {
+        let dfa: DFA = DFA {
             start,
             accepts,
             transition,
-        }
+            otherwise: HashMap::default(),
+        };
+        (dfa, context.statemap)
     }
 }
 
 #[cfg(test)] #[rustfmt::skip]
 mod tests {
     use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
 
     #[test]
     fn dfa_context() {
@@ -148,7 +218,7 @@ mod tests {
     fn dfa_from_nfa_simple() {
         // -> 0 --a--> 1
         // accept: 1
-        let dfa = DFA::from_nfa(NFA::new(NFAState(0), [NFAState(1)].into()).add_transition(
+        let dfa = DFA::from_nfa(NFA::new(NFAState(0), HashSet::from_iter([NFAState(1)])).add_transition(
             NFAState(0),
             'a',
             NFAState(1),
@@ -157,7 +227,7 @@ mod tests {
         // -> 0 --a--> 1
         // accept: 1
         assert_eq!(dfa.start, DFAState(0));
-        assert_eq!(dfa.accepts, [DFAState(1)].into());
+        assert_eq!(dfa.accepts, HashSet::from_iter([DFAState(1)]));
         assert_eq!(dfa.transition.len(), 1);
         assert_eq!(dfa.transition[&(DFAState(0), 'a')], DFAState(1));
     }
@@ -167,7 +237,7 @@ mod tests {
         // -> 0 --a--> 1 --b--> 2
         // accept: 2
         let dfa = DFA::from_nfa(
-            NFA::new(NFAState(0), [NFAState(2)].into())
+            NFA::new(NFAState(0), HashSet::from_iter([NFAState(2)]))
                 .add_transition(NFAState(0), 'a', NFAState(1))
                 .add_transition(NFAState(1), 'b', NFAState(2)),
         );
@@ -175,7 +245,7 @@ mod tests {
         // -> 0 --a--> 1 --b--> 2
         // accept: 2
         assert_eq!(dfa.start, DFAState(0));
-        assert_eq!(dfa.accepts, [DFAState(2)].into());
+        assert_eq!(dfa.accepts, HashSet::from_iter([DFAState(2)]));
         assert_eq!(dfa.transition.len(), 2);
         assert_eq!(dfa.transition[&(DFAState(0), 'a')], DFAState(1));
         assert_eq!(dfa.transition[&(DFAState(1), 'b')], DFAState(2));
@@ -188,26 +258,51 @@ mod tests {
         //     \--ε--> 3 --b--> 4
         // accept: 2, 4
         let dfa = DFA::from_nfa(
-            NFA::new(NFAState(0), [NFAState(2), NFAState(4)].into())
+            NFA::new(NFAState(0), HashSet::from_iter([NFAState(2), NFAState(4)]))
                 .add_empty_transition(NFAState(0), NFAState(1))
                 .add_empty_transition(NFAState(0), NFAState(3))
                 .add_transition(NFAState(1), 'a', NFAState(2))
                 .add_transition(NFAState(3), 'b', NFAState(4)),
         );
 
-        //     /--a--> 1 (or 2)
+        //     /--a--> 1
         // -> 0
-        //     \--b--> 2 (or 1)
+        //     \--b--> 2
         // accept: 1, 2
         assert_eq!(dfa.start, DFAState(0));
-        assert_eq!(dfa.accepts, [DFAState(1), DFAState(2)].into());
+        assert_eq!(dfa.accepts, HashSet::from_iter([DFAState(1), DFAState(2)]));
         assert_eq!(dfa.transition.len(), 2);
-        if dfa.transition[&(DFAState(0), 'a')] == DFAState(1) {
-            assert_eq!(dfa.transition[&(DFAState(0), 'b')], DFAState(2));
-        } else {
-            assert_eq!(dfa.transition[&(DFAState(0), 'a')], DFAState(2));
-            assert_eq!(dfa.transition[&(DFAState(0), 'b')], DFAState(1));
-        }
+        assert_eq!(dfa.transition[&(DFAState(0), 'a')], DFAState(1));
+        assert_eq!(dfa.transition[&(DFAState(0), 'b')], DFAState(2));
+    }
+
+    #[test]
+    fn dfa_from_nfa_reproducible() {
+        // ハッシュ表の順序によらず, 同じパターンからは同じ番号のDFAができる
+        let dfa = || {
+            let node = Parser::new(Lexer::new(r"(a|b|c|d)*(ab|cd|e)")).parse().unwrap();
+            DFA::from_nfa(NFA::from_node(node))
+        };
+        let (dfa1, dfa2) = (dfa(), dfa());
+        assert_eq!(dfa1.accepts, dfa2.accepts);
+        assert_eq!(dfa1.transition, dfa2.transition);
+    }
+
+    #[test]
+    fn dfa_start_chars() {
+        //     /--ε--> 1 --a--> 2 --c--> 5
+        // -> 0
+        //     \--ε--> 3 --b--> 4
+        // accept: 4, 5
+        let dfa = DFA::from_nfa(
+            NFA::new(NFAState(0), HashSet::from_iter([NFAState(4), NFAState(5)]))
+                .add_empty_transition(NFAState(0), NFAState(1))
+                .add_empty_transition(NFAState(0), NFAState(3))
+                .add_transition(NFAState(1), 'a', NFAState(2))
+                .add_transition(NFAState(2), 'c', NFAState(5))
+                .add_transition(NFAState(3), 'b', NFAState(4)),
+        );
+        assert_eq!(dfa.start_chars(), HashSet::from_iter(['a', 'b']));
     }
 
     #[test]
@@ -216,7 +311,7 @@ mod tests {
         //              \<--ε--/
         // accept: 0, 2
         let dfa = DFA::from_nfa(
-            NFA::new(NFAState(0), [NFAState(0), NFAState(2)].into())
+            NFA::new(NFAState(0), HashSet::from_iter([NFAState(0), NFAState(2)]))
                 .add_empty_transition(NFAState(0), NFAState(1))
                 .add_transition(NFAState(1), 'a', NFAState(2))
                 .add_empty_transition(NFAState(2), NFAState(1)),
@@ -228,7 +323,7 @@ mod tests {
         //           <-a-/
         // accept: 0, 1
         assert_eq!(dfa.start, DFAState(0));
-        assert_eq!(dfa.accepts, [DFAState(0), DFAState(1)].into());
+        assert_eq!(dfa.accepts, HashSet::from_iter([DFAState(0), DFAState(1)]));
         assert_eq!(dfa.transition.len(), 2);
         assert_eq!(dfa.transition[&(DFAState(0), 'a')], DFAState(1));
         assert_eq!(dfa.transition[&(DFAState(1), 'a')], DFAState(1));
@@ -246,7 +341,7 @@ mod tests {
         //      --ε--> 5 --z--> 6
         // accept: 6
         let dfa = DFA::from_nfa(
-            NFA::new(NFAState(0), [NFAState(6)].into())
+            NFA::new(NFAState(0), HashSet::from_iter([NFAState(6)]))
                 .add_transition(NFAState(0), 'x', NFAState(1))
                 .add_empty_transition(NFAState(1), NFAState(2))
                 .add_empty_transition(NFAState(1), NFAState(5))
@@ -265,18 +360,13 @@ mod tests {
         //  /   \
         //  <-y-/
         // accept: 3
-        // NOTE: 2 and 3 can be swapped
         assert_eq!(dfa.start, DFAState(0));
+        assert_eq!(dfa.accepts, HashSet::from_iter([DFAState(3)]));
         assert_eq!(dfa.transition.len(), 5);
-        let (s2, s3) = if dfa.accepts == [DFAState(3)].into() {
-            (2, 3)
-        } else {
-            (3, 2)
-        };
         assert_eq!(dfa.transition[&(DFAState(0), 'x')], DFAState(1));
-        assert_eq!(dfa.transition[&(DFAState(1), 'y')], DFAState(s2));
-        assert_eq!(dfa.transition[&(DFAState(1), 'z')], DFAState(s3));
-        assert_eq!(dfa.transition[&(DFAState(s2), 'z')], DFAState(s3));
-        assert_eq!(dfa.transition[&(DFAState(s2), 'y')], DFAState(s2));
+        assert_eq!(dfa.transition[&(DFAState(1), 'y')], DFAState(2));
+        assert_eq!(dfa.transition[&(DFAState(1), 'z')], DFAState(3));
+        assert_eq!(dfa.transition[&(DFAState(2), 'z')], DFAState(3));
+        assert_eq!(dfa.transition[&(DFAState(2), 'y')], DFAState(2));
     }
 }
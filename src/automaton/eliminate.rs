@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::automaton::{DFA, DFAState};
+use crate::parser::{Kind, Node};
+
+/// 一般化NFA (遷移に正規表現を載せたNFA) の状態
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum State {
+    Start,
+    Accept,
+    Inner(DFAState),
+}
+
+impl DFA {
+    /// 状態除去法で同じ言語を表す構文木を作る
+    ///
+    /// パターンの構文で表せない場合 (「それ以外の文字」による遷移がある, 受理する文字列が存在しない) は `None`.
+    pub(crate) fn to_node(&self) -> Option<Node> {
+        let distances = self.distances_to_accept();
+        if !distances.contains_key(&self.start) {
+            return None;
+        }
+        let useful: BTreeSet<DFAState> = distances.keys().cloned().collect();
+        if useful.iter().any(|s| self.otherwise.contains_key(s)) {
+            return None;
+        }
+
+        let mut edges: BTreeMap<(State, State), Node> = BTreeMap::new();
+        add_edge(
+            &mut edges,
+            State::Start,
+            State::Inner(self.start),
+            Node::empty(),
+        );
+        for state in &useful {
+            if self.is_accept(*state) {
+                add_edge(
+                    &mut edges,
+                    State::Inner(*state),
+                    State::Accept,
+                    Node::empty(),
+                );
+            }
+            for (chara, to) in self.edges(*state) {
+                if useful.contains(&to) {
+                    let (from, to) = (State::Inner(*state), State::Inner(to));
+                    add_edge(&mut edges, from, to, Node::character(chara));
+                }
+            }
+        }
+
+        let mut remaining: BTreeSet<DFAState> = useful;
+        while !remaining.is_empty() {
+            // 出入りする遷移の数の積が最小の状態から除去する
+            let degree = |state: &DFAState| {
+                let q = State::Inner(*state);
+                let ins = edges.keys().filter(|(f, t)| *t == q && *f != q).count();
+                let outs = edges.keys().filter(|(f, t)| *f == q && *t != q).count();
+                ins * outs
+            };
+            let state: DFAState = *remaining.iter().min_by_key(|s| degree(s)).unwrap();
+            remaining.remove(&state);
+            let q = State::Inner(state);
+
+            let looped: Option<Node> = edges.remove(&(q, q)).map(star);
+            let in_keys: Vec<(State, State)> =
+                edges.keys().filter(|(_, to)| *to == q).cloned().collect();
+            let ins: Vec<(State, Node)> = in_keys
+                .into_iter()
+                .map(|key| (key.0, edges.remove(&key).unwrap()))
+                .collect();
+            let out_keys: Vec<(State, State)> = edges
+                .keys()
+                .filter(|(from, _)| *from == q)
+                .cloned()
+                .collect();
+            let outs: Vec<(State, Node)> = out_keys
+                .into_iter()
+                .map(|key| (key.1, edges.remove(&key).unwrap()))
+                .collect();
+            for (from, n1) in &ins {
+                for (to, n2) in &outs {
+                    let mut node: Node = n1.clone();
+                    if let Some(looped) = &looped {
+                        node = concat(node, looped.clone());
+                    }
+                    add_edge(&mut edges, *from, *to, concat(node, n2.clone()));
+                }
+            }
+        }
+        edges.remove(&(State::Start, State::Accept))
+    }
+}
+
+/// 既存の遷移があれば和集合をとって遷移を追加する
+fn add_edge(edges: &mut BTreeMap<(State, State), Node>, from: State, to: State, node: Node) {
+    let node: Node = match edges.remove(&(from, to)) {
+        Some(old) => union(old, node),
+        None => node,
+    };
+    edges.insert((from, to), node);
+}
+
+fn union(n1: Node, n2: Node) -> Node {
+    if n1 == n2 { n1 } else { n1.union(n2) }
+}
+
+fn concat(n1: Node, n2: Node) -> Node {
+    if n1.root().kind() == Kind::Empty {
+        n2
+    } else if n2.root().kind() == Kind::Empty {
+        n1
+    } else {
+        n1.concat(n2)
+    }
+}
+
+fn star(node: Node) -> Node {
+    match node.root().kind() {
+        Kind::Empty | Kind::Star(_) => node,
+        _ => node.star(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node)).minimize()
+    }
+
+    #[test]
+    fn to_node() {
+        assert_eq!(dfa(r"abc").to_node().unwrap().to_string(), "abc");
+        assert_eq!(dfa(r"a*").to_node().unwrap().to_string(), "a*");
+        assert_eq!(dfa(r"(a|b)*").to_node().unwrap().to_string(), "(a|b)*");
+        assert_eq!(dfa(r"a\*").to_node().unwrap().to_string(), r"a\*");
+    }
+
+    #[test]
+    fn to_node_inexpressible() {
+        assert!(dfa(r"a").complement().to_node().is_none());
+        assert!(
+            dfa(r"a")
+                .product(&dfa(r"b"), |a, b| a && b)
+                .to_node()
+                .is_none()
+        );
+    }
+}
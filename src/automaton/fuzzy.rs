@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+/// 元のDFAの状態と, そこへ至るまでの誤りの数の組の集合. 同じ状態については最小の誤りの数のみを持つ
+type Errors = BTreeMap<DFAState, usize>;
+
+impl DFA {
+    /// 受理する文字列とのレーベンシュタイン距離が `k` 以下の文字列を受理するDFA
+    ///
+    /// 元のDFAの状態と誤りの数の組を状態とするNFAに, 部分集合構成法を適用する.
+    /// 置換と挿入は任意の1文字を読み, 削除は文字を読まずに元のDFAの遷移を1つ進める.
+    pub(crate) fn fuzzy(&self, k: usize) -> DFA {
+        let alphabet: BTreeSet<char> = self.alphabet();
+        let mut ids: HashMap<Vec<(DFAState, usize)>, DFAState> = HashMap::default();
+        let mut queue: VecDeque<Errors> = VecDeque::new();
+        let mut get_state = |errors: Errors, queue: &mut VecDeque<Errors>| -> DFAState {
+            let key: Vec<(DFAState, usize)> = errors.iter().map(|(q, e)| (*q, *e)).collect();
+            let len: u32 = ids.len() as u32;
+            *ids.entry(key).or_insert_with(|| {
+                queue.push_back(errors);
+                DFAState(len)
+            })
+        };
+
+        let start: DFAState = get_state(self.deletions([(self.start, 0)].into(), k), &mut queue);
+        let mut accepts: HashSet<DFAState> = HashSet::default();
+        let mut transition: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
+        let mut otherwise: HashMap<DFAState, DFAState> = HashMap::default();
+        let mut from: u32 = 0;
+        while let Some(errors) = queue.pop_front() {
+            let from_state: DFAState = DFAState(from);
+            from += 1;
+            if errors.keys().any(|q| self.is_accept(*q)) {
+                accepts.insert(from_state);
+            }
+            for chara in &alphabet {
+                let next: Errors = self.read(&errors, k, |q| self.next_state(q, *chara));
+                if !next.is_empty() {
+                    transition.insert((from_state, *chara), get_state(next, &mut queue));
+                }
+            }
+            let next: Errors = self.read(&errors, k, |q| self.otherwise.get(&q).cloned());
+            if !next.is_empty() {
+                otherwise.insert(from_state, get_state(next, &mut queue));
+            }
+        }
+
+        DFA {
+            start,
+            accepts,
+            transition,
+            otherwise,
+        }
+    }
+
+    /// 1文字を読んだ後の集合. `next` はその文字による元のDFAの遷移
+    fn read(
+        &self,
+        errors: &Errors,
+        k: usize,
+        next: impl Fn(DFAState) -> Option<DFAState>,
+    ) -> Errors {
+        let mut result: Errors = Errors::new();
+        let mut insert = |q: DFAState, e: usize| {
+            let min: &mut usize = result.entry(q).or_insert(e);
+            *min = (*min).min(e);
+        };
+        for (q, e) in errors {
+            if let Some(next) = next(*q) {
+                insert(next, *e);
+            }
+            if *e < k {
+                // 挿入: 読んだ文字を読み飛ばす
+                insert(*q, e + 1);
+                // 置換: 読んだ文字を別の文字として遷移する
+                for successor in self.successors(*q) {
+                    insert(successor, e + 1);
+                }
+            }
+        }
+        self.deletions(result, k)
+    }
+
+    /// 削除 (文字を読まずに遷移する) を `k` 回まで繰り返して到達できる組を加える
+    fn deletions(&self, mut errors: Errors, k: usize) -> Errors {
+        let mut stack: Vec<(DFAState, usize)> = errors.iter().map(|(q, e)| (*q, *e)).collect();
+        while let Some((q, e)) = stack.pop() {
+            if e >= k {
+                continue;
+            }
+            for successor in self.successors(q) {
+                let min: &mut usize = errors.entry(successor).or_insert(usize::MAX);
+                if e + 1 < *min {
+                    *min = e + 1;
+                    stack.push((successor, e + 1));
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node))
+    }
+
+    fn accepts(dfa: &DFA, text: &str) -> bool {
+        let mut state: DFAState = dfa.start;
+        for chara in text.chars() {
+            match dfa.next_state(state, chara) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        dfa.is_accept(state)
+    }
+
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut prev: usize = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let current: usize = row[j + 1];
+                row[j + 1] = (prev + usize::from(ca != *cb))
+                    .min(row[j] + 1)
+                    .min(current + 1);
+                prev = current;
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn fuzzy() {
+        let words: [&str; 2] = ["abc", "bd"];
+        let base: DFA = dfa(r"abc|bd");
+        for k in 0..=2 {
+            let fuzzy: DFA = base.fuzzy(k);
+            // {a, b, c, d, x} 上の長さ4以下の文字列すべて
+            let mut texts: Vec<String> = vec![String::new()];
+            for len in 0..4 {
+                let longer: Vec<String> = texts
+                    .iter()
+                    .filter(|t| t.chars().count() == len)
+                    .flat_map(|t| "abcdx".chars().map(move |c| format!("{}{}", t, c)))
+                    .collect();
+                texts.extend(longer);
+            }
+            for text in texts {
+                let distance: usize = words.iter().map(|w| levenshtein(&text, w)).min().unwrap();
+                assert_eq!(accepts(&fuzzy, &text), distance <= k, "{:?} {}", text, k);
+            }
+        }
+    }
+
+    #[test]
+    fn fuzzy_infinite() {
+        let fuzzy: DFA = dfa(r"a(bc)*").fuzzy(1);
+        for text in ["a", "abcbc", "bc", "abcbcb", "abxbc", "xabc", "ab", "abcc"] {
+            assert!(accepts(&fuzzy, text), "{:?}", text);
+        }
+        for text in ["xbcx", "abxbcx", "xx"] {
+            assert!(!accepts(&fuzzy, text), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use crate::automaton::{NFA, NFAState};
+use crate::hash::HashSet;
+use crate::parser::{Kind, Node, NodeRef};
+
+/// Glushkov構成 (位置オートマトン) のための, 構文木中の文字の位置の情報
+///
+/// 構文木中の各文字を出現順に 0, 1, 2, ... と番号付けしたものを位置と呼ぶ.
+pub(crate) struct Positions {
+    /// chars[i]: 位置 `i` の文字
+    pub(crate) chars: Vec<char>,
+    /// 空文字列を受理するか
+    pub(crate) nullable: bool,
+    /// 最初に読む文字の位置の集合
+    pub(crate) first: BTreeSet<usize>,
+    /// 最後に読む文字の位置の集合
+    pub(crate) last: BTreeSet<usize>,
+    /// follow[i]: 位置 `i` の次に読む文字の位置の集合
+    pub(crate) follow: Vec<BTreeSet<usize>>,
+}
+
+impl Positions {
+    pub(crate) fn from_node(node: &Node) -> Self {
+        let mut chars: Vec<char> = Vec::new();
+        let mut follow: Vec<BTreeSet<usize>> = Vec::new();
+        let (nullable, first, last) = analyze(node.root(), &mut chars, &mut follow);
+        Positions {
+            chars,
+            nullable,
+            first,
+            last,
+            follow,
+        }
+    }
+}
+
+type Analysis = (bool, BTreeSet<usize>, BTreeSet<usize>);
+
+/// 部分木の (nullable, first, last) を求め, follow を更新する
+fn analyze(
+    node: NodeRef<'_>,
+    chars: &mut Vec<char>,
+    follow: &mut Vec<BTreeSet<usize>>,
+) -> Analysis {
+    match node.kind() {
+        Kind::Character(chara) => {
+            let position: usize = chars.len();
+            chars.push(chara);
+            follow.push(BTreeSet::new());
+            (false, [position].into(), [position].into())
+        }
+        Kind::Empty => (true, BTreeSet::new(), BTreeSet::new()),
+        Kind::Star(node) => {
+            let (_, first, last) = analyze(node, chars, follow);
+            for position in &last {
+                follow[*position].extend(&first);
+            }
+            (true, first, last)
+        }
+        Kind::Union(n1, n2) => {
+            let (nullable1, mut first, mut last) = analyze(n1, chars, follow);
+            let (nullable2, first2, last2) = analyze(n2, chars, follow);
+            first.extend(first2);
+            last.extend(last2);
+            (nullable1 || nullable2, first, last)
+        }
+        Kind::Concat(n1, n2) => {
+            let (nullable1, mut first, last1) = analyze(n1, chars, follow);
+            let (nullable2, first2, mut last) = analyze(n2, chars, follow);
+            for position in &last1 {
+                follow[*position].extend(&first2);
+            }
+            if nullable1 {
+                first.extend(first2);
+            }
+            if nullable2 {
+                last.extend(last1);
+            }
+            (nullable1 && nullable2, first, last)
+        }
+        Kind::Group(_, node) => analyze(node, chars, follow),
+    }
+}
+
+impl NFA {
+    /// Glushkov構成で空遷移を持たないNFAを作る
+    ///
+    /// 状態 0 が開始状態, 状態 `i + 1` が位置 `i` の文字を読んだ直後の状態に対応する.
+    pub(crate) fn glushkov(node: &Node) -> Self {
+        let positions: Positions = Positions::from_node(node);
+        let state = |position: usize| NFAState(position as u32 + 1);
+        let start: NFAState = NFAState(0);
+        let mut accepts: HashSet<NFAState> = positions.last.iter().map(|p| state(*p)).collect();
+        if positions.nullable {
+            accepts.insert(start);
+        }
+        let mut nfa: NFA = NFA::new(start, accepts);
+        for to in &positions.first {
+            nfa = nfa.add_transition(start, positions.chars[*to], state(*to));
+        }
+        for (from, follow) in positions.follow.iter().enumerate() {
+            for to in follow {
+                nfa = nfa.add_transition(state(from), positions.chars[*to], state(*to));
+            }
+        }
+        nfa
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(pattern: &str) -> Node {
+        Parser::new(Lexer::new(pattern)).parse().unwrap()
+    }
+
+    #[test]
+    fn positions() {
+        // 位置: a=0, b=1, c=2
+        let positions = Positions::from_node(&parse(r"a(b|)c*"));
+        assert_eq!(positions.chars, vec!['a', 'b', 'c']);
+        assert!(!positions.nullable);
+        assert_eq!(positions.first, [0].into());
+        assert_eq!(positions.last, [0, 1, 2].into());
+        assert_eq!(
+            positions.follow,
+            vec![[1, 2].into(), [2].into(), [2].into()]
+        );
+    }
+
+    #[test]
+    fn glushkov_star() {
+        let nfa = NFA::glushkov(&parse(r"a*"));
+
+        // -> 0 --a--> 1 --a--> 1
+        // accept: 0, 1
+        assert_eq!(nfa.start, NFAState(0));
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(0), NFAState(1)]));
+        assert_eq!(
+            nfa.transition,
+            [
+                vec![(Some('a'), NFAState(1))],
+                vec![(Some('a'), NFAState(1))]
+            ]
+        );
+    }
+
+    #[test]
+    fn glushkov_union() {
+        let nfa = NFA::glushkov(&parse(r"ab|c"));
+
+        //     /--a--> 1 --b--> 2
+        // -> 0
+        //     \--c--> 3
+        // accept: 2, 3
+        assert_eq!(nfa.start, NFAState(0));
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(2), NFAState(3)]));
+        assert_eq!(
+            nfa.transition,
+            [
+                vec![(Some('a'), NFAState(1)), (Some('c'), NFAState(3))],
+                vec![(Some('b'), NFAState(2))]
+            ]
+        );
+    }
+}
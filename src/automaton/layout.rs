@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+impl DFA {
+    /// 照合中によく訪れる状態を前にした状態の並び
+    ///
+    /// 開始状態, 自己ループを持つ状態 (長い入力の間とどまりやすい), 残りの状態の順で, それぞれ開始状態からの幅優先探索の順.
+    pub(crate) fn hot_order(&self) -> Vec<DFAState> {
+        let bfs: Vec<DFAState> = self.bfs_order();
+        let (looping, rest): (Vec<DFAState>, Vec<DFAState>) = bfs[1..]
+            .iter()
+            .partition(|state| self.successors(**state).any(|next| next == **state));
+        [self.start]
+            .into_iter()
+            .chain(looping)
+            .chain(rest)
+            .collect()
+    }
+
+    /// `sample` を照合したときに多く訪れた状態を前にした状態の並び. 訪れなかった状態は [`DFA::hot_order`] の順で後ろに並べる
+    ///
+    /// 探索と同じく, `sample` の各位置から最長のマッチを試して数える.
+    pub(crate) fn profile_order(&self, sample: &str) -> Vec<DFAState> {
+        let mut visits: HashMap<DFAState, usize> = HashMap::default();
+        let starts = sample.char_indices().map(|(i, _)| i).chain([sample.len()]);
+        for start in starts {
+            let mut state: DFAState = self.start;
+            *visits.entry(state).or_default() += 1;
+            for chara in sample[start..].chars() {
+                match self.next_state(state, chara) {
+                    Some(next) => state = next,
+                    None => break,
+                }
+                *visits.entry(state).or_default() += 1;
+            }
+        }
+        let mut order: Vec<DFAState> = self.hot_order();
+        // 安定ソートなので, 訪れた回数が同じ状態は hot_order の順のまま
+        order.sort_by_key(|state| std::cmp::Reverse(visits.get(state).copied().unwrap_or(0)));
+        order
+    }
+
+    /// `order` の順に 0 から番号を振り直したDFA. `order` に無い状態は取り除く
+    pub(crate) fn renumber(&self, order: &[DFAState]) -> DFA {
+        let ids: HashMap<DFAState, DFAState> = order
+            .iter()
+            .enumerate()
+            .map(|(i, state)| (*state, DFAState(i as u32)))
+            .collect();
+        DFA {
+            start: ids[&self.start],
+            accepts: self
+                .accepts
+                .iter()
+                .filter_map(|s| ids.get(s))
+                .cloned()
+                .collect(),
+            transition: self
+                .transition
+                .iter()
+                .filter_map(|((from, chara), to)| Some(((*ids.get(from)?, *chara), *ids.get(to)?)))
+                .collect(),
+            otherwise: self
+                .otherwise
+                .iter()
+                .filter_map(|(from, to)| Some((*ids.get(from)?, *ids.get(to)?)))
+                .collect(),
+        }
+    }
+
+    /// 開始状態から到達可能な状態を幅優先探索の順に並べたもの
+    fn bfs_order(&self) -> Vec<DFAState> {
+        let mut order: Vec<DFAState> = vec![self.start];
+        let mut visited: HashSet<DFAState> = HashSet::from_iter([self.start]);
+        let mut queue: VecDeque<DFAState> = [self.start].into();
+        while let Some(state) = queue.pop_front() {
+            for (_, next) in self.edges(state) {
+                if visited.insert(next) {
+                    order.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        order
+    }
+}
+
+/// 遷移先が無いことを表す [`AsciiTable`] の値
+const DEAD: u32 = u32::MAX;
+
+/// 状態番号が 0 から連続するDFAの, ASCII 文字による遷移と受理状態を配列にしたもの
+///
+/// 状態番号の順に行を並べるので, [`DFA::hot_order`] などで番号を振り直しておくと, よく訪れる状態の行が隣り合う.
+/// ASCII 以外の文字による遷移は元のDFAを引く.
+#[derive(Clone, Debug)]
+pub(crate) struct AsciiTable {
+    /// `rows[state][byte]` が遷移先
+    rows: Vec<[u32; 128]>,
+    accepts: Vec<bool>,
+}
+
+impl AsciiTable {
+    /// これより状態が多い場合は作らない (1状態あたり 512 バイト)
+    pub(crate) const MAX_STATES: usize = 2048;
+
+    /// 状態番号が連続していない場合や, 状態が多すぎる場合は `None`
+    pub(crate) fn new(dfa: &DFA) -> Option<Self> {
+        let states: usize = dfa.states().len();
+        if states > Self::MAX_STATES || dfa.states().iter().any(|s| s.0 as usize >= states) {
+            return None;
+        }
+        let rows: Vec<[u32; 128]> = (0..states as u32)
+            .map(|state| {
+                std::array::from_fn(|byte| {
+                    dfa.next_state(DFAState(state), byte as u8 as char)
+                        .map_or(DEAD, |to| to.0)
+                })
+            })
+            .collect();
+        let accepts: Vec<bool> = (0..states as u32)
+            .map(|state| dfa.is_accept(DFAState(state)))
+            .collect();
+        Some(AsciiTable { rows, accepts })
+    }
+
+    #[inline]
+    pub(crate) fn next_state(&self, dfa: &DFA, state: DFAState, chara: char) -> Option<DFAState> {
+        if chara.is_ascii() {
+            let to: u32 = self.rows[state.0 as usize][chara as usize];
+            (to != DEAD).then_some(DFAState(to))
+        } else {
+            dfa.next_state(state, chara)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_accept(&self, state: DFAState) -> bool {
+        self.accepts[state.0 as usize]
+    }
+
+    /// [`DFA::longest_match_at`] と同じ
+    pub(crate) fn longest_match_at(&self, dfa: &DFA, text: &str, start: usize) -> Option<usize> {
+        let mut state: DFAState = dfa.start;
+        let mut end: Option<usize> = self.is_accept(state).then_some(start);
+        for (i, chara) in text[start..].char_indices() {
+            match self.next_state(dfa, state, chara) {
+                Some(next) => state = next,
+                None => break,
+            }
+            if self.is_accept(state) {
+                end = Some(start + i + chara.len_utf8());
+            }
+        }
+        end
+    }
+
+    /// [`DFA::shortest_match_at`] と同じ
+    pub(crate) fn shortest_match_at(&self, dfa: &DFA, text: &str, start: usize) -> Option<usize> {
+        let mut state: DFAState = dfa.start;
+        if self.is_accept(state) {
+            return Some(start);
+        }
+        for (i, chara) in text[start..].char_indices() {
+            state = self.next_state(dfa, state, chara)?;
+            if self.is_accept(state) {
+                return Some(start + i + chara.len_utf8());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node))
+    }
+
+    #[test]
+    fn hot_order() {
+        let dfa: DFA = dfa(r"ab*c|d");
+        let order: Vec<DFAState> = dfa.hot_order();
+        assert_eq!(order.len(), dfa.states().len());
+        assert_eq!(order[0], dfa.start);
+        // `b*` の状態だけが自己ループを持つ
+        assert!(dfa.successors(order[1]).any(|next| next == order[1]));
+
+        let renumbered: DFA = dfa.renumber(&order);
+        assert_eq!(renumbered.start, DFAState(0));
+        assert_eq!(renumbered.fingerprint(), dfa.fingerprint());
+    }
+
+    #[test]
+    fn profile_order() {
+        let dfa: DFA = dfa(r"xyz|ab*");
+        let order: Vec<DFAState> = dfa.profile_order("abbbbbbb");
+        assert_eq!(order[0], dfa.start);
+        // 開始状態の次は, 最も長くとどまる `b*` の状態
+        assert!(dfa.successors(order[1]).any(|next| next == order[1]));
+        assert_eq!(dfa.renumber(&order).fingerprint(), dfa.fingerprint());
+    }
+
+    #[test]
+    fn ascii_table() {
+        let dfa: DFA = dfa(r"(a|山)*b").complement().minimize();
+        let dfa: DFA = dfa.renumber(&dfa.hot_order());
+        let table: AsciiTable = AsciiTable::new(&dfa).unwrap();
+        for state in dfa.states() {
+            for chara in ['a', 'b', 'z', '\0', '\x7f', '山', 'é'] {
+                assert_eq!(
+                    table.next_state(&dfa, state, chara),
+                    dfa.next_state(state, chara)
+                );
+            }
+        }
+        for text in ["aab", "山b", "abc", "", "é"] {
+            assert_eq!(
+                table.longest_match_at(&dfa, text, 0),
+                dfa.longest_match_at(text, 0)
+            );
+            assert_eq!(
+                table.shortest_match_at(&dfa, text, 0),
+                dfa.shortest_match_at(text, 0)
+            );
+        }
+    }
+}
@@ -1,5 +1,4 @@
-use std::collections::{HashMap, HashSet};
-
+use crate::hash::HashSet;
 use crate::parser::Node;
 
 /// NFAの状態
@@ -22,15 +21,19 @@ impl Context {
     }
 }
 
+/// 1つの状態からの遷移. 文字が `None` なら空遷移
+type Edge = (Option<char>, NFAState);
+
 /// NondeterministicFiniteAutomaton
+#[derive(Debug)]
 #[allow(clippy::upper_case_acronyms)]
 pub(crate) struct NFA {
     /// 開始状態
     pub start: NFAState,
     /// 受理状態
     pub accepts: HashSet<NFAState>,
-    /// 遷移テーブル
-    pub(crate) transition: HashMap<NFAState, HashMap<Option<char>, HashSet<NFAState>>>,
+    /// 遷移テーブル. 状態の番号を添字とし, 各状態からの遷移を (文字, 遷移先) の順に重複なく並べる
+    pub(crate) transition: Vec<Vec<Edge>>,
 }
 
 impl NFA {
@@ -38,23 +41,34 @@ impl NFA {
         NFA {
             start,
             accepts,
-            transition: HashMap::new(),
+            transition: Vec::new(),
         }
     }
 
-    pub(crate) fn next_chars(&self, state: NFAState) -> HashSet<Option<char>> {
+    fn edges(&self, state: NFAState) -> &[Edge] {
         self.transition
-            .get(&state)
-            .map(|table| table.keys().cloned().collect())
-            .unwrap_or_default()
+            .get(state.0 as usize)
+            .map_or(&[], Vec::as_slice)
     }
 
-    pub(crate) fn next_states(&self, state: NFAState, chara: Option<char>) -> HashSet<NFAState> {
-        self.transition
-            .get(&state)
-            .and_then(|table| table.get(&chara))
-            .cloned()
-            .unwrap_or(HashSet::new())
+    /// `state` から遷移できる文字. 昇順で, 空遷移があれば先頭に `None` が来る
+    pub(crate) fn next_chars(&self, state: NFAState) -> impl Iterator<Item = Option<char>> + '_ {
+        self.edges(state)
+            .chunk_by(|(c1, _), (c2, _)| c1 == c2)
+            .map(|edges| edges[0].0)
+    }
+
+    pub(crate) fn next_states(
+        &self,
+        state: NFAState,
+        chara: Option<char>,
+    ) -> impl Iterator<Item = NFAState> + '_ {
+        let edges: &[Edge] = self.edges(state);
+        let start: usize = edges.partition_point(|(c, _)| *c < chara);
+        edges[start..]
+            .iter()
+            .take_while(move |(c, _)| *c == chara)
+            .map(|(_, to)| *to)
     }
 
     pub(crate) fn add_transition(mut self, from: NFAState, chara: char, to: NFAState) -> Self {
@@ -67,32 +81,52 @@ impl NFA {
         self
     }
 
-    pub(crate) fn merge_transition(mut self, other: &Self) -> Self {
-        for (from, trans) in &other.transition {
-            for (chara, to) in trans {
-                self.transition
-                    .entry(*from)
-                    .or_default()
-                    .entry(*chara)
-                    .or_default()
-                    .extend(to);
+    /// `other` の遷移を加える. 状態が重ならなければ, 各状態の遷移を移すだけで済む
+    pub(crate) fn merge_transition(mut self, mut other: Self) -> Self {
+        if self.transition.len() < other.transition.len() {
+            std::mem::swap(&mut self.transition, &mut other.transition);
+        }
+        for (from, edges) in other.transition.into_iter().enumerate() {
+            let table: &mut Vec<Edge> = &mut self.transition[from];
+            if table.is_empty() {
+                *table = edges;
+            } else if !edges.is_empty() {
+                table.extend(edges);
+                table.sort_unstable();
+                table.dedup();
             }
         }
         self
     }
 
-    fn _insert_transition(&mut self, from: NFAState, to: NFAState, chara: Option<char>) {
-        let states = self
-            .transition
-            .entry(from)
-            .or_default()
-            .entry(chara)
-            .or_default();
-        states.insert(to);
+    pub(crate) fn _insert_transition(&mut self, from: NFAState, to: NFAState, chara: Option<char>) {
+        let from: usize = from.0 as usize;
+        if self.transition.len() <= from {
+            self.transition.resize_with(from + 1, Vec::new);
+        }
+        let table: &mut Vec<Edge> = &mut self.transition[from];
+        if let Err(i) = table.binary_search(&(chara, to)) {
+            table.insert(i, (chara, to));
+        }
     }
 
     pub(crate) fn from_node(node: Node) -> Self {
-        node.assemble(&mut Context::new())
+        node.root().assemble(&mut Context::new())
+    }
+
+    /// 文字列 `literal` のみを受理するNFA
+    pub(crate) fn from_literal(literal: &str) -> Self {
+        let mut context: Context = Context::new();
+        let start: NFAState = context.new_state();
+        let mut nfa: NFA = NFA::new(start, HashSet::default());
+        let mut current: NFAState = start;
+        for chara in literal.chars() {
+            let next: NFAState = context.new_state();
+            nfa = nfa.add_transition(current, chara, next);
+            current = next;
+        }
+        nfa.accepts.insert(current);
+        nfa
     }
 }
 
@@ -107,4 +141,49 @@ mod tests {
         assert_eq!(context.new_state(), NFAState(1));
         assert_eq!(context.new_state(), NFAState(2));
     }
+
+    #[test]
+    fn from_literal() {
+        let nfa = NFA::from_literal("ab");
+
+        // -> 0 --a--> 1 --b--> 2
+        // accept: 2
+        assert_eq!(nfa.start, NFAState(0));
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(2)]));
+        assert_eq!(
+            nfa.transition,
+            [
+                vec![(Some('a'), NFAState(1))],
+                vec![(Some('b'), NFAState(2))]
+            ]
+        );
+    }
+
+    #[test]
+    fn transitions() {
+        let nfa = NFA::new(NFAState(0), HashSet::from_iter([NFAState(2)]))
+            .add_transition(NFAState(0), 'b', NFAState(2))
+            .add_transition(NFAState(0), 'a', NFAState(1))
+            .add_empty_transition(NFAState(0), NFAState(2))
+            .add_transition(NFAState(0), 'a', NFAState(1))
+            .merge_transition(
+                NFA::new(NFAState(1), HashSet::default())
+                    .add_transition(NFAState(0), 'a', NFAState(2))
+                    .add_transition(NFAState(1), 'c', NFAState(2)),
+            );
+        assert_eq!(
+            nfa.next_chars(NFAState(0)).collect::<Vec<_>>(),
+            [None, Some('a'), Some('b')]
+        );
+        assert_eq!(
+            nfa.next_states(NFAState(0), Some('a')).collect::<Vec<_>>(),
+            [NFAState(1), NFAState(2)]
+        );
+        assert_eq!(
+            nfa.next_states(NFAState(1), Some('c')).collect::<Vec<_>>(),
+            [NFAState(2)]
+        );
+        assert_eq!(nfa.next_states(NFAState(2), None).count(), 0);
+        assert_eq!(nfa.next_chars(NFAState(3)).count(), 0);
+    }
 }
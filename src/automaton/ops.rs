@@ -0,0 +1,612 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::RangeInclusive;
+
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+impl DFA {
+    /// 到達可能な状態の集合
+    pub(crate) fn states(&self) -> BTreeSet<DFAState> {
+        let mut visited: BTreeSet<DFAState> = [self.start].into();
+        let mut stack: Vec<DFAState> = vec![self.start];
+        while let Some(state) = stack.pop() {
+            for next in self.successors(state) {
+                if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        visited
+    }
+
+    /// 遷移テーブルに現れる文字の集合
+    pub(crate) fn alphabet(&self) -> BTreeSet<char> {
+        self.transition.keys().map(|(_, chara)| *chara).collect()
+    }
+
+    /// 遷移テーブルに現れない文字のうち1つ. 「それ以外の文字」による遷移の代表として使う
+    pub(crate) fn other_char(&self) -> char {
+        let alphabet: BTreeSet<char> = self.alphabet();
+        ('a'..='z')
+            .chain('A'..='Z')
+            .chain('0'..='9')
+            .chain('\u{100}'..=char::MAX)
+            .find(|c| !alphabet.contains(c))
+            .expect("alphabet covers every character")
+    }
+
+    /// `state` から出る遷移を (文字, 遷移先) の形で文字順に列挙する
+    ///
+    /// 「それ以外の文字」による遷移は [`DFA::other_char`] で代表させ, 最後に並べる.
+    pub(crate) fn edges(&self, state: DFAState) -> Vec<(char, DFAState)> {
+        let mut edges: Vec<(char, DFAState)> = self.transitions_from(state).collect();
+        if let Some(to) = self.otherwise.get(&state) {
+            edges.push((self.other_char(), *to));
+        }
+        edges
+    }
+
+    /// 開始状態から, 各状態の遷移を [`DFA::edges`] の順に辿る幅優先探索で状態に付けた番号
+    ///
+    /// 状態の内部的な番号によらないので, 同じ形のDFAには同じ番号が付く. 開始状態は 0.
+    /// 遷移先が「それ以外の文字」による遷移先と同じ文字の遷移は, 省略されているものとして扱う.
+    pub(crate) fn canonical_numbering(&self) -> HashMap<DFAState, u32> {
+        let mut numbering: HashMap<DFAState, u32> = HashMap::from_iter([(self.start, 0)]);
+        let mut queue: VecDeque<DFAState> = [self.start].into();
+        while let Some(state) = queue.pop_front() {
+            let otherwise: Option<DFAState> = self.otherwise.get(&state).cloned();
+            let edges = self.edges(state).into_iter().filter(|(chara, next)| {
+                !self.transition.contains_key(&(state, *chara)) || Some(*next) != otherwise
+            });
+            for (_, next) in edges {
+                let len: u32 = numbering.len() as u32;
+                numbering.entry(next).or_insert_with(|| {
+                    queue.push_back(next);
+                    len
+                });
+            }
+        }
+        numbering
+    }
+
+    /// [`DFA::canonical_numbering`] で並べた状態と遷移の FNV-1a ハッシュ値. 最小化したDFAに使う
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let numbering: HashMap<DFAState, u32> = self.canonical_numbering();
+        let mut states: Vec<(u32, DFAState)> = numbering.iter().map(|(s, n)| (*n, *s)).collect();
+        states.sort();
+        let mut bytes: Vec<u8> = Vec::new();
+        for (_, state) in states {
+            bytes.push(self.is_accept(state) as u8);
+            let otherwise: Option<DFAState> = self.otherwise.get(&state).cloned();
+            for (chara, next) in self.edges(state) {
+                if self.transition.contains_key(&(state, chara)) && Some(next) != otherwise {
+                    bytes.push(b'c');
+                    bytes.extend_from_slice(&(chara as u32).to_le_bytes());
+                    bytes.extend_from_slice(&numbering[&next].to_le_bytes());
+                }
+            }
+            if let Some(next) = otherwise {
+                bytes.push(b'o');
+                bytes.extend_from_slice(&numbering[&next].to_le_bytes());
+            }
+            bytes.push(b';');
+        }
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// 受理する文字列のうち最短のもの (同じ長さなら辞書順で最小のもの)
+    pub(crate) fn shortest_accepted(&self) -> Option<String> {
+        let mut parent: HashMap<DFAState, (DFAState, char)> = HashMap::default();
+        let mut queue: VecDeque<DFAState> = [self.start].into();
+        let mut visited: HashSet<DFAState> = HashSet::from_iter([self.start]);
+        while let Some(state) = queue.pop_front() {
+            if self.is_accept(state) {
+                let mut chars: Vec<char> = Vec::new();
+                let mut current: DFAState = state;
+                while let Some((prev, chara)) = parent.get(&current) {
+                    chars.push(*chara);
+                    current = *prev;
+                }
+                return Some(chars.into_iter().rev().collect());
+            }
+            for (chara, next) in self.edges(state) {
+                if visited.insert(next) {
+                    parent.insert(next, (state, chara));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// `state` から1回の遷移で移れる状態
+    pub(crate) fn successors(&self, state: DFAState) -> impl Iterator<Item = DFAState> + '_ {
+        self.transitions_from(state)
+            .map(|(_, to)| to)
+            .chain(self.otherwise.get(&state).cloned())
+    }
+
+    /// 遷移テーブルにある `state` からの遷移を文字順に列挙する. 遷移テーブル全体は走査しない
+    fn transitions_from(&self, state: DFAState) -> impl Iterator<Item = (char, DFAState)> + '_ {
+        self.transition
+            .range((state, '\0')..=(state, char::MAX))
+            .map(|((_, chara), to)| (*chara, *to))
+    }
+
+    /// 受理する文字列が存在しないか
+    pub(crate) fn is_empty(&self) -> bool {
+        self.states().iter().all(|state| !self.is_accept(*state))
+    }
+
+    /// 各状態から受理状態までの最短の遷移回数. 受理状態に到達できない状態は含まない
+    pub(crate) fn distances_to_accept(&self) -> HashMap<DFAState, usize> {
+        let mut reverse: HashMap<DFAState, Vec<DFAState>> = HashMap::default();
+        for state in self.states() {
+            for next in self.successors(state) {
+                reverse.entry(next).or_default().push(state);
+            }
+        }
+        let mut distances: HashMap<DFAState, usize> = HashMap::default();
+        let mut queue: VecDeque<DFAState> = VecDeque::new();
+        for state in self.states().into_iter().filter(|s| self.is_accept(*s)) {
+            distances.insert(state, 0);
+            queue.push_back(state);
+        }
+        while let Some(state) = queue.pop_front() {
+            let distance: usize = distances[&state];
+            for prev in reverse.get(&state).into_iter().flatten() {
+                distances.entry(*prev).or_insert_with(|| {
+                    queue.push_back(*prev);
+                    distance + 1
+                });
+            }
+        }
+        distances
+    }
+
+    /// その後にどんな文字列が続いても受理する状態の集合
+    pub(crate) fn always_accepting(&self) -> HashSet<DFAState> {
+        // 「それ以外の文字」による遷移を持つ受理状態から始め, 集合の外へ遷移する状態を取り除いていく
+        let mut states: HashSet<DFAState> = self
+            .states()
+            .into_iter()
+            .filter(|state| self.is_accept(*state) && self.otherwise.contains_key(state))
+            .collect();
+        loop {
+            let escaping: Vec<DFAState> = states
+                .iter()
+                .filter(|state| self.successors(**state).any(|next| !states.contains(&next)))
+                .cloned()
+                .collect();
+            if escaping.is_empty() {
+                return states;
+            }
+            for state in escaping {
+                states.remove(&state);
+            }
+        }
+    }
+
+    /// 受理状態へ到達できる遷移のうち, 開始状態から出るものの文字を, 昇順で重ならない範囲の列にして返す
+    pub(crate) fn start_char_ranges(&self) -> Vec<RangeInclusive<char>> {
+        let live: HashMap<DFAState, usize> = self.distances_to_accept();
+        // 遷移テーブルにある文字と, その遷移が受理状態へ到達できるか
+        let explicit: BTreeMap<char, bool> = self
+            .transition
+            .iter()
+            .filter(|((from, _), _)| *from == self.start)
+            .map(|((_, chara), to)| (*chara, live.contains_key(to)))
+            .collect();
+        let otherwise: bool = self
+            .otherwise
+            .get(&self.start)
+            .is_some_and(|to| live.contains_key(to));
+        let mut ranges: Vec<RangeInclusive<char>> = Vec::new();
+        let mut push = |range: RangeInclusive<char>| match ranges.last_mut() {
+            Some(last) if next_char(*last.end()) == Some(*range.start()) => {
+                *last = *last.start()..=*range.end();
+            }
+            _ => ranges.push(range),
+        };
+        let mut gap: Option<char> = Some('\0');
+        for (chara, included) in explicit {
+            if otherwise
+                && let Some(from) = gap
+                && from < chara
+            {
+                push(from..=prev_char(chara).unwrap());
+            }
+            if included {
+                push(chara..=chara);
+            }
+            gap = next_char(chara);
+        }
+        if otherwise && let Some(from) = gap {
+            push(from..=char::MAX);
+        }
+        ranges
+    }
+
+    /// 受理する文字列のうち最長のものの長さ. 受理する文字列が無限にある場合や, 存在しない場合は `None`
+    pub(crate) fn max_accepted_len(&self) -> Option<usize> {
+        // 受理状態へ到達できる状態のみを辿る
+        let distances: HashMap<DFAState, usize> = self.distances_to_accept();
+        if !distances.contains_key(&self.start) {
+            return None;
+        }
+        // longest[state]: `state` から受理状態までの最長の遷移回数. `None` は探索中
+        let mut longest: HashMap<DFAState, Option<usize>> = HashMap::default();
+        let mut stack: Vec<(DFAState, bool)> = vec![(self.start, false)];
+        while let Some((state, finished)) = stack.pop() {
+            let nexts = self.successors(state).filter(|s| distances.contains_key(s));
+            if finished {
+                let len: Option<usize> = nexts.map(|next| longest[&next].unwrap() + 1).max();
+                longest.insert(state, len.or(Some(0)));
+                continue;
+            }
+            if longest.contains_key(&state) {
+                continue;
+            }
+            longest.insert(state, None);
+            stack.push((state, true));
+            for next in nexts {
+                match longest.get(&next) {
+                    // 探索中の状態に戻る閉路がある
+                    Some(None) => return None,
+                    Some(Some(_)) => {}
+                    None => stack.push((next, false)),
+                }
+            }
+        }
+        longest[&self.start]
+    }
+
+    /// 補集合の言語を受理するDFA
+    pub(crate) fn complement(&self) -> DFA {
+        self.product(self, |accept, _| !accept)
+    }
+
+    /// 直積構成. 受理状態は各DFAでの受理/非受理を `op` で組み合わせて決める
+    pub(crate) fn product(&self, other: &DFA, op: impl Fn(bool, bool) -> bool) -> DFA {
+        // 遷移先が無いことを `None` (行き止まり状態) で表す
+        type Pair = (Option<DFAState>, Option<DFAState>);
+        let alphabet: BTreeSet<char> = self.alphabet().union(&other.alphabet()).cloned().collect();
+        let mut ids: HashMap<Pair, DFAState> = HashMap::default();
+        let mut get_state = |pair: Pair, queue: &mut VecDeque<Pair>| -> DFAState {
+            let len: u32 = ids.len() as u32;
+            *ids.entry(pair).or_insert_with(|| {
+                queue.push_back(pair);
+                DFAState(len)
+            })
+        };
+
+        let mut queue: VecDeque<Pair> = VecDeque::new();
+        let start: DFAState = get_state((Some(self.start), Some(other.start)), &mut queue);
+        let mut accepts: HashSet<DFAState> = HashSet::default();
+        let mut transition: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
+        let mut otherwise: HashMap<DFAState, DFAState> = HashMap::default();
+        let mut from: u32 = 0;
+        while let Some((s1, s2)) = queue.pop_front() {
+            let from_state: DFAState = DFAState(from);
+            from += 1;
+            let accept1: bool = s1.is_some_and(|s| self.is_accept(s));
+            let accept2: bool = s2.is_some_and(|s| other.is_accept(s));
+            if op(accept1, accept2) {
+                accepts.insert(from_state);
+            }
+            for chara in &alphabet {
+                let next: Pair = (
+                    s1.and_then(|s| self.next_state(s, *chara)),
+                    s2.and_then(|s| other.next_state(s, *chara)),
+                );
+                transition.insert((from_state, *chara), get_state(next, &mut queue));
+            }
+            let next: Pair = (
+                s1.and_then(|s| self.otherwise.get(&s).cloned()),
+                s2.and_then(|s| other.otherwise.get(&s).cloned()),
+            );
+            otherwise.insert(from_state, get_state(next, &mut queue));
+        }
+
+        DFA {
+            start,
+            accepts,
+            transition,
+            otherwise,
+        }
+    }
+
+    /// 状態数最小のDFA (Mooreのアルゴリズム)
+    ///
+    /// 受理状態に到達できない状態は取り除き, そこへの遷移は遷移先無しとして表す.
+    pub(crate) fn minimize(&self) -> DFA {
+        let states: Vec<DFAState> = self.states().into_iter().collect();
+        let alphabet: Vec<char> = self.alphabet().into_iter().collect();
+        let index: HashMap<DFAState, usize> =
+            states.iter().enumerate().map(|(i, s)| (*s, i)).collect();
+        // 添字 states.len() は行き止まり状態
+        let sink: usize = states.len();
+        let to_index = |state: Option<DFAState>| state.map(|s| index[&s]).unwrap_or(sink);
+        // next[i][k]: 状態 i から alphabet[k] (k == alphabet.len() のときはそれ以外の文字) で移る状態
+        let next: Vec<Vec<usize>> = states
+            .iter()
+            .map(|state| {
+                alphabet
+                    .iter()
+                    .map(|c| to_index(self.next_state(*state, *c)))
+                    .chain([to_index(self.otherwise.get(state).cloned())])
+                    .collect()
+            })
+            .chain([vec![sink; alphabet.len() + 1]])
+            .collect();
+
+        let mut class: Vec<usize> = (0..=sink)
+            .map(|i| usize::from(i != sink && self.is_accept(states[i])))
+            .collect();
+        let mut classes: usize = class.iter().collect::<HashSet<_>>().len();
+        loop {
+            let mut ids: HashMap<Vec<usize>, usize> = HashMap::default();
+            let refined: Vec<usize> = (0..=sink)
+                .map(|i| {
+                    let signature: Vec<usize> = [class[i]]
+                        .into_iter()
+                        .chain(next[i].iter().map(|j| class[*j]))
+                        .collect();
+                    let len: usize = ids.len();
+                    *ids.entry(signature).or_insert(len)
+                })
+                .collect();
+            class = refined;
+            if ids.len() == classes {
+                break;
+            }
+            classes = ids.len();
+        }
+
+        // 開始状態から幅優先で番号を振り直す
+        let dead: usize = class[sink];
+        let start: usize = class[index[&self.start]];
+        let representative: HashMap<usize, usize> =
+            (0..sink).rev().map(|i| (class[i], i)).collect();
+        let mut ids: HashMap<usize, DFAState> = HashMap::default();
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        if start != dead {
+            ids.insert(start, DFAState(0));
+            queue.push_back(start);
+        }
+        let mut accepts: HashSet<DFAState> = HashSet::default();
+        let mut transition: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
+        let mut otherwise: HashMap<DFAState, DFAState> = HashMap::default();
+        while let Some(from) = queue.pop_front() {
+            let from_state: DFAState = ids[&from];
+            let i: usize = representative[&from];
+            if self.is_accept(states[i]) {
+                accepts.insert(from_state);
+            }
+            let mut get_state = |to: usize| -> DFAState {
+                let len: u32 = ids.len() as u32;
+                *ids.entry(to).or_insert_with(|| {
+                    queue.push_back(to);
+                    DFAState(len)
+                })
+            };
+            let other: usize = class[next[i][alphabet.len()]];
+            if other != dead {
+                otherwise.insert(from_state, get_state(other));
+            }
+            for (k, chara) in alphabet.iter().enumerate() {
+                let to: usize = class[next[i][k]];
+                // それ以外の文字と同じ遷移先なら省略する.
+                // それ以外の文字では受理状態へ進める場合は, 行き止まり状態への遷移も明示する
+                if to != other {
+                    transition.insert((from_state, *chara), get_state(to));
+                }
+            }
+        }
+
+        DFA {
+            start: DFAState(0),
+            accepts,
+            transition,
+            otherwise,
+        }
+    }
+}
+
+/// 次の文字. サロゲートの範囲は飛ばす
+fn next_char(chara: char) -> Option<char> {
+    match chara {
+        '\u{D7FF}' => Some('\u{E000}'),
+        chara => char::from_u32(chara as u32 + 1),
+    }
+}
+
+/// 前の文字. サロゲートの範囲は飛ばす
+fn prev_char(chara: char) -> Option<char> {
+    match chara {
+        '\u{E000}' => Some('\u{D7FF}'),
+        chara => char::from_u32((chara as u32).checked_sub(1)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node))
+    }
+
+    fn accepts(dfa: &DFA, text: &str) -> bool {
+        let mut state: DFAState = dfa.start;
+        for chara in text.chars() {
+            match dfa.next_state(state, chara) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        dfa.is_accept(state)
+    }
+
+    #[test]
+    fn always_accepting() {
+        assert!(dfa(r"ab*").always_accepting().is_empty());
+        // `a` 以外の文字列全体. 開始状態は受理状態だが, `a` を読むと受理しなくなる
+        let complement: DFA = dfa(r"a").complement().minimize();
+        let always: HashSet<DFAState> = complement.always_accepting();
+        assert_eq!(always.len(), 1);
+        assert!(!always.contains(&complement.start));
+        let mut state: DFAState = complement.start;
+        for chara in "ba".chars() {
+            state = complement.next_state(state, chara).unwrap();
+        }
+        assert!(always.contains(&state));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let explicit: DFA = dfa(r"a").complement();
+        // `b` による遷移を明示しても, 「それ以外の文字」と同じ遷移先なら同じ値になる
+        let mut redundant: DFA = explicit.clone();
+        for state in explicit.states() {
+            if let Some(to) = explicit.otherwise.get(&state) {
+                redundant.transition.insert((state, 'b'), *to);
+            }
+        }
+        assert_eq!(redundant.fingerprint(), explicit.fingerprint());
+        assert_ne!(dfa(r"a").fingerprint(), explicit.fingerprint());
+    }
+
+    #[test]
+    fn start_char_ranges() {
+        assert_eq!(dfa(r"(d|b|c)x|a*|e").start_char_ranges(), ['a'..='e']);
+        assert_eq!(dfa(r"(z|a)b").start_char_ranges(), ['a'..='a', 'z'..='z']);
+        // 補集合では, 遷移テーブルに無い文字も含む
+        let complement: DFA = dfa(r"b(a|b)*").complement().minimize();
+        assert_eq!(complement.start_char_ranges(), ['\0'..=char::MAX]);
+        // `b` からは受理状態へ到達できない
+        let minus: DFA = dfa(r"(a|b)*").product(&dfa(r"b(a|b)*"), |a, b| a && !b);
+        assert_eq!(minus.start_char_ranges(), ['a'..='a']);
+        assert_eq!(dfa(r"").start_char_ranges(), []);
+    }
+
+    #[test]
+    fn minimize() {
+        let minimized = dfa(r"(a|b)*").minimize();
+        assert_eq!(minimized.states().len(), 1);
+        assert_eq!(minimized.accepts, HashSet::from_iter([DFAState(0)]));
+
+        let minimized = dfa(r"abc|abd|x*").minimize();
+        assert_eq!(minimized.states().len(), 5);
+        for text in ["abc", "abd", "", "xxx"] {
+            assert!(accepts(&minimized, text));
+        }
+        for text in ["ab", "abx", "xa"] {
+            assert!(!accepts(&minimized, text));
+        }
+    }
+
+    #[test]
+    fn minimize_explicit_dead_state() {
+        // -> 0 --a--> 2
+        //     \--(それ以外)--> 1 --(それ以外)--> 1
+        // accept: 1
+        let dfa = DFA {
+            start: DFAState(0),
+            accepts: HashSet::from_iter([DFAState(1)]),
+            transition: BTreeMap::from_iter([((DFAState(0), 'a'), DFAState(2))]),
+            otherwise: HashMap::from_iter([(DFAState(0), DFAState(1)), (DFAState(1), DFAState(1))]),
+        };
+        let minimized = dfa.minimize();
+        assert_eq!(minimized.states().len(), 3);
+        assert!(accepts(&minimized, "b"));
+        assert!(accepts(&minimized, "ba"));
+        assert!(!accepts(&minimized, "a"));
+        assert!(!accepts(&minimized, "ab"));
+    }
+
+    #[test]
+    fn minimize_empty_language() {
+        let minimized = dfa(r"a").product(&dfa(r"b"), |a, b| a && b).minimize();
+        assert_eq!(minimized.states().len(), 1);
+        assert!(minimized.accepts.is_empty());
+        assert!(minimized.transition.is_empty());
+    }
+
+    #[test]
+    fn complement() {
+        let complement = dfa(r"ab*").complement().minimize();
+        for text in ["", "b", "abc", "ba", "あ"] {
+            assert!(accepts(&complement, text));
+        }
+        for text in ["a", "ab", "abbb"] {
+            assert!(!accepts(&complement, text));
+        }
+    }
+
+    #[test]
+    fn is_empty() {
+        assert!(!dfa(r"a").is_empty());
+        assert!(!dfa(r"").is_empty());
+        assert!(dfa(r"a").product(&dfa(r"b"), |a, b| a && b).is_empty());
+        assert!(dfa(r"a*").product(&dfa(r"(a|)*"), |a, b| a != b).is_empty());
+    }
+
+    #[test]
+    fn shortest_accepted() {
+        assert_eq!(dfa(r"ab*c|bb").shortest_accepted(), Some("ac".to_string()));
+        assert_eq!(dfa(r"(a|b)*").shortest_accepted(), Some("".to_string()));
+        assert_eq!(
+            dfa(r"山田(太|一)郎").shortest_accepted(),
+            Some("山田一郎".to_string())
+        );
+        assert_eq!(
+            dfa(r"a")
+                .product(&dfa(r"b"), |a, b| a && b)
+                .shortest_accepted(),
+            None
+        );
+        assert_eq!(
+            dfa(r"|a").complement().shortest_accepted(),
+            Some("b".to_string())
+        );
+    }
+
+    #[test]
+    fn distances_to_accept() {
+        let dfa = dfa(r"abc|x");
+        let distances = dfa.distances_to_accept();
+        assert_eq!(distances[&dfa.start], 1);
+        assert_eq!(distances.len(), 5);
+    }
+
+    #[test]
+    fn max_accepted_len() {
+        assert_eq!(dfa(r"(p(erl|ython|hp)|ruby)").max_accepted_len(), Some(6));
+        assert_eq!(dfa(r"").max_accepted_len(), Some(0));
+        assert_eq!(dfa(r"ab*").max_accepted_len(), None);
+        assert_eq!(dfa(r"a(b|c)(d|e|)").max_accepted_len(), Some(3));
+        assert_eq!(
+            dfa(r"a")
+                .product(&dfa(r"b"), |a, b| a && b)
+                .max_accepted_len(),
+            None
+        );
+        assert_eq!(dfa(r"a").complement().max_accepted_len(), None);
+    }
+
+    #[test]
+    fn product() {
+        let intersection = dfa(r"a*b").product(&dfa(r"ab*"), |a, b| a && b);
+        assert!(accepts(&intersection, "ab"));
+        assert!(!accepts(&intersection, "b"));
+        assert!(!accepts(&intersection, "a"));
+        assert!(!accepts(&intersection, "aab"));
+    }
+}
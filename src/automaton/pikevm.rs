@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use crate::parser::{Kind, Node, NodeRef};
+
+/// キャプチャグループの位置を求めるための命令列 (Pike VM)
+///
+/// スロット `2i`, `2i + 1` にグループ `i` の開始位置と終了位置を記録する. グループ 0 はマッチ全体.
+#[derive(Clone, Debug)]
+pub(crate) struct Program {
+    insts: Vec<Inst>,
+    /// グループ 0 を含むグループの数
+    groups: usize,
+    /// 各グループの名前. グループ 0 は常に `None`
+    names: Arc<[Option<String>]>,
+    /// どのマッチでも同じグループが関与する場合, グループ 0 を含むその数
+    static_groups: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Inst {
+    Char(char),
+    /// 両方に分岐する. 前者を優先する
+    Split(usize, usize),
+    Jump(usize),
+    /// 現在位置をスロットに記録する
+    Save(usize),
+    Match,
+}
+
+/// 各スレッドの位置 (命令の番号) とスロット
+type Threads = Vec<(usize, Vec<Option<usize>>)>;
+
+impl Program {
+    pub(crate) fn from_node(node: &Node) -> Self {
+        let mut insts: Vec<Inst> = vec![Inst::Save(0)];
+        compile(node.root(), &mut insts);
+        insts.push(Inst::Save(1));
+        insts.push(Inst::Match);
+        let groups: usize = node.root().groups() + 1;
+        Program {
+            insts,
+            groups,
+            names: vec![None; groups].into(),
+            static_groups: node.root().static_groups().map(|n| n + 1),
+        }
+    }
+
+    /// `node` を `min` 回以上 `max` 回以下 (`None` なら上限なし) 繰り返す命令列
+    ///
+    /// 繰り返しを展開した構文木は入れ子が回数に比例して深くなるので, 各回の命令を平らに並べる.
+    /// 各回のグループは同じスロットを使うので, 最後の繰り返しでマッチした部分が残る.
+    pub(crate) fn repeat(node: &Node, min: usize, max: Option<usize>) -> Self {
+        let mut insts: Vec<Inst> = vec![Inst::Save(0)];
+        for _ in 0..min {
+            compile(node.root(), &mut insts);
+        }
+        match max {
+            None => compile_star(node.root(), &mut insts),
+            Some(max) => {
+                // Split(L1, L3); L1: node; Split(L2, L3); L2: node; ...; L3:
+                let splits: Vec<usize> = (min..max)
+                    .map(|_| {
+                        let split: usize = insts.len();
+                        insts.push(Inst::Split(split + 1, 0));
+                        compile(node.root(), &mut insts);
+                        split
+                    })
+                    .collect();
+                for split in splits {
+                    insts[split] = Inst::Split(split + 1, insts.len());
+                }
+            }
+        }
+        insts.push(Inst::Save(1));
+        insts.push(Inst::Match);
+        let groups: usize = node.root().groups() + 1;
+        // 回数が変わりうる場合, 関与するグループも変わりうる
+        let static_groups: Option<usize> = match node.root().static_groups() {
+            Some(0) => Some(1),
+            Some(n) if max == Some(min) && min > 0 => Some(n + 1),
+            _ => None,
+        };
+        Program {
+            insts,
+            groups,
+            names: vec![None; groups].into(),
+            static_groups,
+        }
+    }
+
+    /// グループ 1 以降の名前 ([`Parser::take_group_names`](crate::parser::Parser::take_group_names) の結果) を設定する
+    pub(crate) fn with_names(mut self, names: Vec<Option<String>>) -> Self {
+        debug_assert_eq!(names.len() + 1, self.groups);
+        self.names = [None].into_iter().chain(names).collect();
+        self
+    }
+
+    /// グループ 0 を含むグループの数
+    pub(crate) fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// どのマッチでも同じグループが関与する場合, グループ 0 を含むその数
+    pub(crate) fn static_groups(&self) -> Option<usize> {
+        self.static_groups
+    }
+
+    /// 各グループの名前. グループ 0 は常に `None`
+    pub(crate) fn names(&self) -> &Arc<[Option<String>]> {
+        &self.names
+    }
+
+    /// `text[start..end]` 全体にマッチさせたときの各スロットの値.
+    /// 複数の分け方がある場合は, 選択肢は左のものを, 繰り返しは長いものを優先する
+    pub(crate) fn captures(
+        &self,
+        text: &str,
+        start: usize,
+        end: usize,
+    ) -> Option<Vec<Option<usize>>> {
+        let mut current: Threads = Vec::new();
+        // 各命令を最後にたどった位置. 位置ごとに全体を消さずに済むよう, 真偽値ではなく位置を記録する
+        let mut seen: Vec<usize> = vec![usize::MAX; self.insts.len()];
+        self.add(
+            &mut current,
+            &mut seen,
+            0,
+            start,
+            vec![None; self.groups * 2],
+        );
+        let mut chars = text[start..end].char_indices();
+        loop {
+            let next_char: Option<(usize, char)> = chars.next();
+            let Some((i, chara)) = next_char else {
+                // 終端に達したスレッドのうち, 最も優先度の高いもの
+                return current
+                    .into_iter()
+                    .find(|(pc, _)| matches!(self.insts[*pc], Inst::Match))
+                    .map(|(_, slots)| slots);
+            };
+            let pos: usize = start + i + chara.len_utf8();
+            let mut next: Threads = Vec::new();
+            for (pc, slots) in current {
+                if let Inst::Char(c) = self.insts[pc]
+                    && c == chara
+                {
+                    self.add(&mut next, &mut seen, pc + 1, pos, slots);
+                }
+            }
+            if next.is_empty() {
+                return None;
+            }
+            current = next;
+        }
+    }
+
+    /// 空遷移をたどって `pc` から到達できるスレッドを優先度順に追加する
+    ///
+    /// 空文字列にマッチする繰り返しでは空遷移が長く連なるので, 再帰せずにスタックでたどる.
+    fn add(
+        &self,
+        threads: &mut Threads,
+        seen: &mut [usize],
+        pc: usize,
+        pos: usize,
+        slots: Vec<Option<usize>>,
+    ) {
+        let mut stack: Threads = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = stack.pop() {
+            if seen[pc] == pos {
+                continue;
+            }
+            seen[pc] = pos;
+            match self.insts[pc] {
+                Inst::Jump(to) => stack.push((to, slots)),
+                Inst::Split(first, second) => {
+                    // 後に取り出す方を先に積む
+                    stack.push((second, slots.clone()));
+                    stack.push((first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[slot] = Some(pos);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Char(_) | Inst::Match => threads.push((pc, slots)),
+            }
+        }
+    }
+}
+
+fn compile(node: NodeRef<'_>, insts: &mut Vec<Inst>) {
+    match node.kind() {
+        Kind::Character(chara) => insts.push(Inst::Char(chara)),
+        Kind::Empty => {}
+        Kind::Star(node) => compile_star(node, insts),
+        Kind::Union(n1, n2) => {
+            // Split(L1, L2); L1: n1; Jump(L3); L2: n2; L3:
+            let split: usize = insts.len();
+            insts.push(Inst::Split(split + 1, 0));
+            compile(n1, insts);
+            let jump: usize = insts.len();
+            insts.push(Inst::Jump(0));
+            insts[split] = Inst::Split(split + 1, insts.len());
+            compile(n2, insts);
+            insts[jump] = Inst::Jump(insts.len());
+        }
+        Kind::Concat(n1, n2) => {
+            compile(n1, insts);
+            compile(n2, insts);
+        }
+        Kind::Group(index, node) => {
+            insts.push(Inst::Save(index * 2));
+            compile(node, insts);
+            insts.push(Inst::Save(index * 2 + 1));
+        }
+    }
+}
+
+fn compile_star(node: NodeRef<'_>, insts: &mut Vec<Inst>) {
+    // L0: Split(L1, L2); L1: node; Jump(L0); L2:
+    let split: usize = insts.len();
+    insts.push(Inst::Split(split + 1, 0));
+    compile(node, insts);
+    insts.push(Inst::Jump(split));
+    insts[split] = Inst::Split(split + 1, insts.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn program(pattern: &str) -> Program {
+        let node = Parser::new(Lexer::new(pattern))
+            .with_captures()
+            .parse()
+            .unwrap();
+        Program::from_node(&node)
+    }
+
+    fn spans(pattern: &str, text: &str) -> Option<Vec<Option<(usize, usize)>>> {
+        let slots = program(pattern).captures(text, 0, text.len())?;
+        Some(
+            slots
+                .chunks(2)
+                .map(|pair| Some((pair[0]?, pair[1]?)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn captures() {
+        assert_eq!(
+            spans(r"山田(太|一)郎", "山田太郎"),
+            Some(vec![Some((0, 12)), Some((6, 9))])
+        );
+        assert_eq!(
+            spans(r"(a|b)*c", "abac"),
+            Some(vec![Some((0, 4)), Some((2, 3))])
+        );
+        assert_eq!(
+            spans(r"a(x)*|(b)", "a"),
+            Some(vec![Some((0, 1)), None, None])
+        );
+        assert_eq!(spans(r"ab", "abc"), None);
+    }
+
+    #[test]
+    fn priority() {
+        // 繰り返しは長いものを優先する
+        assert_eq!(
+            spans(r"(a*)(a*)", "aa"),
+            Some(vec![Some((0, 2)), Some((0, 2)), Some((2, 2))])
+        );
+        // 選択肢は左のものを優先する
+        assert_eq!(
+            spans(r"(a|ab)(b|)", "ab"),
+            Some(vec![Some((0, 2)), Some((0, 1)), Some((1, 2))])
+        );
+    }
+
+    #[test]
+    fn empty_loop() {
+        assert_eq!(
+            spans(r"(a|)*", "aa"),
+            Some(vec![Some((0, 2)), Some((1, 2))])
+        );
+        assert_eq!(program(r"(a)(b(c))").groups(), 4);
+    }
+
+    #[test]
+    fn repeat() {
+        let node = Parser::new(Lexer::new(r"(a|)b"))
+            .with_captures()
+            .parse()
+            .unwrap();
+        let program = Program::repeat(&node, 2, Some(3));
+        let slots = |text: &str| program.captures(text, 0, text.len());
+        assert_eq!(slots("bab"), Some(vec![Some(0), Some(3), Some(1), Some(2)]));
+        assert_eq!(
+            slots("abbab"),
+            Some(vec![Some(0), Some(5), Some(3), Some(4)])
+        );
+        assert_eq!(slots("b"), None);
+        assert_eq!(slots("bbbb"), None);
+        assert_eq!(program.static_groups(), None);
+        assert_eq!(
+            Program::repeat(&node, 2, None)
+                .captures("bbbb", 0, 4)
+                .map(|s| s[1]),
+            Some(Some(4))
+        );
+        assert_eq!(Program::repeat(&node, 2, Some(2)).static_groups(), Some(2));
+
+        // 空文字列にマッチする繰り返しでも, 空遷移を再帰せずにたどる
+        let node = Parser::new(Lexer::new(r"a*")).parse().unwrap();
+        let program = Program::repeat(&node, 0, Some(100_000));
+        assert_eq!(program.captures("aa", 0, 2), Some(vec![Some(0), Some(2)]));
+    }
+}
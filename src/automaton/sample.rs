@@ -0,0 +1,68 @@
+use rand::{Rng, RngExt};
+
+use crate::automaton::{DFA, DFAState};
+use crate::hash::HashMap;
+
+impl DFA {
+    /// 受理する長さ `max_len` 以下の文字列をランダムに1つ生成する
+    pub(crate) fn sample<R: Rng + ?Sized>(&self, rng: &mut R, max_len: usize) -> Option<String> {
+        let distances: HashMap<DFAState, usize> = self.distances_to_accept();
+        if distances.get(&self.start).is_none_or(|d| *d > max_len) {
+            return None;
+        }
+        let mut ret: String = String::new();
+        let mut state: DFAState = self.start;
+        for remaining in (0..max_len).rev() {
+            // 残りの文字数で受理状態に到達できる遷移のみを候補にする
+            let candidates: Vec<(char, DFAState)> = self
+                .edges(state)
+                .into_iter()
+                .filter(|(_, next)| distances.get(next).is_some_and(|d| *d <= remaining))
+                .collect();
+            if candidates.is_empty()
+                || self.is_accept(state) && rng.random_ratio(1, candidates.len() as u32 + 1)
+            {
+                break;
+            }
+            let (chara, next) = candidates[rng.random_range(0..candidates.len())];
+            ret.push(chara);
+            state = next;
+        }
+        Some(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node))
+    }
+
+    #[test]
+    fn sample() {
+        let mut rng = StdRng::seed_from_u64(2357);
+        let dfa = dfa(r"(ab)*c|x*");
+        for _ in 0..100 {
+            let sample = dfa.sample(&mut rng, 6).unwrap();
+            assert!(sample.chars().count() <= 6);
+            assert!(sample.chars().all(|c| c == 'x') || sample.ends_with('c'));
+        }
+        assert_eq!(dfa.sample(&mut rng, 0), Some("".to_string()));
+    }
+
+    #[test]
+    fn sample_too_short() {
+        let mut rng = StdRng::seed_from_u64(2357);
+        assert_eq!(dfa(r"abc").sample(&mut rng, 2), None);
+        assert_eq!(dfa(r"abc").sample(&mut rng, 3), Some("abc".to_string()));
+    }
+}
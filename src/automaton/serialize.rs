@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+/// 直列化した DFA の先頭に置く識別子
+const MAGIC: &[u8; 4] = b"RDFA";
+/// 直列化の形式を変えたら増やす
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+impl DFA {
+    /// リトルエンディアンの u32 の列に直列化する
+    ///
+    /// 形式: MAGIC, FORMAT_VERSION, 開始状態, 受理状態の数, 受理状態..., 遷移の数, (遷移元, 文字, 遷移先)...,
+    /// 「それ以外の文字」による遷移の数, (遷移元, 遷移先)...
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut accepts: Vec<u32> = self.accepts.iter().map(|s| s.0).collect();
+        accepts.sort();
+        let mut transition: Vec<(u32, u32, u32)> = self
+            .transition
+            .iter()
+            .map(|((from, chara), to)| (from.0, *chara as u32, to.0))
+            .collect();
+        transition.sort();
+        let mut otherwise: Vec<(u32, u32)> = self
+            .otherwise
+            .iter()
+            .map(|(from, to)| (from.0, to.0))
+            .collect();
+        otherwise.sort();
+
+        let mut words: Vec<u32> = vec![FORMAT_VERSION, self.start.0, accepts.len() as u32];
+        words.extend(accepts);
+        words.push(transition.len() as u32);
+        words.extend(transition.into_iter().flat_map(|(f, c, t)| [f, c, t]));
+        words.push(otherwise.len() as u32);
+        words.extend(otherwise.into_iter().flat_map(|(f, t)| [f, t]));
+
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        bytes.extend(words.into_iter().flat_map(u32::to_le_bytes));
+        bytes
+    }
+
+    /// [`DFA::to_bytes`] の逆. 形式が合わない場合は `None`
+    ///
+    /// 同じ受理状態や遷移を重ねて含むもの, 開始状態から到達できない状態を含むものも `None` にする.
+    /// [`DFA::to_bytes`] で直列化するDFAは, どれも到達できる状態のみからなる.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let body: &[u8] = bytes.strip_prefix(MAGIC)?;
+        if !body.len().is_multiple_of(4) {
+            return None;
+        }
+        let mut words = body
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+        let mut next = || words.next();
+
+        if next()? != FORMAT_VERSION {
+            return None;
+        }
+        let start: DFAState = DFAState(next()?);
+        let len: u32 = next()?;
+        let accepts: HashSet<DFAState> = (0..len)
+            .map(|_| next().map(DFAState))
+            .collect::<Option<_>>()?;
+        if accepts.len() != len as usize {
+            return None;
+        }
+        let len: u32 = next()?;
+        let transition: BTreeMap<(DFAState, char), DFAState> = (0..len)
+            .map(|_| {
+                let (from, chara, to) = (next()?, char::from_u32(next()?)?, next()?);
+                Some(((DFAState(from), chara), DFAState(to)))
+            })
+            .collect::<Option<_>>()?;
+        if transition.len() != len as usize {
+            return None;
+        }
+        let len: u32 = next()?;
+        let otherwise: HashMap<DFAState, DFAState> = (0..len)
+            .map(|_| Some((DFAState(next()?), DFAState(next()?))))
+            .collect::<Option<_>>()?;
+        if otherwise.len() != len as usize || next().is_some() {
+            return None;
+        }
+        let dfa: DFA = DFA {
+            start,
+            accepts,
+            transition,
+            otherwise,
+        };
+        let states: BTreeSet<DFAState> = dfa.states();
+        let known: bool = dfa
+            .accepts
+            .iter()
+            .chain(dfa.transition.iter().flat_map(|((from, _), to)| [from, to]))
+            .chain(dfa.otherwise.iter().flat_map(|(from, to)| [from, to]))
+            .all(|state| states.contains(state));
+        known.then_some(dfa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node))
+    }
+
+    #[test]
+    fn round_trip() {
+        for dfa in [dfa(r"山田(太|一)郎"), dfa(r"a*b").complement()] {
+            let restored = DFA::from_bytes(&dfa.to_bytes()).unwrap();
+            assert_eq!(restored.start, dfa.start);
+            assert_eq!(restored.accepts, dfa.accepts);
+            assert_eq!(restored.transition, dfa.transition);
+            assert_eq!(restored.otherwise, dfa.otherwise);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let bytes = dfa(r"ab").to_bytes();
+        assert!(DFA::from_bytes(&bytes[..bytes.len() - 4]).is_none());
+        assert!(DFA::from_bytes(&bytes[1..]).is_none());
+        let mut extra = bytes.clone();
+        extra.extend([0, 0, 0, 0]);
+        assert!(DFA::from_bytes(&extra).is_none());
+        let mut version = bytes.clone();
+        version[4] += 1;
+        assert!(DFA::from_bytes(&version).is_none());
+
+        // 到達できない受理状態を加える
+        let dfa: DFA = dfa(r"ab");
+        let mut unreachable: DFA = dfa.clone();
+        unreachable.accepts.insert(DFAState(100));
+        assert!(DFA::from_bytes(&unreachable.to_bytes()).is_none());
+        // 遷移先が存在しない
+        let mut dangling: DFA = dfa.clone();
+        dangling.otherwise.insert(dfa.start, DFAState(100));
+        dangling.otherwise.insert(DFAState(100), DFAState(101));
+        assert!(DFA::from_bytes(&dangling.to_bytes()).is_some());
+        let mut dangling: DFA = dfa.clone();
+        dangling.otherwise.insert(DFAState(100), DFAState(101));
+        assert!(DFA::from_bytes(&dangling.to_bytes()).is_none());
+        // 同じ受理状態を重ねて含む (MAGIC, FORMAT_VERSION, 開始状態の後に受理状態の数が来る)
+        let mut duplicated: Vec<u8> = bytes[..20].to_vec();
+        duplicated[12] += 1;
+        duplicated.extend_from_slice(&bytes[16..]);
+        assert!(DFA::from_bytes(&duplicated).is_none());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::automaton::{AsciiTable, DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+use crate::prefilter::Prefilter;
+
+/// 最も左にあるマッチの開始位置を, 開始位置を1つずつ試さずに絞り込むためのDFAの組
+///
+/// 1. `forward` で前向きに読み, いずれかのマッチが終わる最も早い位置 `end` を求める.
+///    最も左のマッチはそれ以前に始まる.
+/// 2. `reverse` で `end` から後ろ向きに読み, マッチの接頭辞が始まりうる最も左の位置を求める.
+///    `end` で終わるマッチがあるので, この位置は必ず見つかる.
+/// 3. その位置から `end` までを, 元のDFAで最長一致を試す.
+#[derive(Clone, Debug)]
+pub(crate) struct TwoPass {
+    /// 任意の位置から始まるマッチを受理するDFA (`.*R` に相当)
+    forward: DFA,
+    forward_table: Option<AsciiTable>,
+    /// マッチの接頭辞を逆順にしたものを受理するDFA
+    reverse: DFA,
+    reverse_table: Option<AsciiTable>,
+}
+
+impl TwoPass {
+    /// 元のDFAの状態がこれより多い場合は作らない
+    const MAX_INPUT_STATES: usize = 128;
+    /// 作るDFAの状態がこれより多くなる場合は諦める
+    const MAX_STATES: usize = 512;
+
+    pub(crate) fn new(dfa: &DFA) -> Option<Self> {
+        let states: BTreeSet<DFAState> = dfa.states();
+        if states.len() > Self::MAX_INPUT_STATES {
+            return None;
+        }
+        // 受理状態に到達できない状態は, どちらの向きでも候補に含めない
+        let live: BTreeSet<DFAState> = dfa.distances_to_accept().into_keys().collect();
+        let mut alphabet: Vec<char> = dfa.alphabet().into_iter().collect();
+        alphabet.push(dfa.other_char());
+
+        let forward: DFA = subsets(
+            &alphabet,
+            [dfa.start].into(),
+            |set, chara| {
+                set.iter()
+                    .filter_map(|state| dfa.next_state(*state, chara))
+                    .filter(|next| live.contains(next))
+                    .chain([dfa.start])
+                    .collect()
+            },
+            |set| set.iter().any(|state| dfa.is_accept(*state)),
+        )?;
+        let reverse: DFA = subsets(
+            &alphabet,
+            live.clone(),
+            |set, chara| {
+                live.iter()
+                    .filter(|state| {
+                        dfa.next_state(**state, chara)
+                            .is_some_and(|next| set.contains(&next))
+                    })
+                    .cloned()
+                    .collect()
+            },
+            |set| set.contains(&dfa.start),
+        )?;
+        Some(TwoPass {
+            forward_table: AsciiTable::new(&forward),
+            forward,
+            reverse_table: AsciiTable::new(&reverse),
+            reverse,
+        })
+    }
+
+    /// `text[at..]` 中のいずれかのマッチが終わる最も早い位置
+    ///
+    /// `forward` の開始状態にいる間は, 元のDFAの開始状態しか候補が無いので, `prefilter` で次の候補まで読み飛ばす.
+    pub(crate) fn earliest_end(
+        &self,
+        text: &str,
+        mut at: usize,
+        prefilter: Option<&dyn Prefilter>,
+    ) -> Option<usize> {
+        let start: DFAState = self.forward.start;
+        let mut state: DFAState = start;
+        if self.forward.is_accept(state) {
+            return Some(at);
+        }
+        loop {
+            if state == start
+                && let Some(prefilter) = prefilter
+            {
+                at = prefilter.find(text, at)?;
+            }
+            let chara: char = text[at..].chars().next()?;
+            state = match &self.forward_table {
+                Some(table) => table.next_state(&self.forward, state, chara),
+                None => self.forward.next_state(state, chara),
+            }?;
+            at += chara.len_utf8();
+            let accept: bool = match &self.forward_table {
+                Some(table) => table.is_accept(state),
+                None => self.forward.is_accept(state),
+            };
+            if accept {
+                return Some(at);
+            }
+        }
+    }
+
+    /// `text[from..end]` のうち `end` で終わり, マッチの接頭辞であるものが始まる最も左の位置. 無ければ `end`
+    pub(crate) fn leftmost_start(&self, text: &str, from: usize, end: usize) -> usize {
+        let mut state: DFAState = self.reverse.start;
+        let mut start: usize = end;
+        for (i, chara) in text[from..end].char_indices().rev() {
+            let next: Option<DFAState> = match &self.reverse_table {
+                Some(table) => table.next_state(&self.reverse, state, chara),
+                None => self.reverse.next_state(state, chara),
+            };
+            match next {
+                Some(next) => state = next,
+                None => break,
+            }
+            let accept: bool = match &self.reverse_table {
+                Some(table) => table.is_accept(state),
+                None => self.reverse.is_accept(state),
+            };
+            if accept {
+                start = from + i;
+            }
+        }
+        start
+    }
+}
+
+/// 元のDFAの状態の集合を状態とするDFAを, 部分集合構成法で作る. 空集合への遷移は遷移先無しとする
+///
+/// `alphabet` の最後の文字は「それ以外の文字」の代表として扱う.
+fn subsets(
+    alphabet: &[char],
+    start: BTreeSet<DFAState>,
+    step: impl Fn(&BTreeSet<DFAState>, char) -> BTreeSet<DFAState>,
+    accept: impl Fn(&BTreeSet<DFAState>) -> bool,
+) -> Option<DFA> {
+    let (other, chars) = alphabet.split_last()?;
+    let mut ids: HashMap<BTreeSet<DFAState>, DFAState> =
+        HashMap::from_iter([(start.clone(), DFAState(0))]);
+    let mut queue: VecDeque<BTreeSet<DFAState>> = [start].into();
+    let mut accepts: HashSet<DFAState> = HashSet::default();
+    let mut transition: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
+    let mut otherwise: HashMap<DFAState, DFAState> = HashMap::default();
+    while let Some(set) = queue.pop_front() {
+        let from: DFAState = ids[&set];
+        if accept(&set) {
+            accepts.insert(from);
+        }
+        for chara in chars.iter().chain([other]) {
+            let next: BTreeSet<DFAState> = step(&set, *chara);
+            if next.is_empty() {
+                continue;
+            }
+            let len: u32 = ids.len() as u32;
+            let to: DFAState = *ids.entry(next).or_insert_with_key(|next| {
+                queue.push_back(next.clone());
+                DFAState(len)
+            });
+            if chara == other {
+                otherwise.insert(from, to);
+            } else {
+                transition.insert((from, *chara), to);
+            }
+        }
+        if ids.len() > TwoPass::MAX_STATES {
+            return None;
+        }
+    }
+    Some(DFA {
+        start: DFAState(0),
+        accepts,
+        transition,
+        otherwise,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automaton::NFA;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn dfa(pattern: &str) -> DFA {
+        let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+        DFA::from_nfa(NFA::from_node(node))
+    }
+
+    #[test]
+    fn two_pass() {
+        let two_pass: TwoPass = TwoPass::new(&dfa(r"abc|b")).unwrap();
+        // 最も早く終わるのは "b" だが, 最も左のマッチは "abc"
+        assert_eq!(two_pass.earliest_end("xxabc", 0, None), Some(4));
+        assert_eq!(two_pass.leftmost_start("xxabc", 0, 4), 2);
+        assert_eq!(two_pass.leftmost_start("xxabc", 3, 4), 3);
+        assert_eq!(two_pass.earliest_end("xxac", 0, None), None);
+
+        let two_pass: TwoPass = TwoPass::new(&dfa(r"(a|b)*c")).unwrap();
+        assert_eq!(two_pass.earliest_end("xabzabbc", 0, None), Some(8));
+        assert_eq!(two_pass.leftmost_start("xabzabbc", 0, 8), 4);
+        assert_eq!(two_pass.leftmost_start("xabzabbc", 5, 8), 5);
+
+        let two_pass: TwoPass = TwoPass::new(&dfa(r"山*")).unwrap();
+        assert_eq!(two_pass.earliest_end("x", 0, None), Some(0));
+        assert_eq!(two_pass.leftmost_start("x", 0, 0), 0);
+    }
+
+    #[test]
+    fn too_many_states() {
+        // n 文字前が a であることを調べる `.*` 付きのDFAは状態が 2^n 個になる
+        let dfa: DFA = dfa(&format!("a{}", "(a|b)".repeat(10)));
+        assert!(TwoPass::new(&dfa).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+use std::error::Error;
+
+use crate::{Cache, Regex};
+
+/// 真偽値を1ビットずつ詰めた列. [`Regex::is_match_slice`] などが返す
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BitVec {
+    /// `i` 番目の値は `words[i / 64]` の下から `i % 64` ビット目にある. 末尾の余りのビットは 0
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVec {
+    pub fn new() -> Self {
+        BitVec::default()
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        BitVec {
+            words: Vec::with_capacity(capacity.div_ceil(64)),
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        self.words[self.len / 64] |= (bit as u64) << (self.len % 64);
+        self.len += 1;
+    }
+
+    /// `index` 番目の値. 範囲外ならパニックする
+    pub fn get(&self, index: usize) -> bool {
+        assert!(
+            index < self.len,
+            "index {} out of range for length {}",
+            index,
+            self.len
+        );
+        self.words[index / 64] >> (index % 64) & 1 == 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// `true` の数
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// `true` である位置を小さい順に返す
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word: u64 = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit: usize = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+
+    /// 64個ずつ詰めた値. 他の `BitVec` との論理演算などに使う
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl Regex {
+    /// 複数のパターンをまとめてコンパイルする. 結果は `patterns` と同じ順に並ぶ
+    ///
+    /// `rayon` 機能が有効な場合は複数のスレッドでコンパイルする.
+    pub fn new_many<S: AsRef<str> + Sync>(patterns: &[S]) -> Vec<Result<Regex, Box<dyn Error>>> {
+        // `Box<dyn Error>` はスレッド間で送れないので, エラーはメッセージにしてから戻す
+        let compile = |pattern: &S| Regex::new(pattern.as_ref()).map_err(|err| err.to_string());
+        #[cfg(feature = "rayon")]
+        let results: Vec<Result<Regex, String>> = {
+            use rayon::prelude::*;
+            patterns.par_iter().map(compile).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<Result<Regex, String>> = patterns.iter().map(compile).collect();
+        results
+            .into_iter()
+            .map(|result| result.map_err(Into::into))
+            .collect()
+    }
+
+    /// `texts` の各文字列が [`Regex::is_match`] を満たすかを並べたビット列
+    ///
+    /// 照合の途中経過 ([`Cache`]) を全ての文字列で共有するので, 短い文字列を大量に調べる場合に速い.
+    pub fn is_match_slice(&self, texts: &[&str]) -> BitVec {
+        self.is_match_iter(texts.iter().copied())
+    }
+
+    /// [`Regex::is_match_slice`] と同じ. 文字列をイテレータから受け取る
+    pub fn is_match_iter<'a>(&self, texts: impl IntoIterator<Item = &'a str>) -> BitVec {
+        let texts = texts.into_iter();
+        let mut bits: BitVec = BitVec::with_capacity(texts.size_hint().0);
+        let mut cache: Cache = Cache::new();
+        for text in texts {
+            bits.push(self.is_match_with(&mut cache, text));
+        }
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_many() {
+        let patterns: Vec<String> = (0..100).map(|i| format!("a{}(b|c)*", i)).collect();
+        let regexes = Regex::new_many(&patterns);
+        assert_eq!(regexes.len(), 100);
+        for (i, regex) in regexes.iter().enumerate() {
+            let regex = regex.as_ref().unwrap();
+            assert_eq!(regex.as_str(), patterns[i]);
+            assert!(regex.matches(format!("a{}bc", i)));
+        }
+    }
+
+    #[test]
+    fn new_many_error() {
+        let results = Regex::new_many(&["a", "a(", "b"]);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            Regex::new("a(").unwrap_err().to_string()
+        );
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn is_match_slice() {
+        for pattern in [r"ab", r"a(b|c)*d", &format!("({})*b", "a|".repeat(65))] {
+            let regex = Regex::new(pattern).unwrap();
+            let texts: Vec<String> = (0..200)
+                .map(|i| ["x", "ab", "acbd", "aab", ""][i % 5].repeat(i % 3))
+                .collect();
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            let bits: BitVec = regex.is_match_slice(&texts);
+            assert_eq!(bits.len(), texts.len());
+            for (i, text) in texts.iter().enumerate() {
+                assert_eq!(
+                    bits.get(i),
+                    regex.is_match(text),
+                    "{:?} {:?}",
+                    pattern,
+                    text
+                );
+            }
+            let ones: Vec<usize> = bits.iter_ones().collect();
+            assert_eq!(ones.len(), bits.count_ones());
+            assert!(ones.iter().all(|&i| bits.get(i)));
+            assert_eq!(regex.is_match_iter(texts.iter().copied()), bits);
+        }
+        assert!(Regex::new(r"a").unwrap().is_match_slice(&[]).is_empty());
+    }
+
+    #[test]
+    fn bit_vec() {
+        let mut bits = BitVec::new();
+        for i in 0..130 {
+            bits.push(i % 3 == 0);
+        }
+        assert_eq!((bits.len(), bits.count_ones()), (130, 44));
+        assert!(bits.get(129) && !bits.get(128));
+        assert_eq!(bits.as_words().len(), 3);
+        assert_eq!(bits.iter_ones().nth(43), Some(129));
+    }
+}
@@ -0,0 +1,296 @@
+//! grep のように, パターンにマッチする行を出力する
+//!
+//! 使い方: regrep [-ivnco] [-A NUM] [-B NUM] [-C NUM] [--color=auto|always|never] PATTERN [FILE...]
+//!
+//! マッチ部分の色付けに [`Highlighter`] を使うので, `term` 機能が必要.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::process::ExitCode;
+
+use ryota2357_regex::{Highlighter, Regex, RegexBuilder};
+
+const USAGE: &str = "usage: regrep [-ivnco] [-A NUM] [-B NUM] [-C NUM] [--color=auto|always|never] PATTERN [FILE...]";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    /// -i: 大文字と小文字を区別しない
+    ignore_case: bool,
+    /// -v: マッチしない行を出力する
+    invert: bool,
+    /// -n: 行番号を出力する
+    line_number: bool,
+    /// -c: 行の代わりにマッチした行数を出力する
+    count: bool,
+    /// -o: マッチした部分のみを出力する
+    only_matching: bool,
+    /// -B NUM: 選ばれた行の前の行数
+    before: usize,
+    /// -A NUM: 選ばれた行の後の行数
+    after: usize,
+    color: bool,
+    pattern: String,
+    files: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>, terminal: bool) -> Result<Options, String> {
+    let mut options: Options = Options::default();
+    let mut color: Option<bool> = None;
+    let mut positional: Vec<String> = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positional.extend(args.by_ref());
+        } else if let Some(when) = arg.strip_prefix("--color=") {
+            color = match when {
+                "always" => Some(true),
+                "never" => Some(false),
+                "auto" => None,
+                _ => return Err(format!("invalid --color value: {}", when)),
+            };
+        } else if arg.starts_with('-') && arg.len() > 1 && positional.is_empty() {
+            for (i, flag) in arg[1..].char_indices() {
+                match flag {
+                    'i' => options.ignore_case = true,
+                    'v' => options.invert = true,
+                    'n' => options.line_number = true,
+                    'c' => options.count = true,
+                    'o' => options.only_matching = true,
+                    'A' | 'B' | 'C' => {
+                        // 値は同じ引数の残り (-A3) か次の引数 (-A 3)
+                        let rest: &str = &arg[1 + i + 1..];
+                        let value: String = match rest {
+                            "" => args.next().ok_or(format!("missing NUM for -{}", flag))?,
+                            rest => rest.to_string(),
+                        };
+                        let lines: usize = value
+                            .parse()
+                            .map_err(|_| format!("invalid NUM for -{}: {}", flag, value))?;
+                        if flag != 'A' {
+                            options.before = lines;
+                        }
+                        if flag != 'B' {
+                            options.after = lines;
+                        }
+                        break;
+                    }
+                    _ => return Err(format!("unknown option: -{}", flag)),
+                }
+            }
+        } else {
+            positional.push(arg);
+        }
+    }
+    let mut positional = positional.into_iter();
+    options.pattern = positional.next().ok_or("missing PATTERN")?;
+    options.files = positional.collect();
+    options.color = color.unwrap_or(terminal);
+    Ok(options)
+}
+
+/// `reader` の各行を調べて出力し, 選ばれた行の数を返す
+fn grep(
+    regex: &Regex,
+    options: &Options,
+    reader: impl BufRead,
+    name: Option<&str>,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut error: Option<io::Error> = None;
+    let lines = reader.split(b'\n').map_while(|line| match line {
+        Ok(mut line) => {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            Some(String::from_utf8_lossy(&line).into_owned())
+        }
+        Err(err) => {
+            error = Some(err);
+            None
+        }
+    });
+    let (before, after) = if options.count || options.only_matching {
+        (0, 0)
+    } else {
+        (options.before, options.after)
+    };
+    // マッチ部分の色は Highlighter の既定 (太字の赤)
+    let highlighter: Option<Highlighter> = options.color.then(Highlighter::default);
+    let mut selected: usize = 0;
+    let mut last: Option<usize> = None;
+    for line in regex
+        .grep(lines)
+        .before(before)
+        .after(after)
+        .invert(options.invert)
+    {
+        let line_is_match: bool = line.is_match();
+        if line_is_match {
+            selected += 1;
+        }
+        if options.count {
+            continue;
+        }
+        // 前後の行を出力する場合, 連続しない行の間に区切りを入れる
+        if before + after > 0 && last.is_some_and(|last| last + 1 < line.number()) {
+            writeln!(out, "--")?;
+        }
+        last = Some(line.number());
+        let separator: char = if line_is_match { ':' } else { '-' };
+        let prefix: String = match (name, options.line_number) {
+            (Some(name), true) => format!("{}{}{}{}", name, separator, line.number(), separator),
+            (Some(name), false) => format!("{}{}", name, separator),
+            (None, true) => format!("{}{}", line.number(), separator),
+            (None, false) => String::new(),
+        };
+        let line: &str = line.text();
+        if options.only_matching {
+            if !options.invert {
+                for found in regex.find_iter(line).filter(|m| !m.as_str().is_empty()) {
+                    match &highlighter {
+                        Some(highlighter) => {
+                            writeln!(out, "{}{}", prefix, highlighter.wrap(found.as_str()))?
+                        }
+                        None => writeln!(out, "{}{}", prefix, found.as_str())?,
+                    }
+                }
+            }
+        } else if let Some(highlighter) = highlighter.as_ref().filter(|_| !options.invert) {
+            writeln!(out, "{}{}", prefix, highlighter.highlight(regex, line))?;
+        } else {
+            writeln!(out, "{}{}", prefix, line)?;
+        }
+    }
+    if let Some(err) = error {
+        return Err(err);
+    }
+    if options.count {
+        match name {
+            Some(name) => writeln!(out, "{}:{}", name, selected)?,
+            None => writeln!(out, "{}", selected)?,
+        }
+    }
+    Ok(selected)
+}
+
+fn main() -> ExitCode {
+    let stdout = io::stdout();
+    let options: Options = match parse_args(std::env::args().skip(1), stdout.is_terminal()) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("regrep: {}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let regex: Regex = match RegexBuilder::new(&options.pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(err) => {
+            eprintln!("regrep: invalid pattern: {}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut selected: usize = 0;
+    let mut failed: bool = false;
+    if options.files.is_empty() {
+        match grep(&regex, &options, io::stdin().lock(), None, &mut out) {
+            Ok(n) => selected += n,
+            Err(err) => {
+                eprintln!("regrep: (standard input): {}", err);
+                failed = true;
+            }
+        }
+    }
+    let show_name: bool = options.files.len() > 1;
+    for file in &options.files {
+        let name: Option<&str> = show_name.then_some(file.as_str());
+        let result = File::open(file)
+            .and_then(|f| grep(&regex, &options, BufReader::new(f), name, &mut out));
+        match result {
+            Ok(n) => selected += n,
+            Err(err) => {
+                eprintln!("regrep: {}: {}", file, err);
+                failed = true;
+            }
+        }
+    }
+    if let Err(err) = out.flush() {
+        eprintln!("regrep: {}", err);
+        failed = true;
+    }
+    if failed {
+        ExitCode::from(2)
+    } else if selected > 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|s| s.to_string()), false)
+    }
+
+    fn run(args: &[&str], input: &str) -> String {
+        let options = self::args(args).unwrap();
+        let regex = RegexBuilder::new(&options.pattern)
+            .case_insensitive(options.ignore_case)
+            .build()
+            .unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        grep(&regex, &options, input.as_bytes(), None, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let options = args(&["-in", "--color=always", "a*", "x.txt", "-v"]).unwrap();
+        assert!(options.ignore_case && options.line_number && options.color);
+        assert!(!options.invert);
+        assert_eq!(options.pattern, "a*");
+        assert_eq!(options.files, vec!["x.txt", "-v"]);
+        assert!(args(&["-x", "a"]).is_err());
+        assert!(args(&["-i"]).is_err());
+        assert_eq!(args(&["--", "-a"]).unwrap().pattern, "-a");
+        let options = args(&["-nA2", "-B", "3", "a"]).unwrap();
+        assert_eq!((options.before, options.after), (3, 2));
+        assert!(options.line_number);
+        let options = args(&["-C1", "a"]).unwrap();
+        assert_eq!((options.before, options.after), (1, 1));
+        assert!(args(&["-Ax", "a"]).is_err());
+        assert!(args(&["-B"]).is_err());
+    }
+
+    #[test]
+    fn options() {
+        let input = "山田太郎\n山田次郎\nYAMADA\n";
+        assert_eq!(run(&["山田(太|一)郎"], input), "山田太郎\n");
+        assert_eq!(run(&["-v", "山田"], input), "YAMADA\n");
+        assert_eq!(run(&["-n", "山田"], input), "1:山田太郎\n2:山田次郎\n");
+        assert_eq!(run(&["-c", "山田"], input), "2\n");
+        assert_eq!(run(&["-o", "(太|次)郎"], input), "太郎\n次郎\n");
+        assert_eq!(run(&["-i", "yamada"], input), "YAMADA\n");
+        assert_eq!(
+            run(&["-n", "-C1", "次"], input),
+            "1-山田太郎\n2:山田次郎\n3-YAMADA\n"
+        );
+        assert_eq!(run(&["-A", "1", "a"], "a\nb\nc\nd\na\n"), "a\nb\n--\na\n");
+        assert_eq!(run(&["-B1", "-c", "b"], "a\nb\n"), "1\n");
+        assert_eq!(
+            run(&["--color=always", "a"], "banana\n"),
+            "b\x1b[1;31ma\x1b[0mn\x1b[1;31ma\x1b[0mn\x1b[1;31ma\x1b[0m\n"
+        );
+        assert_eq!(
+            run(&["--color=always", "-o", "a"], "ba\n"),
+            "\x1b[1;31ma\x1b[0m\n"
+        );
+    }
+}
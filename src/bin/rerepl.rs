@@ -0,0 +1,221 @@
+//! パターンと文字列を対話的に入力して, マッチするかを確かめる
+//!
+//! 使い方: rerepl [PATTERN]
+//!
+//! `:` で始まる行はコマンド, それ以外の行は照合する文字列として扱う.
+//! 文字列ごとに, 全体がマッチするか, マッチしない場合は行き詰まった位置と受理される文字,
+//! 最初に見つかったマッチを表示する. `:trace` を有効にするとDFAの遷移も表示する.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+
+use ryota2357_regex::{Regex, RegexBuilder};
+
+const USAGE: &str = "usage: rerepl [PATTERN]";
+
+const HELP: &str = "\
+:pattern PATTERN  set the pattern (also :p)
+:ignore-case      toggle case-insensitive matching (also :i)
+:trace            toggle printing the DFA transitions (also :t)
+:help             show this help (also :h)
+:quit             exit (also :q)
+other lines are matched against the current pattern";
+
+/// 入力の間保持する設定
+#[derive(Debug, Default)]
+struct Session {
+    pattern: Option<String>,
+    regex: Option<Regex>,
+    ignore_case: bool,
+    trace: bool,
+}
+
+impl Session {
+    /// パターンを設定し直す. コンパイルに失敗した場合は前のパターンのまま
+    fn set_pattern(&mut self, pattern: &str, out: &mut impl Write) -> io::Result<()> {
+        match RegexBuilder::new(pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+        {
+            Ok(regex) => {
+                self.pattern = Some(pattern.to_string());
+                self.regex = Some(regex);
+                writeln!(out, "pattern: {}", pattern)
+            }
+            Err(err) => writeln!(out, "invalid pattern: {}", err),
+        }
+    }
+
+    /// 1行を処理する. 終了する場合は `false`
+    fn eval(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let Some(command) = line.strip_prefix(':') else {
+            self.test(line, out)?;
+            return Ok(true);
+        };
+        let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+        match name {
+            "p" | "pattern" => self.set_pattern(arg, out)?,
+            "i" | "ignore-case" => {
+                self.ignore_case = !self.ignore_case;
+                writeln!(
+                    out,
+                    "ignore-case: {}",
+                    if self.ignore_case { "on" } else { "off" }
+                )?;
+                if let Some(pattern) = self.pattern.take() {
+                    self.set_pattern(&pattern, out)?;
+                }
+            }
+            "t" | "trace" => {
+                self.trace = !self.trace;
+                writeln!(out, "trace: {}", if self.trace { "on" } else { "off" })?;
+            }
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "unknown command: :{} (try :help)", name)?,
+        }
+        Ok(true)
+    }
+
+    /// `text` を現在のパターンで照合した結果を表示する
+    fn test(&self, text: &str, out: &mut impl Write) -> io::Result<()> {
+        let Some(regex) = &self.regex else {
+            return writeln!(out, "no pattern (set one with :pattern PATTERN)");
+        };
+        match regex.explain_failure(text) {
+            None => writeln!(out, "match")?,
+            Some(failure) => writeln!(out, "no match: {}", failure)?,
+        }
+        match regex.find(text) {
+            Some(found) => writeln!(
+                out,
+                "find: {:?} at {}..{}",
+                found.as_str(),
+                found.start(),
+                found.end()
+            )?,
+            None => writeln!(out, "find: none")?,
+        }
+        if self.trace {
+            writeln!(out, "trace: start {}", regex.start_state())?;
+            for step in regex.trace(text) {
+                let to: String = step.to().map_or("dead".to_string(), |to| to.to_string());
+                writeln!(
+                    out,
+                    "  {:>4} {:?} {} -> {}",
+                    step.offset(),
+                    step.char(),
+                    step.from(),
+                    to
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `reader` から1行ずつ読んで処理する. 端末から読む場合はプロンプトを出す
+fn run(
+    session: &mut Session,
+    reader: impl BufRead,
+    out: &mut impl Write,
+    prompt: bool,
+) -> io::Result<()> {
+    let mut lines = reader.lines();
+    loop {
+        if prompt {
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line: String = line?;
+        let line: &str = line.strip_suffix('\r').unwrap_or(&line);
+        if !session.eval(line, out)? {
+            break;
+        }
+    }
+    if prompt {
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let pattern: Option<String> = args.next();
+    if args.next().is_some() {
+        eprintln!("rerepl: too many arguments\n{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut session: Session = Session::default();
+    let prompt: bool = stdin.is_terminal();
+    if prompt {
+        println!("type :help for commands");
+    }
+    let result = pattern
+        .map_or(Ok(()), |pattern| session.set_pattern(&pattern, &mut out))
+        .and_then(|_| run(&mut session, stdin.lock(), &mut out, prompt));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("rerepl: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_lines(input: &str) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        run(&mut Session::default(), input.as_bytes(), &mut out, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn session() {
+        let output = run_lines(":p 山田(太|一)郎\n山田一郎\n私は山田太郎\n:q\nignored\n");
+        assert_eq!(
+            output,
+            "pattern: 山田(太|一)郎\n\
+             match\n\
+             find: \"山田一郎\" at 0..12\n\
+             no match: Expected one of ['山'], found '私' at 0\n\
+             find: \"山田太郎\" at 6..18\n"
+        );
+        assert_eq!(
+            run_lines("abc\n:p (a\n:x\n"),
+            "no pattern (set one with :pattern PATTERN)\n\
+             invalid pattern: "
+                .to_string()
+                + &RegexBuilder::new("(a").build().unwrap_err().to_string()
+                + "\nunknown command: :x (try :help)\n"
+        );
+    }
+
+    #[test]
+    fn options() {
+        let output = run_lines(":p ab\n:i\nAB\n:t\naX\n");
+        assert_eq!(
+            output,
+            "pattern: ab\n\
+             ignore-case: on\n\
+             pattern: ab\n\
+             match\n\
+             find: \"AB\" at 0..2\n\
+             trace: on\n\
+             no match: Expected one of ['B', 'b'], found 'X' at 1\n\
+             find: none\n\
+             trace: start 0\n     \
+             0 'a' 0 -> 2\n     \
+             1 'X' 2 -> dead\n"
+        );
+    }
+}
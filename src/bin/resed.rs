@@ -0,0 +1,316 @@
+//! sed の `s` コマンドのように, 各行のマッチした部分を置き換えて出力する
+//!
+//! 使い方: resed s/PATTERN/REPLACEMENT/[gi] [FILE...]
+//!
+//! 区切り文字には `s` の直後の任意の文字を使える. REPLACEMENT 中の `\1`..`\9` はグループ,
+//! `&` はマッチ全体を表し, `\&`, `\\`, `\n` はそれぞれ `&`, `\`, 改行を表す.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::ExitCode;
+
+use ryota2357_regex::{Captures, Regex, RegexBuilder};
+
+const USAGE: &str = "usage: resed s/PATTERN/REPLACEMENT/[gi] [FILE...]";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Command {
+    pattern: String,
+    replacement: Vec<Piece>,
+    /// g: 行中のすべてのマッチを置き換える
+    global: bool,
+    /// i: 大文字と小文字を区別しない
+    ignore_case: bool,
+}
+
+/// 置換後の文字列の断片
+#[derive(Debug, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    /// グループの番号. 0 はマッチ全体
+    Group(usize),
+}
+
+fn parse_command(script: &str) -> Result<Command, String> {
+    let rest: &str = script
+        .strip_prefix('s')
+        .ok_or_else(|| format!("unknown command: {}", script))?;
+    let delimiter: char = rest.chars().next().ok_or("missing delimiter")?;
+    if delimiter == '\\' || delimiter == '\n' {
+        return Err(format!("invalid delimiter: {:?}", delimiter));
+    }
+    let rest: &str = &rest[delimiter.len_utf8()..];
+    let (pattern, rest) = split_field(rest, delimiter).ok_or("unterminated `s' command")?;
+    let (replacement, flags) = split_field(rest, delimiter).ok_or("unterminated `s' command")?;
+
+    let mut command: Command = Command {
+        pattern: unescape_delimiter(pattern, delimiter),
+        replacement: parse_replacement(replacement, delimiter),
+        global: false,
+        ignore_case: false,
+    };
+    for flag in flags.chars() {
+        match flag {
+            'g' => command.global = true,
+            'i' | 'I' => command.ignore_case = true,
+            _ => return Err(format!("unknown option to `s': {}", flag)),
+        }
+    }
+    Ok(command)
+}
+
+/// エスケープされていない最初の `delimiter` で分ける
+fn split_field(text: &str, delimiter: char) -> Option<(&str, &str)> {
+    let mut chars = text.char_indices();
+    while let Some((i, chara)) = chars.next() {
+        if chara == '\\' {
+            chars.next();
+        } else if chara == delimiter {
+            return Some((&text[..i], &text[i + delimiter.len_utf8()..]));
+        }
+    }
+    None
+}
+
+/// パターン中の `\<delimiter>` を区切り文字そのものに戻す. それ以外のエスケープは正規表現に任せる
+fn unescape_delimiter(pattern: &str, delimiter: char) -> String {
+    let mut result: String = String::new();
+    let mut chars = pattern.chars();
+    while let Some(chara) = chars.next() {
+        if chara != '\\' {
+            result.push(chara);
+            continue;
+        }
+        match chars.next() {
+            Some(next) if next == delimiter => result.push(next),
+            Some(next) => {
+                result.push('\\');
+                result.push(next);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn parse_replacement(replacement: &str, delimiter: char) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut literal: String = String::new();
+    let mut chars = replacement.chars();
+    while let Some(chara) = chars.next() {
+        let group: Option<usize> = match chara {
+            '&' => Some(0),
+            '\\' => match chars.next() {
+                Some(digit @ '1'..='9') => digit.to_digit(10).map(|d| d as usize),
+                Some('n') => {
+                    literal.push('\n');
+                    None
+                }
+                Some(next @ ('&' | '\\')) => {
+                    literal.push(next);
+                    None
+                }
+                Some(next) if next == delimiter => {
+                    literal.push(next);
+                    None
+                }
+                Some(next) => {
+                    literal.push('\\');
+                    literal.push(next);
+                    None
+                }
+                None => {
+                    literal.push('\\');
+                    None
+                }
+            },
+            chara => {
+                literal.push(chara);
+                None
+            }
+        };
+        if let Some(group) = group {
+            if !literal.is_empty() {
+                pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+            }
+            pieces.push(Piece::Group(group));
+        }
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(literal));
+    }
+    pieces
+}
+
+/// 置換後の文字列を組み立てる. 関与しなかったグループは空文字列になる
+fn expand(pieces: &[Piece], caps: &Captures<'_>) -> String {
+    let mut result: String = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Literal(literal) => result.push_str(literal),
+            Piece::Group(index) => {
+                if let Some(m) = caps.get(*index) {
+                    result.push_str(m.as_str());
+                }
+            }
+        }
+    }
+    result
+}
+
+/// `reader` の各行を置き換えて出力する
+fn substitute(
+    regex: &Regex,
+    command: &Command,
+    reader: impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    for line in reader.split(b'\n') {
+        let line: Vec<u8> = line?;
+        let line = String::from_utf8_lossy(&line);
+        let (line, cr) = match line.strip_suffix('\r') {
+            Some(line) => (line, "\r"),
+            None => (&*line, ""),
+        };
+        let replaced = if command.global {
+            // sed と同じく, 直前のマッチの終わりに接する空文字列へのマッチは置き換えない
+            let mut last: Option<usize> = None;
+            regex.replace_all_with(line, |caps| {
+                let found = caps.get(0).unwrap();
+                let adjacent: bool = found.as_str().is_empty() && last == Some(found.start());
+                last = Some(found.end());
+                if adjacent {
+                    String::new()
+                } else {
+                    expand(&command.replacement, caps)
+                }
+            })
+        } else {
+            regex.replace_with(line, |caps| expand(&command.replacement, caps))
+        };
+        writeln!(out, "{}{}", replaced, cr)?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command: Command = match args.next().ok_or("missing command".to_string()) {
+        Ok(script) => match parse_command(&script) {
+            Ok(command) => command,
+            Err(message) => {
+                eprintln!("resed: {}\n{}", message, USAGE);
+                return ExitCode::from(2);
+            }
+        },
+        Err(message) => {
+            eprintln!("resed: {}\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let regex: Regex = match RegexBuilder::new(&command.pattern)
+        .case_insensitive(command.ignore_case)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(err) => {
+            eprintln!("resed: invalid pattern: {}", err);
+            return ExitCode::from(2);
+        }
+    };
+    if let Some(Piece::Group(index)) = command
+        .replacement
+        .iter()
+        .find(|piece| matches!(piece, Piece::Group(i) if *i >= regex.captures_len()))
+    {
+        eprintln!("resed: invalid reference \\{} on `s' command's RHS", index);
+        return ExitCode::from(2);
+    }
+
+    let files: Vec<String> = args.collect();
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut failed: bool = false;
+    if files.is_empty()
+        && let Err(err) = substitute(&regex, &command, io::stdin().lock(), &mut out)
+    {
+        eprintln!("resed: (standard input): {}", err);
+        failed = true;
+    }
+    for file in &files {
+        let result = File::open(file)
+            .and_then(|f| substitute(&regex, &command, BufReader::new(f), &mut out));
+        if let Err(err) = result {
+            eprintln!("resed: {}: {}", file, err);
+            failed = true;
+        }
+    }
+    if let Err(err) = out.flush() {
+        eprintln!("resed: {}", err);
+        failed = true;
+    }
+    if failed {
+        ExitCode::from(2)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(script: &str, input: &str) -> String {
+        let command = parse_command(script).unwrap();
+        let regex = RegexBuilder::new(&command.pattern)
+            .case_insensitive(command.ignore_case)
+            .build()
+            .unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        substitute(&regex, &command, input.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let command = parse_command(r"s|a\|b|[&]\1\&\\|gi").unwrap();
+        assert_eq!(command.pattern, "a|b");
+        assert_eq!(
+            command.replacement,
+            vec![
+                Piece::Literal("[".to_string()),
+                Piece::Group(0),
+                Piece::Literal("]".to_string()),
+                Piece::Group(1),
+                Piece::Literal(r"&\".to_string()),
+            ]
+        );
+        assert!(command.global && command.ignore_case);
+        assert_eq!(parse_command(r"s/a\*/b/").unwrap().pattern, r"a\*");
+        assert!(parse_command("s/a/b").is_err());
+        assert!(parse_command("s/a/b/x").is_err());
+        assert!(parse_command("y/a/b/").is_err());
+    }
+
+    #[test]
+    fn commands() {
+        let input = "山田太郎と山田一郎\nYAMADA\n";
+        assert_eq!(
+            run(r"s/山田(太|一)郎/\1さん/", input),
+            "太さんと山田一郎\nYAMADA\n"
+        );
+        assert_eq!(
+            run(r"s/山田(太|一)郎/\1さん/g", input),
+            "太さんと一さん\nYAMADA\n"
+        );
+        assert_eq!(
+            run(r"s/yamada/<&>/i", input),
+            "山田太郎と山田一郎\n<YAMADA>\n"
+        );
+        // 直前のマッチに接する空文字列へのマッチは置き換えない
+        assert_eq!(run(r"s/a*/-/g", "baac\n"), "-b-c-\n");
+        assert_eq!(run(r"s/a*/-/g", "aa\n"), "-\n");
+        assert_eq!(run(r"s/b*/-/g", "abc\n"), "-a-c-\n");
+        assert_eq!(run(r"s/(a)|b/[\1]/g", "ab\n"), "[a][]\n");
+    }
+}
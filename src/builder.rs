@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+use crate::automaton::{BitNFA, DFA, NFA, Program};
+use crate::disk_cache;
+use crate::hook::{AstHook, AstNode, Nfa, NfaHook};
+use crate::lexer::{self, Lexer};
+use crate::lint::Warning;
+use crate::parser::{Node, Parser};
+use crate::prefilter::{CharSetPrefilter, Prefilter, ReverseSuffix};
+use crate::trace::{self, DeterminizationTrace};
+use crate::{Engine, Origin, Regex, Slot};
+
+/// NFAの構成法
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Construction {
+    /// Thompson構成. 空遷移を含むNFAを作る
+    #[default]
+    Thompson,
+    /// Glushkov構成 (位置オートマトン). 空遷移を含まないNFAを作る
+    Glushkov,
+}
+
+/// 設定を指定して [`Regex`] を作る
+#[derive(Clone, Debug)]
+pub struct RegexBuilder {
+    pattern: String,
+    construction: Construction,
+    case_insensitive: bool,
+    deferred: bool,
+    cache_dir: Option<PathBuf>,
+    /// 状態の並びを決めるために照合してみる文字列
+    profile: Option<String>,
+    /// `\g<name>` で参照できる部分パターンの名前とパターン (定義順)
+    definitions: Vec<(String, String)>,
+    /// パターンから作るものの代わりに使う, 探索開始位置の候補を見つけるフィルタ
+    prefilter: Option<Arc<dyn Prefilter>>,
+    /// 構文解析の後に構文木を書き換えるフック (登録順)
+    ast_hooks: Vec<Arc<dyn AstHook>>,
+    /// 決定化の前にNFAを書き換えるフック (登録順)
+    nfa_hooks: Vec<Arc<dyn NfaHook>>,
+}
+
+/// 構文解析のみ済ませ, 初めて使われるときにコンパイルするパターン
+#[derive(Clone, Debug)]
+pub(crate) struct Deferred {
+    builder: RegexBuilder,
+    node: Node,
+    compiled: OnceLock<Engine>,
+}
+
+impl Deferred {
+    pub(crate) fn engine(&self) -> &Engine {
+        self.compiled.get_or_init(|| {
+            enter_span!(DEBUG, "compile", pattern = %self.builder.pattern);
+            self.builder.compile(self.node.clone())
+        })
+    }
+
+    pub(crate) fn is_compiled(&self) -> bool {
+        self.compiled.get().is_some()
+    }
+}
+
+impl RegexBuilder {
+    pub fn new(pattern: &str) -> Self {
+        RegexBuilder {
+            pattern: pattern.to_string(),
+            construction: Construction::default(),
+            case_insensitive: false,
+            deferred: false,
+            cache_dir: None,
+            profile: None,
+            definitions: Vec::new(),
+            prefilter: None,
+            ast_hooks: Vec::new(),
+            nfa_hooks: Vec::new(),
+        }
+    }
+
+    /// パターンを置き換える. 部分パターンの定義などの設定はそのまま使う
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.pattern = pattern.to_string();
+        self
+    }
+
+    /// パターン中の `\g<name>` で参照できる部分パターンを定義する
+    ///
+    /// `name` は ASCII の英数字と '_' からなり, 数字で始まらないこと. 部分パターンはそれより前に定義した部分パターンを参照できる.
+    /// 部分パターン中の括弧はキャプチャグループにならない.
+    pub fn define(mut self, name: &str, pattern: &str) -> Self {
+        self.definitions
+            .push((name.to_string(), pattern.to_string()));
+        self
+    }
+
+    /// DFAを作る前段のNFAの構成法を指定する
+    pub fn construction(mut self, construction: Construction) -> Self {
+        self.construction = construction;
+        self
+    }
+
+    /// `true` の場合, 大文字と小文字を区別しない
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// `true` の場合, [`RegexBuilder::build`] は構文解析のみを行い,
+    /// オートマトンの構築は初めて照合するとき (または [`Regex::ensure_compiled`]) まで遅らせる
+    pub fn deferred(mut self, deferred: bool) -> Self {
+        self.deferred = deferred;
+        self
+    }
+
+    /// 決定化したDFAを `dir` に保存し, 次回以降 (別のプロセスでも) はそれを読み込む
+    ///
+    /// キャッシュはパターン, 構成法, crate のバージョンごとに作られ, 読み書きに失敗した場合は通常通りコンパイルする.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// 照合する入力の見本. DFAの状態を `sample` を照合したときに多く訪れた順に並べ, 遷移表のよく使う部分を近くに置く
+    ///
+    /// 照合の結果は変わらない. 見本の長さとマッチの長さの積程度の時間がコンパイルに加わる.
+    /// 指定しない場合は, 開始状態と自己ループを持つ状態を前に並べる.
+    pub fn profile(mut self, sample: &str) -> Self {
+        self.profile = Some(sample.to_string());
+        self
+    }
+
+    /// 探索開始位置の候補を, パターンから作るフィルタの代わりに `prefilter` で見つける
+    ///
+    /// 索引などから候補を得られる場合に使う. パターンが文字列そのもの (演算子を含まない) の場合は使われない.
+    pub fn prefilter(mut self, prefilter: impl Prefilter + 'static) -> Self {
+        self.prefilter = Some(Arc::new(prefilter));
+        self
+    }
+
+    /// 構文解析の後に `hook` で構文木を書き換える. 複数登録した場合は登録順に適用する
+    ///
+    /// フックを登録するとディスクキャッシュ ([`RegexBuilder::cache_dir`]) は使われない.
+    pub fn ast_hook(mut self, hook: impl AstHook + 'static) -> Self {
+        self.ast_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// NFAを作った後, 決定化する前に `hook` でNFAを書き換える. 複数登録した場合は登録順に適用する
+    ///
+    /// フックを登録すると, パターンが文字列そのものの場合などもNFAを経由してコンパイルし,
+    /// ディスクキャッシュ ([`RegexBuilder::cache_dir`]) は使われない. キャプチャグループの位置は書き換える前の構文木から求める.
+    pub fn nfa_hook(mut self, hook: impl NfaHook + 'static) -> Self {
+        self.nfa_hooks.push(Arc::new(hook));
+        self
+    }
+
+    pub fn build(&self) -> Result<Regex, Box<dyn Error>> {
+        self.build_with_warnings().map(|(regex, _)| regex)
+    }
+
+    /// [`RegexBuilder::build`] と同じく `Regex` を作り, パターン中の冗長な構文の警告 ([`lint`](crate::lint) と同じもの) も返す
+    pub fn build_with_warnings(&self) -> Result<(Regex, Vec<Warning>), Box<dyn Error>> {
+        enter_span!(DEBUG, "compile", pattern = %self.pattern);
+        let definitions: HashMap<String, Node> = self.definitions()?;
+        let mut parser: Parser<'_> = Parser::new(Lexer::new(&self.pattern))
+            .with_definitions(&definitions)
+            .with_captures();
+        let node: Node = {
+            enter_span!(DEBUG, "parse");
+            parser.parse()?
+        };
+        let names: Vec<Option<String>> = parser.take_group_names();
+        Ok((self.regex(node, names), parser.take_warnings()))
+    }
+
+    /// 部分パターンを定義順に構文解析する
+    fn definitions(&self) -> Result<HashMap<String, Node>, Box<dyn Error>> {
+        let mut definitions: HashMap<String, Node> = HashMap::new();
+        for (name, pattern) in &self.definitions {
+            if !lexer::is_identifier(name) {
+                return Err(format!("Invalid sub-pattern name {:?}", name).into());
+            }
+            let node: Node = Parser::new(Lexer::new(pattern))
+                .with_definitions(&definitions)
+                .parse()?;
+            definitions.insert(name.clone(), node);
+        }
+        Ok(definitions)
+    }
+
+    /// ディスクキャッシュのキーに使うパターン. 部分パターンの定義も含める
+    fn cache_key(&self) -> String {
+        let mut key: String = self.pattern.clone();
+        for (name, pattern) in &self.definitions {
+            key.push_str(&format!("\0{}={}", name, pattern));
+        }
+        key
+    }
+
+    /// [`Regex::concat`] などで組み立てた構文木から, 既定の設定で `Regex` を作る
+    pub(crate) fn from_node(node: Node, names: Vec<Option<String>>) -> Regex {
+        RegexBuilder::new(&node.to_string()).regex(node, names)
+    }
+
+    /// キャプチャグループを含む構文木と各グループの名前から `Regex` を作る
+    fn regex(&self, node: Node, mut names: Vec<Option<String>>) -> Regex {
+        let node: Node = self.transform(node);
+        // フックでグループが増減した場合, 名前はパターン中の番号のものだけ残す
+        names.resize(node.root().groups(), None);
+        let program: Program = Program::from_node(&node).with_names(names);
+        let ast: Node = node.clone();
+        let node: Node = node.without_groups();
+        let engine: Slot = if self.deferred {
+            Slot::Deferred(Box::new(Deferred {
+                builder: self.clone(),
+                node,
+                compiled: OnceLock::new(),
+            }))
+        } else {
+            Slot::Ready(self.compile(node))
+        };
+        Regex {
+            pattern: self.pattern.as_str().into(),
+            case_insensitive: self.case_insensitive,
+            engine: Arc::new(engine),
+            program: Some(Arc::new(program)),
+            ast: Some(Arc::new(ast)),
+            // フックで言語が変わりうるので, パターン文字列では比較できない
+            origin: if self.ast_hooks.is_empty() && self.nfa_hooks.is_empty() {
+                Origin::Pattern(self.definitions.as_slice().into())
+            } else {
+                Origin::Language(OnceLock::new())
+            },
+        }
+    }
+
+    /// 設定とフックに応じて構文木を書き換える
+    fn transform(&self, node: Node) -> Node {
+        let node: Node = if self.case_insensitive {
+            node.case_insensitive()
+        } else {
+            node
+        };
+        self.ast_hooks.iter().fold(node, |node, hook| {
+            hook.rewrite(AstNode::from(node.root())).into()
+        })
+    }
+
+    fn compile(&self, node: Node) -> Engine {
+        let engine: Engine = self.compile_engine(node);
+        match &self.prefilter {
+            Some(prefilter) => engine.with_prefilter(prefilter.clone()),
+            None => engine,
+        }
+    }
+
+    fn compile_engine(&self, node: Node) -> Engine {
+        // NFAのフックは, NFAを経由しない照合には適用できない
+        let shortcut: bool = self.nfa_hooks.is_empty();
+        if shortcut && let Some(literal) = node.root().literal() {
+            event!(DEBUG, engine = "literal", len = literal.len());
+            return Engine::Literal(literal);
+        }
+        // NFAのフックで言語が変わりうるので, 構文木から求める接頭辞と接尾辞は使えない
+        let (prefix, suffix): (String, Option<ReverseSuffix>) = if shortcut {
+            (node.root().literal_prefix(), ReverseSuffix::new(&node))
+        } else {
+            (String::new(), None)
+        };
+        if shortcut && let Some(nfa) = BitNFA::from_node(&node) {
+            let prefilter: Option<Arc<dyn Prefilter>> = if nfa.is_nullable() {
+                None
+            } else {
+                CharSetPrefilter::new(nfa.start_chars()).map(|p| Arc::new(p) as Arc<dyn Prefilter>)
+            };
+            event!(DEBUG, engine = "bitparallel");
+            return Engine::BitParallel {
+                nfa,
+                prefilter,
+                prefix,
+                suffix,
+            };
+        }
+        // キャッシュのキーはフックを区別できない
+        let cache_dir: Option<&PathBuf> = self
+            .cache_dir
+            .as_ref()
+            .filter(|_| self.ast_hooks.is_empty() && self.nfa_hooks.is_empty());
+        if let Some(dir) = cache_dir
+            && let Some(dfa) = disk_cache::load(
+                dir,
+                &self.cache_key(),
+                self.construction,
+                self.case_insensitive,
+            )
+        {
+            event!(DEBUG, engine = "automaton", cache = "hit");
+            return Engine::automaton(dfa, prefix, suffix, self.profile.as_deref());
+        }
+        let nfa: NFA = self.nfa(node);
+        let dfa: DFA = {
+            enter_span!(DEBUG, "determinize");
+            DFA::from_nfa(nfa)
+        };
+        if let Some(dir) = cache_dir {
+            let _result = disk_cache::store(
+                dir,
+                &self.cache_key(),
+                self.construction,
+                self.case_insensitive,
+                &dfa,
+            );
+            event!(DEBUG, cache = "store", ok = _result.is_ok());
+        }
+        event!(
+            DEBUG,
+            engine = "automaton",
+            dfa_states = dfa.states().len(),
+            dfa_transitions = dfa.transition.len()
+        );
+        Engine::automaton(dfa, prefix, suffix, self.profile.as_deref())
+    }
+
+    /// パターンから作ったNFAに部分集合構成法を適用し, その途中経過を返す
+    ///
+    /// [`RegexBuilder::build`] と異なり, パターンによらず常にNFAからDFAを作る.
+    pub fn determinization_trace(&self) -> Result<DeterminizationTrace, Box<dyn Error>> {
+        let definitions: HashMap<String, Node> = self.definitions()?;
+        let node: Node = Parser::new(Lexer::new(&self.pattern))
+            .with_definitions(&definitions)
+            .parse()?;
+        Ok(trace::determinize(&self.nfa(self.transform(node))))
+    }
+
+    fn nfa(&self, node: Node) -> NFA {
+        enter_span!(DEBUG, "nfa", construction = ?self.construction);
+        let nfa: NFA = match self.construction {
+            Construction::Thompson => NFA::from_node(node),
+            Construction::Glushkov => NFA::glushkov(&node),
+        };
+        event!(
+            DEBUG,
+            nfa_transitions = nfa.transition.iter().map(Vec::len).sum::<usize>()
+        );
+        if self.nfa_hooks.is_empty() {
+            return nfa;
+        }
+        self.nfa_hooks
+            .iter()
+            .fold(Nfa::new(nfa), |nfa, hook| hook.rewrite(nfa))
+            .into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiteralPrefilter;
+
+    #[test]
+    fn glushkov() {
+        let pattern: String = format!("({})*b", "a|".repeat(65));
+        for construction in [Construction::Thompson, Construction::Glushkov] {
+            let regex = RegexBuilder::new(&pattern)
+                .construction(construction)
+                .build()
+                .unwrap();
+            assert!(matches!(regex.engine(), Engine::Automaton { .. }));
+            assert!(regex.matches("aab"));
+            assert!(regex.matches("b"));
+            assert!(!regex.matches("aa"));
+            assert_eq!(regex.find("xaab").unwrap().as_str(), "aab");
+        }
+    }
+
+    #[test]
+    fn case_insensitive() {
+        for pattern in [r"ab", r"a(b|c)*", &format!("({})*b", "a|".repeat(65))] {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .unwrap();
+            assert!(regex.matches("AB"));
+            assert!(regex.matches("aB"));
+        }
+        let regex = RegexBuilder::new(r"Straße")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert_eq!(regex.find("die STRAßE").unwrap().start(), 4);
+        assert_ne!(regex, Regex::new(r"Straße").unwrap());
+    }
+
+    #[test]
+    fn deferred() {
+        for pattern in [r"abc", r"a(b|c)*", &format!("({})*b", "a|".repeat(65))] {
+            let regex = RegexBuilder::new(pattern).deferred(true).build().unwrap();
+            assert!(!regex.is_compiled());
+            regex.is_match("abc");
+            assert!(regex.is_compiled());
+            let eager = Regex::new(pattern).unwrap();
+            for text in ["abc", "xabcb", "aab", ""] {
+                assert_eq!(regex.find(text), eager.find(text));
+            }
+        }
+        assert!(RegexBuilder::new(r"a(").deferred(true).build().is_err());
+
+        let regex = RegexBuilder::new(r"ab*").deferred(true).build().unwrap();
+        regex.ensure_compiled();
+        assert!(regex.is_compiled());
+        assert!(regex.clone().is_compiled());
+    }
+
+    #[test]
+    fn profile() {
+        let pattern: String = format!("({})*b|z(a|b)*", "a|".repeat(65));
+        let eager = Regex::new(&pattern).unwrap();
+        let regex = RegexBuilder::new(&pattern)
+            .profile("xaaab zbba aab")
+            .build()
+            .unwrap();
+        assert_eq!(regex.start_state(), 0);
+        for text in ["aab", "xzab", "b", "", "zzz", "xxaaaaab"] {
+            assert_eq!(regex.find(text), eager.find(text));
+            assert_eq!(regex.matches(text), eager.matches(text));
+            assert_eq!(regex.is_match(text), eager.is_match(text));
+        }
+        assert_eq!(regex.fingerprint(), eager.fingerprint());
+    }
+
+    #[test]
+    fn prefilter() {
+        /// 索引から得た位置だけを候補にする
+        #[derive(Debug)]
+        struct Index(Vec<usize>);
+        impl Prefilter for Index {
+            fn find(&self, _: &str, at: usize) -> Option<usize> {
+                self.0.iter().copied().find(|pos| *pos >= at)
+            }
+        }
+        let text: &str = "ab ab ab";
+        // 位置の数で照合方法が変わるので, 両方を試す
+        for pattern in [r"a(b|c)".to_string(), format!("({})*b", "a|".repeat(65))] {
+            let regex = RegexBuilder::new(&pattern)
+                .prefilter(Index(vec![6]))
+                .build()
+                .unwrap();
+            assert_eq!(regex.find(text).unwrap().range(), 6..8);
+            assert_eq!(regex.find_iter(text).count(), 1);
+        }
+        let regex = RegexBuilder::new(r"ab|c")
+            .prefilter(LiteralPrefilter::new("c"))
+            .build()
+            .unwrap();
+        assert_eq!(regex.find("abc").unwrap().range(), 2..3);
+        // 文字列そのもののパターンでは使われない
+        let regex = RegexBuilder::new(r"ab")
+            .prefilter(Index(Vec::new()))
+            .build()
+            .unwrap();
+        assert!(regex.is_match("ab"));
+    }
+
+    #[test]
+    fn hooks() {
+        /// パターン全体を `n` 番のグループで囲む
+        #[derive(Debug)]
+        struct Wrap(usize);
+        impl AstHook for Wrap {
+            fn rewrite(&self, ast: AstNode) -> AstNode {
+                AstNode::Group(self.0, Box::new(ast))
+            }
+        }
+        /// 空文字列も受理させる
+        #[derive(Debug)]
+        struct Optional;
+        impl NfaHook for Optional {
+            fn rewrite(&self, mut nfa: Nfa) -> Nfa {
+                let start: u32 = nfa.start();
+                nfa.set_accept(start, true);
+                nfa
+            }
+        }
+
+        let regex = RegexBuilder::new(r"(a)b")
+            .ast_hook(Wrap(2))
+            .ast_hook(Wrap(3))
+            .build()
+            .unwrap();
+        let caps = regex.captures("xab").unwrap();
+        assert_eq!(caps.len(), 4);
+        assert_eq!(caps.get(3).unwrap().range(), 1..3);
+        assert_eq!(caps.get(1).unwrap().range(), 1..2);
+
+        // 文字列そのもののパターンや大文字と小文字を区別しない場合もNFAを経由する
+        for builder in [
+            RegexBuilder::new(r"ab"),
+            RegexBuilder::new(r"ab").case_insensitive(true),
+            RegexBuilder::new(&format!("({})*b", "a|".repeat(65))),
+        ] {
+            let regex = builder.nfa_hook(Optional).build().unwrap();
+            assert!(regex.matches("") && regex.matches("ab"));
+            assert_eq!(regex.find("xab").unwrap().range(), 0..0);
+        }
+        let trace = RegexBuilder::new(r"ab")
+            .nfa_hook(Optional)
+            .determinization_trace()
+            .unwrap();
+        assert!(trace.subsets()[trace.start() as usize].is_accept());
+
+        // フックで書き換えた `Regex` は言語で比較する
+        let hooked = RegexBuilder::new(r"ab").nfa_hook(Optional).build().unwrap();
+        assert_ne!(hooked, Regex::new(r"ab").unwrap());
+        assert_eq!(
+            hooked,
+            RegexBuilder::new(r"(ab|)")
+                .nfa_hook(Optional)
+                .build()
+                .unwrap()
+        );
+        assert_ne!(
+            RegexBuilder::new(r"a").ast_hook(Wrap(1)).build().unwrap(),
+            Regex::new(r"a").unwrap()
+        );
+    }
+
+    #[test]
+    fn build_with_warnings() {
+        let (regex, warnings) = RegexBuilder::new(r"(a*)*|b|()")
+            .build_with_warnings()
+            .unwrap();
+        assert!(regex.matches("aa"));
+        assert_eq!(
+            warnings.iter().map(|w| w.kind()).collect::<Vec<_>>(),
+            crate::lint(r"(a*)*|b|()")
+                .unwrap()
+                .iter()
+                .map(|w| w.kind())
+                .collect::<Vec<_>>()
+        );
+        assert!(!warnings.is_empty());
+
+        let (_, warnings) = RegexBuilder::new(r"ab").build_with_warnings().unwrap();
+        assert!(warnings.is_empty());
+        assert!(RegexBuilder::new(r"(").build_with_warnings().is_err());
+    }
+
+    #[test]
+    fn define() {
+        let builder = RegexBuilder::new("")
+            .define("digit", r"0|1|2|3|4|5|6|7|8|9")
+            .define("time", r"\g<digit>\g<digit>:\g<digit>\g<digit>");
+        let regex = builder
+            .clone()
+            .pattern(r"(?<start>\g<time>)-(?<end>\g<time>)")
+            .build()
+            .unwrap();
+        assert_eq!(regex.captures_len(), 3);
+        let caps = regex.captures("営業時間 09:00-18:30").unwrap();
+        assert_eq!((&caps["start"], &caps["end"]), ("09:00", "18:30"));
+        assert!(
+            builder
+                .clone()
+                .pattern(r"\g<digit>*")
+                .build()
+                .unwrap()
+                .matches("2024")
+        );
+
+        let err = builder.clone().pattern(r"\g<date>").build().unwrap_err();
+        assert_eq!(err.to_string(), r"Undefined sub-pattern \g<date>");
+        let err = RegexBuilder::new(r"a")
+            .define("1st", r"a")
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), r#"Invalid sub-pattern name "1st""#);
+        assert!(RegexBuilder::new(r"a").define("a", r"(").build().is_err());
+        assert!(RegexBuilder::new(r"a").define("a", r"b\").build().is_err());
+
+        // 同じパターンでも, 参照する部分パターンが違えば別の `Regex`
+        let a = RegexBuilder::new(r"\g<x>")
+            .define("x", r"a")
+            .build()
+            .unwrap();
+        let b = RegexBuilder::new(r"\g<x>")
+            .define("x", r"b")
+            .build()
+            .unwrap();
+        assert_ne!(a, b);
+        assert_eq!(
+            a,
+            RegexBuilder::new(r"\g<x>")
+                .define("x", r"a")
+                .build()
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use crate::Slot;
+use crate::automaton::LazyDFA;
+
+/// [`Regex::find_with`](crate::Regex::find_with) などで使う, 照合の途中経過の記録
+///
+/// 1つの `Cache` を複数のスレッドで同時に使うことはできないので, スレッドごとに用意する.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    /// 記録の対象. 別の `Regex` で使われたら記録を捨てる
+    engine: Option<Arc<Slot>>,
+    lazy: LazyDFA,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Cache {
+            engine: None,
+            lazy: LazyDFA::new(),
+        }
+    }
+
+    /// 記録を捨てる
+    pub fn reset(&mut self) {
+        self.engine = None;
+        self.lazy.clear();
+    }
+
+    pub(crate) fn prepare(&mut self, engine: &Arc<Slot>) -> &mut LazyDFA {
+        if !self.engine.as_ref().is_some_and(|e| Arc::ptr_eq(e, engine)) {
+            self.reset();
+            self.engine = Some(Arc::clone(engine));
+        }
+        &mut self.lazy
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Regex;
+
+    #[test]
+    fn find_with() {
+        let regex = Regex::new(r"山田(太|一)郎|(a|ab)*c").unwrap();
+        let mut cache = Cache::new();
+        for text in ["私は山田太郎です", "xababac", "山田次郎", ""] {
+            assert_eq!(regex.find_with(&mut cache, text), regex.find(text));
+            assert_eq!(regex.is_match_with(&mut cache, text), regex.is_match(text));
+        }
+        assert!(cache.lazy.len() > 0);
+    }
+
+    #[test]
+    fn switch_regex() {
+        let mut cache = Cache::new();
+        let r1 = Regex::new(r"ab*").unwrap();
+        let r2 = Regex::new(r"ba*").unwrap();
+        assert_eq!(r1.find_with(&mut cache, "xabb").unwrap().as_str(), "abb");
+        assert_eq!(r2.find_with(&mut cache, "xabb").unwrap().as_str(), "b");
+        assert_eq!(r1.find_with(&mut cache, "xabb").unwrap().as_str(), "abb");
+    }
+
+    #[test]
+    fn other_engines() {
+        let mut cache = Cache::new();
+        let literal = Regex::new(r"abc").unwrap();
+        assert_eq!(literal.find_with(&mut cache, "xabc").unwrap().start(), 1);
+        let dfa = Regex::new(&format!("({})*b", "a|".repeat(65))).unwrap();
+        assert_eq!(dfa.find_with(&mut cache, "xaab").unwrap().as_str(), "aab");
+        assert_eq!(cache.lazy.len(), 0);
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+use std::ops::Index;
+use std::sync::Arc;
+
+use crate::Match;
+
+/// [`Regex::captures`](crate::Regex::captures) が返す, 各キャプチャグループにマッチした部分文字列
+///
+/// グループ 0 はマッチ全体, グループ `i` は左から `i` 番目の開き括弧に対応する.
+/// `(?<name>...)` で名前を付けたグループは名前でも参照できる.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    /// グループ `i` の開始位置と終了位置が `2i`, `2i + 1` にある
+    slots: Vec<Option<usize>>,
+    /// グループ `i` の名前が `i` にある
+    names: Arc<[Option<String>]>,
+}
+
+impl<'h> Captures<'h> {
+    pub(crate) fn new(
+        haystack: &'h str,
+        slots: Vec<Option<usize>>,
+        names: Arc<[Option<String>]>,
+    ) -> Self {
+        Captures {
+            haystack,
+            slots,
+            names,
+        }
+    }
+
+    /// グループ `index` にマッチした部分. グループが照合に関与しなかった場合は `None`
+    pub fn get(&self, index: usize) -> Option<Match<'h>> {
+        let start: usize = (*self.slots.get(index * 2)?)?;
+        let end: usize = (*self.slots.get(index * 2 + 1)?)?;
+        Some(Match {
+            haystack: self.haystack,
+            start,
+            end,
+        })
+    }
+
+    /// `name` という名前のグループにマッチした部分. 同じ名前のグループが複数あれば最も左のもの
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        let index: usize = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(index)
+    }
+
+    /// グループ 0 から順に, 各グループにマッチした部分を返す
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h>>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+
+    /// マッチ全体と, グループ 1 から `N` にマッチした部分
+    ///
+    /// [`Regex::static_captures_len`](crate::Regex::static_captures_len) が `Some(N + 1)` のパターンでは常に成功する.
+    /// グループの数が `N + 1` でないか, 照合に関与しなかったグループがある場合はパニックする.
+    pub fn extract<const N: usize>(&self) -> (&'h str, [&'h str; N]) {
+        assert_eq!(
+            self.len(),
+            N + 1,
+            "expected {} capture groups, but the pattern has {}",
+            N,
+            self.len() - 1
+        );
+        let group = |index: usize| {
+            self.get(index)
+                .unwrap_or_else(|| panic!("no match for group {}", index))
+                .as_str()
+        };
+        (group(0), std::array::from_fn(|i| group(i + 1)))
+    }
+
+    /// グループ 0 を含むグループの数
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    /// 常に `false` (グループ 0 が必ずある)
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// `replacement` 中の `$n`, `${n}` をグループ `n` にマッチした部分に置き換えて `dst` に追加する
+    ///
+    /// `$$` は `$` 自身を表す. 存在しない, または関与しなかったグループは空文字列になる.
+    pub fn expand(&self, replacement: &str, dst: &mut String) {
+        let mut rest: &str = replacement;
+        while let Some(i) = rest.find('$') {
+            dst.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                dst.push('$');
+                rest = after;
+                continue;
+            }
+            let (digits, after) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+                Some((digits, after)) => (digits, after),
+                None => {
+                    let len: usize = rest
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(rest.len());
+                    rest.split_at(len)
+                }
+            };
+            match digits.parse::<usize>() {
+                Ok(index) => {
+                    if let Some(m) = self.get(index) {
+                        dst.push_str(m.as_str());
+                    }
+                    rest = after;
+                }
+                // グループの参照でなければそのまま残す
+                Err(_) => dst.push('$'),
+            }
+        }
+        dst.push_str(rest);
+    }
+}
+
+/// グループ `index` にマッチした部分. グループが存在しないか, 照合に関与しなかった場合はパニックする
+impl<'h> Index<usize> for Captures<'h> {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        self.get(index)
+            .unwrap_or_else(|| panic!("no match for group {}", index))
+            .as_str()
+    }
+}
+
+/// `name` という名前のグループにマッチした部分. グループが存在しないか, 照合に関与しなかった場合はパニックする
+impl<'h> Index<&str> for Captures<'h> {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.name(name)
+            .unwrap_or_else(|| panic!("no match for group named {:?}", name))
+            .as_str()
+    }
+}
+
+/// `{0: Some("..."), 1/name: None, ...}` の形で各グループにマッチした部分を表示する
+impl fmt::Debug for Captures<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Key<'a>(usize, Option<&'a str>);
+
+        impl fmt::Debug for Key<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self.1 {
+                    Some(name) => write!(f, "{}/{}", self.0, name),
+                    None => write!(f, "{}", self.0),
+                }
+            }
+        }
+
+        let names = |index: usize| self.names.get(index).and_then(|name| name.as_deref());
+        f.debug_map()
+            .entries(
+                self.iter()
+                    .enumerate()
+                    .map(|(index, m)| (Key(index, names(index)), m.map(|m| m.as_str()))),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(slots: Vec<Option<usize>>, replacement: &str) -> String {
+        let mut dst = String::new();
+        Captures::new("山田太郎", slots, Arc::new([None, None, None]))
+            .expand(replacement, &mut dst);
+        dst
+    }
+
+    #[test]
+    fn get() {
+        let names: Arc<[Option<String>]> = Arc::new([None, Some("x".to_string()), None]);
+        let caps = Captures::new(
+            "abc",
+            vec![Some(0), Some(3), Some(1), Some(2), None, None],
+            names,
+        );
+        assert_eq!(caps.len(), 3);
+        assert_eq!(caps.get(0).unwrap().as_str(), "abc");
+        assert_eq!(caps.get(1).unwrap().start(), 1);
+        assert!(caps.get(2).is_none());
+        assert!(caps.get(3).is_none());
+        assert_eq!(caps.name("x").unwrap().as_str(), "b");
+        assert!(caps.name("y").is_none());
+        assert_eq!((&caps[0], &caps[1], &caps["x"]), ("abc", "b", "b"));
+        let groups: Vec<Option<&str>> = caps.iter().map(|m| m.map(|m| m.as_str())).collect();
+        assert_eq!(groups, [Some("abc"), Some("b"), None]);
+        assert_eq!(
+            format!("{:?}", caps),
+            r#"{0: Some("abc"), 1/x: Some("b"), 2: None}"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 2 capture groups, but the pattern has 1")]
+    fn extract_wrong_len() {
+        let caps = Captures::new(
+            "ab",
+            vec![Some(0), Some(2), Some(0), Some(1)],
+            Arc::new([None, None]),
+        );
+        let _: (&str, [&str; 2]) = caps.extract();
+    }
+
+    #[test]
+    #[should_panic(expected = "no match for group 2")]
+    fn index_unmatched() {
+        let caps = Captures::new(
+            "ab",
+            vec![Some(0), Some(2), None, None],
+            Arc::new([None, None]),
+        );
+        let _ = &caps[2];
+    }
+
+    #[test]
+    fn expand_references() {
+        let slots = vec![Some(0), Some(12), Some(0), Some(6), Some(6), Some(12)];
+        assert_eq!(expand(slots.clone(), "$2$1"), "太郎山田");
+        assert_eq!(expand(slots.clone(), "${1}さん"), "山田さん");
+        assert_eq!(expand(slots.clone(), "$1さん"), "山田さん");
+        assert_eq!(expand(slots.clone(), "$$1 $9"), "$1 ");
+        assert_eq!(expand(slots.clone(), "$x ${y"), "$x ${y");
+        assert_eq!(expand(slots, "$"), "$");
+    }
+}
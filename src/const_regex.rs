@@ -0,0 +1,476 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::Regex;
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+/// パターン中の文字の出現 (位置) の数の上限. 位置の集合を `u64` で表す
+const MAX_POSITIONS: usize = 64;
+/// 遷移先が無いことを表す値
+const DEAD: u16 = u16::MAX;
+
+/// 部分パターンの Glushkov 構成での性質. 位置の集合はビット列で表す
+#[derive(Clone, Copy)]
+struct Fragment {
+    /// 空文字列にマッチするか
+    nullable: bool,
+    /// 最初に読みうる位置
+    first: u64,
+    /// 最後に読みうる位置
+    last: u64,
+}
+
+/// `const` 文脈で動く構文解析器. 解析しながら各位置の文字と, 各位置の次に来うる位置を求める
+struct ConstParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    chars: [char; MAX_POSITIONS],
+    positions: usize,
+    follow: [u64; MAX_POSITIONS],
+}
+
+impl ConstParser<'_> {
+    /// <sub_expression> ::= <sequence> '|' <sub_expression> | <sequence>
+    const fn sub_expression(&mut self) -> Fragment {
+        let mut result: Fragment = self.sequence();
+        while self.pos < self.bytes.len() && self.bytes[self.pos] == b'|' {
+            self.pos += 1;
+            let branch: Fragment = self.sequence();
+            result = Fragment {
+                nullable: result.nullable || branch.nullable,
+                first: result.first | branch.first,
+                last: result.last | branch.last,
+            };
+        }
+        result
+    }
+
+    /// <sequence> ::= <star>*
+    const fn sequence(&mut self) -> Fragment {
+        let mut result: Fragment = Fragment {
+            nullable: true,
+            first: 0,
+            last: 0,
+        };
+        while self.pos < self.bytes.len() && !matches!(self.bytes[self.pos], b'|' | b')') {
+            let next: Fragment = self.star();
+            self.link(result.last, next.first);
+            result = Fragment {
+                nullable: result.nullable && next.nullable,
+                first: if result.nullable {
+                    result.first | next.first
+                } else {
+                    result.first
+                },
+                last: if next.nullable {
+                    result.last | next.last
+                } else {
+                    next.last
+                },
+            };
+        }
+        result
+    }
+
+    /// <star> ::= <factor> '*' | <factor>
+    const fn star(&mut self) -> Fragment {
+        let factor: Fragment = self.factor();
+        if self.pos < self.bytes.len() && self.bytes[self.pos] == b'*' {
+            self.pos += 1;
+            self.link(factor.last, factor.first);
+            return Fragment {
+                nullable: true,
+                ..factor
+            };
+        }
+        factor
+    }
+
+    /// <factor> ::= '(' <sub_expression> ')' | 文字
+    const fn factor(&mut self) -> Fragment {
+        match self.bytes[self.pos] {
+            b'(' => {
+                self.pos += 1;
+                // グループ名とブランチリセットの印 `?|` は読み飛ばす
+                if self.pos < self.bytes.len()
+                    && self.bytes[self.pos] == b'?'
+                    && let Some(len) = name_len(self.bytes, self.pos + 1)
+                {
+                    self.pos += 1 + len;
+                } else if self.pos + 1 < self.bytes.len()
+                    && self.bytes[self.pos] == b'?'
+                    && self.bytes[self.pos + 1] == b'<'
+                {
+                    panic!("ConstRegex: invalid group name");
+                } else if self.pos + 1 < self.bytes.len()
+                    && self.bytes[self.pos] == b'?'
+                    && self.bytes[self.pos + 1] == b'|'
+                {
+                    self.pos += 2;
+                }
+                let inner: Fragment = self.sub_expression();
+                if self.pos >= self.bytes.len() || self.bytes[self.pos] != b')' {
+                    panic!("ConstRegex: expected ')'");
+                }
+                self.pos += 1;
+                inner
+            }
+            b'*' => panic!("ConstRegex: unexpected '*'"),
+            b'\\' => {
+                self.pos += 1;
+                if self.pos >= self.bytes.len() {
+                    panic!("ConstRegex: EOF detected after '\\'");
+                }
+                if self.bytes[self.pos] == b'g' && name_len(self.bytes, self.pos + 1).is_some() {
+                    panic!("ConstRegex: \\g<name> is not supported");
+                }
+                self.character()
+            }
+            _ => self.character(),
+        }
+    }
+
+    /// 次の文字を新しい位置として読む
+    const fn character(&mut self) -> Fragment {
+        if self.positions == MAX_POSITIONS {
+            panic!("ConstRegex: too many characters in the pattern (at most 64)");
+        }
+        let (chara, len) = decode_utf8(self.bytes, self.pos);
+        self.pos += len;
+        let position: u64 = 1 << self.positions;
+        self.chars[self.positions] = chara;
+        self.positions += 1;
+        Fragment {
+            nullable: false,
+            first: position,
+            last: position,
+        }
+    }
+
+    /// `from` の各位置の次に `to` の位置が来うる
+    const fn link(&mut self, from: u64, to: u64) {
+        let mut rest: u64 = from;
+        while rest != 0 {
+            self.follow[rest.trailing_zeros() as usize] |= to;
+            rest &= rest - 1;
+        }
+    }
+}
+
+/// `bytes[at..]` が `<name>` (`name` は識別子) で始まれば, その長さ
+const fn name_len(bytes: &[u8], at: usize) -> Option<usize> {
+    if at >= bytes.len() || bytes[at] != b'<' {
+        return None;
+    }
+    let mut i: usize = at + 1;
+    while i < bytes.len() && bytes[i] != b'>' {
+        let b: u8 = bytes[i];
+        let valid: bool =
+            b.is_ascii_alphabetic() || b == b'_' || (i > at + 1 && b.is_ascii_digit());
+        if !valid {
+            return None;
+        }
+        i += 1;
+    }
+    if i >= bytes.len() || i == at + 1 {
+        return None;
+    }
+    Some(i + 1 - at)
+}
+
+/// `bytes[at..]` の先頭の文字と, その UTF-8 でのバイト数
+const fn decode_utf8(bytes: &[u8], at: usize) -> (char, usize) {
+    let b: u8 = bytes[at];
+    let (len, init): (usize, u32) = match b {
+        0x00..=0x7f => (1, b as u32),
+        0xc0..=0xdf => (2, (b & 0x1f) as u32),
+        0xe0..=0xef => (3, (b & 0x0f) as u32),
+        _ => (4, (b & 0x07) as u32),
+    };
+    let mut code: u32 = init;
+    let mut i: usize = 1;
+    while i < len {
+        code = (code << 6) | (bytes[at + i] & 0x3f) as u32;
+        i += 1;
+    }
+    match char::from_u32(code) {
+        Some(chara) => (chara, len),
+        None => panic!("ConstRegex: invalid UTF-8"),
+    }
+}
+
+/// `const` 文脈でコンパイルしたパターン. `static` に置けば, 遷移表はコンパイル時に作られる
+///
+/// `static NAME: ConstRegex<8> = ConstRegex::new(r"(山田|佐藤)(太|一)郎");` のように使う. `STATES` はDFAの状態数の上限で,
+/// 足りない場合やパターンが不正な場合はコンパイルエラー (実行時に作った場合はパニック) になる.
+/// パターン中の文字の出現は64個まで, `\g<name>` は使えず, キャプチャグループは持たない. 照合の結果は [`Regex`] と同じ.
+#[derive(Clone, Debug)]
+pub struct ConstRegex<const STATES: usize> {
+    pattern: &'static str,
+    /// パターンに現れる文字 (昇順, 重複なし)
+    alphabet: [char; MAX_POSITIONS],
+    symbols: usize,
+    /// `transition[state][k]` が `alphabet[k]` による遷移先. 状態 0 が開始状態
+    transition: [[u16; MAX_POSITIONS]; STATES],
+    accepts: [bool; STATES],
+    states: usize,
+}
+
+impl<const STATES: usize> ConstRegex<STATES> {
+    pub const fn new(pattern: &'static str) -> Self {
+        let mut parser: ConstParser<'_> = ConstParser {
+            bytes: pattern.as_bytes(),
+            pos: 0,
+            chars: ['\0'; MAX_POSITIONS],
+            positions: 0,
+            follow: [0; MAX_POSITIONS],
+        };
+        let root: Fragment = parser.sub_expression();
+        if parser.pos < parser.bytes.len() {
+            panic!("ConstRegex: unexpected ')'");
+        }
+
+        // アルファベットと, 各文字が現れる位置の集合
+        let mut alphabet: [char; MAX_POSITIONS] = ['\0'; MAX_POSITIONS];
+        let mut masks: [u64; MAX_POSITIONS] = [0; MAX_POSITIONS];
+        let mut symbols: usize = 0;
+        let mut p: usize = 0;
+        while p < parser.positions {
+            let chara: char = parser.chars[p];
+            // 挿入位置を探す
+            let mut k: usize = 0;
+            while k < symbols && alphabet[k] < chara {
+                k += 1;
+            }
+            if k == symbols || alphabet[k] != chara {
+                let mut j: usize = symbols;
+                while j > k {
+                    alphabet[j] = alphabet[j - 1];
+                    masks[j] = masks[j - 1];
+                    j -= 1;
+                }
+                alphabet[k] = chara;
+                masks[k] = 0;
+                symbols += 1;
+            }
+            masks[k] |= 1 << p;
+            p += 1;
+        }
+
+        // 部分集合構成法. 状態 0 は開始状態で, それ以外は最後に読んだ位置の集合
+        if STATES == 0 {
+            panic!("ConstRegex: STATES must be at least 1");
+        }
+        let mut sets: [u64; STATES] = [0; STATES];
+        let mut transition: [[u16; MAX_POSITIONS]; STATES] = [[DEAD; MAX_POSITIONS]; STATES];
+        let mut accepts: [bool; STATES] = [false; STATES];
+        let mut states: usize = 1;
+        let mut state: usize = 0;
+        while state < states {
+            let mut next: u64 = 0;
+            if state == 0 {
+                next = root.first;
+                accepts[0] = root.nullable;
+            } else {
+                let mut rest: u64 = sets[state];
+                while rest != 0 {
+                    next |= parser.follow[rest.trailing_zeros() as usize];
+                    rest &= rest - 1;
+                }
+                accepts[state] = sets[state] & root.last != 0;
+            }
+            let mut k: usize = 0;
+            while k < symbols {
+                let target: u64 = next & masks[k];
+                if target != 0 {
+                    let mut found: usize = 1;
+                    while found < states && sets[found] != target {
+                        found += 1;
+                    }
+                    if found == states {
+                        if states == STATES {
+                            panic!("ConstRegex: too many DFA states; increase STATES");
+                        }
+                        sets[states] = target;
+                        states += 1;
+                    }
+                    transition[state][k] = found as u16;
+                }
+                k += 1;
+            }
+            state += 1;
+        }
+
+        ConstRegex {
+            pattern,
+            alphabet,
+            symbols,
+            transition,
+            accepts,
+            states,
+        }
+    }
+
+    /// 元のパターン文字列
+    pub fn as_str(&self) -> &'static str {
+        self.pattern
+    }
+
+    /// DFAの状態数
+    pub fn state_count(&self) -> usize {
+        self.states
+    }
+
+    fn next_state(&self, state: usize, chara: char) -> Option<usize> {
+        let k: usize = self.alphabet[..self.symbols].binary_search(&chara).ok()?;
+        let to: u16 = self.transition[state][k];
+        (to != DEAD).then_some(to as usize)
+    }
+
+    /// `text[start..]` の接頭辞のうち, 受理される最長のものの終了位置
+    fn longest_match_at(&self, text: &str, start: usize) -> Option<usize> {
+        let mut state: usize = 0;
+        let mut end: Option<usize> = self.accepts[0].then_some(start);
+        for (i, chara) in text[start..].char_indices() {
+            match self.next_state(state, chara) {
+                Some(next) => state = next,
+                None => break,
+            }
+            if self.accepts[state] {
+                end = Some(start + i + chara.len_utf8());
+            }
+        }
+        end
+    }
+
+    /// [`Regex::matches`] と同じ
+    pub fn matches(&self, text: &str) -> bool {
+        let mut state: usize = 0;
+        for chara in text.chars() {
+            match self.next_state(state, chara) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        self.accepts[state]
+    }
+
+    /// [`Regex::is_match`] と同じ
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    /// [`Regex::find`] と同じマッチの範囲 (バイトオフセット)
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        let starts = text.char_indices().map(|(i, _)| i).chain([text.len()]);
+        for start in starts {
+            if let Some(end) = self.longest_match_at(text, start) {
+                return Some(start..end);
+            }
+        }
+        None
+    }
+
+    /// 同じ遷移表を使う [`Regex`]. パターンの再コンパイルは行わない
+    pub fn to_regex(&self) -> Regex {
+        let mut transition: BTreeMap<(DFAState, char), DFAState> = BTreeMap::new();
+        let mut accepts: HashSet<DFAState> = HashSet::default();
+        for state in 0..self.states {
+            if self.accepts[state] {
+                accepts.insert(DFAState(state as u32));
+            }
+            for (k, to) in self.transition[state][..self.symbols].iter().enumerate() {
+                if *to != DEAD {
+                    transition.insert(
+                        (DFAState(state as u32), self.alphabet[k]),
+                        DFAState(*to as u32),
+                    );
+                }
+            }
+        }
+        let dfa: DFA = DFA {
+            start: DFAState(0),
+            accepts,
+            transition,
+            otherwise: HashMap::default(),
+        };
+        Regex::from_dfa(self.pattern.to_string(), dfa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static NAME: ConstRegex<8> = ConstRegex::new(r"(?<family>山田|佐藤)(太|一)郎");
+
+    #[test]
+    fn compile_time() {
+        assert!(NAME.matches("山田太郎"));
+        assert!(!NAME.matches("山田次郎"));
+        assert_eq!(NAME.find("私は佐藤一郎です"), Some(6..18));
+        assert_eq!(NAME.as_str(), r"(?<family>山田|佐藤)(太|一)郎");
+    }
+
+    #[test]
+    fn same_as_regex() {
+        const PATTERNS: [&str; 9] = [
+            r"a|b*c",
+            r"(a|b)*abb",
+            r"",
+            r"a||b",
+            r"(ab|)*\*",
+            r"x(\(|\))*y",
+            r"\g(a)",
+            r"(()|a)*b",
+            r"(?|a|(b))*c",
+        ];
+        let texts: [&str; 9] = ["", "a", "bbbc", "abb", "babb", "abab*", "x()y", "ga", "aab"];
+        for pattern in PATTERNS {
+            let compiled: ConstRegex<16> = ConstRegex::new(pattern);
+            let regex = Regex::new(pattern).unwrap();
+            for text in texts {
+                assert_eq!(
+                    compiled.matches(text),
+                    regex.matches(text),
+                    "{:?} {:?}",
+                    pattern,
+                    text
+                );
+                assert_eq!(
+                    compiled.find(text),
+                    regex.find(text).map(|found| found.range()),
+                    "{:?} {:?}",
+                    pattern,
+                    text
+                );
+            }
+            let converted: Regex = compiled.to_regex();
+            assert_eq!(
+                converted.fingerprint(),
+                regex.fingerprint(),
+                "{:?}",
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "too many DFA states")]
+    fn too_many_states() {
+        let _ = ConstRegex::<2>::new(r"abc");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected ')'")]
+    fn invalid() {
+        let _ = ConstRegex::<4>::new(r"(a");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid group name")]
+    fn invalid_group_name() {
+        let _ = ConstRegex::<4>::new(r"(?<my-name>a)");
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::automaton::{DFA, FORMAT_VERSION};
+use crate::builder::Construction;
+
+/// ファイルの先頭に置く, キャッシュの対象を表す文字列.
+/// crate のバージョンや直列化の形式が変われば一致しなくなり, 古いキャッシュは使われない
+fn header(pattern: &str, construction: Construction, case_insensitive: bool) -> String {
+    format!(
+        "{} {} {} {:?} {}\0{}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        FORMAT_VERSION,
+        construction,
+        case_insensitive,
+        pattern
+    )
+}
+
+/// キャッシュのファイルのパス. ファイル名はヘッダの FNV-1a ハッシュ値
+fn path(dir: &Path, header: &str) -> PathBuf {
+    let hash: u64 = header.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    dir.join(format!("{:016x}.dfa", hash))
+}
+
+/// キャッシュされたDFAを読み込む. 無い場合や, 壊れている・古い場合は `None`
+pub(crate) fn load(
+    dir: &Path,
+    pattern: &str,
+    construction: Construction,
+    case_insensitive: bool,
+) -> Option<DFA> {
+    let header: String = header(pattern, construction, case_insensitive);
+    let bytes: Vec<u8> = fs::read(path(dir, &header)).ok()?;
+    let (len, rest) = bytes.split_first_chunk::<4>()?;
+    let (stored, dfa) = rest.split_at_checked(u32::from_le_bytes(*len) as usize)?;
+    if stored != header.as_bytes() {
+        return None;
+    }
+    DFA::from_bytes(dfa)
+}
+
+/// DFAをキャッシュに書き込む. 書き込み途中のファイルが読まれないよう, 一時ファイルに書いてから名前を変える
+pub(crate) fn store(
+    dir: &Path,
+    pattern: &str,
+    construction: Construction,
+    case_insensitive: bool,
+    dfa: &DFA,
+) -> io::Result<()> {
+    let header: String = header(pattern, construction, case_insensitive);
+    let mut bytes: Vec<u8> = (header.len() as u32).to_le_bytes().to_vec();
+    bytes.extend(header.as_bytes());
+    bytes.extend(dfa.to_bytes());
+
+    fs::create_dir_all(dir)?;
+    let path: PathBuf = path(dir, &header);
+    let temporary: PathBuf = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RegexBuilder;
+
+    fn temporary_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ryota2357-regex-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = temporary_dir("store");
+        let pattern: String = format!("({})*b", "a|".repeat(65));
+        let builder = RegexBuilder::new(&pattern).cache_dir(&dir);
+        assert!(load(&dir, &pattern, Construction::Thompson, false).is_none());
+
+        let regex = builder.build().unwrap();
+        let cached = load(&dir, &pattern, Construction::Thompson, false).unwrap();
+        // 照合に使うDFAは, 保存したDFAの状態の番号を振り直したもの
+        let renumbered = cached.renumber(&cached.hot_order());
+        assert_eq!(renumbered.transition, regex.dfa().transition);
+        assert!(load(&dir, &pattern, Construction::Glushkov, false).is_none());
+        assert!(load(&dir, &pattern, Construction::Thompson, true).is_none());
+
+        let loaded = builder.build().unwrap();
+        assert!(loaded.matches("aab"));
+        assert_eq!(loaded.find("xaab").unwrap().as_str(), "aab");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted() {
+        let dir = temporary_dir("corrupted");
+        let pattern: String = format!("({})*c", "a|b|".repeat(40));
+        let regex = RegexBuilder::new(&pattern).cache_dir(&dir).build().unwrap();
+        let file = path(&dir, &header(&pattern, Construction::Thompson, false));
+        fs::write(&file, b"broken").unwrap();
+        assert!(load(&dir, &pattern, Construction::Thompson, false).is_none());
+
+        let rebuilt = RegexBuilder::new(&pattern).cache_dir(&dir).build().unwrap();
+        assert_eq!(rebuilt.find("xabc"), regex.find("xabc"));
+        assert!(load(&dir, &pattern, Construction::Thompson, false).is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
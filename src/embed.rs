@@ -0,0 +1,128 @@
+use std::borrow::Cow;
+use std::error::Error;
+
+use crate::Regex;
+use crate::automaton::{DFA, FORMAT_VERSION};
+
+/// [`Regex::to_dfa_bytes`] の先頭に置く識別子
+const MAGIC: &[u8; 4] = b"RREX";
+
+impl Regex {
+    /// 照合に使うDFAをパターン文字列とともに直列化する. [`Regex::from_dfa_bytes`] や [`include_dfa!`](crate::include_dfa!) で読み込める
+    ///
+    /// ビルドスクリプトなどで書き出しておけば, 実行時にパターンをコンパイルせずに済む.
+    /// 形式: MAGIC, パターンの長さ (u32), パターン, 大文字と小文字を区別しないか (u8), DFA.
+    pub fn to_dfa_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        bytes.extend((self.pattern.len() as u32).to_le_bytes());
+        bytes.extend(self.pattern.as_bytes());
+        bytes.push(self.case_insensitive as u8);
+        // 同じパターンからは同じバイト列になるよう, 状態の番号を構造から決まるものに揃える
+        let dfa: Cow<'_, DFA> = self.dfa();
+        bytes.extend(dfa.renumber(&dfa.hot_order()).to_bytes());
+        bytes
+    }
+
+    /// [`Regex::to_dfa_bytes`] で直列化したものから `Regex` を作る. パターンのコンパイルは行わない
+    ///
+    /// 形式が合わない場合や, 別のバージョンの形式で書かれている場合はエラーになる.
+    /// [`Regex::negate`] の結果などと同様に, キャプチャグループは持たず, 等しいかどうかはマッチする言語で判定する.
+    /// [`Regex::as_str`] は書き込まれていたパターン文字列をそのまま返すが, DFA と一致するかは確かめない.
+    pub fn from_dfa_bytes(bytes: &[u8]) -> Result<Regex, Box<dyn Error>> {
+        let invalid = || -> Box<dyn Error> { "invalid serialized DFA".into() };
+        let rest: &[u8] = bytes.strip_prefix(MAGIC).ok_or_else(invalid)?;
+        let (len, rest) = rest.split_first_chunk::<4>().ok_or_else(invalid)?;
+        let (pattern, rest) = rest
+            .split_at_checked(u32::from_le_bytes(*len) as usize)
+            .ok_or_else(invalid)?;
+        let pattern: &str = std::str::from_utf8(pattern).map_err(|_| invalid())?;
+        let (case_insensitive, rest) = rest.split_first().ok_or_else(invalid)?;
+        // DFA の形式の版は, DFA の識別子の直後に置かれる
+        let version: Option<u32> = rest
+            .get(4..8)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        if version.is_some_and(|version| version != FORMAT_VERSION) {
+            return Err(format!(
+                "serialized DFA has format version {}, but this build reads version {}",
+                version.unwrap(),
+                FORMAT_VERSION
+            )
+            .into());
+        }
+        let dfa: DFA = DFA::from_bytes(rest).ok_or_else(invalid)?;
+        if *case_insensitive > 1 {
+            return Err(invalid());
+        }
+        // パターン文字列は DFA と照らし合わせていないので, 比較は DFA の言語で行う
+        let mut regex: Regex = Regex::from_dfa(pattern.into(), dfa);
+        regex.case_insensitive = *case_insensitive == 1;
+        Ok(regex)
+    }
+}
+
+/// [`Regex::to_dfa_bytes`] で書き出したファイルを埋め込み, 初回の評価時に一度だけ読み込んで `&'static Regex` を返す
+///
+/// パスは `include_bytes!` と同じく, 呼び出し元のファイルからの相対パス. 内容が不正な場合はパスとエラーを含むメッセージで panic する.
+#[macro_export]
+macro_rules! include_dfa {
+    ($path:expr $(,)?) => {{
+        static REGEX: ::std::sync::OnceLock<$crate::Regex> = ::std::sync::OnceLock::new();
+        REGEX.get_or_init(
+            || match $crate::Regex::from_dfa_bytes(::std::include_bytes!($path)) {
+                Ok(regex) => regex,
+                Err(err) => panic!("invalid DFA file {:?}: {}", $path, err),
+            },
+        )
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for pattern in [r"山田(太|一)郎", r"(a|b)*abb", r""] {
+            let regex = Regex::new(pattern).unwrap();
+            let loaded = Regex::from_dfa_bytes(&regex.to_dfa_bytes()).unwrap();
+            assert!(loaded.is_equivalent(&regex));
+            assert_eq!(loaded.fingerprint(), regex.fingerprint());
+            assert_eq!(
+                loaded,
+                Regex::from_dfa_bytes(&regex.to_dfa_bytes()).unwrap()
+            );
+            assert_eq!(loaded.find("xabbb"), regex.find("xabbb"));
+        }
+        let negated = Regex::new(r"ab").unwrap().negate();
+        let loaded = Regex::from_dfa_bytes(&negated.to_dfa_bytes()).unwrap();
+        assert!(loaded.matches("ba") && !loaded.matches("ab"));
+        // 書き込まれたパターン文字列ではなく, DFA の言語で比較する
+        assert_eq!(loaded, negated);
+        assert_ne!(loaded, Regex::new(loaded.as_str()).unwrap());
+    }
+
+    #[test]
+    fn include_dfa() {
+        // testdata/yamada.dfa は Regex::new(r"山田(太|一)郎").to_dfa_bytes() で書き出したもの
+        let regex: &'static Regex = crate::include_dfa!("testdata/yamada.dfa");
+        assert_eq!(regex.as_str(), r"山田(太|一)郎");
+        assert!(regex.matches("山田一郎"));
+        assert_eq!(regex.find("私は山田太郎です").unwrap().as_str(), "山田太郎");
+        assert_eq!(
+            regex.to_dfa_bytes(),
+            Regex::new(r"山田(太|一)郎").unwrap().to_dfa_bytes()
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut bytes: Vec<u8> = Regex::new(r"ab*").unwrap().to_dfa_bytes();
+        assert!(Regex::from_dfa_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Regex::from_dfa_bytes(b"RREX").is_err());
+        // DFA の形式の版は, パターン (3バイト) と大文字・小文字の指定の後にある
+        let version: usize = 4 + 4 + 3 + 1 + 4;
+        bytes[version] += 1;
+        let err = Regex::from_dfa_bytes(&bytes).unwrap_err();
+        assert!(err.to_string().contains("format version"));
+    }
+}
@@ -0,0 +1,205 @@
+use std::error::Error;
+use std::ops::Range;
+
+use encoding_rs::Encoding;
+
+use crate::automaton::{DFA, DFAState, NFA};
+use crate::lexer::Lexer;
+use crate::parser::{Kind, Node, NodeId, Parser};
+
+/// 指定した文字コード (Shift_JIS, Latin-1 など) で符号化されたバイト列を, 変換せずに照合するパターン
+///
+/// パターンの各文字をその文字コードでのバイト列に置き換え, バイト単位のDFAを作る.
+/// 照合は [`Regex`](crate::Regex) と同じく最左最長で, 位置はバイトオフセット.
+#[derive(Clone, Debug)]
+pub struct EncodedRegex {
+    dfa: DFA,
+    encoding: &'static Encoding,
+    /// マッチの先頭になりうるバイト. 空文字列や任意のバイトで始まるマッチがあれば `None`
+    first_bytes: Option<Box<[bool; 256]>>,
+}
+
+impl EncodedRegex {
+    /// `pattern` を `encoding` で符号化した言語を受理するDFAを作る
+    ///
+    /// ASCII と互換でない文字コード (UTF-16, ISO-2022-JP など) や, その文字コードで表せない文字を含むパターンはエラー.
+    pub fn new(pattern: &str, encoding: &'static Encoding) -> Result<Self, Box<dyn Error>> {
+        if !encoding.is_ascii_compatible() {
+            return Err(format!("{} is not an ASCII-compatible encoding", encoding.name()).into());
+        }
+        let node: Node = Parser::new(Lexer::new(pattern)).parse()?;
+        let node: Node = encode(&node, encoding)?;
+        let dfa: DFA = DFA::from_nfa(NFA::from_node(node));
+        let first_bytes = (!dfa.is_accept(dfa.start) && !dfa.otherwise.contains_key(&dfa.start))
+            .then(|| {
+                let mut table: Box<[bool; 256]> = Box::new([false; 256]);
+                for (from, byte) in dfa.transition.keys() {
+                    if *from == dfa.start {
+                        table[*byte as usize] = true;
+                    }
+                }
+                table
+            });
+        Ok(EncodedRegex {
+            dfa,
+            encoding,
+            first_bytes,
+        })
+    }
+
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// `bytes` の部分列にマッチするか
+    pub fn is_match(&self, bytes: &[u8]) -> bool {
+        self.find(bytes).is_some()
+    }
+
+    /// `bytes` 全体にマッチするか
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        self.longest_match_at(bytes, 0) == Some(bytes.len())
+    }
+
+    /// `bytes` 中で最も左にあるマッチ (同じ位置からのマッチは最長のもの) の範囲
+    ///
+    /// マッチは文字の境界から始まるものに限る (2バイト文字の2バイト目から始まるマッチは無視する).
+    pub fn find(&self, bytes: &[u8]) -> Option<Range<usize>> {
+        self.char_starts(bytes)
+            .filter(|start| match &self.first_bytes {
+                Some(table) => bytes.get(*start).is_some_and(|byte| table[*byte as usize]),
+                None => true,
+            })
+            .find_map(|start| Some(start..self.longest_match_at(bytes, start)?))
+    }
+
+    /// 文字の開始位置 (末尾を含む) を先頭から順に返す
+    ///
+    /// ASCII と互換な文字コードでは, 文字の境界にある ASCII のバイトはそれだけで1文字なので, 復号せずに進める.
+    fn char_starts<'b>(&self, bytes: &'b [u8]) -> impl Iterator<Item = usize> + 'b {
+        let single_byte: bool = self.encoding.is_single_byte();
+        let mut decoder = self.encoding.new_decoder_without_bom_handling();
+        let mut next: Option<usize> = Some(0);
+        std::iter::from_fn(move || {
+            let start: usize = next?;
+            next = (start < bytes.len()).then(|| {
+                if single_byte || bytes[start].is_ascii() {
+                    return start + 1;
+                }
+                let mut buffer: [u8; 16] = [0; 16];
+                let mut end: usize = start;
+                loop {
+                    end += 1;
+                    let last: bool = end == bytes.len();
+                    let (_, _, written, _) =
+                        decoder.decode_to_utf8(&bytes[end - 1..end], &mut buffer, last);
+                    // 1文字が確定したら, 次のバイトから新しい文字が始まる
+                    if written > 0 || last {
+                        return end;
+                    }
+                }
+            });
+            Some(start)
+        })
+    }
+
+    fn longest_match_at(&self, bytes: &[u8], start: usize) -> Option<usize> {
+        let mut state: DFAState = self.dfa.start;
+        let mut end: Option<usize> = self.dfa.is_accept(state).then_some(start);
+        for (i, byte) in bytes[start..].iter().enumerate() {
+            match self.dfa.next_state(state, *byte as char) {
+                Some(next) => state = next,
+                None => break,
+            }
+            if self.dfa.is_accept(state) {
+                end = Some(start + i + 1);
+            }
+        }
+        end
+    }
+}
+
+/// 各文字を, 符号化したバイト列 (各バイトを U+0000..=U+00FF の文字で表す) の連接に置き換える
+fn encode(node: &Node, encoding: &'static Encoding) -> Result<Node, String> {
+    node.try_rebuild(|arena, kind| {
+        let Kind::Character(chara) = kind else {
+            return Ok(arena.push(kind));
+        };
+        let mut utf8: [u8; 4] = [0; 4];
+        let (bytes, _, unmappable) = encoding.encode(chara.encode_utf8(&mut utf8));
+        if unmappable {
+            return Err(format!(
+                "character '{}' cannot be encoded in {}",
+                chara,
+                encoding.name()
+            ));
+        }
+        let mut bytes = bytes.iter().map(|byte| Kind::Character(*byte as char));
+        let Some(first) = bytes.next() else {
+            return Ok(arena.push(Kind::Empty));
+        };
+        let first: NodeId = arena.push(first);
+        Ok(bytes.fold(first, |n1, byte| {
+            let n2: NodeId = arena.push(byte);
+            arena.push(Kind::Concat(n1, n2))
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{SHIFT_JIS, UTF_16LE, WINDOWS_1252};
+
+    use super::*;
+
+    fn sjis(text: &str) -> Vec<u8> {
+        SHIFT_JIS.encode(text).0.into_owned()
+    }
+
+    #[test]
+    fn shift_jis() {
+        let regex = EncodedRegex::new(r"山田(太|一)郎", SHIFT_JIS).unwrap();
+        let text: Vec<u8> = sjis("私は山田一郎です");
+        assert!(regex.is_match(&text));
+        assert_eq!(regex.find(&text), Some(4..12));
+        assert!(regex.matches(&sjis("山田太郎")));
+        assert!(!regex.is_match(&sjis("山田次郎")));
+    }
+
+    #[test]
+    fn trail_byte() {
+        // 'ソ' は Shift_JIS で 0x83 0x5C で, 2バイト目は '\' と同じ
+        let regex = EncodedRegex::new(r"\\", SHIFT_JIS).unwrap();
+        assert!(!regex.is_match(&sjis("ソ")));
+        assert_eq!(regex.find(&sjis("ソ\\")), Some(2..3));
+    }
+
+    #[test]
+    fn char_starts() {
+        let regex = EncodedRegex::new(r"a", SHIFT_JIS).unwrap();
+        let text: Vec<u8> = sjis("aソ山ｱb");
+        let starts: Vec<usize> = regex.char_starts(&text).collect();
+        assert_eq!(starts, [0, 1, 3, 5, 6, 7]);
+        // 途中で切れた文字は, 末尾までを1文字とみなす
+        let starts: Vec<usize> = regex.char_starts(&text[..4]).collect();
+        assert_eq!(starts, [0, 1, 3, 4]);
+        // 空文字列にマッチするパターンは, 絞り込まずに末尾も試す
+        let regex = EncodedRegex::new(r"(ソ)*", SHIFT_JIS).unwrap();
+        assert_eq!(regex.find(&sjis("山")), Some(0..0));
+        assert_eq!(regex.find(b""), Some(0..0));
+    }
+
+    #[test]
+    fn latin1() {
+        let regex = EncodedRegex::new(r"caf(e|é)", WINDOWS_1252).unwrap();
+        assert_eq!(regex.find(b"un caf\xe9"), Some(3..7));
+        assert!(regex.matches(b"cafe"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(EncodedRegex::new(r"山", WINDOWS_1252).is_err());
+        assert!(EncodedRegex::new(r"a", UTF_16LE).is_err());
+        assert!(EncodedRegex::new(r"a(", SHIFT_JIS).is_err());
+    }
+}
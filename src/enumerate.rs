@@ -0,0 +1,117 @@
+use crate::automaton::{DFA, DFAState};
+use crate::hash::{HashMap, HashSet};
+
+/// [`Regex::strings`](crate::Regex::strings) が返すイテレータ
+///
+/// 受理する長さ `max_len` 以下の文字列を辞書順に列挙する.
+/// 受理状態へ到達できない遷移は辿らないので, 1つの文字列を得るまでの計算量は `max_len` に比例する.
+pub struct Strings {
+    /// edges[state]: 残りの文字数で受理状態に到達できる可能性のある遷移
+    edges: HashMap<DFAState, Vec<(char, DFAState)>>,
+    distances: HashMap<DFAState, usize>,
+    accepts: HashSet<DFAState>,
+    max_len: usize,
+    /// 探索中の経路. (状態, 次に試す遷移の添字)
+    stack: Vec<(DFAState, usize)>,
+    prefix: String,
+    /// 開始状態を訪れた直後か
+    pending: bool,
+}
+
+impl Strings {
+    pub(crate) fn new(dfa: &DFA, max_len: usize) -> Self {
+        let distances: HashMap<DFAState, usize> = dfa.distances_to_accept();
+        let edges: HashMap<DFAState, Vec<(char, DFAState)>> = distances
+            .keys()
+            .map(|state| {
+                let edges: Vec<(char, DFAState)> = dfa
+                    .edges(*state)
+                    .into_iter()
+                    .filter(|(_, next)| distances.contains_key(next))
+                    .collect();
+                (*state, edges)
+            })
+            .collect();
+        let stack: Vec<(DFAState, usize)> = match distances.get(&dfa.start) {
+            Some(distance) if *distance <= max_len => vec![(dfa.start, 0)],
+            _ => vec![],
+        };
+        Strings {
+            edges,
+            distances,
+            accepts: dfa.accepts.clone(),
+            max_len,
+            pending: !stack.is_empty(),
+            stack,
+            prefix: String::new(),
+        }
+    }
+}
+
+impl Iterator for Strings {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if std::mem::take(&mut self.pending) && self.accepts.contains(&self.stack[0].0) {
+            return Some(self.prefix.clone());
+        }
+        while let Some(&(state, index)) = self.stack.last() {
+            // あと何文字追加できるか
+            let remaining: usize = self.max_len + 1 - self.stack.len();
+            let edges: &[(char, DFAState)] = &self.edges[&state][index..];
+            match edges
+                .iter()
+                .position(|(_, next)| self.distances[next] < remaining)
+            {
+                Some(offset) => {
+                    let (chara, next) = edges[offset];
+                    self.stack.last_mut().unwrap().1 = index + offset + 1;
+                    self.stack.push((next, 0));
+                    self.prefix.push(chara);
+                    if self.accepts.contains(&next) {
+                        return Some(self.prefix.clone());
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    self.prefix.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Regex;
+
+    fn strings(pattern: &str, max_len: usize) -> Vec<String> {
+        Regex::new(pattern).unwrap().strings(max_len).collect()
+    }
+
+    #[test]
+    fn finite() {
+        assert_eq!(
+            strings(r"(p(erl|ython|hp)|ruby)", 10),
+            ["perl", "php", "python", "ruby"]
+        );
+        assert_eq!(
+            strings(r"(p(erl|ython|hp)|ruby)", 4),
+            ["perl", "php", "ruby"]
+        );
+        assert_eq!(strings(r"a(b|)", 0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn infinite() {
+        assert_eq!(strings(r"a*", 3), ["", "a", "aa", "aaa"]);
+        assert_eq!(strings(r"(a|b)*c", 2), ["ac", "bc", "c"]);
+    }
+
+    #[test]
+    fn lazy() {
+        let regex = Regex::new(r"(a|b|c|d)*").unwrap();
+        assert_eq!(regex.strings(1000).nth(3), Some("aaa".to_string()));
+    }
+}
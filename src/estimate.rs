@@ -0,0 +1,63 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::automaton::Positions;
+use crate::lexer::Lexer;
+use crate::parser::{Node, Parser};
+
+/// DFAを作らずに, パターンから作られる最小DFAの状態数の上限を見積もる
+///
+/// Glushkov構成のNFAに部分集合構成法を適用したとき, 開始状態以外の各状態は同じ文字が置かれた位置の集合になる.
+/// NFAが決定的 (各状態から同じ文字で移れる位置が高々1つ) なら状態数は位置の数 + 1 以下,
+/// そうでなければ文字ごとの位置の部分集合の数の和で抑える. 値は `u64::MAX` で飽和する.
+pub fn estimate_dfa_states(pattern: &str) -> Result<u64, Box<dyn Error>> {
+    let node: Node = Parser::new(Lexer::new(pattern)).parse()?;
+    Ok(estimate(&Positions::from_node(&node)))
+}
+
+fn estimate(positions: &Positions) -> u64 {
+    let deterministic = |set: &BTreeSet<usize>| {
+        let mut seen: BTreeSet<char> = BTreeSet::new();
+        set.iter().all(|p| seen.insert(positions.chars[*p]))
+    };
+    if deterministic(&positions.first) && positions.follow.iter().all(deterministic) {
+        return positions.chars.len() as u64 + 1;
+    }
+
+    let mut counts: HashMap<char, u32> = HashMap::new();
+    for chara in &positions.chars {
+        *counts.entry(*chara).or_default() += 1;
+    }
+    counts.values().fold(1u64, |total, count| {
+        let subsets: u64 = 1u64.checked_shl(*count).map_or(u64::MAX, |n| n - 1);
+        total.saturating_add(subsets)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Regex;
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(estimate_dfa_states(&"a".repeat(100)).unwrap(), 101);
+        assert_eq!(estimate_dfa_states(r"(ab|c)*d").unwrap(), 5);
+    }
+
+    #[test]
+    fn nondeterministic() {
+        // 位置: a=0, a=1, b=2
+        assert_eq!(estimate_dfa_states(r"(a|ab)*").unwrap(), 1 + 3 + 1);
+        let explosive = format!("(a|b)*a{}", "(a|b)".repeat(70));
+        assert_eq!(estimate_dfa_states(&explosive).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn upper_bound() {
+        for pattern in [r"(a|ab)*", r"(a|b)*a(a|b)(a|b)", r"a*ba*|ab*"] {
+            let states = Regex::new(pattern).unwrap().dfa().minimize().states().len();
+            assert!(states as u64 <= estimate_dfa_states(pattern).unwrap());
+        }
+    }
+}
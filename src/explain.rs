@@ -0,0 +1,124 @@
+use std::error::Error;
+
+use crate::lexer::Lexer;
+use crate::parser::{Kind, Node, NodeRef, Parser};
+
+/// パターンの構造を字下げした英語の説明文にする
+pub fn explain(pattern: &str) -> Result<String, Box<dyn Error>> {
+    let node: Node = Parser::new(Lexer::new(pattern)).parse()?;
+    let mut text: String = String::new();
+    write_node(&mut text, node.root(), 0);
+    Ok(text)
+}
+
+/// 説明の1項目
+enum Item<'a> {
+    /// 連続する文字
+    Text(String),
+    Node(NodeRef<'a>),
+}
+
+fn write_node(text: &mut String, node: NodeRef<'_>, depth: usize) {
+    let indent: String = "  ".repeat(depth);
+    match node.kind() {
+        Kind::Character(_) | Kind::Concat(_, _) => {
+            let mut items: Vec<Item<'_>> = Vec::new();
+            collect_sequence(node, &mut items);
+            match items.as_slice() {
+                [Item::Text(string)] => text.push_str(&format!("{}{:?}\n", indent, string)),
+                _ => {
+                    text.push_str(&format!("{}a sequence of:\n", indent));
+                    for item in &items {
+                        match item {
+                            Item::Text(string) => {
+                                text.push_str(&format!("{}  {:?}\n", indent, string))
+                            }
+                            Item::Node(node) => write_node(text, *node, depth + 1),
+                        }
+                    }
+                }
+            }
+        }
+        Kind::Empty => text.push_str(&format!("{}the empty string\n", indent)),
+        Kind::Star(node) => match node.literal() {
+            Some(string) if !string.is_empty() => {
+                text.push_str(&format!("{}zero or more of {:?}\n", indent, string))
+            }
+            _ => {
+                text.push_str(&format!("{}zero or more of:\n", indent));
+                write_node(text, node, depth + 1);
+            }
+        },
+        Kind::Group(index, node) => {
+            text.push_str(&format!("{}group {}:\n", indent, index));
+            write_node(text, node, depth + 1);
+        }
+        Kind::Union(_, _) => {
+            text.push_str(&format!("{}one of:\n", indent));
+            let mut branches: Vec<NodeRef<'_>> = Vec::new();
+            collect_union(node, &mut branches);
+            for branch in branches {
+                write_node(text, branch, depth + 1);
+            }
+        }
+    }
+}
+
+/// 連接を平坦にし, 連続する文字を1つの文字列にまとめる
+fn collect_sequence<'a>(node: NodeRef<'a>, items: &mut Vec<Item<'a>>) {
+    match node.kind() {
+        Kind::Concat(n1, n2) => {
+            collect_sequence(n1, items);
+            collect_sequence(n2, items);
+        }
+        Kind::Character(chara) => match items.last_mut() {
+            Some(Item::Text(string)) => string.push(chara),
+            _ => items.push(Item::Text(chara.to_string())),
+        },
+        Kind::Empty => {}
+        _ => items.push(Item::Node(node)),
+    }
+}
+
+fn collect_union<'a>(node: NodeRef<'a>, branches: &mut Vec<NodeRef<'a>>) {
+    match node.kind() {
+        Kind::Union(n1, n2) => {
+            collect_union(n1, branches);
+            collect_union(n2, branches);
+        }
+        _ => branches.push(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explain_simple() {
+        assert_eq!(explain(r"abc").unwrap(), "\"abc\"\n");
+        assert_eq!(explain(r"").unwrap(), "the empty string\n");
+        assert_eq!(explain(r"(ab)*").unwrap(), "zero or more of \"ab\"\n");
+    }
+
+    #[test]
+    fn explain_nested() {
+        assert_eq!(
+            explain(r"a(b|)*c").unwrap(),
+            concat!(
+                "a sequence of:\n",
+                "  \"a\"\n",
+                "  zero or more of:\n",
+                "    one of:\n",
+                "      \"b\"\n",
+                "      the empty string\n",
+                "  \"c\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn explain_fail() {
+        assert!(explain(r"a(b").is_err());
+    }
+}
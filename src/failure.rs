@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+
+use crate::automaton::{DFA, DFAState};
+
+/// [`Regex::explain_failure`](crate::Regex::explain_failure) が返す, マッチしなかった理由
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchFailure {
+    offset: usize,
+    state: u32,
+    found: Option<char>,
+    expected: Vec<char>,
+    expects_other: bool,
+    expects_end: bool,
+}
+
+impl MatchFailure {
+    /// 照合ではマッチしなかったが, DFA では理由が見つからなかった場合の失敗. 文字列の終わりで何も受理されないものとして扱う
+    pub(crate) fn unexplained(text: &str, state: u32) -> Self {
+        MatchFailure {
+            offset: text.len(),
+            state,
+            found: None,
+            expected: Vec::new(),
+            expects_other: false,
+            expects_end: false,
+        }
+    }
+
+    /// DFAが行き詰まった位置 (バイトオフセット). 文字列の最後まで読めた場合は文字列の長さ
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 行き詰まったときのDFAの状態番号
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    /// 受理されなかった文字. 文字列の最後まで読めた場合は `None`
+    pub fn found(&self) -> Option<char> {
+        self.found
+    }
+
+    /// その位置で受理される文字 (昇順)
+    pub fn expected(&self) -> &[char] {
+        &self.expected
+    }
+
+    /// [`MatchFailure::expected`] に無い任意の文字も受理されるか
+    pub fn expects_other(&self) -> bool {
+        self.expects_other
+    }
+
+    /// その位置で文字列が終わっていれば受理されたか
+    pub fn expects_end(&self) -> bool {
+        self.expects_end
+    }
+}
+
+impl std::fmt::Display for MatchFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut expected: Vec<String> = self.expected.iter().map(|c| format!("'{}'", c)).collect();
+        if self.expects_other {
+            expected.push("any other character".to_string());
+        }
+        if self.expects_end {
+            expected.push("EOF".to_string());
+        }
+        let found: String = match self.found {
+            Some(chara) => format!("'{}'", chara),
+            None => "EOF".to_string(),
+        };
+        write!(
+            f,
+            "Expected one of [{}], found {} at {}",
+            expected.join(", "),
+            found,
+            self.offset
+        )
+    }
+}
+
+/// [`Regex::validate`](crate::Regex::validate) が返す, 文字列全体がパターンにマッチしなかったことを表すエラー
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    pattern: String,
+    failure: MatchFailure,
+}
+
+impl ValidationError {
+    pub(crate) fn new(pattern: &str, failure: MatchFailure) -> Self {
+        ValidationError {
+            pattern: pattern.to_string(),
+            failure,
+        }
+    }
+
+    /// 検証に使ったパターン
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// マッチしなくなった位置 (バイトオフセット). 文字列が途中で終わった場合は文字列の長さ
+    pub fn offset(&self) -> usize {
+        self.failure.offset()
+    }
+
+    /// 受理されなかった文字. 文字列が途中で終わった場合は `None`
+    pub fn found(&self) -> Option<char> {
+        self.failure.found()
+    }
+
+    /// その位置で受理される文字 (昇順)
+    pub fn expected(&self) -> &[char] {
+        self.failure.expected()
+    }
+
+    /// 詳しい理由
+    pub fn failure(&self) -> &MatchFailure {
+        &self.failure
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "text does not match the pattern {:?}: {}",
+            self.pattern, self.failure
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// `text` 全体をDFAで読み, 受理されなかった場合はその理由を返す
+pub(crate) fn explain(dfa: &DFA, text: &str) -> Option<MatchFailure> {
+    let mut state: DFAState = dfa.start;
+    let mut dead: Option<(usize, char)> = None;
+    for (i, chara) in text.char_indices() {
+        match dfa.next_state(state, chara) {
+            Some(next) => state = next,
+            None => {
+                dead = Some((i, chara));
+                break;
+            }
+        }
+    }
+    if dead.is_none() && dfa.is_accept(state) {
+        return None;
+    }
+    let expected: BTreeSet<char> = dfa
+        .transition
+        .keys()
+        .filter(|(from, _)| *from == state)
+        .map(|(_, chara)| *chara)
+        .collect();
+    Some(MatchFailure {
+        offset: dead.map_or(text.len(), |(i, _)| i),
+        state: state.0,
+        found: dead.map(|(_, chara)| chara),
+        expected: expected.into_iter().collect(),
+        expects_other: dfa.otherwise.contains_key(&state),
+        expects_end: dfa.is_accept(state),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Regex;
+
+    #[test]
+    fn dead() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let failure = regex.explain_failure("山田次郎").unwrap();
+        assert_eq!(failure.offset(), 6);
+        assert_eq!(failure.found(), Some('次'));
+        assert_eq!(failure.expected(), ['一', '太']);
+        assert!(!failure.expects_other());
+        assert!(!failure.expects_end());
+        assert_eq!(
+            failure.to_string(),
+            "Expected one of ['一', '太'], found '次' at 6"
+        );
+    }
+
+    #[test]
+    fn too_short() {
+        let regex = Regex::new(r"ab*c").unwrap();
+        let failure = regex.explain_failure("abb").unwrap();
+        assert_eq!(failure.offset(), 3);
+        assert_eq!(failure.found(), None);
+        assert_eq!(failure.expected(), ['b', 'c']);
+    }
+
+    #[test]
+    fn matched() {
+        let regex = Regex::new(r"ab*c").unwrap();
+        assert!(regex.explain_failure("abbc").is_none());
+    }
+
+    #[test]
+    fn unexplained() {
+        let failure = super::MatchFailure::unexplained("abc", 0);
+        assert_eq!((failure.offset(), failure.found()), (3, None));
+        assert!(failure.expected().is_empty() && !failure.expects_end());
+        assert_eq!(failure.to_string(), "Expected one of [], found EOF at 3");
+    }
+
+    #[test]
+    fn validate() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        assert!(regex.validate("山田一郎").is_ok());
+        let err = regex.validate("山田次郎").unwrap_err();
+        assert_eq!(err.pattern(), r"山田(太|一)郎");
+        assert_eq!((err.offset(), err.found()), (6, Some('次')));
+        assert_eq!(err.expected(), ['一', '太']);
+        assert_eq!(
+            err.to_string(),
+            r#"text does not match the pattern "山田(太|一)郎": Expected one of ['一', '太'], found '次' at 6"#
+        );
+        assert_eq!(regex.validate("山田").unwrap_err().found(), None);
+    }
+
+    #[test]
+    fn negated() {
+        let regex = Regex::new(r"a*").unwrap().negate();
+        let failure = regex.explain_failure("aa").unwrap();
+        assert_eq!(failure.offset(), 2);
+        assert!(failure.expects_other());
+        assert!(!failure.expects_end());
+    }
+}
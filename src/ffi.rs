@@ -0,0 +1,134 @@
+//! C から使うための関数. 宣言は `include/ryota2357_regex.h` にある
+
+use std::ffi::{CStr, c_char};
+
+use crate::Regex;
+
+/// NUL終端のパターン文字列をコンパイルする. 不正なパターンやUTF-8でない場合はNULL
+///
+/// C の呼び出し元へ panic を伝えるとプロセスが終了するので, コンパイル中の panic もNULLにする.
+///
+/// # Safety
+///
+/// `pattern` はNULLか, NUL終端の文字列を指すこと.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn regex_new(pattern: *const c_char) -> *mut Regex {
+    if pattern.is_null() {
+        return std::ptr::null_mut();
+    }
+    let pattern: &CStr = unsafe { CStr::from_ptr(pattern) };
+    let compile = || pattern.to_str().ok().and_then(|p| Regex::new(p).ok());
+    match std::panic::catch_unwind(compile) {
+        Ok(Some(regex)) => Box::into_raw(Box::new(regex)),
+        Ok(None) | Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// `text` から始まる `len` バイトの文字列の部分文字列にマッチするか. UTF-8でない場合は `false`
+///
+/// # Safety
+///
+/// `regex` は [`regex_new`] が返したNULLでないポインタで, `text` は `len` バイト読めること.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn regex_is_match(regex: *const Regex, text: *const u8, len: usize) -> bool {
+    let regex: &Regex = unsafe { &*regex };
+    match unsafe { haystack(text, len) } {
+        Some(text) => regex.is_match(text),
+        None => false,
+    }
+}
+
+/// 最も左にあるマッチを探し, 見つかれば `*start`, `*end` にそのバイトオフセットを書いて `true` を返す
+///
+/// # Safety
+///
+/// [`regex_is_match`] の条件に加え, `start`, `end` は書き込み可能な `size_t` を指すこと.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn regex_find(
+    regex: *const Regex,
+    text: *const u8,
+    len: usize,
+    start: *mut usize,
+    end: *mut usize,
+) -> bool {
+    let regex: &Regex = unsafe { &*regex };
+    let Some(found) = unsafe { haystack(text, len) }.and_then(|text| regex.find(text)) else {
+        return false;
+    };
+    unsafe {
+        *start = found.start();
+        *end = found.end();
+    }
+    true
+}
+
+/// [`regex_new`] が返した `Regex` を解放する. NULLの場合は何もしない
+///
+/// # Safety
+///
+/// `regex` は [`regex_new`] が返したポインタで, まだ解放されていないこと.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn regex_free(regex: *mut Regex) {
+    if !regex.is_null() {
+        drop(unsafe { Box::from_raw(regex) });
+    }
+}
+
+unsafe fn haystack<'a>(text: *const u8, len: usize) -> Option<&'a str> {
+    if len == 0 {
+        return Some("");
+    }
+    let bytes: &[u8] = unsafe { std::slice::from_raw_parts(text, len) };
+    std::str::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi() {
+        unsafe {
+            let regex = regex_new(c"山田(太|一)郎".as_ptr());
+            assert!(!regex.is_null());
+            let text = "私は山田太郎です";
+            assert!(regex_is_match(regex, text.as_ptr(), text.len()));
+            let (mut start, mut end) = (0, 0);
+            assert!(regex_find(
+                regex,
+                text.as_ptr(),
+                text.len(),
+                &mut start,
+                &mut end
+            ));
+            assert_eq!(&text[start..end], "山田太郎");
+            assert!(!regex_is_match(regex, [0xff].as_ptr(), 1));
+            assert!(!regex_find(
+                regex,
+                std::ptr::null(),
+                0,
+                &mut start,
+                &mut end
+            ));
+            regex_free(regex);
+        }
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        unsafe {
+            assert!(regex_new(c"a(".as_ptr()).is_null());
+            assert!(regex_new(c"a\\".as_ptr()).is_null());
+            assert!(regex_new(std::ptr::null()).is_null());
+            regex_free(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn header() {
+        let header: &str = include_str!("../include/ryota2357_regex.h");
+        for name in ["regex_new", "regex_is_match", "regex_find", "regex_free"] {
+            assert!(header.contains(&format!("{}(", name)));
+        }
+    }
+}
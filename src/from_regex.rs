@@ -0,0 +1,168 @@
+use std::error::Error;
+
+use crate::{Captures, Regex};
+
+/// パターンにマッチした文字列から作ることができる型. 通常は `#[derive(FromRegex)]` で実装する
+pub trait FromRegex: Sized {
+    /// 照合に使うパターン
+    fn regex() -> &'static Regex;
+
+    /// キャプチャグループから値を作る
+    fn from_captures(caps: &Captures<'_>) -> Result<Self, FromRegexError>;
+
+    /// `text` 全体がパターンにマッチする場合, そのキャプチャグループから値を作る
+    fn from_regex(text: &str) -> Result<Self, FromRegexError> {
+        let regex: &Regex = Self::regex();
+        if !regex.matches(text) {
+            return Err(FromRegexError::NoMatch);
+        }
+        let caps: Captures<'_> = regex.captures(text).ok_or(FromRegexError::NoMatch)?;
+        Self::from_captures(&caps)
+    }
+}
+
+/// [`FromRegex::from_regex`] の失敗
+#[derive(Debug)]
+pub enum FromRegexError {
+    /// 文字列全体がパターンにマッチしなかった
+    NoMatch,
+    /// フィールドに対応するグループが照合に関与しなかった
+    MissingGroup(usize),
+    /// グループにマッチした部分を, フィールドの型に変換できなかった
+    Parse {
+        field: &'static str,
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl FromRegexError {
+    #[doc(hidden)]
+    pub fn parse(field: &'static str, err: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        FromRegexError::Parse {
+            field,
+            source: err.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for FromRegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FromRegexError::NoMatch => write!(f, "text does not match the pattern"),
+            FromRegexError::MissingGroup(group) => {
+                write!(f, "group {} did not participate in the match", group)
+            }
+            FromRegexError::Parse { field, source } => {
+                write!(f, "failed to parse field `{}`: {}", field, source)
+            }
+        }
+    }
+}
+
+impl Error for FromRegexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FromRegexError::Parse { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FromRegex;
+
+    #[derive(Debug, PartialEq, FromRegex)]
+    #[regex(pattern = r"(?<method>GET|POST) (?<path>/(a|b|c)*) (2|4|5)(0|1)(0|1|4)")]
+    struct Request {
+        method: String,
+        path: String,
+        #[regex(group = 4)]
+        class: u8,
+    }
+
+    #[derive(Debug, PartialEq, FromRegex)]
+    #[regex(pattern = r"(山田|佐藤)(|((太|一)郎))")]
+    struct Name(String, #[regex(group = 3)] Option<String>);
+
+    #[test]
+    fn derive() {
+        assert_eq!(
+            Request::from_regex("GET /abc 404").unwrap(),
+            Request {
+                method: "GET".to_string(),
+                path: "/abc".to_string(),
+                class: 4,
+            }
+        );
+        assert!(matches!(
+            Request::from_regex("GET /abc 404 extra"),
+            Err(FromRegexError::NoMatch)
+        ));
+        assert_eq!(
+            Name::from_regex("山田").unwrap(),
+            Name("山田".to_string(), None)
+        );
+        assert_eq!(
+            Name::from_regex("佐藤一郎").unwrap(),
+            Name("佐藤".to_string(), Some("一郎".to_string()))
+        );
+    }
+
+    #[derive(Debug, FromRegex)]
+    #[regex(pattern = r"(?<n>a*)")]
+    struct Count {
+        #[allow(dead_code)]
+        n: u8,
+    }
+
+    #[derive(Debug, PartialEq, FromRegex)]
+    #[regex(pattern = r"(?<b>b*)(?<a>a)")]
+    struct Swapped {
+        a: String,
+        b: String,
+    }
+
+    #[derive(Debug, FromRegex)]
+    #[regex(pattern = r"(?<name>a)")]
+    struct Unnamed {
+        #[allow(dead_code)]
+        nmae: String,
+    }
+
+    #[derive(Debug, FromRegex)]
+    #[regex(pattern = r"(a)")]
+    struct OutOfRange(#[allow(dead_code)] String, #[allow(dead_code)] String);
+
+    #[test]
+    fn bind_by_name() {
+        // フィールドの順序ではなく, 名前でグループに対応させる
+        assert_eq!(
+            Swapped::from_regex("bba").unwrap(),
+            Swapped {
+                a: "a".to_string(),
+                b: "bb".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "has no group named `nmae` for field `nmae`")]
+    fn missing_name() {
+        Unnamed::regex();
+    }
+
+    #[test]
+    #[should_panic(expected = "has no group 2 for field `1` (it has 1 groups)")]
+    fn missing_group() {
+        OutOfRange::regex();
+    }
+
+    #[test]
+    fn parse_error() {
+        let err = Count::from_regex("aaa").unwrap_err();
+        assert!(matches!(err, FromRegexError::Parse { field: "n", .. }));
+        assert!(err.to_string().starts_with("failed to parse field `n`"));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+
+use crate::Regex;
+
+/// [`Regex::grep`] が返す行の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    /// 選ばれた行 (マッチした行. [`Grep::invert`] を指定した場合はマッチしなかった行)
+    Match,
+    /// 選ばれた行の前後の行
+    Context,
+}
+
+/// [`Regex::grep`] が返す行
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line<L> {
+    number: usize,
+    text: L,
+    kind: LineKind,
+}
+
+impl<L: AsRef<str>> Line<L> {
+    /// 行番号 (1 から数える)
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_ref()
+    }
+
+    pub fn into_text(self) -> L {
+        self.text
+    }
+
+    pub fn kind(&self) -> LineKind {
+        self.kind
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.kind == LineKind::Match
+    }
+}
+
+/// 選ばれた行と, その前後の行を順に返すイテレータ
+///
+/// 前後の行が重なる場合も各行は一度だけ返す. 返した行の番号が連続していなければ, その間で区切られている.
+pub struct Grep<'r, I: Iterator> {
+    regex: &'r Regex,
+    lines: std::iter::Enumerate<I>,
+    before: usize,
+    after: usize,
+    invert: bool,
+    /// 選ばれた行の前に返すかもしれない, まだ返していない直前の行 (最大 `before` 行)
+    recent: VecDeque<Line<I::Item>>,
+    /// 返す順に並べた行
+    queue: VecDeque<Line<I::Item>>,
+    /// 直前に選ばれた行の後に, あと何行を返すか
+    remaining_after: usize,
+}
+
+impl<'r, I> Grep<'r, I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    /// 選ばれた行の前の `lines` 行も返す (grep の `-B`)
+    pub fn before(mut self, lines: usize) -> Self {
+        self.before = lines;
+        self
+    }
+
+    /// 選ばれた行の後の `lines` 行も返す (grep の `-A`)
+    pub fn after(mut self, lines: usize) -> Self {
+        self.after = lines;
+        self
+    }
+
+    /// 選ばれた行の前後の `lines` 行も返す (grep の `-C`)
+    pub fn context(self, lines: usize) -> Self {
+        self.before(lines).after(lines)
+    }
+
+    /// `true` の場合, マッチしなかった行を選ぶ (grep の `-v`)
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+}
+
+impl<I> Iterator for Grep<'_, I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    type Item = Line<I::Item>;
+
+    fn next(&mut self) -> Option<Line<I::Item>> {
+        loop {
+            if let Some(line) = self.queue.pop_front() {
+                return Some(line);
+            }
+            let (i, text) = self.lines.next()?;
+            let selected: bool = self.regex.is_match(text.as_ref()) != self.invert;
+            let mut line: Line<I::Item> = Line {
+                number: i + 1,
+                text,
+                kind: LineKind::Context,
+            };
+            if selected {
+                line.kind = LineKind::Match;
+                self.queue.extend(self.recent.drain(..));
+                self.queue.push_back(line);
+                self.remaining_after = self.after;
+            } else if self.remaining_after > 0 {
+                self.remaining_after -= 1;
+                return Some(line);
+            } else if self.before > 0 {
+                if self.recent.len() == self.before {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back(line);
+            }
+        }
+    }
+}
+
+impl Regex {
+    /// `lines` のうちマッチする行を順に返す. [`Grep::context`] などで前後の行も返すようにできる
+    ///
+    /// `text.lines()` や `reader.lines().map_while(Result::ok)` などを渡す. 前の行は必要な分だけを保持する.
+    pub fn grep<I>(&self, lines: I) -> Grep<'_, I::IntoIter>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        Grep {
+            regex: self,
+            lines: lines.into_iter().enumerate(),
+            before: 0,
+            after: 0,
+            invert: false,
+            recent: VecDeque::new(),
+            queue: VecDeque::new(),
+            remaining_after: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep(lines: Grep<'_, std::str::Lines<'_>>) -> Vec<(usize, bool)> {
+        lines.map(|line| (line.number(), line.is_match())).collect()
+    }
+
+    #[test]
+    fn context() {
+        let regex = Regex::new(r"山田").unwrap();
+        let text: &str = "a\n山田\nb\nc\nd\ne\n山田\nf\n山田\ng\nh";
+        assert_eq!(
+            grep(regex.grep(text.lines())),
+            [(2, true), (7, true), (9, true)]
+        );
+        assert_eq!(
+            grep(regex.grep(text.lines()).context(1)),
+            [
+                (1, false),
+                (2, true),
+                (3, false),
+                (6, false),
+                (7, true),
+                (8, false),
+                (9, true),
+                (10, false)
+            ]
+        );
+        assert_eq!(
+            grep(regex.grep(text.lines()).before(3)),
+            [
+                (1, false),
+                (2, true),
+                (4, false),
+                (5, false),
+                (6, false),
+                (7, true),
+                (8, false),
+                (9, true)
+            ]
+        );
+        assert_eq!(
+            grep(regex.grep(text.lines()).after(2).invert(true)).len(),
+            text.lines().count()
+        );
+        let first = regex.grep(text.lines()).next().unwrap();
+        assert_eq!((first.text(), first.kind()), ("山田", LineKind::Match));
+    }
+
+    #[test]
+    fn owned_lines() {
+        let regex = Regex::new(r"b").unwrap();
+        let reader: &[u8] = b"a\nb\nc\n";
+        let lines: Vec<String> = std::io::BufRead::lines(reader)
+            .map_while(Result::ok)
+            .collect();
+        let found: Vec<String> = regex.grep(lines).after(5).map(Line::into_text).collect();
+        assert_eq!(found, ["b", "c"]);
+    }
+}
@@ -0,0 +1,13 @@
+//! オートマトンの構築に使うハッシュ表
+//!
+//! `fxhash` 機能を有効にすると, 標準の SipHash の代わりに FxHash を使う. 鍵は状態の番号や文字で, ハッシュ値を
+//! 求める時間が表の操作の大半を占めるため. FxHash は衝突させる入力を作れるので, 信頼できないパターンを
+//! コンパイルする場合は有効にしない.
+
+#[cfg(feature = "fxhash")]
+pub(crate) type BuildHasher = rustc_hash::FxBuildHasher;
+#[cfg(not(feature = "fxhash"))]
+pub(crate) type BuildHasher = std::hash::RandomState;
+
+pub(crate) type HashMap<K, V> = std::collections::HashMap<K, V, BuildHasher>;
+pub(crate) type HashSet<T> = std::collections::HashSet<T, BuildHasher>;
@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+
+use crate::Regex;
+
+/// マッチした部分を印で囲んで強調する
+///
+/// 既定では ANSI エスケープシーケンスの太字の赤で囲む. 空文字列へのマッチは囲まない.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Highlighter {
+    before: String,
+    after: String,
+}
+
+impl Highlighter {
+    /// マッチの前に `before`, 後に `after` を挿入する
+    pub fn new(before: &str, after: &str) -> Self {
+        Highlighter {
+            before: before.to_string(),
+            after: after.to_string(),
+        }
+    }
+
+    /// SGR のパラメータ (`"1;31"` など) で指定した ANSI スタイルで囲み, 後でスタイルを戻す
+    pub fn ansi(style: &str) -> Self {
+        Highlighter {
+            before: format!("\x1b[{}m", style),
+            after: "\x1b[0m".to_string(),
+        }
+    }
+
+    /// `haystack` 中の重ならないマッチ ([`Regex::find_iter`] と同じ) を囲んだ文字列. マッチがなければ複製しない
+    pub fn highlight<'h>(&self, regex: &Regex, haystack: &'h str) -> Cow<'h, str> {
+        let mut found = regex
+            .find_iter(haystack)
+            .filter(|m| !m.as_str().is_empty())
+            .peekable();
+        if found.peek().is_none() {
+            return Cow::Borrowed(haystack);
+        }
+        let mut highlighted: String = String::with_capacity(haystack.len());
+        let mut last: usize = 0;
+        for m in found {
+            highlighted.push_str(&haystack[last..m.start()]);
+            self.push_wrapped(&mut highlighted, m.as_str());
+            last = m.end();
+        }
+        highlighted.push_str(&haystack[last..]);
+        Cow::Owned(highlighted)
+    }
+
+    /// `text` 全体を囲んだ文字列
+    pub fn wrap(&self, text: &str) -> String {
+        let mut wrapped: String = String::new();
+        self.push_wrapped(&mut wrapped, text);
+        wrapped
+    }
+
+    fn push_wrapped(&self, dst: &mut String, text: &str) {
+        dst.push_str(&self.before);
+        dst.push_str(text);
+        dst.push_str(&self.after);
+    }
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Highlighter::ansi("1;31")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let text: &str = "山田太郎と山田一郎";
+        assert_eq!(
+            Highlighter::new("[", "]").highlight(&regex, text),
+            "[山田太郎]と[山田一郎]"
+        );
+        assert_eq!(
+            Highlighter::default().highlight(&regex, "山田太郎です"),
+            "\x1b[1;31m山田太郎\x1b[0mです"
+        );
+        assert_eq!(
+            Highlighter::ansi("4").highlight(&Regex::new(r"a").unwrap(), "ba"),
+            "b\x1b[4ma\x1b[0m"
+        );
+        assert!(matches!(
+            Highlighter::default().highlight(&regex, "鈴木"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(Highlighter::new("[", "]").wrap("山田"), "[山田]");
+    }
+
+    #[test]
+    fn empty_matches() {
+        let regex = Regex::new(r"a*").unwrap();
+        assert_eq!(
+            Highlighter::new("<", ">").highlight(&regex, "baac"),
+            "b<aa>c"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::error::Error;
+
+use regex_syntax::hir::{Capture, Hir, Repetition};
+
+use crate::lexer::Lexer;
+use crate::parser::{Kind, Node, NodeRef, Parser};
+
+/// パターンを `regex-syntax` の [`Hir`] に変換する
+///
+/// 括弧はキャプチャグループ (番号は左から数えた開き括弧の順) になり, `(?<name>...)` の名前も引き継ぐ.
+/// [`Hir`] のグループの番号は重なってはならないので, ブランチリセット (`(?|...)`) で番号が重なる場合はエラーになる.
+pub fn to_hir(pattern: &str) -> Result<Hir, Box<dyn Error>> {
+    let mut parser: Parser<'_> = Parser::new(Lexer::new(pattern)).with_captures();
+    let node: Node = parser.parse()?;
+    let names: Vec<Option<String>> = parser.take_group_names();
+    if count_groups(node.root()) != names.len() {
+        return Err("branch reset groups with shared indices cannot be converted to Hir".into());
+    }
+    Ok(hir(node.root(), &names))
+}
+
+/// 構文木にある [`Kind::Group`] の数. ブランチリセットで番号が重なると, グループの数より多くなる
+fn count_groups(node: NodeRef<'_>) -> usize {
+    match node.kind() {
+        Kind::Character(_) | Kind::Empty => 0,
+        Kind::Star(node) => count_groups(node),
+        Kind::Union(n1, n2) | Kind::Concat(n1, n2) => count_groups(n1) + count_groups(n2),
+        Kind::Group(_, node) => 1 + count_groups(node),
+    }
+}
+
+/// `names` はグループ `i` の名前を `i - 1` に持つ
+fn hir(node: NodeRef<'_>, names: &[Option<String>]) -> Hir {
+    match node.kind() {
+        Kind::Character(chara) => Hir::literal(chara.to_string().into_bytes()),
+        Kind::Empty => Hir::empty(),
+        Kind::Star(node) => Hir::repetition(Repetition {
+            min: 0,
+            max: None,
+            greedy: true,
+            sub: Box::new(hir(node, names)),
+        }),
+        Kind::Union(n1, n2) => Hir::alternation(vec![hir(n1, names), hir(n2, names)]),
+        Kind::Concat(n1, n2) => Hir::concat(vec![hir(n1, names), hir(n2, names)]),
+        Kind::Group(index, node) => Hir::capture(Capture {
+            index: index as u32,
+            name: names[index - 1].as_deref().map(Box::from),
+            sub: Box::new(hir(node, names)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion() {
+        for pattern in [
+            r"abc",
+            r"山田(太|一)郎",
+            r"(a|b)*c",
+            r"a\*\|",
+            r"a()",
+            r"(ab)*",
+            r"",
+        ] {
+            assert_eq!(
+                to_hir(pattern).unwrap(),
+                regex_syntax::parse(pattern).unwrap(),
+                "{}",
+                pattern
+            );
+        }
+        assert!(to_hir(r"a(").is_err());
+    }
+
+    #[test]
+    fn names() {
+        let pattern: &str = r"(?<year>0|1)-((?<day>2)|3)";
+        let hir: Hir = to_hir(pattern).unwrap();
+        assert_eq!(hir, regex_syntax::parse(pattern).unwrap());
+        assert_eq!(hir.properties().explicit_captures_len(), 3);
+    }
+
+    #[test]
+    fn branch_reset() {
+        let err = to_hir(r"(?|(a)|(b))").unwrap_err();
+        assert!(err.to_string().contains("branch reset"));
+        // 番号が重ならなければ変換できる
+        assert_eq!(
+            to_hir(r"(?|(a)|b)c").unwrap(),
+            regex_syntax::parse(r"(?:(a)|b)c").unwrap()
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! コンパイルの途中に処理を差し込むためのフック
+//!
+//! [`RegexBuilder::ast_hook`](crate::RegexBuilder::ast_hook) と [`RegexBuilder::nfa_hook`](crate::RegexBuilder::nfa_hook) で登録する.
+//! フックは登録した順に呼ばれ, 前のフックの結果が次のフックに渡される.
+
+use crate::automaton::{NFA, NFAState};
+use crate::parser::{Kind, Node, NodeRef};
+
+/// 構文解析の後の構文木を書き換える
+///
+/// 大文字と小文字を区別しない場合は, その変換の後に呼ばれる. キャプチャグループの位置は書き換えた構文木から求める.
+pub trait AstHook: std::fmt::Debug + Send + Sync {
+    fn rewrite(&self, ast: AstNode) -> AstNode;
+}
+
+/// 決定化する前のNFAを書き換える
+pub trait NfaHook: std::fmt::Debug + Send + Sync {
+    fn rewrite(&self, nfa: Nfa) -> Nfa;
+}
+
+/// [`AstHook`] に渡す構文木
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AstNode {
+    Character(char),
+    Empty,
+    Star(Box<AstNode>),
+    Union(Box<AstNode>, Box<AstNode>),
+    Concat(Box<AstNode>, Box<AstNode>),
+    /// キャプチャグループ. 番号は 1 以上
+    Group(usize, Box<AstNode>),
+}
+
+impl From<NodeRef<'_>> for AstNode {
+    fn from(node: NodeRef<'_>) -> Self {
+        match node.kind() {
+            Kind::Character(chara) => AstNode::Character(chara),
+            Kind::Empty => AstNode::Empty,
+            Kind::Star(node) => AstNode::Star(Box::new(node.into())),
+            Kind::Union(n1, n2) => AstNode::Union(Box::new(n1.into()), Box::new(n2.into())),
+            Kind::Concat(n1, n2) => AstNode::Concat(Box::new(n1.into()), Box::new(n2.into())),
+            Kind::Group(index, node) => AstNode::Group(index, Box::new(node.into())),
+        }
+    }
+}
+
+impl From<AstNode> for Node {
+    fn from(ast: AstNode) -> Self {
+        match ast {
+            AstNode::Character(chara) => Node::character(chara),
+            AstNode::Empty => Node::empty(),
+            AstNode::Star(node) => Node::from(*node).star(),
+            AstNode::Union(n1, n2) => Node::from(*n1).union((*n2).into()),
+            AstNode::Concat(n1, n2) => Node::from(*n1).concat((*n2).into()),
+            AstNode::Group(index, node) => {
+                assert!(index > 0, "capture group index must be at least 1");
+                Node::from(*node).group(index)
+            }
+        }
+    }
+}
+
+/// [`NfaHook`] に渡すNFA. 状態は `0..state_count()` の番号で表し, 文字が `None` の遷移は空遷移
+#[derive(Debug)]
+pub struct Nfa {
+    nfa: NFA,
+    states: u32,
+}
+
+impl Nfa {
+    pub(crate) fn new(nfa: NFA) -> Self {
+        let states: u32 = nfa
+            .transition
+            .iter()
+            .flatten()
+            .map(|(_, to)| to.0 + 1)
+            .chain(nfa.accepts.iter().map(|state| state.0 + 1))
+            .chain([nfa.start.0 + 1, nfa.transition.len() as u32])
+            .max()
+            .unwrap();
+        Nfa { nfa, states }
+    }
+
+    pub(crate) fn into_inner(self) -> NFA {
+        self.nfa
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.states as usize
+    }
+
+    /// 遷移を持たない状態を加え, その番号を返す
+    pub fn add_state(&mut self) -> u32 {
+        self.states += 1;
+        self.states - 1
+    }
+
+    pub fn start(&self) -> u32 {
+        self.nfa.start.0
+    }
+
+    pub fn set_start(&mut self, state: u32) {
+        self.check(state);
+        self.nfa.start = NFAState(state);
+    }
+
+    pub fn is_accept(&self, state: u32) -> bool {
+        self.nfa.accepts.contains(&NFAState(state))
+    }
+
+    /// 受理状態の番号. 順序は決まっていない
+    pub fn accepts(&self) -> impl Iterator<Item = u32> + '_ {
+        self.nfa.accepts.iter().map(|state| state.0)
+    }
+
+    pub fn set_accept(&mut self, state: u32, accept: bool) {
+        self.check(state);
+        if accept {
+            self.nfa.accepts.insert(NFAState(state));
+        } else {
+            self.nfa.accepts.remove(&NFAState(state));
+        }
+    }
+
+    /// すべての遷移 (遷移元, 文字, 遷移先). 遷移元, 文字, 遷移先の順に昇順で, 空遷移は各状態の先頭に来る
+    pub fn transitions(&self) -> impl Iterator<Item = (u32, Option<char>, u32)> + '_ {
+        self.nfa
+            .transition
+            .iter()
+            .enumerate()
+            .flat_map(|(from, edges)| {
+                edges
+                    .iter()
+                    .map(move |(chara, to)| (from as u32, *chara, to.0))
+            })
+    }
+
+    /// 遷移を加える. 同じ遷移が既にあれば何もしない
+    pub fn add_transition(&mut self, from: u32, chara: Option<char>, to: u32) {
+        self.check(from);
+        self.check(to);
+        self.nfa
+            ._insert_transition(NFAState(from), NFAState(to), chara);
+    }
+
+    /// `f` が `false` を返す遷移を取り除く
+    pub fn retain_transitions(&mut self, mut f: impl FnMut(u32, Option<char>, u32) -> bool) {
+        for (from, edges) in self.nfa.transition.iter_mut().enumerate() {
+            edges.retain(|(chara, to)| f(from as u32, *chara, to.0));
+        }
+    }
+
+    fn check(&self, state: u32) {
+        assert!(
+            state < self.states,
+            "state {} is out of range (the NFA has {} states)",
+            state,
+            self.states
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn ast() {
+        let node: Node = Parser::new(Lexer::new(r"(山|a*)b"))
+            .with_captures()
+            .parse()
+            .unwrap();
+        let ast: AstNode = AstNode::from(node.root());
+        assert_eq!(
+            ast,
+            AstNode::Concat(
+                Box::new(AstNode::Group(
+                    1,
+                    Box::new(AstNode::Union(
+                        Box::new(AstNode::Character('山')),
+                        Box::new(AstNode::Star(Box::new(AstNode::Character('a')))),
+                    )),
+                )),
+                Box::new(AstNode::Character('b')),
+            )
+        );
+        assert_eq!(Node::from(ast).to_string(), r"(山|a*)b");
+    }
+
+    #[test]
+    fn nfa() {
+        let node: Node = Parser::new(Lexer::new(r"ab")).parse().unwrap();
+        let mut nfa: Nfa = Nfa::new(NFA::from_node(node));
+        let states: usize = nfa.state_count();
+        let transitions: Vec<(u32, Option<char>, u32)> = nfa.transitions().collect();
+        assert!(transitions.is_sorted());
+        assert_eq!(
+            transitions
+                .iter()
+                .filter(|(_, chara, _)| chara.is_some())
+                .count(),
+            2
+        );
+
+        let state: u32 = nfa.add_state();
+        assert_eq!(state as usize, states);
+        nfa.add_transition(nfa.start(), Some('c'), state);
+        nfa.add_transition(nfa.start(), Some('c'), state);
+        nfa.set_accept(state, true);
+        assert!(nfa.is_accept(state));
+        assert_eq!(nfa.transitions().count(), transitions.len() + 1);
+        nfa.retain_transitions(|_, chara, _| chara != Some('c'));
+        assert_eq!(nfa.transitions().collect::<Vec<_>>(), transitions);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn out_of_range() {
+        let node: Node = Parser::new(Lexer::new(r"a")).parse().unwrap();
+        let mut nfa: Nfa = Nfa::new(NFA::from_node(node));
+        let states: u32 = nfa.state_count() as u32;
+        nfa.add_transition(0, None, states);
+    }
+}
@@ -0,0 +1,485 @@
+use std::error::Error;
+use std::ops::Range;
+
+use crate::{Engine, Regex};
+
+/// [`Regex::search`] で探索できる文字列
+///
+/// 位置はすべて先頭からのバイトオフセット. UTF-8 として正しくない部分の扱いは [`Input::invalid_utf8`] で指定する.
+pub trait Haystack {
+    /// 全体の長さ (バイト)
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `at` から `end` までの文字を, その文字の終了位置と組にして順に返す. 正しくない部分に達したら終わる
+    fn chars_at(&self, at: usize, end: usize) -> impl Iterator<Item = (usize, char)> + '_;
+
+    /// `at` にある文字 (正しくない部分ならその1バイト) の次の位置
+    fn next_boundary(&self, at: usize) -> usize;
+
+    /// [`Haystack::chars_at`] と同じだが, 正しくない部分は [`String::from_utf8_lossy`] と同じく U+FFFD として返す
+    fn chars_lossy_at(&self, at: usize, end: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        self.chars_at(at, end)
+    }
+
+    /// `at` を含み, 正しくない部分か `end` の手前まで続く文字列と, その開始位置
+    ///
+    /// 候補位置の絞り込み (prefilter) に使う. 連続した文字列として持たない場合は `None` で, 絞り込みを行わない.
+    fn valid_str_at(&self, _at: usize, _end: usize) -> Option<(usize, &str)> {
+        None
+    }
+}
+
+impl Haystack for str {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn chars_at(&self, at: usize, end: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        self[at..end]
+            .char_indices()
+            .map(move |(i, chara)| (at + i + chara.len_utf8(), chara))
+    }
+
+    fn next_boundary(&self, at: usize) -> usize {
+        at + self[at..].chars().next().map_or(1, char::len_utf8)
+    }
+
+    fn valid_str_at(&self, _at: usize, end: usize) -> Option<(usize, &str)> {
+        Some((0, &self[..end]))
+    }
+}
+
+/// `bytes` の先頭の文字. 正しくない部分なら, [`String::from_utf8_lossy`] で1つの U+FFFD になる長さを `Err` で返す
+///
+/// 先頭の4バイトまでしか読まない. `bytes` が空なら `None`
+fn decode_first(bytes: &[u8]) -> Option<Result<char, usize>> {
+    let chunk = bytes[..bytes.len().min(4)].utf8_chunks().next()?;
+    Some(chunk.valid().chars().next().ok_or(chunk.invalid().len()))
+}
+
+impl Haystack for [u8] {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn chars_at(&self, at: usize, end: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut offset: usize = at;
+        std::iter::from_fn(move || {
+            let chara: char = decode_first(&self[offset..end])?.ok()?;
+            offset += chara.len_utf8();
+            Some((offset, chara))
+        })
+    }
+
+    fn next_boundary(&self, at: usize) -> usize {
+        let chara: Option<char> = decode_first(&self[at..]).and_then(Result::ok);
+        at + chara.map_or(1, char::len_utf8)
+    }
+
+    fn chars_lossy_at(&self, at: usize, end: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let mut offset: usize = at;
+        std::iter::from_fn(move || {
+            let (len, chara) = match decode_first(&self[offset..end])? {
+                Ok(chara) => (chara.len_utf8(), chara),
+                Err(len) => (len, char::REPLACEMENT_CHARACTER),
+            };
+            offset += len;
+            Some((offset, chara))
+        })
+    }
+
+    fn valid_str_at(&self, at: usize, end: usize) -> Option<(usize, &str)> {
+        let valid: &str = self[at..end].utf8_chunks().next().map_or("", |c| c.valid());
+        Some((at, valid))
+    }
+}
+
+/// 複数の文字列を連結したものとして扱う. マッチは文字列の境界をまたいでもよい
+#[derive(Clone, Debug)]
+pub struct Chunked<'a> {
+    chunks: Vec<&'a str>,
+    /// 各文字列の開始位置
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl<'a> Chunked<'a> {
+    pub fn new(chunks: impl IntoIterator<Item = &'a str>) -> Self {
+        let chunks: Vec<&str> = chunks.into_iter().filter(|c| !c.is_empty()).collect();
+        let mut starts: Vec<usize> = Vec::with_capacity(chunks.len());
+        let mut len: usize = 0;
+        for chunk in &chunks {
+            starts.push(len);
+            len += chunk.len();
+        }
+        Chunked {
+            chunks,
+            starts,
+            len,
+        }
+    }
+
+    /// `at` を含む文字列の番号
+    fn chunk_index(&self, at: usize) -> usize {
+        self.starts
+            .partition_point(|start| *start <= at)
+            .saturating_sub(1)
+    }
+}
+
+impl Haystack for Chunked<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn chars_at(&self, at: usize, end: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+        let first: usize = self.chunk_index(at);
+        self.chunks[first..]
+            .iter()
+            .zip(&self.starts[first..])
+            .take_while(move |(_, start)| **start < end)
+            .flat_map(move |(chunk, start)| {
+                let from: usize = at.saturating_sub(*start);
+                let to: usize = (end - start).min(chunk.len());
+                chunk[from..to]
+                    .char_indices()
+                    .map(move |(i, chara)| (start + from + i + chara.len_utf8(), chara))
+            })
+    }
+
+    fn next_boundary(&self, at: usize) -> usize {
+        match self.chunks.get(self.chunk_index(at)) {
+            Some(chunk) => {
+                let from: usize = at - self.starts[self.chunk_index(at)];
+                at + chunk[from..].chars().next().map_or(1, char::len_utf8)
+            }
+            None => at + 1,
+        }
+    }
+}
+
+/// UTF-8 として正しくない部分に達したときの動作. [`Input::invalid_utf8`] で指定する
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// どの文字にもマッチしない
+    #[default]
+    NoMatch,
+    /// 照合がその部分を読もうとした時点で探索をやめ, [`InvalidUtf8Error`] を返す
+    Quit,
+    /// [`String::from_utf8_lossy`] と同じく U+FFFD として照合する
+    Replace,
+}
+
+/// [`InvalidUtf8::Quit`] で探索をやめたときのエラー
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidUtf8Error {
+    offset: usize,
+}
+
+impl InvalidUtf8Error {
+    /// 正しくない部分の開始位置 (バイトオフセット)
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl std::fmt::Display for InvalidUtf8Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid UTF-8 at byte offset {}", self.offset)
+    }
+}
+
+impl Error for InvalidUtf8Error {}
+
+/// [`Regex::search`] に渡す探索の条件
+#[derive(Clone, Debug)]
+pub struct Input<'h, H: Haystack + ?Sized> {
+    haystack: &'h H,
+    span: Range<usize>,
+    anchored: bool,
+    invalid_utf8: InvalidUtf8,
+}
+
+impl<'h, H: Haystack + ?Sized> Input<'h, H> {
+    /// `haystack` 全体を, 任意の位置から始まるマッチについて探索する
+    pub fn new(haystack: &'h H) -> Self {
+        Input {
+            haystack,
+            span: 0..haystack.len(),
+            anchored: false,
+            invalid_utf8: InvalidUtf8::NoMatch,
+        }
+    }
+
+    /// 探索する範囲. マッチはこの範囲に収まる. 両端は文字の境界であること
+    pub fn span(mut self, span: Range<usize>) -> Self {
+        assert!(
+            span.start <= span.end && span.end <= self.haystack.len(),
+            "invalid span {:?} for haystack of length {}",
+            span,
+            self.haystack.len()
+        );
+        self.span = span;
+        self
+    }
+
+    /// `true` の場合, 範囲の先頭から始まるマッチのみを探す
+    ///
+    /// 他の処理系の `\G` と同じく, 前のマッチの終了位置を範囲の先頭にして繰り返せば, 字句解析のように
+    /// 隙間なく続くマッチを順に取り出せる. マッチしない文字があればそこで止まり, 読み飛ばさない.
+    /// 空文字列のマッチでは位置が進まないので, その場合は繰り返しをやめること.
+    pub fn anchored(mut self, anchored: bool) -> Self {
+        self.anchored = anchored;
+        self
+    }
+
+    /// UTF-8 として正しくない部分に達したときの動作. 既定は [`InvalidUtf8::NoMatch`]
+    pub fn invalid_utf8(mut self, invalid_utf8: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+}
+
+/// 文字を順に返し, 最後まで読まれたかを記録する
+///
+/// 照合は受理できなくなった時点で読むのをやめるので, 最後まで読まれたのは照合が続きを必要としていた場合に限る.
+/// [`InvalidUtf8::Quit`] ではこれを「正しくない部分で止まる状態」として扱う.
+struct Watched<I> {
+    chars: I,
+    /// 最後に返した文字の終了位置
+    end: usize,
+    exhausted: bool,
+}
+
+impl<I: Iterator<Item = (usize, char)>> Iterator for Watched<I> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        let next: Option<(usize, char)> = self.chars.next();
+        match next {
+            Some((end, _)) => self.end = end,
+            None => self.exhausted = true,
+        }
+        next
+    }
+}
+
+impl Regex {
+    /// `input` の範囲で最も左にあるマッチ (同じ位置からのマッチは最長のもの) のバイト範囲
+    ///
+    /// [`Regex::find`] と同じく, 候補位置の絞り込み (prefilter) と接尾辞による確認を, 正しい部分が続く範囲で行う.
+    /// [`InvalidUtf8::Quit`] を指定して正しくない部分で探索をやめた場合はパニックする. [`Regex::try_search`] を参照.
+    pub fn search<H: Haystack + ?Sized>(&self, input: &Input<'_, H>) -> Option<Range<usize>> {
+        self.try_search(input)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// [`Regex::search`] と同じ. [`InvalidUtf8::Quit`] を指定して正しくない部分で探索をやめた場合はエラーを返す
+    pub fn try_search<H: Haystack + ?Sized>(
+        &self,
+        input: &Input<'_, H>,
+    ) -> Result<Option<Range<usize>>, InvalidUtf8Error> {
+        let Input {
+            haystack,
+            span,
+            anchored,
+            invalid_utf8,
+        } = input;
+        let (prefilter, suffix) = match self.engine() {
+            Engine::Literal(_) => (None, None),
+            Engine::BitParallel {
+                prefilter, suffix, ..
+            }
+            | Engine::Automaton {
+                prefilter, suffix, ..
+            } => (prefilter.as_deref(), suffix.as_ref()),
+        };
+        let mut at: usize = span.start;
+        // 正しくない部分は U+FFFD としてマッチの先頭になりうるので, 置き換える場合は絞り込まない
+        let filtered: bool = *invalid_utf8 != InvalidUtf8::Replace;
+        // 候補位置を探している, 正しい部分が続く文字列とその開始位置
+        let mut valid: Option<(usize, &str)> = filtered
+            .then(|| haystack.valid_str_at(at, span.end))
+            .flatten();
+        if let Some((start, text)) = valid
+            && start + text.len() == span.end
+            && suffix.is_some_and(|suffix| suffix.rules_out(&text[at - start..]))
+        {
+            return Ok(None);
+        }
+        loop {
+            if let Some(((start, text), prefilter)) = valid.zip(prefilter).filter(|_| !*anchored) {
+                if at > start + text.len() {
+                    valid = haystack.valid_str_at(at, span.end);
+                    continue;
+                }
+                match prefilter.find(text, at - start) {
+                    Some(pos) => at = start + pos,
+                    None if start + text.len() == span.end => return Ok(None),
+                    // 正しくない部分の位置から照合を続ける
+                    None => at = start + text.len(),
+                }
+            }
+            let longest: Option<usize> = if *invalid_utf8 == InvalidUtf8::Replace {
+                self.longest_match_in(at, haystack.chars_lossy_at(at, span.end))
+            } else {
+                let mut chars = Watched {
+                    chars: haystack.chars_at(at, span.end),
+                    end: at,
+                    exhausted: false,
+                };
+                let longest: Option<usize> = self.longest_match_in(at, &mut chars);
+                if *invalid_utf8 == InvalidUtf8::Quit && chars.exhausted && chars.end < span.end {
+                    return Err(InvalidUtf8Error { offset: chars.end });
+                }
+                longest
+            };
+            if let Some(end) = longest {
+                return Ok(Some(at..end));
+            }
+            if *anchored || at >= span.end {
+                return Ok(None);
+            }
+            at = haystack.next_boundary(at).min(span.end);
+        }
+    }
+
+    /// `at` から続く文字を読み, 受理される最長の接頭辞の終了位置を返す
+    fn longest_match_in(
+        &self,
+        at: usize,
+        chars: impl Iterator<Item = (usize, char)>,
+    ) -> Option<usize> {
+        match self.engine() {
+            Engine::Literal(literal) => {
+                let mut end: usize = at;
+                let mut chars = chars;
+                literal
+                    .chars()
+                    .all(|expected| match chars.next() {
+                        Some((char_end, chara)) if chara == expected => {
+                            end = char_end;
+                            true
+                        }
+                        _ => false,
+                    })
+                    .then_some(end)
+            }
+            Engine::BitParallel { nfa, .. } => nfa.longest_match_in(at, chars),
+            Engine::Automaton { dfa, .. } => dfa.longest_match_in(at, chars),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns() -> Vec<Regex> {
+        [
+            r"山田",
+            r"山田(太|一)郎",
+            &format!("山田(太|一)郎|({})*b", "a|".repeat(65)),
+            r"a*",
+        ]
+        .iter()
+        .map(|p| Regex::new(p).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn str_input() {
+        let text: &str = "私は山田太郎です. aaab";
+        for regex in patterns() {
+            let found = regex.find(text).map(|m| m.start()..m.end());
+            assert_eq!(regex.search(&Input::new(text)), found, "{}", regex);
+        }
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        assert_eq!(regex.search(&Input::new(text).span(0..15)), None);
+        assert_eq!(regex.search(&Input::new(text).anchored(true)), None);
+        assert_eq!(
+            regex.search(&Input::new(text).span(6..text.len()).anchored(true)),
+            Some(6..18)
+        );
+    }
+
+    #[test]
+    fn bytes_input() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let mut bytes: Vec<u8> = b"\xff\xfe".to_vec();
+        bytes.extend_from_slice("山田一郎".as_bytes());
+        assert_eq!(regex.search(&Input::new(bytes.as_slice())), Some(2..14));
+        let regex = Regex::new(r"ab").unwrap();
+        assert_eq!(regex.search(&Input::new(&b"a\xffb"[..])), None);
+    }
+
+    #[test]
+    fn prefilter() {
+        // 候補を `ac` の位置に絞ると, その前の `ab` は試さない
+        let regex = crate::RegexBuilder::new(r"a(b|c)")
+            .prefilter(crate::LiteralPrefilter::new("ac"))
+            .build()
+            .unwrap();
+        assert_eq!(regex.search(&Input::new("ab ac")), Some(3..5));
+        assert_eq!(regex.search(&Input::new(&b"ab\xffac"[..])), Some(3..5));
+        assert_eq!(regex.search(&Input::new(&b"ab\xff\xfe"[..])), None);
+        let input = Input::new(&b"ab\xffac"[..]).invalid_utf8(InvalidUtf8::Replace);
+        assert_eq!(regex.search(&input), Some(0..2));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let regex = Regex::new("a(b|\u{FFFD})*c").unwrap();
+        let bytes: &[u8] = b"xa\xffb\xe3\x81c";
+        assert_eq!(regex.search(&Input::new(bytes)), None);
+        let input = Input::new(bytes).invalid_utf8(InvalidUtf8::Replace);
+        assert_eq!(regex.search(&input), Some(1..7));
+        let input = Input::new(bytes).invalid_utf8(InvalidUtf8::Quit);
+        assert_eq!(regex.try_search(&input).map_err(|err| err.offset()), Err(2));
+        // 照合が正しくない部分を読もうとしなければ, 探索をやめない
+        let bytes: &[u8] = b"\xffxac";
+        let input = Input::new(bytes)
+            .span(1..bytes.len())
+            .invalid_utf8(InvalidUtf8::Quit);
+        assert_eq!(regex.try_search(&input), Ok(Some(2..4)));
+        let input = Input::new(bytes).invalid_utf8(InvalidUtf8::Quit);
+        assert!(regex.try_search(&input).is_err());
+    }
+
+    #[test]
+    fn anchored_steps() {
+        // 前のマッチの終了位置から続くマッチだけを取り出す. `x` は読み飛ばさない
+        let regex = Regex::new(r"(a|b)(a|b)*|(0|1)(0|1)*| ").unwrap();
+        let text: &str = "ab 01 x ab";
+        let mut tokens: Vec<&str> = Vec::new();
+        let mut pos: usize = 0;
+        while let Some(range) = regex.search(&Input::new(text).span(pos..text.len()).anchored(true))
+            && !range.is_empty()
+        {
+            tokens.push(&text[range.clone()]);
+            pos = range.end;
+        }
+        assert_eq!((tokens, pos), (vec!["ab", " ", "01", " "], 6));
+        // 先頭に固定しなければ, `x` を飛ばして次のマッチを探す
+        assert_eq!(
+            regex.search(&Input::new(text).span(pos..text.len())),
+            Some(7..8)
+        );
+    }
+
+    #[test]
+    fn chunked_input() {
+        let chunks = Chunked::new(["私は山", "", "田", "太郎です"]);
+        assert_eq!(chunks.len(), 24);
+        for regex in patterns() {
+            let found = regex.find("私は山田太郎です").map(|m| m.start()..m.end());
+            assert_eq!(regex.search(&Input::new(&chunks)), found, "{}", regex);
+        }
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        assert_eq!(regex.search(&Input::new(&chunks).span(0..15)), None);
+    }
+}
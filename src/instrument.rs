@@ -0,0 +1,17 @@
+//! `tracing` 機能が有効なときだけ `tracing` のスパンやイベントを出すマクロ.
+//! 無効なときは引数の式も評価されない
+
+/// スパンに入る. スパンは呼び出したブロックの終わりで閉じる
+macro_rules! enter_span {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $($arg)+).entered();
+    };
+}
+
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($arg)+);
+    };
+}
@@ -0,0 +1,316 @@
+use std::error::Error;
+
+use crate::Regex;
+use crate::lexer::{Lexer, Token};
+
+/// パターンのメタ文字をエスケープし, `text` そのものにマッチするパターンにする
+pub fn escape(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    push_escaped(&mut escaped, text);
+    escaped
+}
+
+fn push_escaped(pattern: &mut String, text: &str) {
+    for chara in text.chars() {
+        if matches!(chara, '\\' | '|' | '*' | '(' | ')' | '?') {
+            pattern.push('\\');
+        }
+        pattern.push(chara);
+    }
+}
+
+/// 1文字 (エスケープしたものを含む) にマッチするパターンか. それ以外は後に続く `*` などと結び付かないよう括弧で囲む
+fn is_atom(fragment: &str) -> bool {
+    let mut chars = fragment.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('\\'), Some(_), None) => true,
+        (Some(chara), None, _) => chara != '\\',
+        _ => false,
+    }
+}
+
+/// 括弧の外に `|` があり, かつキャプチャグループを含むパターンか
+///
+/// `(?|...)` はブランチリセットなので, そのような値を囲むと各選択肢のグループが同じ番号になってしまう.
+fn has_grouped_alternation(fragment: &str) -> bool {
+    let mut lexer: Lexer = Lexer::new(fragment);
+    let mut depth: usize = 0;
+    let (mut alternation, mut groups): (bool, bool) = (false, false);
+    loop {
+        match lexer.scan() {
+            Token::LeftParen => {
+                depth += 1;
+                groups |= !lexer.take_branch_reset();
+            }
+            Token::RightParen => depth = depth.saturating_sub(1),
+            Token::UnionOp if depth == 0 => alternation = true,
+            Token::End => return alternation && groups,
+            _ => {}
+        }
+    }
+}
+
+/// [`Interpolation`] でテンプレートに埋め込む値
+pub trait Fragment {
+    /// `pattern` の末尾に書き込む
+    fn push_to(&self, pattern: &mut String);
+}
+
+/// 文字列そのものにマッチするよう, メタ文字をエスケープして埋め込む
+impl Fragment for &str {
+    fn push_to(&self, pattern: &mut String) {
+        push_escaped(pattern, self);
+    }
+}
+
+/// 文字列そのものにマッチするよう, メタ文字をエスケープして埋め込む
+impl Fragment for String {
+    fn push_to(&self, pattern: &mut String) {
+        push_escaped(pattern, self);
+    }
+}
+
+/// 文字そのものにマッチするよう, メタ文字をエスケープして埋め込む
+impl Fragment for char {
+    fn push_to(&self, pattern: &mut String) {
+        push_escaped(pattern, self.encode_utf8(&mut [0; 4]));
+    }
+}
+
+/// パターンとしてそのまま埋め込む断片. 信頼できる (利用者の入力でない) パターンにのみ使う
+#[derive(Clone, Copy, Debug)]
+pub struct Trusted<'a>(pub &'a str);
+
+impl Fragment for Trusted<'_> {
+    fn push_to(&self, pattern: &mut String) {
+        pattern.push_str(self.0);
+    }
+}
+
+/// テンプレート中の `{name}` に値を埋め込んでパターンを組み立てる
+///
+/// 値は [`Fragment`] の実装に従って埋め込まれ, 文字列はエスケープされる. `{{`, `}}` は `{`, `}` そのものを表す.
+/// 1文字でない値は, 後に続く `*` や前後の `|` と結び付かないよう `(?|...)` で囲む. これはブランチリセットの括弧で,
+/// 選択肢が1つならグループの番号に影響しない. 括弧の外の `|` とグループを両方含む [`Trusted`] の値は, 囲むと
+/// 各選択肢のグループが同じ番号になるのでエラーにする. `((a)|(b))` のように括弧で囲んで渡せばよい.
+/// [`pattern!`](crate::pattern!) マクロからも使える.
+pub struct Interpolation<'a> {
+    template: &'a str,
+    values: Vec<(&'a str, &'a dyn Fragment)>,
+}
+
+impl<'a> Interpolation<'a> {
+    pub fn new(template: &'a str) -> Self {
+        Interpolation {
+            template,
+            values: Vec::new(),
+        }
+    }
+
+    /// `{name}` に `value` を埋め込む
+    pub fn with(mut self, name: &'a str, value: &'a dyn Fragment) -> Self {
+        self.values.push((name, value));
+        self
+    }
+
+    /// パターン文字列を組み立てる
+    ///
+    /// 値のない `{name}`, テンプレートで使われない値, 対応しない `{` や `}` はエラーになる.
+    pub fn to_pattern(&self) -> Result<String, InterpolationError> {
+        let mut pattern: String = String::with_capacity(self.template.len());
+        let mut used: Vec<bool> = vec![false; self.values.len()];
+        let mut rest: &str = self.template;
+        while let Some(i) = rest.find(['{', '}']) {
+            pattern.push_str(&rest[..i]);
+            let offset: usize = self.template.len() - rest.len() + i;
+            let (brace, after) = rest[i..].split_at(1);
+            if let Some(after) = after.strip_prefix(brace) {
+                pattern.push_str(brace);
+                rest = after;
+                continue;
+            }
+            let (name, after) = match brace {
+                "{" => after
+                    .split_once('}')
+                    .ok_or(InterpolationError::UnmatchedBrace(offset))?,
+                _ => return Err(InterpolationError::UnmatchedBrace(offset)),
+            };
+            let index: usize = self
+                .values
+                .iter()
+                .position(|(n, _)| *n == name)
+                .ok_or_else(|| InterpolationError::Undefined(name.to_string()))?;
+            let start: usize = pattern.len();
+            self.values[index].1.push_to(&mut pattern);
+            if has_grouped_alternation(&pattern[start..]) {
+                return Err(InterpolationError::GroupedAlternation(name.to_string()));
+            }
+            if !is_atom(&pattern[start..]) {
+                pattern.insert_str(start, "(?|");
+                pattern.push(')');
+            }
+            used[index] = true;
+            rest = after;
+        }
+        pattern.push_str(rest);
+        if let Some(index) = used.iter().position(|used| !used) {
+            return Err(InterpolationError::Unused(self.values[index].0.to_string()));
+        }
+        Ok(pattern)
+    }
+
+    /// 組み立てたパターンから `Regex` を作る
+    pub fn build(&self) -> Result<Regex, Box<dyn Error>> {
+        Regex::new(&self.to_pattern()?)
+    }
+}
+
+/// [`Interpolation::to_pattern`] の失敗
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterpolationError {
+    /// テンプレート中の `{name}` に値が与えられていない
+    Undefined(String),
+    /// 与えられた値がテンプレートで使われていない
+    Unused(String),
+    /// 対応しない `{` または `}` (バイトオフセット)
+    UnmatchedBrace(usize),
+    /// 値が括弧の外の `|` とキャプチャグループを両方含む
+    GroupedAlternation(String),
+}
+
+impl std::fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpolationError::Undefined(name) => write!(f, "no value for {{{}}}", name),
+            InterpolationError::Unused(name) => write!(f, "value {:?} is never used", name),
+            InterpolationError::UnmatchedBrace(offset) => {
+                write!(
+                    f,
+                    "unmatched brace at {} (use {{{{ or }}}} for a literal brace)",
+                    offset
+                )
+            }
+            InterpolationError::GroupedAlternation(name) => write!(
+                f,
+                "value {:?} has groups in a top-level alternation (wrap it in parentheses)",
+                name
+            ),
+        }
+    }
+}
+
+impl Error for InterpolationError {}
+
+/// テンプレート中の `{name}` に値を埋め込んだパターン文字列 (`Result<String, InterpolationError>`) を作る
+///
+/// `pattern!("{lit}(-{lit})*", lit = user_input)` のように使う. 値は `&str`, `String`, `char` ならエスケープされ,
+/// [`Trusted`] で包んだものはそのまま埋め込まれる. `format!` と異なり, 変数を暗黙に参照することはない.
+#[macro_export]
+macro_rules! pattern {
+    ($template:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::Interpolation::new($template)
+            $(.with(::std::stringify!($name), &$value))*
+            .to_pattern()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(super::escape(r"a|b*(c)\?"), r"a\|b\*\(c\)\\\?");
+        let text: &str = r"(?<x>\g<y>)|*";
+        assert!(Regex::new(&super::escape(text)).unwrap().matches(text));
+    }
+
+    #[test]
+    fn interpolate() {
+        let user_input: &str = "a*b";
+        let pattern: String = crate::pattern!("{lit}(-{lit})*", lit = user_input).unwrap();
+        assert_eq!(pattern, r"(?|a\*b)(-(?|a\*b))*");
+        let regex = Regex::new(&pattern).unwrap();
+        assert!(regex.matches("a*b-a*b") && !regex.matches("aab"));
+
+        let digits = Trusted("(0|1)(0|1)*");
+        let pattern: String = crate::pattern!(
+            "{{{name}}}={digits}",
+            name = String::from("x|y"),
+            digits = digits
+        )
+        .unwrap();
+        assert_eq!(pattern, r"{(?|x\|y)}=(?|(0|1)(0|1)*)");
+        assert_eq!(crate::pattern!("{c}", c = '(').unwrap(), r"\(");
+    }
+
+    #[test]
+    fn atomic() {
+        // 値は1つのまとまりとして繰り返す
+        let regex = Interpolation::new("{w}*").with("w", &"ab").build().unwrap();
+        assert!(regex.matches("abab") && !regex.matches("abb"));
+        let regex = Interpolation::new("{w}*").with("w", &"").build().unwrap();
+        assert!(regex.matches("") && !regex.matches("*"));
+        let regex = Interpolation::new("{w}*").with("w", &'a').build().unwrap();
+        assert!(regex.matches("aaa"));
+        // 埋め込んだ括弧はグループにならない
+        let regex = Interpolation::new("({w})c")
+            .with("w", &Trusted("a|b"))
+            .build()
+            .unwrap();
+        assert_eq!(regex.captures_len(), 2);
+        assert!(regex.matches("bc") && !regex.matches("a"));
+    }
+
+    #[test]
+    fn group_numbering() {
+        // 選択肢のない値のグループは, 囲んでも書いた順に番号が付く
+        let regex = Interpolation::new("{t}*(c)")
+            .with("t", &Trusted("(a)(b)"))
+            .build()
+            .unwrap();
+        assert_eq!(regex.captures_len(), 4);
+        assert_eq!(&regex.captures("abc").unwrap()[3], "c");
+        // 選択肢ごとのグループは, 囲むと番号が重なるのでエラーにする
+        let err = Interpolation::new("{t}(c)")
+            .with("t", &Trusted("(a)|(b)"))
+            .to_pattern();
+        assert_eq!(
+            err,
+            Err(InterpolationError::GroupedAlternation("t".to_string()))
+        );
+        let regex = Interpolation::new("{t}(c)")
+            .with("t", &Trusted("((a)|(b))"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            regex.captures_len(),
+            Regex::new("((a)|(b))(c)").unwrap().captures_len()
+        );
+        assert_eq!(regex.captures_len(), 5);
+        let caps = regex.captures("bc").unwrap();
+        assert_eq!((caps.get(2), &caps[3], &caps[4]), (None, "b", "c"));
+        // グループのない選択肢はそのまま囲める
+        let regex = Interpolation::new("{t}(c)")
+            .with("t", &Trusted("a|(?|b)"))
+            .build()
+            .unwrap();
+        assert_eq!(regex.captures_len(), 2);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Interpolation::new("{a}{b}").with("a", &"x").to_pattern();
+        assert_eq!(err, Err(InterpolationError::Undefined("b".to_string())));
+        let err = Interpolation::new("{a}")
+            .with("a", &"x")
+            .with("b", &"y")
+            .to_pattern();
+        assert_eq!(err, Err(InterpolationError::Unused("b".to_string())));
+        let err = Interpolation::new("ab{a").with("a", &"x").to_pattern();
+        assert_eq!(err, Err(InterpolationError::UnmatchedBrace(2)));
+        let err = Interpolation::new("a}").to_pattern();
+        assert_eq!(err, Err(InterpolationError::UnmatchedBrace(1)));
+        assert!(Interpolation::new("(").build().is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! 構文木と JSON の相互変換
+//!
+//! 各頂点は `type` で種類を表すオブジェクトになる.
+//!
+//! | `type`     | その他のフィールド                          | パターン   |
+//! |------------|---------------------------------------------|------------|
+//! | `"char"`   | `value`: 1文字の文字列                      | `a`        |
+//! | `"empty"`  | なし                                        | `()` など  |
+//! | `"star"`   | `node`: 繰り返す頂点                        | `a*`       |
+//! | `"union"`  | `left`, `right`: 選択肢                     | `a\|b`     |
+//! | `"concat"` | `left`, `right`: 連接する頂点                | `ab`       |
+//! | `"group"`  | `index`: グループの番号, `node`: 括弧の中身 | `(a)`      |
+//!
+//! 例えば `a*|b` は次のようになる.
+//!
+//! ```json
+//! {"type":"union","left":{"type":"star","node":{"type":"char","value":"a"}},"right":{"type":"char","value":"b"}}
+//! ```
+
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lexer::Lexer;
+use crate::parser::{Kind, Node, NodeRef, Parser};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonNode {
+    Char {
+        value: char,
+    },
+    Empty,
+    Star {
+        node: Box<JsonNode>,
+    },
+    Union {
+        left: Box<JsonNode>,
+        right: Box<JsonNode>,
+    },
+    Concat {
+        left: Box<JsonNode>,
+        right: Box<JsonNode>,
+    },
+    Group {
+        index: usize,
+        node: Box<JsonNode>,
+    },
+}
+
+impl From<&Node> for JsonNode {
+    fn from(node: &Node) -> Self {
+        node.root().into()
+    }
+}
+
+impl From<NodeRef<'_>> for JsonNode {
+    fn from(node: NodeRef<'_>) -> Self {
+        match node.kind() {
+            Kind::Character(chara) => JsonNode::Char { value: chara },
+            Kind::Empty => JsonNode::Empty,
+            Kind::Star(node) => JsonNode::Star {
+                node: Box::new(node.into()),
+            },
+            Kind::Union(n1, n2) => JsonNode::Union {
+                left: Box::new(n1.into()),
+                right: Box::new(n2.into()),
+            },
+            Kind::Concat(n1, n2) => JsonNode::Concat {
+                left: Box::new(n1.into()),
+                right: Box::new(n2.into()),
+            },
+            Kind::Group(index, node) => JsonNode::Group {
+                index,
+                node: Box::new(node.into()),
+            },
+        }
+    }
+}
+
+impl From<JsonNode> for Node {
+    fn from(node: JsonNode) -> Self {
+        match node {
+            JsonNode::Char { value } => Node::character(value),
+            JsonNode::Empty => Node::empty(),
+            JsonNode::Star { node } => Node::from(*node).star(),
+            JsonNode::Union { left, right } => Node::from(*left).union((*right).into()),
+            JsonNode::Concat { left, right } => Node::from(*left).concat((*right).into()),
+            JsonNode::Group { index, node } => Node::from(*node).group(index),
+        }
+    }
+}
+
+/// パターンを構文解析し, 構文木を JSON にする. 括弧は `group` になる
+pub fn to_json(pattern: &str) -> Result<String, Box<dyn Error>> {
+    let node: Node = Parser::new(Lexer::new(pattern)).with_captures().parse()?;
+    Ok(serde_json::to_string(&JsonNode::from(&node))?)
+}
+
+/// JSON の構文木をパターン文字列にする
+///
+/// `group` の `index` は使わず, パターン中の位置で番号が決まる.
+pub fn from_json(json: &str) -> Result<String, Box<dyn Error>> {
+    let node: JsonNode = serde_json::from_str(json)?;
+    Ok(Node::from(node).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema() {
+        assert_eq!(
+            to_json(r"a*|b").unwrap(),
+            r#"{"type":"union","left":{"type":"star","node":{"type":"char","value":"a"}},"right":{"type":"char","value":"b"}}"#
+        );
+        assert_eq!(
+            to_json(r"(山)").unwrap(),
+            r#"{"type":"group","index":1,"node":{"type":"char","value":"山"}}"#
+        );
+        assert_eq!(to_json(r"").unwrap(), r#"{"type":"empty"}"#);
+        assert!(to_json(r"a(").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for pattern in [r"山田(太|一)郎", r"(a|b)*c", r"a\*\|\\", r"a(b|)", r"(ab)*"] {
+            assert_eq!(from_json(&to_json(pattern).unwrap()).unwrap(), pattern);
+        }
+        assert!(from_json(r#"{"type":"char","value":"ab"}"#).is_err());
+        assert!(from_json(r#"{"type":"plus"}"#).is_err());
+    }
+}
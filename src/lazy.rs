@@ -0,0 +1,39 @@
+/// 初回の評価時に一度だけパターンをコンパイルし, `&'static Regex` を返す
+///
+/// パターンが不正な場合はパターンとエラーを含むメッセージで panic する.
+#[macro_export]
+macro_rules! lazy_regex {
+    ($pattern:expr $(,)?) => {{
+        static REGEX: ::std::sync::OnceLock<$crate::Regex> = ::std::sync::OnceLock::new();
+        REGEX.get_or_init(|| {
+            let pattern: &str = $pattern;
+            match $crate::Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(err) => panic!("invalid pattern {:?}: {}", pattern, err),
+            }
+        })
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Regex;
+
+    fn is_name(text: &str) -> bool {
+        crate::lazy_regex!(r"山田(太|一)郎").matches(text)
+    }
+
+    #[test]
+    fn lazy_regex() {
+        assert!(is_name("山田太郎"));
+        assert!(!is_name("山田次郎"));
+        let first: &'static Regex = crate::lazy_regex!("a*");
+        assert!(first.matches("aaa"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid pattern \"a(\"")]
+    fn lazy_regex_invalid() {
+        crate::lazy_regex!("a(");
+    }
+}
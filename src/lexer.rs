@@ -13,31 +13,113 @@ pub(crate) enum Token {
     LeftParen,
     /// 右括弧 )
     RightParen,
+    /// 定義済みの部分パターンの参照 \g<name>
+    Reference,
+    /// 字句として不正なもの. 理由は [`Lexer::take_error`] で取り出す
+    Invalid,
     /// 文末
     End,
 }
 
 pub(crate) struct Lexer<'a> {
     src: Chars<'a>,
+    len: usize,
+    /// 直前に読んだトークンに付いていた名前 (`(?<name>` のグループ名か `\g<name>` の参照先)
+    name: Option<&'a str>,
+    /// 直前に読んだ左括弧が `(?|` (ブランチリセット) だったか
+    branch_reset: bool,
+    /// 直前に読んだ [`Token::Invalid`] の理由
+    error: Option<String>,
 }
 
-impl Lexer<'_> {
+impl<'a> Lexer<'a> {
     /// create Lexer
-    pub fn new(src: &str) -> Lexer {
-        Lexer { src: src.chars() }
+    pub fn new(src: &'a str) -> Lexer<'a> {
+        Lexer {
+            src: src.chars(),
+            len: src.len(),
+            name: None,
+            branch_reset: false,
+            error: None,
+        }
+    }
+    /// byte offset of the next character to scan
+    pub fn offset(&self) -> usize {
+        self.len - self.src.as_str().len()
     }
     /// scan next character
     pub fn scan(&mut self) -> Token {
         match self.src.next() {
-            Some('\\') => Token::Character(self.src.next().expect("EOF detected after '\\'.")),
+            Some('\\') if self.src.as_str().starts_with('g') => {
+                self.src.next();
+                match self.scan_name("<") {
+                    Some(name) => {
+                        self.name = Some(name);
+                        Token::Reference
+                    }
+                    None => Token::Character('g'),
+                }
+            }
+            Some('\\') => match self.src.next() {
+                Some(c) => Token::Character(c),
+                None => self.invalid("Trailing '\\' at the end of the pattern".to_string()),
+            },
             Some('|') => Token::UnionOp,
-            Some('(') => Token::LeftParen,
+            Some('(') if self.src.as_str().starts_with("?<") => {
+                self.name = self.scan_name("?<");
+                if self.name.is_some() {
+                    return Token::LeftParen;
+                }
+                let rest: &str = &self.src.as_str()[2..];
+                let name: &str = rest.split_once('>').map_or(rest, |(name, _)| name);
+                self.invalid(format!("Invalid group name {:?}", name))
+            }
+            Some('(') => {
+                self.name = None;
+                self.branch_reset = self.src.as_str().starts_with("?|");
+                if self.branch_reset {
+                    self.src.nth(1);
+                }
+                Token::LeftParen
+            }
             Some(')') => Token::RightParen,
             Some('*') => Token::StarOp,
             Some(c) => Token::Character(c),
             None => Token::End,
         }
     }
+    /// take the name attached to the last scanned '(' or reference
+    pub fn take_name(&mut self) -> Option<&'a str> {
+        self.name.take()
+    }
+    /// take the reason of the last scanned invalid token
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+    fn invalid(&mut self, error: String) -> Token {
+        self.error = Some(error);
+        Token::Invalid
+    }
+    /// whether the last scanned '(' opened a branch reset group
+    pub fn take_branch_reset(&mut self) -> bool {
+        std::mem::take(&mut self.branch_reset)
+    }
+    /// scan "{prefix}name>". nothing is consumed unless the name is an identifier
+    fn scan_name(&mut self, prefix: &str) -> Option<&'a str> {
+        let rest: &'a str = self.src.as_str().strip_prefix(prefix)?;
+        let (name, _) = rest.split_once('>')?;
+        if !is_identifier(name) {
+            return None;
+        }
+        self.src = rest[name.len() + 1..].chars();
+        Some(name)
+    }
+}
+
+/// グループ名や部分パターンの名前に使えるか. ASCII の英数字と '_' からなり, 数字で始まらないこと
+pub(crate) fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 #[cfg(test)]
@@ -69,9 +151,86 @@ mod tests {
         assert_eq!(lexer.scan(), Token::End);
     }
 
+    #[test]
+    fn offset() {
+        let mut lexer = Lexer::new(r"山\|a");
+        assert_eq!(lexer.offset(), 0);
+        assert_eq!(lexer.scan(), Token::Character('山'));
+        assert_eq!(lexer.offset(), 3);
+        assert_eq!(lexer.scan(), Token::Character('|'));
+        assert_eq!(lexer.offset(), 5);
+        assert_eq!(lexer.scan(), Token::Character('a'));
+        assert_eq!(lexer.scan(), Token::End);
+        assert_eq!(lexer.offset(), 6);
+    }
+
+    #[test]
+    fn trailing_backslash() {
+        let mut lexer = Lexer::new(r"a\");
+        assert_eq!(lexer.scan(), Token::Character('a'));
+        assert_eq!(lexer.scan(), Token::Invalid);
+        assert!(lexer.take_error().unwrap().contains("Trailing"));
+        assert_eq!(lexer.scan(), Token::End);
+    }
+
     #[test]
     fn with_empty() {
         let mut lexer = Lexer::new(r#""#);
         assert_eq!(lexer.scan(), Token::End);
     }
+
+    #[test]
+    fn names() {
+        let mut lexer = Lexer::new(r"(?<year>a)(\?<1>\g<year>\g\g<1>\\g<a>");
+        assert_eq!(lexer.scan(), Token::LeftParen);
+        assert_eq!(lexer.take_name(), Some("year"));
+        assert_eq!(lexer.take_name(), None);
+        assert_eq!(lexer.scan(), Token::Character('a'));
+        assert_eq!(lexer.scan(), Token::RightParen);
+        assert_eq!(lexer.scan(), Token::LeftParen);
+        assert_eq!(lexer.take_name(), None);
+        assert_eq!(lexer.scan(), Token::Character('?'));
+        assert_eq!(lexer.scan(), Token::Character('<'));
+        assert_eq!(lexer.scan(), Token::Character('1'));
+        assert_eq!(lexer.scan(), Token::Character('>'));
+        assert_eq!(lexer.scan(), Token::Reference);
+        assert_eq!(lexer.take_name(), Some("year"));
+        assert_eq!(lexer.scan(), Token::Character('g'));
+        assert_eq!(lexer.scan(), Token::Character('g'));
+        assert_eq!(lexer.scan(), Token::Character('<'));
+        assert_eq!(lexer.scan(), Token::Character('1'));
+        assert_eq!(lexer.scan(), Token::Character('>'));
+        assert_eq!(lexer.scan(), Token::Character('\\'));
+        assert_eq!(lexer.scan(), Token::Character('g'));
+    }
+
+    #[test]
+    fn invalid_names() {
+        for (pattern, name) in [
+            (r"(?<1>a)", "1"),
+            (r"(?<my-name>a)", "my-name"),
+            (r"(?<a", "a"),
+        ] {
+            let mut lexer = Lexer::new(pattern);
+            assert_eq!(lexer.scan(), Token::Invalid);
+            assert_eq!(
+                lexer.take_error(),
+                Some(format!("Invalid group name {:?}", name))
+            );
+        }
+    }
+
+    #[test]
+    fn branch_reset() {
+        let mut lexer = Lexer::new(r"(?|a)(\?|)");
+        assert_eq!(lexer.scan(), Token::LeftParen);
+        assert!(lexer.take_branch_reset());
+        assert!(!lexer.take_branch_reset());
+        assert_eq!(lexer.scan(), Token::Character('a'));
+        assert_eq!(lexer.scan(), Token::RightParen);
+        assert_eq!(lexer.scan(), Token::LeftParen);
+        assert!(!lexer.take_branch_reset());
+        assert_eq!(lexer.scan(), Token::Character('?'));
+        assert_eq!(lexer.scan(), Token::UnionOp);
+    }
 }
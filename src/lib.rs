@@ -1,43 +1,1279 @@
+#![cfg_attr(feature = "pattern", feature(pattern))]
+
+#[macro_use]
+mod instrument;
+
+mod approximate;
 mod automaton;
+mod batch;
+mod builder;
+mod cache;
+mod captures;
+mod const_regex;
+mod disk_cache;
+mod embed;
+#[cfg(feature = "encoding")]
+mod encoding;
+mod enumerate;
+mod estimate;
+mod explain;
+mod failure;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "derive")]
+mod from_regex;
+mod grep;
+mod hash;
+#[cfg(feature = "term")]
+mod highlight;
+#[cfg(feature = "regex-syntax")]
+mod hir;
+mod hook;
+mod input;
+mod interpolate;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "lazy")]
+mod lazy;
 mod lexer;
+mod lint;
+#[cfg(feature = "nom")]
+pub mod nom;
+mod os_str;
+#[cfg(feature = "rayon")]
+mod parallel;
 mod parser;
+#[cfg(feature = "pattern")]
+mod pattern;
+#[cfg(feature = "posix")]
+pub mod posix;
+mod prefilter;
+#[cfg(feature = "python")]
+mod python;
+pub mod raw;
+mod regex_cache;
+#[cfg(feature = "serde")]
+mod report;
+mod stream;
+mod table;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod trace;
+#[cfg(feature = "serde")]
+mod validated;
 
+use std::borrow::Cow;
 use std::error::Error;
+use std::ops::{Range, RangeInclusive};
+use std::sync::{Arc, OnceLock};
+
+use crate::automaton::{AsciiTable, BitNFA, DFA, DFAState, LazyDFA, NFA, Program, TwoPass};
+use crate::hash::HashSet;
+use crate::parser::Node;
+use crate::prefilter::{ReverseSuffix, find_literal};
 
-use crate::automaton::{DFA, DFAState, NFA};
-use crate::lexer::Lexer;
-use crate::parser::{Node, Parser};
+pub use crate::approximate::ApproximateMatch;
+pub use crate::batch::BitVec;
+use crate::builder::Deferred;
+pub use crate::builder::{Construction, RegexBuilder};
+pub use crate::cache::Cache;
+pub use crate::captures::Captures;
+pub use crate::const_regex::ConstRegex;
+#[cfg(feature = "encoding")]
+pub use crate::encoding::EncodedRegex;
+pub use crate::enumerate::Strings;
+pub use crate::estimate::estimate_dfa_states;
+pub use crate::explain::explain;
+pub use crate::failure::{MatchFailure, ValidationError};
+#[cfg(feature = "derive")]
+pub use crate::from_regex::{FromRegex, FromRegexError};
+pub use crate::grep::{Grep, Line, LineKind};
+#[cfg(feature = "term")]
+pub use crate::highlight::Highlighter;
+#[cfg(feature = "regex-syntax")]
+pub use crate::hir::to_hir;
+pub use crate::hook::{AstHook, AstNode, Nfa, NfaHook};
+pub use crate::input::{Chunked, Haystack, Input, InvalidUtf8, InvalidUtf8Error};
+pub use crate::interpolate::{Fragment, Interpolation, InterpolationError, Trusted, escape};
+#[cfg(feature = "serde")]
+pub use crate::json::{from_json, to_json};
+pub use crate::lint::{Warning, WarningKind, lint};
+#[cfg(feature = "pattern")]
+pub use crate::pattern::RegexSearcher;
+pub use crate::prefilter::{CharSetPrefilter, LiteralPrefilter, Prefilter};
+pub use crate::regex_cache::RegexCache;
+#[cfg(feature = "serde")]
+pub use crate::report::{
+    MatchReport, REPORT_VERSION, ReportFailure, ReportGroup, ReportMatch, ReportSpan, ReportWarning,
+};
+pub use crate::stream::{CharMatch, CharMatches};
+pub use crate::trace::{Callout, DeterminizationTrace, Step, Subset, SubsetTransition};
+#[cfg(feature = "serde")]
+pub use crate::validated::{Validated, ValidationPattern, deserialize_matching};
+#[cfg(feature = "derive")]
+pub use ryota2357_regex_derive::FromRegex;
 
+// derive が生成するコードが参照する `::ryota2357_regex` を, crate 内のテストでも使えるようにする
+#[cfg(all(test, feature = "derive"))]
+extern crate self as ryota2357_regex;
+
+#[derive(Clone, Debug)]
 pub struct Regex {
-    dfa: DFA,
+    /// 元のパターン文字列
+    pattern: Arc<str>,
+    /// 大文字と小文字を区別しないか
+    case_insensitive: bool,
+    /// 複製を安価にするため, 遷移表などは共有する
+    engine: Arc<Slot>,
+    /// キャプチャグループを求める命令列. 演算で作った `Regex` では `None`
+    program: Option<Arc<Program>>,
+    /// キャプチャグループを含む構文木 (大文字・小文字の変換後). 演算で作った `Regex` では `None`
+    ast: Option<Arc<Node>>,
+    /// 比較とハッシュの方法を決める, 作り方の区別
+    origin: Origin,
+}
+
+/// `Regex` の作り方
+#[derive(Clone, Debug)]
+enum Origin {
+    /// パターン文字列から作った. パターン文字列と, そこから参照する部分パターンの定義 (名前とパターン, 定義順) で言語が決まる
+    Pattern(Arc<[(String, String)]>),
+    /// DFA の演算などで作った. パターン文字列は表記にすぎないので, 言語で比較する. 中身は求めた [`Regex::fingerprint`]
+    Language(OnceLock<u64>),
+}
+
+#[derive(Clone, Debug)]
+enum Engine {
+    /// 演算子を含まないパターン. オートマトンを使わず部分文字列検索で照合する
+    Literal(String),
+    /// 位置の数が64以下のパターン. 状態集合をビット列で表して照合する
+    BitParallel {
+        nfa: BitNFA,
+        prefilter: Option<Arc<dyn Prefilter>>,
+        /// どのマッチもこの文字列から始まる. 位置を固定した照合では, オートマトンを動かす前に直接比較する
+        prefix: String,
+        /// どのマッチも同じ文字列で終わる場合, 前向きの照合の前にマッチが無いことを確かめる
+        suffix: Option<ReverseSuffix>,
+    },
+    Automaton {
+        dfa: DFA,
+        prefilter: Option<Arc<dyn Prefilter>>,
+        prefix: String,
+        suffix: Option<ReverseSuffix>,
+        /// その後にどんな文字列が続いても受理する状態. ここに達したら残りを読まずに照合を終える
+        always_accepting: HashSet<DFAState>,
+        /// ASCII 文字による遷移の表. 状態が多すぎる場合は `None` で, `dfa` を直接引く
+        table: Option<AsciiTable>,
+        /// マッチの開始位置を絞り込む. 状態が多すぎる場合は `None`
+        two_pass: Option<Box<TwoPass>>,
+    },
+}
+
+/// 構築済みのエンジン, または構築を遅らせているパターン ([`RegexBuilder::deferred`] を参照)
+// `Regex` では `Arc` の中に置くので, 構築済みの場合の大きさは問題にならない
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+enum Slot {
+    Ready(Engine),
+    Deferred(Box<Deferred>),
+}
+
+impl Engine {
+    /// `prefix` と `suffix` は構文木から求める. 構文木が無ければ空文字列と `None`
+    ///
+    /// 遷移表でよく訪れる状態の行が隣り合うよう, 状態の番号を振り直す. `profile` があれば, それを照合したときに多く訪れた順にする.
+    fn automaton(
+        dfa: DFA,
+        prefix: String,
+        suffix: Option<ReverseSuffix>,
+        profile: Option<&str>,
+    ) -> Engine {
+        let order: Vec<DFAState> = match profile {
+            Some(sample) => dfa.profile_order(sample),
+            None => dfa.hot_order(),
+        };
+        let dfa: DFA = dfa.renumber(&order);
+        // 空文字列を受理する場合はどの位置からでもマッチするので, フィルタは使えない
+        let prefilter: Option<Arc<dyn Prefilter>> =
+            if dfa.is_accept(dfa.start) || dfa.otherwise.contains_key(&dfa.start) {
+                None
+            } else {
+                CharSetPrefilter::new(dfa.start_chars()).map(|p| Arc::new(p) as Arc<dyn Prefilter>)
+            };
+        Engine::Automaton {
+            always_accepting: dfa.always_accepting(),
+            table: AsciiTable::new(&dfa),
+            two_pass: TwoPass::new(&dfa).map(Box::new),
+            dfa,
+            prefilter,
+            prefix,
+            suffix,
+        }
+    }
+
+    /// 探索開始位置の候補を `prefilter` で見つける. 文字列そのものを探すエンジンでは使わない
+    fn with_prefilter(mut self, prefilter: Arc<dyn Prefilter>) -> Engine {
+        if let Engine::BitParallel {
+            prefilter: slot, ..
+        }
+        | Engine::Automaton {
+            prefilter: slot, ..
+        } = &mut self
+        {
+            *slot = Some(prefilter);
+        }
+        self
+    }
+}
+
+/// 元のパターン文字列を書き出す
+impl std::fmt::Display for Regex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// パターンから作った `Regex` は, 元のパターン文字列, 部分パターンの定義 ([`RegexBuilder::define`]),
+/// 大文字と小文字を区別するかで比較する.
+/// [`Construction`] は照合の結果に影響しないため区別しない.
+///
+/// [`Regex::negate`] などの演算で作った `Regex` と, フック ([`RegexBuilder::ast_hook`] など) を登録して作った `Regex` は,
+/// マッチする文字列の集合 (言語) が等しければ等しい.
+/// パターンから作った `Regex` とは, 言語が同じでも等しくならない.
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.origin, &other.origin) {
+            (Origin::Pattern(d1), Origin::Pattern(d2)) => {
+                self.pattern == other.pattern
+                    && self.case_insensitive == other.case_insensitive
+                    && d1 == d2
+            }
+            (Origin::Language(_), Origin::Language(_)) => {
+                self.language_fingerprint() == other.language_fingerprint()
+                    && self.is_equivalent(other)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Regex {}
+
+impl std::hash::Hash for Regex {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match &self.origin {
+            Origin::Pattern(definitions) => {
+                self.pattern.hash(state);
+                self.case_insensitive.hash(state);
+                definitions.hash(state);
+            }
+            Origin::Language(_) => self.language_fingerprint().hash(state),
+        }
+    }
+}
+
+impl std::str::FromStr for Regex {
+    type Err = Box<dyn Error>;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Regex::new(pattern)
+    }
+}
+
+/// マッチした部分文字列
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    /// 開始位置 (バイトオフセット)
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 終了位置 (バイトオフセット)
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// 開始位置から終了位置までの範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.start..self.end]
+    }
+
+    /// マッチした部分の長さ (バイト数)
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// 空文字列にマッチしたか
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl From<Match<'_>> for Range<usize> {
+    fn from(m: Match<'_>) -> Range<usize> {
+        m.range()
+    }
+}
+
+/// [`Regex::find_iter`] が返す, 重ならないマッチを左から順に返すイテレータ
+#[derive(Clone, Debug)]
+pub struct Matches<'r, 'h> {
+    regex: &'r Regex,
+    haystack: &'h str,
+    /// 次に探索を始める位置. 探索を終えたら `None`
+    at: Option<usize>,
+}
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        let at: usize = self.at?;
+        // 接尾辞による確認は最初の探索でのみ行う. マッチが多い場合に, 同じ範囲を何度も確認しないため
+        let text: &'h str = self.haystack;
+        let found = self.regex.find_by(text, at, at == 0, |at| {
+            self.regex.longest_match_at(text, at)
+        });
+        let Some(found) = found else {
+            self.at = None;
+            return None;
+        };
+        let (start, end) = (found.start(), found.end());
+        self.at = if start < end {
+            Some(end)
+        } else {
+            // 空文字列にマッチした場合は1文字進める
+            self.haystack[end..]
+                .chars()
+                .next()
+                .map(|c| end + c.len_utf8())
+        };
+        Some(Match {
+            haystack: self.haystack,
+            start,
+            end,
+        })
+    }
 }
 
 impl Regex {
     pub fn new(pattern: &str) -> Result<Regex, Box<dyn Error>> {
-        let parser: &mut Parser<'_> = &mut Parser::new(Lexer::new(pattern));
-        let node: Node = parser.parse()?;
-        let nfa: NFA = NFA::from_node(node);
-        let dfa: DFA = DFA::from_nfa(nfa);
-        Ok(Regex { dfa })
+        RegexBuilder::new(pattern).build()
+    }
+
+    fn from_dfa(pattern: String, dfa: DFA) -> Regex {
+        Regex {
+            pattern: pattern.into(),
+            case_insensitive: false,
+            engine: Arc::new(Slot::Ready(Engine::automaton(
+                dfa,
+                String::new(),
+                None,
+                None,
+            ))),
+            program: None,
+            ast: None,
+            origin: Origin::Language(OnceLock::new()),
+        }
+    }
+
+    /// 照合に使うエンジン. 構築を遅らせている場合はここで構築する
+    fn engine(&self) -> &Engine {
+        match self.engine.as_ref() {
+            Slot::Ready(engine) => engine,
+            Slot::Deferred(deferred) => deferred.engine(),
+        }
+    }
+
+    /// オートマトンを構築済みでなければ構築する. [`RegexBuilder::deferred`] を参照
+    pub fn ensure_compiled(&self) {
+        self.engine();
+    }
+
+    /// オートマトンを構築済みか
+    pub fn is_compiled(&self) -> bool {
+        match self.engine.as_ref() {
+            Slot::Ready(_) => true,
+            Slot::Deferred(deferred) => deferred.is_compiled(),
+        }
+    }
+
+    /// 照合に使うエンジンによらず, 同じ言語を受理するDFAを返す
+    fn dfa(&self) -> Cow<'_, DFA> {
+        match self.engine() {
+            Engine::Literal(literal) => Cow::Owned(DFA::from_nfa(NFA::from_literal(literal))),
+            Engine::BitParallel { nfa, .. } => Cow::Owned(DFA::from_nfa(nfa.to_nfa())),
+            Engine::Automaton { dfa, .. } => Cow::Borrowed(dfa),
+        }
+    }
+
+    /// 元のパターン文字列
+    ///
+    /// [`Regex::negate`], [`Regex::minus`], [`Regex::repeat`] で作った `Regex` では `!(a)`, `(a)-(b)`, `(a){2,3}`
+    /// のような表記になる.
+    /// これは表記にすぎず, [`Regex::new`] で解釈すると別の言語になる.
+    /// [`Regex::concat`] などで作った `Regex` では構文木を書き出したパターンになるが, グループの番号や名前は再現されない.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// マッチしない文字列全体 (補集合の言語) にマッチする `Regex`
+    pub fn negate(&self) -> Regex {
+        let pattern: String = format!("!({})", self.pattern);
+        Regex::from_dfa(pattern, self.dfa().complement().minimize())
+    }
+
+    /// `self` にマッチする文字列のいずれかとのレーベンシュタイン距離 (挿入・削除・置換の回数) が `k` 以下の文字列にマッチする `Regex`
+    ///
+    /// パターンは `~1(山田太郎)` のような表記になる. [`Regex::negate`] と同様に, キャプチャグループは持たない.
+    pub fn fuzzy(&self, k: usize) -> Regex {
+        let pattern: String = format!("~{}({})", k, self.pattern);
+        Regex::from_dfa(pattern, self.dfa().fuzzy(k).minimize())
+    }
+
+    /// `self` にマッチし, `other` にはマッチしない文字列全体 (差集合の言語) にマッチする `Regex`
+    pub fn minus(&self, other: &Regex) -> Regex {
+        let dfa: DFA = self.dfa().product(&other.dfa(), |a, b| a && !b);
+        let pattern: String = format!("({})-({})", self.pattern, other.pattern);
+        Regex::from_dfa(pattern, dfa.minimize())
+    }
+
+    /// `self` の後に `other` が続く文字列にマッチする `Regex`
+    ///
+    /// `other` のグループは `self` のグループの後に番号付けされる. [`Regex::negate`], [`Regex::minus`],
+    /// [`Regex::repeat`] で作った `Regex` は構文木を持たないので `None` を返す.
+    pub fn concat(&self, other: &Regex) -> Option<Regex> {
+        self.compose(other, Node::concat)
+    }
+
+    /// `self` または `other` にマッチする `Regex`. グループの番号は [`Regex::concat`] と同じ
+    pub fn alternate(&self, other: &Regex) -> Option<Regex> {
+        self.compose(other, Node::union)
+    }
+
+    /// `self` を `min` 回以上 `max` 回以下 (`None` なら上限なし) 繰り返した文字列にマッチする `Regex`
+    ///
+    /// 各繰り返しのグループは同じ番号を持ち, 最後の繰り返しでマッチした部分が残る. `min > max` ならパニックする.
+    /// 繰り返しを展開した構文木は持たないので, 結果を [`Regex::concat`] などに渡すと `None` を返す.
+    pub fn repeat(&self, min: usize, max: Option<usize>) -> Option<Regex> {
+        if let Some(max) = max {
+            assert!(min <= max, "min ({}) must not exceed max ({})", min, max);
+        }
+        let node: &Node = self.ast.as_deref()?;
+        let program: Program = Program::repeat(node, min, max).with_names(self.group_names());
+        // 1回以上繰り返すなら, 1回分の接頭辞はどのマッチにも現れる
+        let prefix: String = if min == 0 {
+            String::new()
+        } else {
+            node.without_groups().root().literal_prefix()
+        };
+        // 展開した構文木からDFAを作ると, 回数に比例した大きさのNFAに部分集合構成法を適用することになる.
+        // 状態数は回数に比例するので, 最小化 (状態数の2乗かかりうる) はしない
+        let dfa: DFA = self.dfa().minimize().repeat(min, max);
+        let max: String = max.map_or(String::new(), |max| max.to_string());
+        Some(Regex {
+            pattern: format!("({}){{{},{}}}", self.pattern, min, max).into(),
+            case_insensitive: false,
+            engine: Arc::new(Slot::Ready(Engine::automaton(dfa, prefix, None, None))),
+            program: Some(Arc::new(program)),
+            ast: None,
+            origin: Origin::Language(OnceLock::new()),
+        })
+    }
+
+    /// 2つの構文木から新しい `Regex` を作る. `other` のグループの番号は `self` のグループの数だけずらす
+    fn compose(&self, other: &Regex, op: impl FnOnce(Node, Node) -> Node) -> Option<Regex> {
+        let n1: &Node = self.ast.as_deref()?;
+        let n2: &Node = other.ast.as_deref()?;
+        let node: Node = op(n1.clone(), n2.shift_groups(n1.root().groups()));
+        let mut names: Vec<Option<String>> = self.group_names();
+        names.extend(other.group_names());
+        Some(RegexBuilder::from_node(node, names))
+    }
+
+    /// グループ 1 以降の名前
+    fn group_names(&self) -> Vec<Option<String>> {
+        self.capture_names()
+            .skip(1)
+            .map(|name| name.map(str::to_string))
+            .collect()
+    }
+
+    /// `text` 全体にマッチしなかった場合, DFAが行き詰まった位置と, そこで受理される文字を返す
+    pub fn explain_failure(&self, text: &str) -> Option<MatchFailure> {
+        failure::explain(&self.dfa(), text)
+    }
+
+    /// `text` 全体がマッチすれば `Ok`. マッチしなければ, マッチしなくなった位置とそこで受理される文字をエラーとして返す
+    pub fn validate(&self, text: &str) -> Result<(), ValidationError> {
+        if self.matches(text) {
+            return Ok(());
+        }
+        // 照合とDFAの結果が食い違っても, 検証は通さない
+        let failure: MatchFailure = self
+            .explain_failure(text)
+            .unwrap_or_else(|| MatchFailure::unexplained(text, self.start_state()));
+        Err(ValidationError::new(self.as_str(), failure))
+    }
+
+    /// `text` を先頭から読んだときにDFAが行う遷移を順に返す. 遷移できなくなった時点で終わる
+    pub fn trace(&self, text: &str) -> Vec<Step> {
+        trace::trace(&self.dfa(), text)
+    }
+
+    /// [`Regex::find`] と同じくマッチを探すが, 各開始位置で照合を始めるときとDFAが1文字読むごとに `callout` を呼ぶ
+    ///
+    /// `callout` が `false` を返すと, その開始位置ではそれより先を読まず, その時点で終わるマッチも採らない.
+    /// 開始位置を1つずつ試すので [`Regex::find`] より遅い.
+    pub fn find_with_callout<'h>(
+        &self,
+        text: &'h (impl AsRef<str> + ?Sized),
+        callout: impl FnMut(&Callout) -> bool,
+    ) -> Option<Match<'h>> {
+        let text: &'h str = text.as_ref();
+        let (start, end) = trace::find_with_callout(&self.dfa(), text, callout)?;
+        Some(Match {
+            haystack: text,
+            start,
+            end,
+        })
+    }
+
+    /// [`Regex::trace`] で使われるDFAの開始状態の番号
+    pub fn start_state(&self) -> u32 {
+        self.dfa().start.0
+    }
+
+    /// マッチする文字列が1つも存在しないか
+    pub fn matches_nothing(&self) -> bool {
+        self.dfa().is_empty()
+    }
+
+    /// 空でないマッチの先頭になり得る文字の集合を, 昇順で重ならない範囲の列にして返す
+    ///
+    /// 空文字列にマッチするかは含まないので, 必要なら `self.matches("")` で確かめる.
+    pub fn start_chars(&self) -> Vec<RangeInclusive<char>> {
+        self.dfa().start_char_ranges()
+    }
+
+    /// マッチする文字列のうち最短のもの. マッチする文字列が存在しなければ `None`
+    pub fn shortest_example(&self) -> Option<String> {
+        self.dfa().shortest_accepted()
+    }
+
+    /// マッチする長さ `max_len` 以下の文字列をランダムに生成する. 該当する文字列が無ければ `None`
+    #[cfg(feature = "rand")]
+    pub fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R, max_len: usize) -> Option<String> {
+        self.dfa().sample(rng, max_len)
+    }
+
+    /// マッチする文字列が有限個しかないか
+    pub fn is_finite(&self) -> bool {
+        let dfa: Cow<'_, DFA> = self.dfa();
+        dfa.is_empty() || dfa.max_accepted_len().is_some()
+    }
+
+    /// マッチする文字列のうち最長のものの長さ (文字数).
+    /// マッチする文字列が無限にある場合や, 1つも存在しない場合は `None`
+    pub fn max_len(&self) -> Option<usize> {
+        self.dfa().max_accepted_len()
     }
 
-    pub fn matches(&self, text: &str) -> bool {
-        let mut current_state: DFAState = self.dfa.start;
+    /// 最小化したDFAから状態除去法でパターン文字列を作り直す
+    ///
+    /// パターンの構文で表せない場合 ([`Regex::negate`] の結果など) や, マッチする文字列が存在しない場合は `None`.
+    pub fn to_pattern(&self) -> Option<String> {
+        self.dfa().minimize().to_node().map(|node| node.to_string())
+    }
+
+    /// マッチする長さ `max_len` 以下の文字列を辞書順に列挙する
+    ///
+    /// パターンに現れない任意の文字による遷移 ([`Regex::negate`] などで生じる) は, 代表の1文字のみを列挙する.
+    pub fn strings(&self, max_len: usize) -> Strings {
+        Strings::new(&self.dfa(), max_len)
+    }
+
+    /// `self` と `other` が同じ文字列の集合にマッチするか
+    pub fn is_equivalent(&self, other: &Regex) -> bool {
+        // 対称差の言語が空なら等価
+        self.dfa().product(&other.dfa(), |a, b| a != b).is_empty()
+    }
+
+    /// マッチする文字列の集合 (言語) から決まるハッシュ値
+    ///
+    /// 最小化したDFAの状態を番号付け直して計算するので, 書き方が違っても同じ言語のパターンなら同じ値になる.
+    /// 値はバージョンをまたいでも変わらないが, 異なる言語が同じ値になる可能性はあるので, 一致したら [`Regex::is_equivalent`] で確かめる.
+    pub fn fingerprint(&self) -> u64 {
+        self.dfa().minimize().fingerprint()
+    }
+
+    /// 言語で比較する `Regex` の [`Regex::fingerprint`]. 比較のたびに最小化しないよう, 一度求めたら保持する
+    fn language_fingerprint(&self) -> u64 {
+        match &self.origin {
+            Origin::Language(fingerprint) => *fingerprint.get_or_init(|| self.fingerprint()),
+            Origin::Pattern(_) => self.fingerprint(),
+        }
+    }
+
+    /// `self` にマッチする文字列がすべて `other` にもマッチするか
+    pub fn is_subset_of(&self, other: &Regex) -> bool {
+        self.dfa().product(&other.dfa(), |a, b| a && !b).is_empty()
+    }
+
+    /// `other` にマッチする文字列がすべて `self` にもマッチするか
+    pub fn is_superset_of(&self, other: &Regex) -> bool {
+        other.is_subset_of(self)
+    }
+
+    /// `self` と `other` の両方にマッチする文字列が存在しないか
+    pub fn is_disjoint(&self, other: &Regex) -> bool {
+        self.dfa().product(&other.dfa(), |a, b| a && b).is_empty()
+    }
+
+    /// `text` 全体がマッチするか
+    ///
+    /// `text` には `&str` のほか `String`, `Box<str>`, `Cow<str>` なども渡せる. 以下の照合のメソッドも同様.
+    pub fn matches(&self, text: impl AsRef<str>) -> bool {
+        self.matches_str(text.as_ref())
+    }
+
+    fn matches_str(&self, text: &str) -> bool {
+        let (dfa, always_accepting, table) = match self.engine() {
+            Engine::Literal(literal) => return text == literal,
+            Engine::BitParallel { nfa, prefix, .. } => {
+                return has_prefix(text, 0, prefix) && nfa.matches(text);
+            }
+            Engine::Automaton {
+                dfa,
+                prefix,
+                always_accepting,
+                table,
+                ..
+            } => {
+                if !has_prefix(text, 0, prefix) {
+                    return false;
+                }
+                (dfa, always_accepting, table.as_ref())
+            }
+        };
+        let early_exit: bool = !always_accepting.is_empty();
+        let mut current_state: DFAState = dfa.start;
         for chara in text.chars() {
-            if let Some(state) = self.dfa.next_state(current_state, chara) {
+            if early_exit && always_accepting.contains(&current_state) {
+                return true;
+            }
+            let next: Option<DFAState> = match table {
+                Some(table) => table.next_state(dfa, current_state, chara),
+                None => dfa.next_state(current_state, chara),
+            };
+            if let Some(state) = next {
                 current_state = state;
             } else {
                 return false;
             }
         }
-        self.dfa.accepts.contains(&current_state)
+        dfa.accepts.contains(&current_state)
+    }
+
+    /// `text` の部分文字列にマッチするか
+    ///
+    /// [`Regex::matches`], [`Regex::find`] と同様に, ヒープ確保を行わない.
+    pub fn is_match(&self, text: impl AsRef<str>) -> bool {
+        self.is_match_str(text.as_ref())
+    }
+
+    fn is_match_str(&self, text: &str) -> bool {
+        match self.engine() {
+            Engine::BitParallel { nfa, suffix, .. } => {
+                !suffix.as_ref().is_some_and(|suffix| suffix.rules_out(text)) && nfa.is_match(text)
+            }
+            // マッチの存在だけを調べるので, 受理状態に達したらそれ以上読まない
+            Engine::Automaton {
+                dfa,
+                table: Some(table),
+                ..
+            } => self
+                .find_by(text, 0, true, |at| table.shortest_match_at(dfa, text, at))
+                .is_some(),
+            Engine::Automaton { dfa, .. } => self
+                .find_by(text, 0, true, |at| dfa.shortest_match_at(text, at))
+                .is_some(),
+            Engine::Literal(_) => self.find(text).is_some(),
+        }
+    }
+
+    /// `text` 中で最も左にあるマッチを返す (同じ位置からのマッチは最長のもの)
+    pub fn find<'h>(&self, text: &'h (impl AsRef<str> + ?Sized)) -> Option<Match<'h>> {
+        self.find_str(text.as_ref())
+    }
+
+    fn find_str<'h>(&self, text: &'h str) -> Option<Match<'h>> {
+        self.find_by(text, 0, true, |at| self.longest_match_at(text, at))
+    }
+
+    /// `text` の先頭から始まる最長のマッチ. 先頭からマッチしなければ `None`
+    pub fn find_prefix<'h>(&self, text: &'h (impl AsRef<str> + ?Sized)) -> Option<Match<'h>> {
+        let text: &'h str = text.as_ref();
+        let end: usize = self.longest_match_at(text, 0)?;
+        Some(Match {
+            haystack: text,
+            start: 0,
+            end,
+        })
+    }
+
+    /// `text` 中の重ならないマッチを左から順に返す
+    ///
+    /// 空文字列にマッチした場合は, 次の探索を1文字先から始める.
+    pub fn find_iter<'r, 'h>(&'r self, text: &'h (impl AsRef<str> + ?Sized)) -> Matches<'r, 'h> {
+        Matches {
+            regex: self,
+            haystack: text.as_ref(),
+            at: Some(0),
+        }
+    }
+
+    /// 最も左にあるマッチ ([`Regex::find`] と同じ) と, その中で各キャプチャグループにマッチした部分を返す
+    ///
+    /// マッチの範囲を決めた後, その範囲の分け方が複数ある場合は, 選択肢は左のものを, 繰り返しは長いものを優先する.
+    pub fn captures<'h>(&self, text: &'h (impl AsRef<str> + ?Sized)) -> Option<Captures<'h>> {
+        let found: Match<'h> = self.find_str(text.as_ref())?;
+        Some(self.captures_at(found))
+    }
+
+    /// `found` の範囲について各キャプチャグループの位置を求める
+    fn captures_at<'h>(&self, found: Match<'h>) -> Captures<'h> {
+        let slots: Vec<Option<usize>> = self
+            .program
+            .as_ref()
+            .and_then(|program| program.captures(found.haystack, found.start, found.end))
+            .unwrap_or_else(|| vec![Some(found.start), Some(found.end)]);
+        let names: Arc<[Option<String>]> = match &self.program {
+            Some(program) => Arc::clone(program.names()),
+            None => Arc::new([None]),
+        };
+        Captures::new(found.haystack, slots, names)
+    }
+
+    /// グループ 0 (マッチ全体) を含むキャプチャグループの数
+    pub fn captures_len(&self) -> usize {
+        self.program.as_ref().map_or(1, |program| program.groups())
+    }
+
+    /// どのマッチでも照合に関与するキャプチャグループが同じ場合, グループ 0 を含むその数
+    ///
+    /// グループが `*` の中や `|` の選択肢にあると `None`. `Some(n + 1)` なら [`Captures::extract`] で `n` 個のグループを取り出せる.
+    pub fn static_captures_len(&self) -> Option<usize> {
+        self.program
+            .as_ref()
+            .map_or(Some(1), |program| program.static_groups())
+    }
+
+    /// グループ 0 から順に, 各キャプチャグループの名前 (`(?<name>...)`) を返す. グループ 0 と名前のないグループは `None`
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        let names: &[Option<String>] = match &self.program {
+            Some(program) => program.names(),
+            None => &[None],
+        };
+        names.iter().map(|name| name.as_deref())
+    }
+
+    /// `name` という名前のキャプチャグループの番号. 同じ名前のグループが複数あれば最も左のもの
+    pub fn capture_index(&self, name: &str) -> Option<usize> {
+        self.capture_names().position(|n| n == Some(name))
+    }
+
+    /// 最も左にあるマッチを `replacement` で置き換える. `$n` はグループ `n` を表す ([`Captures::expand`] を参照)
+    pub fn replace<'h>(
+        &self,
+        text: &'h (impl AsRef<str> + ?Sized),
+        replacement: &str,
+    ) -> Cow<'h, str> {
+        self.replace_expand(text.as_ref(), 1, replacement)
+    }
+
+    /// 重ならないすべてのマッチ ([`Regex::find_iter`] と同じ) を `replacement` で置き換える
+    pub fn replace_all<'h>(
+        &self,
+        text: &'h (impl AsRef<str> + ?Sized),
+        replacement: &str,
+    ) -> Cow<'h, str> {
+        self.replace_expand(text.as_ref(), usize::MAX, replacement)
+    }
+
+    fn replace_expand<'h>(&self, text: &'h str, limit: usize, replacement: &str) -> Cow<'h, str> {
+        self.replace_n(text, limit, |caps, dst| caps.expand(replacement, dst))
+    }
+
+    /// 最も左にあるマッチを `replacer` が返す文字列で置き換える
+    pub fn replace_with<'h>(
+        &self,
+        text: &'h str,
+        mut replacer: impl FnMut(&Captures<'h>) -> String,
+    ) -> Cow<'h, str> {
+        self.replace_n(text, 1, |caps, dst| dst.push_str(&replacer(caps)))
+    }
+
+    /// 重ならないすべてのマッチを `replacer` が返す文字列で置き換える
+    pub fn replace_all_with<'h>(
+        &self,
+        text: &'h str,
+        mut replacer: impl FnMut(&Captures<'h>) -> String,
+    ) -> Cow<'h, str> {
+        self.replace_n(text, usize::MAX, |caps, dst| dst.push_str(&replacer(caps)))
+    }
+
+    /// 先頭から `limit` 個までのマッチを置き換える. マッチがなければ複製しない
+    fn replace_n<'h>(
+        &self,
+        text: &'h str,
+        limit: usize,
+        mut replace: impl FnMut(&Captures<'h>, &mut String),
+    ) -> Cow<'h, str> {
+        let mut matches = self.find_iter(text).take(limit).peekable();
+        if matches.peek().is_none() {
+            return Cow::Borrowed(text);
+        }
+        let mut dst: String = String::with_capacity(text.len());
+        let mut last: usize = 0;
+        for found in matches {
+            dst.push_str(&text[last..found.start]);
+            replace(&self.captures_at(found), &mut dst);
+            last = found.end;
+        }
+        dst.push_str(&text[last..]);
+        Cow::Owned(dst)
+    }
+
+    /// [`Regex::is_match`] と同じ. 照合の途中経過を `cache` に記録して再利用する
+    pub fn is_match_with(&self, cache: &mut Cache, text: &str) -> bool {
+        self.find_with(cache, text).is_some()
+    }
+
+    /// [`Regex::find`] と同じ. 照合の途中経過を `cache` に記録して再利用する
+    ///
+    /// 別の `Regex` で使われていた `cache` は, 記録を捨ててから使う.
+    pub fn find_with<'h>(&self, cache: &mut Cache, text: &'h str) -> Option<Match<'h>> {
+        match self.engine() {
+            Engine::BitParallel { nfa, .. } => {
+                let lazy: &mut LazyDFA = cache.prepare(&self.engine);
+                self.find_by(text, 0, true, |at| {
+                    nfa.longest_match_at_lazy(lazy, text, at)
+                })
+            }
+            _ => self.find(text),
+        }
+    }
+
+    /// `from` 以降の候補位置ごとに `longest_match_at` を試し, 最も左にあるマッチを返す. `check_suffix` なら先に必要な接尾辞の有無を確かめる
+    ///
+    /// 位置はすべて `text` の先頭からのバイトオフセット. 途中から探す場合も `text` は切り出さずに渡し, プレフィルタに同じ位置を見せる.
+    fn find_by<'h>(
+        &self,
+        text: &'h str,
+        from: usize,
+        check_suffix: bool,
+        mut longest_match_at: impl FnMut(usize) -> Option<usize>,
+    ) -> Option<Match<'h>> {
+        enter_span!(TRACE, "find", pattern = %self.pattern, haystack_len = text.len());
+        let (prefilter, suffix, two_pass) = match self.engine() {
+            Engine::Literal(literal) => {
+                let start: usize = from + find_literal(&text[from..], literal)?;
+                return Some(Match {
+                    haystack: text,
+                    start,
+                    end: start + literal.len(),
+                });
+            }
+            Engine::BitParallel {
+                prefilter, suffix, ..
+            } => (prefilter, suffix, None),
+            Engine::Automaton {
+                prefilter,
+                suffix,
+                two_pass,
+                ..
+            } => (prefilter, suffix, two_pass.as_deref()),
+        };
+        if check_suffix
+            && suffix
+                .as_ref()
+                .is_some_and(|suffix| suffix.rules_out(&text[from..]))
+        {
+            return None;
+        }
+        let mut at: usize = from;
+        if let Some(two_pass) = two_pass {
+            // 前向きに1度だけ読み, マッチが無ければ開始位置を1つずつ試さずに終える.
+            // 最も左のマッチは `end` 以前に始まり, `at` 以降の最初の候補で見つかる
+            let end: usize = two_pass.earliest_end(text, from, prefilter.as_deref())?;
+            at = two_pass.leftmost_start(text, from, end);
+        }
+        loop {
+            if let Some(prefilter) = prefilter {
+                at = prefilter.find(text, at)?;
+            }
+            if let Some(end) = longest_match_at(at) {
+                return Some(Match {
+                    haystack: text,
+                    start: at,
+                    end,
+                });
+            }
+            at += text[at..].chars().next()?.len_utf8();
+        }
+    }
+
+    fn longest_match_at(&self, text: &str, start: usize) -> Option<usize> {
+        match self.engine() {
+            Engine::Literal(literal) => text[start..]
+                .starts_with(literal.as_str())
+                .then_some(start + literal.len()),
+            Engine::BitParallel { nfa, prefix, .. } => {
+                if !has_prefix(text, start, prefix) {
+                    return None;
+                }
+                nfa.longest_match_at(text, start)
+            }
+            Engine::Automaton {
+                dfa, prefix, table, ..
+            } => {
+                if !has_prefix(text, start, prefix) {
+                    return None;
+                }
+                match table {
+                    Some(table) => table.longest_match_at(dfa, text, start),
+                    None => dfa.longest_match_at(text, start),
+                }
+            }
+        }
     }
 }
 
+/// `text[start..]` が `prefix` から始まるか. 探索の各位置で呼ばれるので, 空の場合を先に除く
+#[inline]
+fn has_prefix(text: &str, start: usize, prefix: &str) -> bool {
+    prefix.is_empty() || text.as_bytes()[start..].starts_with(prefix.as_bytes())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// 現在のスレッドでのヒープ確保の回数を数えるアロケータ
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn no_allocation() {
+        let regexes: Vec<Regex> = [
+            r"山田",
+            r"山田(太|一)郎",
+            &format!("({})*b", "a|".repeat(65)),
+            r"a*",
+        ]
+        .iter()
+        .map(|p| Regex::new(p).unwrap())
+        .collect();
+        let text: &str = "私は山田太郎です. aaab";
+        let before: usize = ALLOCATIONS.with(|n| n.get());
+        for regex in &regexes {
+            std::hint::black_box(regex.is_match(text));
+            std::hint::black_box(regex.find(text));
+            std::hint::black_box(regex.matches(text));
+        }
+        assert_eq!(ALLOCATIONS.with(|n| n.get()), before);
+    }
+
+    #[test]
+    fn auto_traits() {
+        fn assert_traits<T: Clone + std::fmt::Debug + Send + Sync + 'static>() {}
+        assert_traits::<Regex>();
+        assert_traits::<RegexBuilder>();
+
+        let regex = Regex::new(r"a(b|c)*").unwrap();
+        let cloned = regex.clone();
+        let handle = std::thread::spawn(move || cloned.matches("abcb"));
+        assert!(handle.join().unwrap());
+        assert!(regex.matches("a"));
+    }
+
+    #[test]
+    fn clone_shares_engine() {
+        let regex = Regex::new(&format!("({})*b", "a|".repeat(65))).unwrap();
+        let cloned = regex.clone();
+        assert!(Arc::ptr_eq(&regex.engine, &cloned.engine));
+        assert!(Arc::ptr_eq(&regex.pattern, &cloned.pattern));
+    }
+
+    #[test]
+    fn find_prefix() {
+        for pattern in [
+            r"山田",
+            r"山田(太|一)*",
+            &format!("({})*b", "a|".repeat(65)),
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            assert_eq!(regex.find_prefix("x山田"), None);
+        }
+        let regex = Regex::new(r"山田(太|一)*").unwrap();
+        assert_eq!(
+            regex.find_prefix("山田太一です").unwrap().as_str(),
+            "山田太一"
+        );
+        assert_eq!(
+            Regex::new(r"a*").unwrap().find_prefix("b").unwrap().end(),
+            0
+        );
+    }
+
+    #[test]
+    fn find_iter() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let found: Vec<(usize, &str)> = regex
+            .find_iter("山田太郎と山田一郎と山田次郎")
+            .map(|m| (m.start(), m.as_str()))
+            .collect();
+        assert_eq!(found, vec![(0, "山田太郎"), (15, "山田一郎")]);
+
+        let regex = Regex::new(r"a*").unwrap();
+        let found: Vec<(usize, usize)> = regex
+            .find_iter("baac")
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        assert_eq!(found, vec![(0, 0), (1, 3), (3, 3), (4, 4)]);
+    }
+
+    #[test]
+    fn two_pass() {
+        let not_ab: Regex = Regex::new(r"ab").unwrap().negate();
+        let cases: [(Regex, &str); 3] = [
+            (
+                Regex::new(r"(a|b)*_(x|xyz)").unwrap().negate().negate(),
+                "ab_x-_xyz_xy__x",
+            ),
+            (not_ab.clone(), "abab"),
+            (
+                Regex::new(r"c")
+                    .unwrap()
+                    .negate()
+                    .minus(&Regex::new(r"(a|b|c)*").unwrap()),
+                "xabcbab",
+            ),
+        ];
+        for (regex, text) in &cases {
+            // 開始位置を1つずつ試した結果と同じになる
+            let mut expected: Vec<(usize, usize)> = Vec::new();
+            let mut at: usize = 0;
+            while at <= text.len() {
+                match (at..=text.len()).find_map(|s| Some((s, regex.longest_match_at(text, s)?))) {
+                    Some((start, end)) => {
+                        expected.push((start, end));
+                        at = if start < end { end } else { end + 1 };
+                    }
+                    None => break,
+                }
+            }
+            let found: Vec<(usize, usize)> = regex
+                .find_iter(text)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            assert_eq!(found, expected, "{}", regex);
+        }
+        assert!(matches!(
+            cases[0].0.engine(),
+            Engine::Automaton {
+                two_pass: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn captures() {
+        let regex = Regex::new(r"(山田|佐藤)(太|一)郎").unwrap();
+        assert_eq!(regex.captures_len(), 3);
+        let caps = regex.captures("私は佐藤一郎です").unwrap();
+        assert_eq!(caps.get(0).unwrap().as_str(), "佐藤一郎");
+        assert_eq!(caps.get(1).unwrap().as_str(), "佐藤");
+        assert_eq!(caps.get(2).unwrap().start(), 12);
+        assert!(regex.captures("山田次郎").is_none());
+
+        let regex = RegexBuilder::new(r"(a|b)*(C)")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let caps = regex.captures("xAbc").unwrap();
+        assert_eq!(caps.get(1).unwrap().as_str(), "b");
+        assert_eq!(caps.get(2).unwrap().as_str(), "c");
+
+        let caps = Regex::new(r"ab").unwrap().negate().captures("b").unwrap();
+        assert_eq!((caps.len(), caps.get(0).unwrap().as_str()), (1, "b"));
+
+        let regex = Regex::new(r"(?<family>山田|佐藤)(?<given>太|一)郎").unwrap();
+        let caps = regex.captures("私は山田太郎です").unwrap();
+        assert_eq!(
+            (&caps["family"], &caps["given"], &caps[0]),
+            ("山田", "太", "山田太郎")
+        );
+
+        // ブランチリセットでは, どちらの選択肢でマッチしても同じ番号で取り出せる
+        let regex = Regex::new(r"(?|(?<y>0|1)-(a)|(2)(b)*)(c)").unwrap();
+        assert_eq!(regex.captures_len(), 4);
+        for (text, groups) in [("1-ac", ["1", "a", "c"]), ("2bbc", ["2", "b", "c"])] {
+            let caps = regex.captures(text).unwrap();
+            assert_eq!([&caps[1], &caps[2], &caps[3]], groups);
+            assert_eq!(&caps["y"], groups[0]);
+        }
+    }
+
+    #[test]
+    fn match_range() {
+        let m = Regex::new(r"b*").unwrap().find("abbc").unwrap();
+        assert_eq!((m.range(), m.len(), m.is_empty()), (0..0, 0, true));
+        let m = Regex::new(r"bb*").unwrap().find("abbc").unwrap();
+        assert_eq!((m.range(), m.len(), m.is_empty()), (1..3, 2, false));
+        assert_eq!(Range::from(m), 1..3);
+    }
+
+    #[test]
+    fn capture_names() {
+        let regex = Regex::new(r"(?<year>0|1)-((?<day>2)|(?<era>3))").unwrap();
+        assert_eq!(regex.captures_len(), 5);
+        let names: Vec<Option<&str>> = regex.capture_names().collect();
+        assert_eq!(names, [None, Some("year"), None, Some("day"), Some("era")]);
+        assert_eq!(regex.capture_index("year"), Some(1));
+        assert_eq!(regex.capture_index("day"), Some(3));
+        assert_eq!(regex.capture_index("month"), None);
+        assert!(Regex::new(r"(?<year>0|1)-(?<year>3)").is_err());
+        // 不正な名前は文字列として読まずにエラーにする
+        assert!(Regex::new(r"(?<my-name>a)").is_err());
+
+        let negated = regex.negate();
+        assert_eq!(negated.capture_names().collect::<Vec<_>>(), [None]);
+        assert_eq!(negated.capture_index("year"), None);
+    }
+
+    #[test]
+    fn static_captures_len() {
+        let regex = Regex::new(r"(0|1)(0|1)-(a*)").unwrap();
+        assert_eq!(regex.static_captures_len(), Some(4));
+        let (whole, [h, l, rest]) = regex.captures("x10-aa").unwrap().extract();
+        assert_eq!((whole, h, l, rest), ("10-aa", "1", "0", "aa"));
+
+        assert_eq!(Regex::new(r"(a)|b").unwrap().static_captures_len(), None);
+        assert_eq!(Regex::new(r"(a)*").unwrap().static_captures_len(), None);
+        assert_eq!(Regex::new(r"ab*").unwrap().static_captures_len(), Some(1));
+        assert_eq!(regex.negate().static_captures_len(), Some(1));
+    }
+
+    #[test]
+    fn replace() {
+        let regex = Regex::new(r"(山田|佐藤)(太|一)郎").unwrap();
+        let text: &str = "山田太郎と佐藤一郎";
+        assert_eq!(regex.replace(text, "$2$1"), "太山田と佐藤一郎");
+        assert_eq!(regex.replace_all(text, "${1}さん"), "山田さんと佐藤さん");
+        assert_eq!(
+            regex.replace_all_with(text, |caps| caps.get(2).unwrap().as_str().repeat(2)),
+            "太太と一一"
+        );
+        assert_eq!(regex.replace_with(text, |_| String::new()), "と佐藤一郎");
+        assert!(matches!(regex.replace_all("鈴木", "x"), Cow::Borrowed(_)));
+        assert_eq!(
+            Regex::new(r"a*").unwrap().replace_all("baac", "-"),
+            "-b--c-"
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        let regex: Regex = "a(b|)".parse().unwrap();
+        assert!(regex.matches("ab"));
+        assert!(regex.matches("a"));
+        assert!("a(b".parse::<Regex>().is_err());
+    }
+
+    #[test]
+    fn as_str() {
+        let regex = Regex::new(r"a(b|)").unwrap();
+        assert_eq!(regex.as_str(), r"a(b|)");
+        assert_eq!(regex.to_string(), r"a(b|)");
+        assert_eq!(Regex::new(r"abc").unwrap().as_str(), "abc");
+        assert_eq!(regex.negate().as_str(), r"!(a(b|))");
+        let other = Regex::new(r"ab").unwrap();
+        assert_eq!(regex.minus(&other).as_str(), r"(a(b|))-(ab)");
+    }
+
+    #[test]
+    // パターンから作った `Regex` のハッシュ値はパターン文字列のみから求めるので, 遅延構築の状態が変わっても変化しない
+    #[allow(clippy::mutable_key_type)]
+    fn eq_hash() {
+        let regex = Regex::new(r"a(b|)").unwrap();
+        let glushkov = RegexBuilder::new(r"a(b|)")
+            .construction(Construction::Glushkov)
+            .build()
+            .unwrap();
+        assert_eq!(regex, glushkov);
+        assert_ne!(regex, Regex::new(r"ab|a").unwrap());
+
+        let set: std::collections::HashSet<Regex> = [regex.clone(), glushkov, regex.negate()]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&regex));
+
+        // 演算で作った `Regex` の表記は, 同じ文字列のパターンと区別する
+        let a = Regex::new(r"a").unwrap();
+        let b = Regex::new(r"b").unwrap();
+        for (derived, pattern) in [
+            (a.negate(), r"!(a)"),
+            (a.fuzzy(1), r"~1(a)"),
+            (a.minus(&b), r"(a)-(b)"),
+        ] {
+            assert_eq!(derived.as_str(), pattern);
+            assert_ne!(derived, Regex::new(pattern).unwrap());
+        }
+        // 演算で作った `Regex` 同士は言語で比較する
+        assert_eq!(a.negate().negate(), a.fuzzy(0));
+        assert_ne!(a.negate(), b.negate());
+        let set: std::collections::HashSet<Regex> = [
+            a.negate(),
+            Regex::new(r"!(a)").unwrap(),
+            a.minus(&b),
+            a.fuzzy(0),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(set.len(), 3);
+    }
+
     #[test]
     fn matches_case1() {
         let regex = Regex::new(r"(p(erl|ython|hp)|ruby)").unwrap();
@@ -84,6 +1320,435 @@ mod tests {
         assert!(!regex.matches(r"abb"));
     }
 
+    #[test]
+    fn is_match() {
+        let regex = Regex::new(r"山田(太|一|次|三)郎").unwrap();
+        assert!(regex.is_match("私は山田太郎です"));
+        assert!(regex.is_match("山田三郎"));
+        assert!(!regex.is_match("私は山田花子です"));
+        assert!(!regex.is_match(""));
+    }
+
+    #[test]
+    fn find() {
+        let regex = Regex::new(r"ab*").unwrap();
+        let m = regex.find("xxabbbxab").unwrap();
+        assert_eq!((m.start(), m.end(), m.as_str()), (2, 6, "abbb"));
+        assert!(regex.find("xyz").is_none());
+
+        let regex = Regex::new(r"(ab|)c").unwrap();
+        assert_eq!(regex.find("aac").unwrap().as_str(), "c");
+        assert_eq!(regex.find("aabc").unwrap().as_str(), "abc");
+    }
+
+    #[test]
+    fn haystack_types() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let owned: String = "私は山田太郎です".to_string();
+        let boxed: Box<str> = "山田一郎".into();
+        let cow: Cow<'_, str> = Cow::Owned("山田一郎と山田太郎".to_string());
+        assert!(regex.is_match(owned.clone()) && regex.matches(&boxed) && !regex.matches(&cow));
+        assert_eq!(regex.find(&owned).unwrap().as_str(), "山田太郎");
+        assert_eq!(regex.find_prefix(&boxed).unwrap().end(), 12);
+        assert_eq!(regex.find_iter(&cow).count(), 2);
+        assert_eq!(&regex.captures(&cow).unwrap()[1], "一");
+        assert_eq!(regex.replace_all(&cow, "X"), "XとX");
+        assert_eq!(regex.replace(&owned, "X"), "私はXです");
+    }
+
+    #[test]
+    fn find_empty() {
+        let regex = Regex::new(r"a*").unwrap();
+        let m = regex.find("bbaa").unwrap();
+        assert_eq!((m.start(), m.end()), (0, 0));
+        assert!(regex.is_match(""));
+    }
+
+    #[test]
+    fn literal() {
+        let regex = Regex::new(r"hello_\(world\)").unwrap();
+        assert!(matches!(regex.engine(), Engine::Literal(_)));
+        assert!(regex.matches("hello_(world)"));
+        assert!(!regex.matches("hello_(world)!"));
+        let m = regex.find("say hello_(world)").unwrap();
+        assert_eq!((m.start(), m.end()), (4, 17));
+
+        let regex = Regex::new(r"").unwrap();
+        assert!(regex.matches(""));
+        assert!(regex.is_match("abc"));
+    }
+
+    #[test]
+    fn literal_prefix() {
+        let long: String = format!("{}(a|b)*", "x".repeat(70));
+        for pattern in [r"山田(太|一)郎", r"abc(d|e)*f", long.as_str()] {
+            let regex = Regex::new(pattern).unwrap();
+            let (Engine::BitParallel { prefix, .. } | Engine::Automaton { prefix, .. }) =
+                regex.engine()
+            else {
+                panic!("{:?}", pattern);
+            };
+            assert!(!prefix.is_empty());
+            let shortest: String = regex.shortest_example().unwrap();
+            assert!(regex.matches(&shortest));
+            let mut truncated: String = shortest.clone();
+            truncated.pop();
+            assert!(!regex.matches(&truncated));
+            assert!(!regex.matches(format!("_{}", shortest)));
+            assert_eq!(
+                regex
+                    .find_prefix(&format!("{}_", shortest))
+                    .unwrap()
+                    .as_str(),
+                shortest
+            );
+            assert_eq!(regex.find(&format!("_{}", shortest)).unwrap().start(), 1);
+        }
+        let regex = Regex::new(r"ab").unwrap().negate();
+        assert!(regex.matches("ba") && !regex.matches("ab"));
+    }
+
+    #[test]
+    fn reverse_suffix() {
+        for pattern in [r"(a|b)(a|b)*\.json", r"(山|田)*(太|一)郎"] {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(matches!(
+                regex.engine(),
+                Engine::BitParallel {
+                    suffix: Some(_),
+                    ..
+                } | Engine::Automaton {
+                    suffix: Some(_),
+                    ..
+                }
+            ));
+            let example: String = regex.shortest_example().unwrap();
+            for text in [
+                format!("_{}_", example),
+                format!("{}{}", example, example),
+                "_".repeat(100),
+                example[example.len() - 3..].repeat(5),
+            ] {
+                let expected = (0..=text.len())
+                    .filter(|at| text.is_char_boundary(*at))
+                    .find_map(|at| {
+                        let end: usize = regex.dfa().longest_match_at(&text, at)?;
+                        Some((at, end))
+                    });
+                let found = regex.find(&text).map(|m| (m.start(), m.end()));
+                assert_eq!(found, expected, "{:?} {:?}", pattern, text);
+                assert_eq!(regex.is_match(&text), expected.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn engine_selection() {
+        let regex = Regex::new(r"ab*c").unwrap();
+        assert!(matches!(regex.engine(), Engine::BitParallel { .. }));
+        let regex = Regex::new(&format!("({})*", "a|".repeat(65))).unwrap();
+        assert!(matches!(regex.engine(), Engine::Automaton { .. }));
+        assert!(regex.matches("aaa"));
+        assert!(regex.is_match("b"));
+        assert_eq!(regex.find("ba").unwrap().as_str(), "");
+    }
+
+    #[test]
+    fn negate() {
+        for pattern in [r"abc", r"ab*", &format!("({})*b", "a|".repeat(65))] {
+            let regex = Regex::new(pattern).unwrap();
+            let negated = regex.negate();
+            for text in ["", "a", "abc", "ab", "b", "aab", "ba", "あ"] {
+                assert_eq!(negated.matches(text), !regex.matches(text));
+            }
+            assert!(regex.negate().negate().matches("abc") == regex.matches("abc"));
+        }
+        let regex = Regex::new(r"ab").unwrap().negate();
+        assert_eq!(regex.find("ab").unwrap().as_str(), "a");
+        assert_eq!(regex.find("xab").unwrap().as_str(), "xab");
+    }
+
+    #[test]
+    fn compose() {
+        let year = Regex::new(r"(?<year>(0|1|2)(0|1|2))").unwrap();
+        let month = Regex::new(r"(?<month>0|1)").unwrap();
+        let date = year
+            .concat(&Regex::new(r"-").unwrap())
+            .and_then(|r| r.concat(&month))
+            .unwrap();
+        assert!(date.matches("12-1"));
+        assert!(!date.matches("12-"));
+        let caps = date.captures("x20-1").unwrap();
+        assert_eq!((&caps["year"], &caps["month"], &caps[2]), ("20", "1", "2"));
+        assert_eq!(date.capture_index("month"), Some(4));
+
+        let either = year.alternate(&month).unwrap();
+        assert!(either.matches("01") && either.matches("1") && !either.matches("0-1"));
+        assert_eq!(either.captures("1").unwrap().get(1), None);
+
+        let repeated = month.repeat(2, Some(3)).unwrap();
+        assert_eq!(repeated.as_str(), r"((?<month>0|1)){2,3}");
+        assert!(!repeated.matches("0") && repeated.matches("01") && repeated.matches("011"));
+        assert!(!repeated.matches("0110"));
+        assert_eq!(&repeated.captures("001").unwrap()["month"], "1");
+        assert!(month.repeat(0, None).unwrap().matches(""));
+        let twice = month.repeat(2, Some(2)).unwrap();
+        assert_eq!(
+            (twice.captures_len(), twice.static_captures_len()),
+            (2, Some(2))
+        );
+
+        // 文字列を組み立てる場合と違い, メタ文字のエスケープを気にしなくてよい
+        let literal = RegexBuilder::new(r"a\*")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let composed = literal.concat(&Regex::new(r"\?").unwrap()).unwrap();
+        assert!(composed.matches("A*?") && !composed.matches("aa?"));
+        assert_eq!(Regex::new(composed.as_str()).unwrap(), composed);
+
+        assert!(year.negate().concat(&month).is_none());
+        assert!(year.alternate(&month.negate()).is_none());
+    }
+
+    #[test]
+    fn repeat_many() {
+        // 繰り返しを展開しないので, 回数が多くてもスタックを使い果たさない
+        let many = Regex::new(r"a").unwrap().repeat(0, Some(10_000)).unwrap();
+        assert!(many.matches("a".repeat(10_000)) && !many.matches("a".repeat(10_001)));
+        assert_eq!(many.as_str(), r"(a){0,10000}");
+        assert!(many.concat(&Regex::new(r"b").unwrap()).is_none());
+
+        let pairs = Regex::new(r"a(c)")
+            .unwrap()
+            .repeat(2, Some(10_000))
+            .unwrap();
+        let text: String = "ac".repeat(10_000);
+        assert!(
+            pairs.matches(&text) && !pairs.matches("ac") && !pairs.matches(&(text.clone() + "ac"))
+        );
+        assert_eq!(
+            pairs.captures(&text).unwrap().get(1).unwrap().start(),
+            text.len() - 1
+        );
+        assert_eq!(pairs.find("xxacacx").map(|m| m.range()), Some(2..6));
+    }
+
+    #[test]
+    #[should_panic(expected = "min (2) must not exceed max (1)")]
+    fn repeat_invalid_range() {
+        let _ = Regex::new(r"a").unwrap().repeat(2, Some(1));
+    }
+
+    #[test]
+    fn minus() {
+        let regex = Regex::new(r"a*")
+            .unwrap()
+            .minus(&Regex::new(r"aa").unwrap());
+        for text in ["", "a", "aaa", "aaaa"] {
+            assert!(regex.matches(text));
+        }
+        assert!(!regex.matches("aa"));
+        assert!(!regex.matches("b"));
+
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let regex = regex.minus(&Regex::new(r"山田一郎").unwrap());
+        assert!(regex.matches("山田太郎"));
+        assert!(!regex.matches("山田一郎"));
+        assert!(regex.minus(&regex).negate().matches("山田太郎"));
+    }
+
+    #[test]
+    fn is_equivalent() {
+        let regex = Regex::new(r"(a|b)*").unwrap();
+        assert!(regex.is_equivalent(&Regex::new(r"(a*b*)*").unwrap()));
+        assert!(regex.is_equivalent(&Regex::new(r"(b|a|)*").unwrap()));
+        assert!(!regex.is_equivalent(&Regex::new(r"(a|b)(a|b)*").unwrap()));
+        let regex = Regex::new(r"p(erl|ython)").unwrap();
+        assert!(regex.is_equivalent(&Regex::new(r"perl|python").unwrap()));
+        assert!(!regex.is_equivalent(&Regex::new(r"perl").unwrap()));
+    }
+
+    #[test]
+    fn is_subset_of() {
+        let strict = Regex::new(r"2024(0|1)(0|1|2)").unwrap();
+        let legacy = Regex::new(r"(0|1|2|4)*").unwrap();
+        assert!(strict.is_subset_of(&legacy));
+        assert!(legacy.is_superset_of(&strict));
+        assert!(!legacy.is_subset_of(&strict));
+        assert!(!strict.is_superset_of(&legacy));
+        assert!(strict.is_subset_of(&strict));
+        assert!(
+            Regex::new(r"a")
+                .unwrap()
+                .negate()
+                .is_superset_of(&Regex::new(r"b*").unwrap())
+        );
+    }
+
+    #[test]
+    fn is_disjoint() {
+        let routes = [r"/users/(0|1)*", r"/user", r"/posts/(a|b)*"]
+            .map(|pattern| Regex::new(pattern).unwrap());
+        for (i, r1) in routes.iter().enumerate() {
+            for r2 in &routes[i + 1..] {
+                assert!(r1.is_disjoint(r2));
+            }
+        }
+        let regex = Regex::new(r"/users/(0|1|2)").unwrap();
+        assert!(!regex.is_disjoint(&routes[0]));
+        assert!(!regex.is_disjoint(&regex));
+    }
+
+    #[test]
+    fn early_exit() {
+        let regex = Regex::new(r"ab").unwrap().negate();
+        let Engine::Automaton {
+            always_accepting, ..
+        } = regex.engine()
+        else {
+            panic!();
+        };
+        assert!(!always_accepting.is_empty());
+        let long: String = "x".repeat(1000);
+        assert!(regex.matches(&long) && regex.matches(format!("ab{}", long)));
+        assert!(!regex.matches("ab") && regex.matches(""));
+
+        let regex = Regex::new(&format!("c{}", "(a|b)".repeat(40))).unwrap();
+        assert!(matches!(regex.engine(), Engine::Automaton { .. }));
+        for text in [
+            format!("xc{}", "ab".repeat(30)),
+            format!("c{}", "a".repeat(39)),
+        ] {
+            assert_eq!(regex.is_match(&text), regex.find(&text).is_some());
+        }
+    }
+
+    #[test]
+    fn fuzzy() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let fuzzy = regex.fuzzy(1);
+        assert_eq!(fuzzy.as_str(), "~1(山田(太|一)郎)");
+        for text in ["山田太郎", "山田太朗", "山太郎", "山田太郎様", "山田一郎"]
+        {
+            assert!(fuzzy.matches(text), "{:?}", text);
+        }
+        assert!(!fuzzy.matches("田太朗") && !fuzzy.matches(""));
+        assert_eq!(fuzzy.find("OCR: 山田大郎 様").unwrap().as_str(), "山田大郎");
+        assert!(regex.fuzzy(0).is_equivalent(&regex));
+        assert!(regex.fuzzy(2).is_superset_of(&fuzzy));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let same: [&str; 3] = [r"(a|b)*", r"(a*b*)*", r"(b|a|)(a|b)*"];
+        let fingerprints: Vec<u64> = same
+            .iter()
+            .map(|p| Regex::new(p).unwrap().fingerprint())
+            .collect();
+        assert!(fingerprints.iter().all(|f| *f == fingerprints[0]));
+        assert_ne!(
+            Regex::new(r"(a|b)(a|b)*").unwrap().fingerprint(),
+            fingerprints[0]
+        );
+        assert_ne!(
+            Regex::new(r"ab").unwrap().fingerprint(),
+            Regex::new(r"ba").unwrap().fingerprint()
+        );
+        // 補集合の補集合は元の言語
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        assert_eq!(regex.negate().negate().fingerprint(), regex.fingerprint());
+        let case = RegexBuilder::new(r"ab")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            case.fingerprint(),
+            Regex::new(r"(a|A)(b|B)").unwrap().fingerprint()
+        );
+    }
+
+    #[test]
+    fn start_chars() {
+        let regex = Regex::new(r"(山|田|a)*b|c").unwrap();
+        assert_eq!(regex.start_chars(), ['a'..='c', '山'..='山', '田'..='田']);
+        assert_eq!(Regex::new(r"abc").unwrap().start_chars(), ['a'..='a']);
+        let negated = Regex::new(r"abc").unwrap().negate();
+        assert_eq!(negated.start_chars(), ['\0'..=char::MAX]);
+        let minus = Regex::new(r"(a|b|c)*")
+            .unwrap()
+            .minus(&Regex::new(r"b(a|b|c)*").unwrap());
+        assert_eq!(minus.start_chars(), ['a'..='a', 'c'..='c']);
+    }
+
+    #[test]
+    fn matches_nothing() {
+        assert!(!Regex::new(r"a").unwrap().matches_nothing());
+        assert!(!Regex::new(r"").unwrap().matches_nothing());
+        assert!(!Regex::new(r"(a|b)*c").unwrap().matches_nothing());
+        let regex = Regex::new(r"(a|b)*").unwrap();
+        assert!(regex.negate().negate().minus(&regex).matches_nothing());
+        assert!(!regex.negate().matches_nothing());
+    }
+
+    #[test]
+    fn shortest_example() {
+        let regex = Regex::new(r"(p(erl|ython|hp)|ruby)").unwrap();
+        assert_eq!(regex.shortest_example(), Some("php".to_string()));
+        let regex = Regex::new(r"hello").unwrap();
+        assert_eq!(regex.shortest_example(), Some("hello".to_string()));
+        assert_eq!(regex.minus(&regex).shortest_example(), None);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn sample() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2357);
+        let regex = Regex::new(r"山田(太|一|次|三)郎").unwrap();
+        for _ in 0..20 {
+            assert!(regex.matches(regex.sample(&mut rng, 10).unwrap()));
+        }
+        let regex = Regex::new(r"a*").unwrap().negate();
+        for _ in 0..20 {
+            assert!(regex.matches(regex.sample(&mut rng, 10).unwrap()));
+        }
+    }
+
+    #[test]
+    fn is_finite() {
+        let regex = Regex::new(r"山田(太|一|次|三)郎").unwrap();
+        assert!(regex.is_finite());
+        assert_eq!(regex.max_len(), Some(4));
+        let regex = Regex::new(r"ｗｗ*|\(笑\)").unwrap();
+        assert!(!regex.is_finite());
+        assert_eq!(regex.max_len(), None);
+        let regex = Regex::new(r"a").unwrap();
+        assert!(regex.minus(&regex).is_finite());
+        assert_eq!(regex.minus(&regex).max_len(), None);
+    }
+
+    #[test]
+    fn to_pattern() {
+        for pattern in [
+            r"(p(erl|ython|hp)|ruby)",
+            r"山田(太|一|次|三)郎",
+            r"ｗｗ*|\(笑\)",
+            r"a\\c",
+            r"a(b|)",
+            r"(a*b*)*c",
+            r"",
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            let rebuilt = Regex::new(&regex.to_pattern().unwrap()).unwrap();
+            assert!(regex.is_equivalent(&rebuilt), "{}", pattern);
+        }
+        let regex = Regex::new(r"(a|b)*").unwrap();
+        assert_eq!(regex.to_pattern(), Some("(a|b)*".to_string()));
+        assert_eq!(regex.negate().to_pattern(), None);
+    }
+
     #[test]
     fn syntax_error() {
         for test in [r"ab(cd", r"e(*)f", r")h", r"i|*", r"*"] {
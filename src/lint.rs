@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::ops::Range;
+
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// 冗長な構文の種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    /// 繰り返しの繰り返し. `(a*)*` は `a*` と同じ
+    NestedStar,
+    /// 同じ選択肢が既に現れている. `a|b|a` は `a|b` と同じ
+    DuplicateBranch,
+    /// 中身の無い括弧 `()`
+    EmptyGroup,
+    /// 空文字列にしかマッチしない選択肢. `a|()*` は `a|` と同じ
+    EmptyBranch,
+}
+
+/// [`lint`] が報告する警告
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Warning {
+    pub(crate) kind: WarningKind,
+    pub(crate) span: Range<usize>,
+}
+
+impl Warning {
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// パターン中の該当箇所 (バイトオフセット)
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self.kind {
+            WarningKind::NestedStar => "nested star has no effect",
+            WarningKind::DuplicateBranch => "duplicate alternation branch",
+            WarningKind::EmptyGroup => "empty group",
+            WarningKind::EmptyBranch => "branch only matches the empty string",
+        };
+        write!(f, "{} at {}..{}", message, self.span.start, self.span.end)
+    }
+}
+
+/// パターン中の冗長な構文を報告する. 構文エラーの場合は `Err`
+pub fn lint(pattern: &str) -> Result<Vec<Warning>, Box<dyn Error>> {
+    let mut parser: Parser<'_> = Parser::new(Lexer::new(pattern));
+    parser.parse()?;
+    Ok(parser.take_warnings())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warnings(pattern: &str) -> Vec<(WarningKind, Range<usize>)> {
+        lint(pattern)
+            .unwrap()
+            .into_iter()
+            .map(|w| (w.kind(), w.span()))
+            .collect()
+    }
+
+    #[test]
+    fn clean() {
+        assert!(warnings(r"(p(erl|ython|hp)|ruby)").is_empty());
+        assert!(warnings(r"a(b|)").is_empty());
+        assert!(warnings(r"ｗｗ*|\(笑\)").is_empty());
+    }
+
+    #[test]
+    fn nested_star() {
+        assert_eq!(warnings(r"x(a*)*"), [(WarningKind::NestedStar, 1..6)]);
+    }
+
+    #[test]
+    fn duplicate_branch() {
+        assert_eq!(warnings(r"ab|c|ab"), [(WarningKind::DuplicateBranch, 5..7)]);
+        assert_eq!(
+            warnings(r"山|川|山"),
+            [(WarningKind::DuplicateBranch, 8..11)]
+        );
+    }
+
+    #[test]
+    fn empty_group() {
+        assert_eq!(warnings(r"a()b"), [(WarningKind::EmptyGroup, 1..3)]);
+    }
+
+    #[test]
+    fn empty_branch() {
+        assert_eq!(
+            warnings(r"a|()*"),
+            [
+                (WarningKind::EmptyGroup, 2..4),
+                (WarningKind::EmptyBranch, 2..5)
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert!(lint(r"a(").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+//! パターンを nom のパーサとして使うための関数
+
+use nom::error::{ErrorKind, ParseError};
+use nom::{Err, IResult};
+
+use crate::Regex;
+
+/// 入力の先頭からパターンにマッチする最長の部分を読み, (残り, 読んだ部分) を返すパーサ
+///
+/// 先頭からマッチしない場合は [`ErrorKind::RegexpMatch`] のエラーになる.
+pub fn longest<'a, E: ParseError<&'a str>>(
+    regex: &Regex,
+) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, E> + '_ {
+    move |input: &'a str| match regex.find_prefix(input) {
+        Some(found) => Ok((&input[found.end()..], found.as_str())),
+        None => Err(Err::Error(E::from_error_kind(
+            input,
+            ErrorKind::RegexpMatch,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::Parser;
+    use nom::bytes::complete::tag;
+    use nom::multi::separated_list1;
+
+    use super::*;
+
+    #[test]
+    fn combinator() {
+        let name = Regex::new(r"(山田|佐藤)(太|一)郎").unwrap();
+        let mut list = separated_list1(tag(", "), longest::<nom::error::Error<&str>>(&name));
+        assert_eq!(
+            list.parse("山田太郎, 佐藤一郎です"),
+            Ok(("です", vec!["山田太郎", "佐藤一郎"]))
+        );
+
+        let digits = Regex::new(r"(0|1|2)*").unwrap();
+        let digits = longest::<nom::error::Error<&str>>(&digits);
+        assert_eq!(digits("120x"), Ok(("x", "120")));
+        assert_eq!(digits("x"), Ok(("x", "")));
+        assert_eq!(
+            longest::<nom::error::Error<&str>>(&name)("私は山田太郎"),
+            Err(Err::Error(nom::error::Error::new(
+                "私は山田太郎",
+                ErrorKind::RegexpMatch
+            )))
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::Regex;
+
+impl Regex {
+    /// `text` の部分文字列にマッチするか
+    ///
+    /// UTF-8 (Windows では WTF-8) として正しくない部分は, どの文字にもマッチしない区切りとして扱う.
+    pub fn is_match_os_str(&self, text: &OsStr) -> bool {
+        self.find_os_str(text).is_some()
+    }
+
+    /// `text` 中で最も左にあるマッチの, [`OsStr::as_encoded_bytes`] でのバイト範囲
+    ///
+    /// マッチは UTF-8 として正しい部分の中にのみ見つかる.
+    pub fn find_os_str(&self, text: &OsStr) -> Option<Range<usize>> {
+        let mut offset: usize = 0;
+        for chunk in text.as_encoded_bytes().utf8_chunks() {
+            if let Some(found) = self.find(chunk.valid()) {
+                return Some(offset + found.start()..offset + found.end());
+            }
+            offset += chunk.valid().len() + chunk.invalid().len();
+        }
+        None
+    }
+
+    /// `text` 全体にマッチするか. UTF-8 として正しくない部分を含む場合は `false`
+    pub fn matches_os_str(&self, text: &OsStr) -> bool {
+        text.to_str().is_some_and(|text| self.matches(text))
+    }
+
+    /// パスの部分文字列にマッチするか ([`Regex::is_match_os_str`] を参照)
+    pub fn is_match_path(&self, path: &Path) -> bool {
+        self.is_match_os_str(path.as_os_str())
+    }
+
+    /// パス全体にマッチするか ([`Regex::matches_os_str`] を参照)
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.matches_os_str(path.as_os_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_str() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let text: &OsStr = OsStr::new("私は山田太郎です");
+        assert!(regex.is_match_os_str(text));
+        assert_eq!(regex.find_os_str(text), Some(6..18));
+        assert!(!regex.matches_os_str(text));
+        assert!(regex.matches_os_str(OsStr::new("山田一郎")));
+        assert!(regex.is_match_path(Path::new("/home/山田一郎.txt")));
+        assert!(!regex.matches_path(Path::new("/home/山田一郎.txt")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let regex = Regex::new(r"ab*").unwrap();
+        let text: &OsStr = OsStr::from_bytes(b"x\xffabb\xfeab");
+        assert_eq!(regex.find_os_str(text), Some(2..5));
+        assert!(!regex.matches_os_str(OsStr::from_bytes(b"a\xff")));
+        // 正しくない部分をまたいではマッチしない
+        let regex = Regex::new(r"xa").unwrap();
+        assert!(!regex.is_match_os_str(OsStr::from_bytes(b"x\xffa")));
+    }
+}
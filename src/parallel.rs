@@ -0,0 +1,140 @@
+use rayon::prelude::*;
+
+use crate::{Match, Regex};
+
+/// これより短い区間には分けない
+const MIN_CHUNK_LEN: usize = 1 << 20;
+
+/// 1つの区間で, 区間の先頭から左から順に探したマッチ
+struct Chain<'h> {
+    /// (探し始めた位置, 見つかったマッチ)
+    matches: Vec<(usize, Match<'h>)>,
+    /// 最後に探し始めた位置. そこから区間内に始まるマッチが無かったか, 区間の終わり以降. テキストの終わりを越えたら `None`
+    stop: Option<usize>,
+}
+
+impl Regex {
+    /// [`Regex::find_iter`] と同じマッチを, テキストを区間に分けて複数のスレッドで探す
+    ///
+    /// 各区間はマッチの最長の長さ ([`Regex::max_len`]) だけ後ろの区間と重ねて照合し, 区間の境界をまたぐマッチも見つける.
+    /// マッチの長さに上限が無い場合や, テキストが短い場合は1つのスレッドで探す.
+    pub fn par_find_iter<'h>(&self, text: &'h str) -> Vec<Match<'h>> {
+        let chunk_len: usize = (text.len() / rayon::current_num_threads()).max(MIN_CHUNK_LEN);
+        self.par_find_chunked(text, chunk_len)
+    }
+
+    fn par_find_chunked<'h>(&self, text: &'h str, chunk_len: usize) -> Vec<Match<'h>> {
+        let Some(max_len) = self.max_len().filter(|_| text.len() > chunk_len) else {
+            return self.find_iter(text).collect();
+        };
+        // (区間の先頭, 区間の終わり, 照合に使う範囲の終わり)
+        let mut chunks: Vec<(usize, usize, usize)> = Vec::new();
+        let mut begin: usize = 0;
+        while begin < text.len() {
+            let end: usize = text.ceil_char_boundary(begin + chunk_len);
+            let limit: usize = text[end..]
+                .char_indices()
+                .nth(max_len)
+                .map_or(text.len(), |(i, _)| end + i);
+            // テキストの終わりでの空文字列のマッチも最後の区間に含める
+            let bound: usize = if end == text.len() { end + 1 } else { end };
+            chunks.push((begin, bound, limit));
+            begin = end;
+        }
+        let chains: Vec<Chain<'h>> = chunks
+            .par_iter()
+            .map(|&(begin, end, limit)| self.chain(text, begin, end, limit))
+            .collect();
+
+        let mut found: Vec<Match<'h>> = Vec::new();
+        // 全体を左から順に探した場合に, 次に探し始める位置
+        let mut at: Option<usize> = Some(0);
+        for (mut chain, (_, end, limit)) in chains.into_iter().zip(chunks) {
+            let Some(p) = at else { break };
+            if p >= end {
+                continue;
+            }
+            // `p` から探した場合と同じマッチが続くところを探す. `p` 以前から探し始め, `p` 以降に始まるマッチが見つかっていればよい
+            let same = (0..=chain.matches.len()).find(|&k| match chain.matches.get(k) {
+                Some((from, found)) => *from <= p && p <= found.start,
+                None => chain.stop.is_some_and(|stop| stop <= p),
+            });
+            let chain: Chain<'h> = match same {
+                Some(k) => Chain {
+                    matches: chain.matches.split_off(k),
+                    stop: chain.stop,
+                },
+                // 前の区間のマッチが, この区間のマッチの途中まで続いている
+                None => self.chain(text, p, end, limit),
+            };
+            found.extend(chain.matches.into_iter().map(|(_, found)| found));
+            at = chain.stop.map(|stop| stop.max(end));
+        }
+        found
+    }
+
+    /// `from` から左から順にマッチを探し, `end` より前に始まるものを返す. 照合は `text[..limit]` の範囲で行う
+    fn chain<'h>(&self, text: &'h str, from: usize, end: usize, limit: usize) -> Chain<'h> {
+        let window: &str = &text[..limit];
+        let mut matches: Vec<(usize, Match<'h>)> = Vec::new();
+        let mut at: Option<usize> = Some(from);
+        while let Some(q) = at.filter(|q| *q < end) {
+            let found = self.find_by(window, q, q == from, |i| self.longest_match_at(window, i));
+            let Some((start, stop)) = found
+                .map(|found| (found.start(), found.end()))
+                .filter(|(start, _)| *start < end)
+            else {
+                break;
+            };
+            matches.push((
+                q,
+                Match {
+                    haystack: text,
+                    start,
+                    end: stop,
+                },
+            ));
+            at = if start < stop {
+                Some(stop)
+            } else {
+                // 空文字列にマッチした場合は1文字進める
+                text[stop..].chars().next().map(|c| stop + c.len_utf8())
+            };
+        }
+        Chain { matches, stop: at }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn par_find_iter() {
+        let cases: [(&str, String); 6] = [
+            (r"abc|b", "xabcbab".repeat(20)),
+            (r"aaa", "a".repeat(50)),
+            (r"a|", "baab".repeat(10)),
+            (
+                r"(山田|佐藤)(太|一)郎",
+                "山田太郎と佐藤一郎と山田次郎".repeat(10),
+            ),
+            (r"(a|b)(a|b)(a|b)(a|b)c", "ababcbbbbc".repeat(10)),
+            (r"ab*", "abbbbbbbbba".repeat(10)),
+        ];
+        for (pattern, text) in cases {
+            let regex = Regex::new(pattern).unwrap();
+            let expected: Vec<Match<'_>> = regex.find_iter(&text).collect();
+            for chunk_len in [1, 2, 3, 7, 16] {
+                assert_eq!(
+                    regex.par_find_chunked(&text, chunk_len),
+                    expected,
+                    "{:?} {}",
+                    pattern,
+                    chunk_len
+                );
+            }
+            assert_eq!(regex.par_find_iter(&text), expected);
+        }
+    }
+}
@@ -1,69 +1,512 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::error::Error;
+use std::ops::Range;
 
 use crate::automaton::{Context, NFA, NFAState};
 use crate::lexer::{Lexer, Token};
+use crate::lint::{Warning, WarningKind};
 
-/// 構文木の頂点
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub(crate) enum Node {
+/// 構文木の頂点の添字. 同じ [`Node`] (または [`Arena`]) の中でのみ意味を持つ
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct NodeId(u32);
+
+/// 構文木の頂点の種類. 子 `C` は, 構文木の中では [`NodeId`], 読み出すときは [`NodeRef`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind<C> {
     Character(char),
     Empty,
-    Star(Box<Node>),
-    Union(Box<Node>, Box<Node>),
-    Concat(Box<Node>, Box<Node>),
+    Star(C),
+    Union(C, C),
+    Concat(C, C),
+    /// 番号付きの括弧 (キャプチャグループ). [`Parser::with_captures`] を指定したときのみ作られる
+    Group(usize, C),
 }
 
-impl Node {
-    pub(crate) fn assemble(&self, context: &mut Context) -> NFA {
+impl<C> Kind<C> {
+    fn map<D>(self, mut f: impl FnMut(C) -> D) -> Kind<D> {
         match self {
-            Node::Character(chara) => {
+            Kind::Character(chara) => Kind::Character(chara),
+            Kind::Empty => Kind::Empty,
+            Kind::Star(node) => Kind::Star(f(node)),
+            Kind::Union(n1, n2) => Kind::Union(f(n1), f(n2)),
+            Kind::Concat(n1, n2) => Kind::Concat(f(n1), f(n2)),
+            Kind::Group(index, node) => Kind::Group(index, f(node)),
+        }
+    }
+}
+
+/// 構文木を組み立てる間, 頂点を置いておく. 子は親より前に置く
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Arena {
+    nodes: Vec<Kind<NodeId>>,
+}
+
+impl Arena {
+    pub(crate) fn push(&mut self, kind: Kind<NodeId>) -> NodeId {
+        self.nodes.push(kind);
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+
+    /// `node` の頂点をすべて写し, その根の添字を返す
+    pub(crate) fn append(&mut self, node: &Node) -> NodeId {
+        let offset: u32 = self.nodes.len() as u32;
+        self.nodes.extend(
+            node.nodes
+                .iter()
+                .map(|kind| kind.map(|id| NodeId(id.0 + offset))),
+        );
+        NodeId(node.root.0 + offset)
+    }
+
+    pub(crate) fn get(&self, id: NodeId) -> NodeRef<'_> {
+        NodeRef {
+            nodes: &self.nodes,
+            id,
+        }
+    }
+
+    pub(crate) fn finish(self, root: NodeId) -> Node {
+        Node {
+            nodes: self.nodes,
+            root,
+        }
+    }
+}
+
+/// 構文木. 頂点を1つの `Vec` に置き, 子を添字で指す
+///
+/// 同じ部分木を複数の親から指してもよい ([`Node::repeat`] を参照).
+#[derive(Clone)]
+pub(crate) struct Node {
+    nodes: Vec<Kind<NodeId>>,
+    root: NodeId,
+}
+
+/// 構文木の頂点への参照. 比較やハッシュは添字ではなく部分木の構造で行う
+#[derive(Clone, Copy)]
+pub(crate) struct NodeRef<'a> {
+    nodes: &'a [Kind<NodeId>],
+    id: NodeId,
+}
+
+impl<'a> NodeRef<'a> {
+    /// 頂点の種類. 子も参照として返す
+    pub(crate) fn kind(self) -> Kind<NodeRef<'a>> {
+        self.nodes[self.id.0 as usize].map(|id| NodeRef {
+            nodes: self.nodes,
+            id,
+        })
+    }
+
+    /// 演算子を含まない (文字の連接のみからなる) 場合, その文字列を返す
+    pub(crate) fn literal(self) -> Option<String> {
+        match self.kind() {
+            Kind::Character(chara) => Some(chara.to_string()),
+            Kind::Empty => Some(String::new()),
+            Kind::Concat(n1, n2) => Some(n1.literal()? + &n2.literal()?),
+            Kind::Group(_, node) => node.literal(),
+            Kind::Star(_) | Kind::Union(_, _) => None,
+        }
+    }
+
+    /// どのマッチもこの文字列から始まる. 例えば `ab(c|cd)e*` なら `abc`
+    pub(crate) fn literal_prefix(self) -> String {
+        match self.kind() {
+            Kind::Character(chara) => chara.to_string(),
+            Kind::Empty | Kind::Star(_) => String::new(),
+            Kind::Union(n1, n2) => {
+                let (p1, p2) = (n1.literal_prefix(), n2.literal_prefix());
+                let len: usize = p1
+                    .char_indices()
+                    .zip(p2.chars())
+                    .find(|((_, c1), c2)| c1 != c2)
+                    .map_or(p1.len().min(p2.len()), |((i, _), _)| i);
+                p1[..len].to_string()
+            }
+            Kind::Concat(n1, n2) => match n1.literal() {
+                Some(literal) => literal + &n2.literal_prefix(),
+                None => n1.literal_prefix(),
+            },
+            Kind::Group(_, node) => node.literal_prefix(),
+        }
+    }
+
+    /// 空文字列のみにマッチするか
+    pub(crate) fn matches_only_empty(self) -> bool {
+        match self.kind() {
+            Kind::Character(_) => false,
+            Kind::Empty => true,
+            Kind::Star(node) | Kind::Group(_, node) => node.matches_only_empty(),
+            Kind::Union(n1, n2) | Kind::Concat(n1, n2) => {
+                n1.matches_only_empty() && n2.matches_only_empty()
+            }
+        }
+    }
+
+    /// 外側のキャプチャグループを取り除いた頂点
+    pub(crate) fn skip_groups(self) -> NodeRef<'a> {
+        match self.kind() {
+            Kind::Group(_, node) => node.skip_groups(),
+            _ => self,
+        }
+    }
+
+    /// キャプチャグループを取り除くと同じ構文木になるか
+    pub(crate) fn eq_without_groups(self, other: NodeRef<'_>) -> bool {
+        match (self.skip_groups().kind(), other.skip_groups().kind()) {
+            (Kind::Star(n1), Kind::Star(n2)) => n1.eq_without_groups(n2),
+            (Kind::Union(a1, b1), Kind::Union(a2, b2))
+            | (Kind::Concat(a1, b1), Kind::Concat(a2, b2)) => {
+                a1.eq_without_groups(a2) && b1.eq_without_groups(b2)
+            }
+            (Kind::Character(c1), Kind::Character(c2)) => c1 == c2,
+            (Kind::Empty, Kind::Empty) => true,
+            _ => false,
+        }
+    }
+
+    /// キャプチャグループの数 (最大の番号). [`Node::repeat`] で複製したグループは1つと数える
+    pub(crate) fn groups(self) -> usize {
+        match self.kind() {
+            Kind::Character(_) | Kind::Empty => 0,
+            Kind::Star(node) => node.groups(),
+            Kind::Union(n1, n2) | Kind::Concat(n1, n2) => n1.groups().max(n2.groups()),
+            Kind::Group(index, node) => index.max(node.groups()),
+        }
+    }
+
+    /// どのマッチでも同じグループが照合に関与する場合, その数.
+    /// グループを含む繰り返しや選択肢があると, マッチによって関与するグループが変わるので `None`
+    pub(crate) fn static_groups(self) -> Option<usize> {
+        self.has_static_groups().then(|| self.groups())
+    }
+
+    /// すべてのグループが, どのマッチでも照合に関与するか
+    fn has_static_groups(self) -> bool {
+        match self.kind() {
+            Kind::Character(_) | Kind::Empty => true,
+            Kind::Star(_) | Kind::Union(_, _) => self.groups() == 0,
+            Kind::Concat(n1, n2) => n1.has_static_groups() && n2.has_static_groups(),
+            Kind::Group(_, node) => node.has_static_groups(),
+        }
+    }
+
+    /// 右に連なる和集合の各項. 和集合でなければ自身のみ
+    fn branches(self) -> Vec<NodeRef<'a>> {
+        match self.kind() {
+            Kind::Union(n1, n2) => [n1].into_iter().chain(n2.branches()).collect(),
+            _ => vec![self],
+        }
+    }
+
+    /// 使われているグループの番号
+    fn group_indices(self, indices: &mut HashSet<usize>) {
+        match self.kind() {
+            Kind::Character(_) | Kind::Empty => {}
+            Kind::Star(node) => node.group_indices(indices),
+            Kind::Union(n1, n2) | Kind::Concat(n1, n2) => {
+                n1.group_indices(indices);
+                n2.group_indices(indices);
+            }
+            Kind::Group(index, node) => {
+                indices.insert(index);
+                node.group_indices(indices);
+            }
+        }
+    }
+
+    /// 複数の選択肢に同じ番号のグループがあるか (`(?|...)` から作られたか)
+    fn resets_groups(self) -> bool {
+        let mut seen: HashSet<usize> = HashSet::new();
+        self.branches().into_iter().any(|branch| {
+            let mut indices: HashSet<usize> = HashSet::new();
+            branch.group_indices(&mut indices);
+            !seen.is_disjoint(&indices) || {
+                seen.extend(indices);
+                false
+            }
+        })
+    }
+
+    pub(crate) fn assemble(self, context: &mut Context) -> NFA {
+        match self.kind() {
+            Kind::Character(chara) => {
                 let start: NFAState = context.new_state();
                 let accept: NFAState = context.new_state();
-                NFA::new(start, [accept].into()).add_transition(start, *chara, accept)
+                NFA::new(start, HashSet::from_iter([accept])).add_transition(start, chara, accept)
             }
-            Node::Empty => {
+            Kind::Empty => {
                 let start: NFAState = context.new_state();
                 let accept: NFAState = context.new_state();
-                NFA::new(start, [accept].into()).add_empty_transition(start, accept)
+                NFA::new(start, HashSet::from_iter([accept])).add_empty_transition(start, accept)
             }
-            Node::Star(node) => {
-                let frag: NFA = node.assemble(context);
+            Kind::Star(node) => {
+                let mut nfa: NFA = node.assemble(context);
                 let start: NFAState = context.new_state();
-                let accepts: HashSet<NFAState> =
-                    frag.accepts.union(&[start].into()).cloned().collect();
-                let mut nfa = NFA::new(start, accepts)
-                    .merge_transition(&frag)
-                    .add_empty_transition(start, frag.start);
-                for accept in &frag.accepts {
-                    nfa = nfa.add_empty_transition(*accept, frag.start);
+                let inner: NFAState = std::mem::replace(&mut nfa.start, start);
+                let accepts: Vec<NFAState> = nfa.accepts.iter().cloned().collect();
+                for accept in accepts {
+                    nfa = nfa.add_empty_transition(accept, inner);
                 }
-                nfa
+                nfa.accepts.insert(start);
+                nfa.add_empty_transition(start, inner)
             }
-            Node::Union(n1, n2) => {
-                let frag1: NFA = n1.assemble(context);
-                let frag2: NFA = n2.assemble(context);
+            Kind::Union(n1, n2) => {
+                let mut nfa: NFA = n1.assemble(context);
+                let frag: NFA = n2.assemble(context);
                 let start: NFAState = context.new_state();
-                let accepts: HashSet<NFAState> =
-                    frag1.accepts.union(&frag2.accepts).cloned().collect();
-                NFA::new(start, accepts)
-                    .merge_transition(&frag1)
-                    .merge_transition(&frag2)
-                    .add_empty_transition(start, frag1.start)
-                    .add_empty_transition(start, frag2.start)
+                let (start1, start2) = (nfa.start, frag.start);
+                nfa.start = start;
+                nfa.accepts.extend(&frag.accepts);
+                nfa.merge_transition(frag)
+                    .add_empty_transition(start, start1)
+                    .add_empty_transition(start, start2)
+            }
+            Kind::Concat(n1, n2) => {
+                let frag: NFA = n1.assemble(context);
+                let mut nfa: NFA = n2.assemble(context);
+                let start2: NFAState = std::mem::replace(&mut nfa.start, frag.start);
+                let accepts: Vec<NFAState> = frag.accepts.iter().cloned().collect();
+                nfa = nfa.merge_transition(frag);
+                for accept in accepts {
+                    nfa = nfa.add_empty_transition(accept, start2);
+                }
+                nfa
+            }
+            Kind::Group(_, node) => node.assemble(context),
+        }
+    }
+
+    /// 演算子の結合の強さ `prec` の位置に置かれたものとしてパターン文字列に書き出す
+    ///
+    /// prec: 0 = 和集合の項, 1 = 連接の項, 2 = 繰り返しの対象
+    fn write_pattern(self, f: &mut std::fmt::Formatter<'_>, prec: u8) -> std::fmt::Result {
+        match self.kind() {
+            Kind::Character(chara @ ('\\' | '|' | '*' | '(' | ')' | '?')) => {
+                write!(f, "\\{}", chara)
             }
-            Node::Concat(n1, n2) => {
-                let frag1: NFA = n1.assemble(context);
-                let frag2: NFA = n2.assemble(context);
-                let mut fragment = NFA::new(frag1.start, frag2.accepts.clone())
-                    .merge_transition(&frag1)
-                    .merge_transition(&frag2);
-                for accept in &frag1.accepts {
-                    fragment = fragment.add_empty_transition(*accept, frag2.start)
+            Kind::Character(chara) => write!(f, "{}", chara),
+            Kind::Empty if prec == 0 => Ok(()),
+            Kind::Empty => write!(f, "()"),
+            Kind::Star(node) if prec < 2 => {
+                node.write_pattern(f, 2)?;
+                write!(f, "*")
+            }
+            Kind::Concat(n1, n2) if prec < 2 => {
+                n1.write_pattern(f, 1)?;
+                n2.write_pattern(f, 1)
+            }
+            // 選択肢の間でグループの番号が重なるなら, ブランチリセットとして書き出す
+            Kind::Union(_, _) if self.resets_groups() => {
+                write!(f, "(?|")?;
+                for (i, branch) in self.branches().into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    branch.write_pattern(f, 0)?;
                 }
-                fragment
+                write!(f, ")")
+            }
+            Kind::Union(n1, n2) if prec < 1 => {
+                n1.write_pattern(f, 0)?;
+                write!(f, "|")?;
+                n2.write_pattern(f, 0)
+            }
+            Kind::Group(_, node) => {
+                write!(f, "(")?;
+                node.write_pattern(f, 0)?;
+                write!(f, ")")
             }
+            _ => {
+                write!(f, "(")?;
+                self.write_pattern(f, 0)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl PartialEq for NodeRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        (std::ptr::eq(self.nodes, other.nodes) && self.id == other.id)
+            || self.kind() == other.kind()
+    }
+}
+
+impl Eq for NodeRef<'_> {}
+
+impl std::hash::Hash for NodeRef<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.kind().hash(state);
+    }
+}
+
+/// 添字ではなく部分木を書き出す
+impl std::fmt::Debug for NodeRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+impl Node {
+    pub(crate) fn root(&self) -> NodeRef<'_> {
+        NodeRef {
+            nodes: &self.nodes,
+            id: self.root,
+        }
+    }
+
+    fn leaf(kind: Kind<NodeId>) -> Node {
+        let mut arena: Arena = Arena::default();
+        let root: NodeId = arena.push(kind);
+        arena.finish(root)
+    }
+
+    pub(crate) fn character(chara: char) -> Node {
+        Node::leaf(Kind::Character(chara))
+    }
+
+    pub(crate) fn empty() -> Node {
+        Node::leaf(Kind::Empty)
+    }
+
+    pub(crate) fn star(self) -> Node {
+        let mut arena: Arena = Arena { nodes: self.nodes };
+        let root: NodeId = arena.push(Kind::Star(self.root));
+        arena.finish(root)
+    }
+
+    pub(crate) fn union(self, other: Node) -> Node {
+        self.binary(&other, Kind::Union)
+    }
+
+    pub(crate) fn concat(self, other: Node) -> Node {
+        self.binary(&other, Kind::Concat)
+    }
+
+    pub(crate) fn group(self, index: usize) -> Node {
+        let mut arena: Arena = Arena { nodes: self.nodes };
+        let root: NodeId = arena.push(Kind::Group(index, self.root));
+        arena.finish(root)
+    }
+
+    fn binary(self, other: &Node, kind: fn(NodeId, NodeId) -> Kind<NodeId>) -> Node {
+        let mut arena: Arena = Arena { nodes: self.nodes };
+        let other: NodeId = arena.append(other);
+        let root: NodeId = arena.push(kind(self.root, other));
+        arena.finish(root)
+    }
+
+    /// 子から順に頂点を作り直した構文木
+    ///
+    /// `f` は子を作り直した後の頂点の種類を受け取り, 新しい構文木の頂点を `arena` に置いてその添字を返す.
+    /// 頂点を1つの `Vec` に子から順に置いているので, 再帰せずに先頭から1度なめるだけで済む.
+    pub(crate) fn try_rebuild<E>(
+        &self,
+        mut f: impl FnMut(&mut Arena, Kind<NodeId>) -> Result<NodeId, E>,
+    ) -> Result<Node, E> {
+        let mut arena: Arena = Arena::default();
+        let mut ids: Vec<NodeId> = Vec::with_capacity(self.nodes.len());
+        for kind in &self.nodes {
+            let id: NodeId = f(&mut arena, kind.map(|child| ids[child.0 as usize]))?;
+            ids.push(id);
         }
+        let root: NodeId = ids[self.root.0 as usize];
+        Ok(arena.finish(root))
+    }
+
+    fn rebuild(&self, mut f: impl FnMut(&mut Arena, Kind<NodeId>) -> NodeId) -> Node {
+        match self.try_rebuild(|arena, kind| Ok::<_, Infallible>(f(arena, kind))) {
+            Ok(node) => node,
+        }
+    }
+
+    /// どのマッチもこの文字列で終わる. 例えば `(a|b)*\.json` なら `.json`
+    pub(crate) fn literal_suffix(&self) -> String {
+        self.reverse()
+            .root()
+            .literal_prefix()
+            .chars()
+            .rev()
+            .collect()
+    }
+
+    /// 逆順の文字列にマッチする構文木
+    pub(crate) fn reverse(&self) -> Node {
+        self.rebuild(|arena, kind| match kind {
+            Kind::Concat(n1, n2) => arena.push(Kind::Concat(n2, n1)),
+            kind => arena.push(kind),
+        })
+    }
+
+    /// 大文字と小文字を区別しない構文木. 各文字を, その大文字・小文字との和集合に置き換える
+    pub(crate) fn case_insensitive(&self) -> Node {
+        self.rebuild(|arena, kind| {
+            let Kind::Character(chara) = kind else {
+                return arena.push(kind);
+            };
+            let mut variants: Vec<char> = vec![chara];
+            let lower: Vec<char> = chara.to_lowercase().collect();
+            let upper: Vec<char> = chara.to_uppercase().collect();
+            for mapped in [lower, upper] {
+                // 複数の文字に対応する場合 ('ß' -> "SS" など) は扱わない
+                if let [mapped] = mapped[..] {
+                    variants.push(mapped);
+                }
+            }
+            variants.sort();
+            variants.dedup();
+            let mut node: NodeId = arena.push(Kind::Character(variants[0]));
+            for variant in &variants[1..] {
+                let variant: NodeId = arena.push(Kind::Character(*variant));
+                node = arena.push(Kind::Union(node, variant));
+            }
+            node
+        })
+    }
+
+    /// キャプチャグループを取り除いた構文木
+    pub(crate) fn without_groups(&self) -> Node {
+        self.rebuild(|arena, kind| match kind {
+            Kind::Group(_, node) => node,
+            kind => arena.push(kind),
+        })
+    }
+
+    /// 各グループの番号を `offset` だけずらした構文木
+    pub(crate) fn shift_groups(&self, offset: usize) -> Node {
+        self.rebuild(|arena, kind| match kind {
+            Kind::Group(index, node) => arena.push(Kind::Group(index + offset, node)),
+            kind => arena.push(kind),
+        })
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.root() == other.root()
+    }
+}
+
+impl Eq for Node {}
+
+impl std::hash::Hash for Node {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.root().hash(state);
+    }
+}
+
+impl std::fmt::Debug for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.root().fmt(f)
+    }
+}
+
+/// パターン文字列として書き出す
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.root().write_pattern(f, 0)
     }
 }
 
@@ -71,98 +514,256 @@ impl Node {
 pub(crate) struct Parser<'a> {
     lexer: Lexer<'a>,
     look: Token,
+    /// `look` の開始位置 (バイトオフセット)
+    pos: usize,
+    /// 構文解析中に見つかった冗長な構文
+    warnings: Vec<Warning>,
+    /// 括弧をキャプチャグループ ([`Kind::Group`]) にするか
+    captures: bool,
+    /// これまでに現れた括弧の数
+    groups: usize,
+    /// グループ `i` の名前が `i - 1` にある
+    names: Vec<Option<String>>,
+    /// `\g<name>` で参照できる部分パターン
+    definitions: Option<&'a HashMap<String, Node>>,
+    /// 作った頂点. 各文法規則はここに置いた頂点の添字を返す
+    arena: Arena,
 }
 
-impl Parser<'_> {
-    pub fn new(mut lexer: Lexer) -> Parser {
+impl<'a> Parser<'a> {
+    pub fn new(mut lexer: Lexer<'a>) -> Parser<'a> {
+        let pos: usize = lexer.offset();
         let look: Token = lexer.scan();
-        Parser { lexer, look }
+        Parser {
+            lexer,
+            look,
+            pos,
+            warnings: Vec::new(),
+            captures: false,
+            groups: 0,
+            names: Vec::new(),
+            definitions: None,
+            arena: Arena::default(),
+        }
+    }
+
+    /// `\g<name>` を `definitions` の `name` の構文木に置き換える. 部分パターン中の括弧はキャプチャグループにならない
+    pub fn with_definitions(mut self, definitions: &'a HashMap<String, Node>) -> Self {
+        self.definitions = Some(definitions);
+        self
+    }
+
+    /// 括弧を, 左括弧の出現順に 1 から番号付けしたキャプチャグループにする
+    pub fn with_captures(mut self) -> Self {
+        self.captures = true;
+        self
     }
 
     pub fn parse(&mut self) -> ParseResult<Node> {
         self.expression()
     }
 
+    /// 各グループの名前 (`(?<name>...)`) を取り出す. グループ `i` の名前が `i - 1` にある
+    pub fn take_group_names(&mut self) -> Vec<Option<String>> {
+        std::mem::take(&mut self.names)
+    }
+
+    /// 構文解析中に見つかった警告を取り出す
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn warn(&mut self, kind: WarningKind, span: Range<usize>) {
+        self.warnings.push(Warning { kind, span });
+    }
+
     fn match_next(&mut self, token: Token) -> ParseResult<()> {
         match &self.look {
             look if *look == token => {
+                self.pos = self.lexer.offset();
                 self.look = self.lexer.scan();
                 Ok(())
             }
-            other => Err(ParseError::new(&[token], *other)),
+            _ => Err(self.unexpected(&[token])),
+        }
+    }
+
+    /// `look` が `expected` のいずれでもない場合のエラー. 字句として不正なら, その理由をエラーにする
+    fn unexpected(&mut self, expected: &[Token]) -> ParseError {
+        match self.lexer.take_error() {
+            Some(message) if self.look == Token::Invalid => ParseError::invalid(message),
+            _ => ParseError::new(expected, self.look),
         }
     }
 
+    fn is_empty(&self, node: NodeId) -> bool {
+        self.arena.get(node).kind() == Kind::Empty
+    }
+
     // --- 文法規則 ---
 
     /// <expression> ::= <sub_expression> Token::End
     fn expression(&mut self) -> ParseResult<Node> {
-        let expression: Node = self.sub_expression()?;
+        let expression: NodeId = self.sub_expression(false)?;
         self.match_next(Token::End)?;
-        Ok(expression)
+        Ok(std::mem::take(&mut self.arena).finish(expression))
     }
 
     /// <sub_expression> ::= <sequence> '|' <sub_expression> | <sequence>
-    fn sub_expression(&mut self) -> ParseResult<Node> {
-        let sequence: Node = self.sequence()?;
-        Ok(match &self.look {
-            Token::UnionOp => {
-                self.match_next(Token::UnionOp)?;
-                Node::Union(Box::new(sequence), Box::new(self.sub_expression()?))
+    ///
+    /// `branch_reset` なら, 各選択肢の括弧を同じ番号から数え直す
+    fn sub_expression(&mut self, branch_reset: bool) -> ParseResult<NodeId> {
+        let mut branches: Vec<(NodeId, Range<usize>)> = Vec::new();
+        let first_group: usize = self.groups;
+        let mut last_group: usize = self.groups;
+        loop {
+            if branch_reset {
+                self.groups = first_group;
             }
-            _ => sequence,
-        })
+            let start: usize = self.pos;
+            let sequence: NodeId = self.sequence()?;
+            branches.push((sequence, start..self.pos));
+            last_group = last_group.max(self.groups);
+            match &self.look {
+                Token::UnionOp => self.match_next(Token::UnionOp)?,
+                _ => break,
+            }
+        }
+        self.groups = last_group;
+        if branches.len() > 1 {
+            let mut warnings: Vec<(WarningKind, Range<usize>)> = Vec::new();
+            for (i, (branch, span)) in branches.iter().enumerate() {
+                let branch: NodeRef<'_> = self.arena.get(*branch);
+                // キャプチャグループの番号は異なっても同じ選択肢とみなす
+                let duplicate: bool = branches[..i]
+                    .iter()
+                    .any(|(other, _)| self.arena.get(*other).eq_without_groups(branch));
+                if duplicate {
+                    warnings.push((WarningKind::DuplicateBranch, span.clone()));
+                } else if branch.skip_groups().kind() != Kind::Empty && branch.matches_only_empty()
+                {
+                    warnings.push((WarningKind::EmptyBranch, span.clone()));
+                }
+            }
+            for (kind, span) in warnings {
+                self.warn(kind, span);
+            }
+        }
+        let (last, _) = branches.pop().unwrap();
+        Ok(branches.into_iter().rfold(last, |rest, (branch, _)| {
+            self.arena.push(Kind::Union(branch, rest))
+        }))
     }
 
     /// <sequence> ::= <sub_sequence> | ''
-    fn sequence(&mut self) -> ParseResult<Node> {
+    fn sequence(&mut self) -> ParseResult<NodeId> {
         match &self.look {
-            Token::LeftParen | Token::Character(_) => self.sub_sequence(),
-            _ => Ok(Node::Empty),
+            Token::LeftParen | Token::Character(_) | Token::Reference => self.sub_sequence(),
+            _ => Ok(self.arena.push(Kind::Empty)),
         }
     }
 
     /// <sub_sequence> ::= <star sub_sequence> | <star>
-    fn sub_sequence(&mut self) -> ParseResult<Node> {
-        let star: Node = self.star()?;
+    fn sub_sequence(&mut self) -> ParseResult<NodeId> {
+        let star: NodeId = self.star()?;
         Ok(match &self.look {
-            Token::LeftParen | Token::Character(_) => {
-                Node::Concat(Box::new(star), Box::new(self.sub_sequence()?))
+            Token::LeftParen | Token::Character(_) | Token::Reference => {
+                let rest: NodeId = self.sub_sequence()?;
+                self.arena.push(Kind::Concat(star, rest))
             }
             _ => star,
         })
     }
 
     /// <star> ::= <factor> '*' | <factor>
-    fn star(&mut self) -> ParseResult<Node> {
-        let factor: Node = self.factor()?;
+    fn star(&mut self) -> ParseResult<NodeId> {
+        let start: usize = self.pos;
+        let factor: NodeId = self.factor()?;
         Ok(match &self.look {
             Token::StarOp => {
                 self.match_next(Token::StarOp)?;
-                Node::Star(Box::new(factor))
+                if let Kind::Star(_) = self.arena.get(factor).skip_groups().kind() {
+                    self.warn(WarningKind::NestedStar, start..self.pos);
+                }
+                self.arena.push(Kind::Star(factor))
             }
             _ => factor,
         })
     }
 
-    /// <factor> ::= '(' <sub_expression> ')' | Token::Character
-    fn factor(&mut self) -> ParseResult<Node> {
+    /// <factor> ::= '(' <sub_expression> ')' | Token::Character | Token::Reference
+    ///
+    /// 左括弧の直後の `?<name>` はグループ名として, `?|` はブランチリセットとして字句解析器が読み取る.
+    /// ブランチリセットの括弧自体はグループにならない
+    fn factor(&mut self) -> ParseResult<NodeId> {
         match &self.look {
+            Token::LeftParen if self.lexer.take_branch_reset() => {
+                let start: usize = self.pos;
+                self.match_next(Token::LeftParen)?;
+                let node: NodeId = self.sub_expression(true)?;
+                self.match_next(Token::RightParen)?;
+                if self.is_empty(node) {
+                    self.warn(WarningKind::EmptyGroup, start..self.pos);
+                }
+                Ok(node)
+            }
             Token::LeftParen => {
+                let start: usize = self.pos;
+                self.groups += 1;
+                let index: usize = self.groups;
+                let name: Option<String> = self.lexer.take_name().map(str::to_string);
+                if let Some(name) = &name {
+                    // ブランチリセットで番号が重なるグループには, 同じ名前を付けられる
+                    let duplicate: bool = self.names.iter().enumerate().any(|(i, other)| {
+                        i != index - 1 && other.as_deref() == Some(name.as_str())
+                    });
+                    if duplicate {
+                        return Err(ParseError::invalid(format!(
+                            "Duplicate group name {:?}",
+                            name
+                        )));
+                    }
+                }
+                // ブランチリセットで番号が重なった場合は, 名前の付いていない選択肢のグループも同じ名前になる
+                match self.names.get_mut(index - 1) {
+                    Some(Some(named)) if name.as_ref().is_some_and(|name| name != named) => {
+                        return Err(ParseError::invalid(format!(
+                            "Different names {:?} and {:?} for group {}",
+                            named,
+                            name.unwrap_or_default(),
+                            index
+                        )));
+                    }
+                    Some(named) => *named = named.take().or(name),
+                    None => self.names.push(name),
+                }
                 self.match_next(Token::LeftParen)?;
-                let result: ParseResult<Node> = self.sub_expression();
+                let node: NodeId = self.sub_expression(false)?;
                 self.match_next(Token::RightParen)?;
-                result
+                if self.is_empty(node) {
+                    self.warn(WarningKind::EmptyGroup, start..self.pos);
+                }
+                if self.captures {
+                    Ok(self.arena.push(Kind::Group(index, node)))
+                } else {
+                    Ok(node)
+                }
             }
             Token::Character(c) => {
-                let node: Node = Node::Character(*c);
-                self.match_next(Token::Character(*c))?;
-                Ok(node)
+                let c: char = *c;
+                self.match_next(Token::Character(c))?;
+                Ok(self.arena.push(Kind::Character(c)))
+            }
+            Token::Reference => {
+                let name: &str = self.lexer.take_name().unwrap_or_default();
+                let definition: &Node = self
+                    .definitions
+                    .and_then(|definitions| definitions.get(name))
+                    .ok_or_else(|| ParseError::undefined(name))?;
+                self.match_next(Token::Reference)?;
+                Ok(self.arena.append(definition))
             }
-            other => Err(ParseError::new(
-                &[Token::LeftParen, Token::Character('_')],
-                *other,
-            )),
+            _ => Err(self.unexpected(&[Token::LeftParen, Token::Character('_')])),
         }
     }
 }
@@ -175,6 +776,8 @@ impl std::fmt::Display for Token {
             Token::StarOp => "*",
             Token::LeftParen => "(",
             Token::RightParen => ")",
+            Token::Reference => "\\g<name>",
+            Token::Invalid => "invalid token",
             Token::End => "EOF",
         };
         write!(f, "{}", str)
@@ -187,16 +790,47 @@ type ParseResult<T> = Result<T, ParseError>;
 pub struct ParseError {
     expected: Vec<Token>,
     actual: Token,
+    /// 期待したトークンの一覧の代わりに示す理由 (定義されていない部分パターンの参照など)
+    message: Option<String>,
 }
 impl ParseError {
     fn new(expected: &[Token], actual: Token) -> Self {
         let expected: Vec<Token> = expected.to_vec();
-        ParseError { expected, actual }
+        ParseError {
+            expected,
+            actual,
+            message: None,
+        }
+    }
+
+    fn undefined(name: &str) -> Self {
+        ParseError {
+            expected: Vec::new(),
+            actual: Token::Reference,
+            message: Some(format!("Undefined sub-pattern \\g<{}>", name)),
+        }
+    }
+
+    fn invalid(message: String) -> Self {
+        ParseError {
+            expected: Vec::new(),
+            actual: Token::Invalid,
+            message: Some(message),
+        }
+    }
+
+    /// 予期しなかったトークン
+    #[cfg(feature = "posix")]
+    pub(crate) fn actual(&self) -> Token {
+        self.actual
     }
 }
 impl Error for ParseError {}
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(message) = &self.message {
+            return write!(f, "{}", message);
+        }
         let expected = self
             .expected
             .iter()
@@ -218,113 +852,136 @@ mod tests {
 
     #[test]
     fn from_character_node() {
-        let nfa = NFA::from_node(Node::Character('a'));
+        let nfa = NFA::from_node(Node::character('a'));
 
         // -> 0 --a--> 1
         // accept: 1
         assert_eq!(nfa.start, NFAState(0));
-        assert_eq!(nfa.accepts, [NFAState(1)].into());
-        assert_eq!(
-            nfa.transition,
-            [(NFAState(0), [(Some('a'), [NFAState(1)].into())].into())].into()
-        );
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(1)]));
+        assert_eq!(nfa.transition, [vec![(Some('a'), NFAState(1))]]);
     }
 
     #[test]
     fn from_empty_node() {
-        let nfa = NFA::from_node(Node::Empty);
+        let nfa = NFA::from_node(Node::empty());
 
         // -> 0 --ε--> 1
         // accept: 1
         assert_eq!(nfa.start, NFAState(0));
-        assert_eq!(nfa.accepts, [NFAState(1)].into());
-        assert_eq!(
-            nfa.transition,
-            [(NFAState(0), [(None, [NFAState(1)].into())].into())].into()
-        );
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(1)]));
+        assert_eq!(nfa.transition, [vec![(None, NFAState(1))]]);
     }
 
     #[test]
     fn from_star_node() {
-        let nfa = NFA::from_node(Node::Star(Box::new(Node::Character('a'))));
+        let nfa = NFA::from_node(Node::character('a').star());
 
         //              /<--ε--\
         // -> 2 --ε--> 0 --a--> 1
         // accept: 2, 1
         assert_eq!(nfa.start, NFAState(2));
-        assert_eq!(nfa.accepts, [NFAState(2), NFAState(1)].into());
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(2), NFAState(1)]));
         assert_eq!(
             nfa.transition,
             [
-                (NFAState(2), [(None, [NFAState(0)].into())].into()),
-                (NFAState(0), [(Some('a'), [NFAState(1)].into())].into()),
-                (NFAState(1), [(None, [NFAState(0)].into())].into())
+                vec![(Some('a'), NFAState(1))],
+                vec![(None, NFAState(0))],
+                vec![(None, NFAState(0))]
             ]
-            .into()
         );
     }
 
     #[test]
     fn from_union_node() {
-        let nfa = NFA::from_node(Node::Union(
-            Box::new(Node::Character('a')),
-            Box::new(Node::Character('b')),
-        ));
+        let nfa = NFA::from_node(Node::character('a').union(Node::character('b')));
 
         //     /--ε--> 0 --a--> 1
         // -> 4
         //     \--ε--> 2 --b--> 3
         // accept: 1, 3
         assert_eq!(nfa.start, NFAState(4));
-        assert_eq!(nfa.accepts, [NFAState(1), NFAState(3)].into());
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(1), NFAState(3)]));
         assert_eq!(
             nfa.transition,
             [
-                (
-                    NFAState(4),
-                    [(None, [NFAState(0), NFAState(2)].into())].into()
-                ),
-                (NFAState(0), [(Some('a'), [NFAState(1)].into())].into()),
-                (NFAState(2), [(Some('b'), [NFAState(3)].into())].into())
+                vec![(Some('a'), NFAState(1))],
+                vec![],
+                vec![(Some('b'), NFAState(3))],
+                vec![],
+                vec![(None, NFAState(0)), (None, NFAState(2))]
             ]
-            .into()
         );
     }
 
     #[test]
     fn from_concat_node() {
-        let nfa = NFA::from_node(Node::Concat(
-            Box::new(Node::Character('a')),
-            Box::new(Node::Character('b')),
-        ));
+        let nfa = NFA::from_node(Node::character('a').concat(Node::character('b')));
 
         // -> 0 --a--> 1 --ε--> 2 --b--> 3
         // accept: 3
         assert_eq!(nfa.start, NFAState(0));
-        assert_eq!(nfa.accepts, [NFAState(3)].into());
+        assert_eq!(nfa.accepts, HashSet::from_iter([NFAState(3)]));
         assert_eq!(
             nfa.transition,
             [
-                (NFAState(0), [(Some('a'), [NFAState(1)].into())].into()),
-                (NFAState(1), [(None, [NFAState(2)].into())].into()),
-                (NFAState(2), [(Some('b'), [NFAState(3)].into())].into())
+                vec![(Some('a'), NFAState(1))],
+                vec![(None, NFAState(2))],
+                vec![(Some('b'), NFAState(3))]
             ]
-            .into()
         );
     }
 
+    #[test]
+    fn literal() {
+        let mut parser = Parser::new(Lexer::new(r"ab(c\*)"));
+        assert_eq!(
+            parser.parse().unwrap().root().literal(),
+            Some("abc*".to_string())
+        );
+        let mut parser = Parser::new(Lexer::new(r"ab|c"));
+        assert_eq!(parser.parse().unwrap().root().literal(), None);
+        let mut parser = Parser::new(Lexer::new(r"ab*"));
+        assert_eq!(parser.parse().unwrap().root().literal(), None);
+    }
+
+    #[test]
+    fn literal_prefix() {
+        let cases: [(&str, &str); 6] = [
+            (r"ab(c|cd)e*", "abc"),
+            (r"山田(太|一)郎", "山田"),
+            (r"(ab)*c", ""),
+            (r"abc|abd|ab", "ab"),
+            (r"(a|b)c", ""),
+            (r"a(b)c(d*)e", "abc"),
+        ];
+        for (pattern, prefix) in cases {
+            let node: Node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+            assert_eq!(node.root().literal_prefix(), prefix, "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn literal_suffix() {
+        let cases: [(&str, &str); 4] = [
+            (r"(a|b)*\.json", ".json"),
+            (r"(山|田)*太郎|一郎", "郎"),
+            (r"a(b|c)", ""),
+            (r"ab*", ""),
+        ];
+        for (pattern, suffix) in cases {
+            let node: Node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+            assert_eq!(node.literal_suffix(), suffix, "{:?}", pattern);
+        }
+        let node: Node = Parser::new(Lexer::new(r"ab(c|de)*")).parse().unwrap();
+        assert_eq!(node.reverse().to_string(), r"(c|ed)*ba");
+    }
+
     #[test]
     fn expression() {
         let mut parser = Parser::new(Lexer::new(r"a|(bc)*"));
         assert_eq!(
             parser.expression().unwrap(),
-            Node::Union(
-                Box::new(Node::Character('a')),
-                Box::new(Node::Star(Box::new(Node::Concat(
-                    Box::new(Node::Character('b')),
-                    Box::new(Node::Character('c'))
-                ))))
-            )
+            Node::character('a').union(Node::character('b').concat(Node::character('c')).star())
         );
     }
 
@@ -333,15 +990,179 @@ mod tests {
         let mut parser = Parser::new(Lexer::new(r"a|"));
         assert_eq!(
             parser.expression().unwrap(),
-            Node::Union(Box::new(Node::Character('a')), Box::new(Node::Empty))
+            Node::character('a').union(Node::empty())
+        );
+    }
+
+    #[test]
+    fn with_captures() {
+        let node = Parser::new(Lexer::new(r"a(b(c)|(d))*"))
+            .with_captures()
+            .parse()
+            .unwrap();
+        assert_eq!(node.root().groups(), 3);
+        assert_eq!(node.to_string(), r"a(b(c)|(d))*");
+        assert_eq!(
+            node.without_groups(),
+            Parser::new(Lexer::new(r"a(b(c)|(d))*")).parse().unwrap()
+        );
+        let Kind::Concat(_, star) = node.root().kind() else {
+            panic!()
+        };
+        let Kind::Star(group) = star.kind() else {
+            panic!()
+        };
+        assert!(matches!(group.kind(), Kind::Group(1, _)));
+    }
+
+    #[test]
+    fn definitions() {
+        let digit = Parser::new(Lexer::new(r"0|1")).parse().unwrap();
+        let definitions: HashMap<String, Node> = [("digit".to_string(), digit)].into();
+        let node = Parser::new(Lexer::new(r"(\g<digit>)*-\g<digit>"))
+            .with_definitions(&definitions)
+            .with_captures()
+            .parse()
+            .unwrap();
+        assert_eq!(node.to_string(), r"(0|1)*-(0|1)");
+        assert_eq!(node.root().groups(), 1);
+
+        let err = Parser::new(Lexer::new(r"a\g<digit>")).parse().unwrap_err();
+        assert_eq!(err.to_string(), r"Undefined sub-pattern \g<digit>");
+        let err = Parser::new(Lexer::new(r"\g<time>"))
+            .with_definitions(&definitions)
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.to_string(), r"Undefined sub-pattern \g<time>");
+    }
+
+    #[test]
+    fn static_groups() {
+        let static_groups = |pattern: &str| {
+            Parser::new(Lexer::new(pattern))
+                .with_captures()
+                .parse()
+                .unwrap()
+                .root()
+                .static_groups()
+        };
+        assert_eq!(static_groups(r"ab*|c"), Some(0));
+        assert_eq!(static_groups(r"(a)((b|c)d(e))"), Some(4));
+        assert_eq!(static_groups(r"(a)((b|c)*d(e))"), None);
+        assert_eq!(static_groups(r"(a*)*"), None);
+        assert_eq!(static_groups(r"(a)|(b)"), None);
+        assert_eq!(static_groups(r"((a)|b)"), None);
+    }
+
+    #[test]
+    fn group_names() {
+        let mut parser = Parser::new(Lexer::new(r"(?<year>ab)((?<day>c)|(\?<>d))")).with_captures();
+        let node = parser.parse().unwrap();
+        assert_eq!(node.root().groups(), 4);
+        assert_eq!(node.to_string(), r"(ab)((c)|(\?<>d))");
+        assert_eq!(
+            parser.take_group_names(),
+            [
+                Some("year".to_string()),
+                None,
+                Some("day".to_string()),
+                None
+            ]
+        );
+
+        let error = |pattern: &str| {
+            Parser::new(Lexer::new(pattern))
+                .parse()
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error(r"(?<my-name>a)"), r#"Invalid group name "my-name""#);
+        assert_eq!(error(r"a(?<>b)"), r#"Invalid group name """#);
+        assert_eq!(error(r"(?<x>a)(?<x>b)"), r#"Duplicate group name "x""#);
+        assert_eq!(
+            error(r"(?|(?<x>a)|(b)(?<x>c))"),
+            r#"Duplicate group name "x""#
+        );
+        // ブランチリセットで同じ番号になるグループには同じ名前を付けられるが, 別の名前は付けられない
+        assert!(
+            Parser::new(Lexer::new(r"(?|(?<x>a)|(?<x>b))"))
+                .parse()
+                .is_ok()
+        );
+        assert_eq!(
+            error(r"(?|(?<x>a)|(?<y>b))"),
+            r#"Different names "x" and "y" for group 1"#
         );
     }
 
+    #[test]
+    fn branch_reset() {
+        let mut parser =
+            Parser::new(Lexer::new(r"(a)(?|(?<x>b)(c)|(d)|(?<x>e)(f)(g))(h)")).with_captures();
+        let node = parser.parse().unwrap();
+        assert_eq!(node.root().groups(), 5);
+        assert_eq!(
+            parser.take_group_names(),
+            [None, Some("x".to_string()), None, None, None]
+        );
+        assert_eq!(node.to_string(), r"(a)(?|(b)(c)|(d)|(e)(f)(g))(h)");
+        let Kind::Concat(_, rest) = node.root().kind() else {
+            panic!()
+        };
+        let Kind::Concat(reset, _) = rest.kind() else {
+            panic!()
+        };
+        assert!(matches!(reset.branches()[1].kind(), Kind::Group(2, _)));
+        // 書き出したパターンを読み直しても同じ番号になる
+        let rendered = node.to_string();
+        let reparsed = Parser::new(Lexer::new(&rendered)).with_captures().parse();
+        assert_eq!(reparsed.unwrap(), node);
+
+        // 番号が重ならなければ, 通常の括弧と同じく書き出す
+        let node = Parser::new(Lexer::new(r"(?|a|(b))"))
+            .with_captures()
+            .parse();
+        assert_eq!(node.unwrap().to_string(), r"a|(b)");
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let parse = |pattern: &str| Parser::new(Lexer::new(pattern)).parse().unwrap();
+        assert_eq!(parse(r"a1").case_insensitive().to_string(), "(A|a)1");
+        assert_eq!(parse(r"(σ)*").case_insensitive().to_string(), "(Σ|σ)*");
+        assert_eq!(parse(r"ß").case_insensitive().to_string(), "ß");
+    }
+
+    #[test]
+    fn display() {
+        for pattern in [
+            r"a|(bc)*",
+            r"(p(erl|ython|hp)|ruby)",
+            r"ｗｗ*|\(笑\)",
+            r"a\\c\*",
+            r"a(b|)",
+            r"(a*)*|()*",
+            r"",
+        ] {
+            let node = Parser::new(Lexer::new(pattern)).parse().unwrap();
+            let rendered = node.to_string();
+            assert_eq!(Parser::new(Lexer::new(&rendered)).parse().unwrap(), node);
+        }
+        let node = Node::character('a')
+            .union(Node::character('|'))
+            .concat(Node::empty().star());
+        assert_eq!(node.to_string(), r"(a|\|)()*");
+    }
+
     #[test]
     fn fail() {
         let mut parser1 = Parser::new(Lexer::new(r"a("));
         let mut parser2 = Parser::new(Lexer::new(r"a)"));
         assert!(parser1.expression().is_err());
         assert!(parser2.expression().is_err());
+        for pattern in [r"\", r"a\", r"(a|b\"] {
+            let err = Parser::new(Lexer::new(pattern)).parse().unwrap_err();
+            assert_eq!(err.to_string(), r"Trailing '\' at the end of the pattern");
+        }
     }
 }
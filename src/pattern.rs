@@ -0,0 +1,92 @@
+//! 標準ライブラリの [`Pattern`] の実装 (nightly が必要)
+
+use std::str::pattern::{Pattern, SearchStep, Searcher};
+
+use crate::{Matches, Regex};
+
+/// `haystack.find(&regex)`, `haystack.split(&regex)` などで使える. マッチは [`Regex::find_iter`] と同じ
+impl<'r> Pattern for &'r Regex {
+    type Searcher<'h> = RegexSearcher<'r, 'h>;
+
+    fn into_searcher(self, haystack: &str) -> RegexSearcher<'r, '_> {
+        RegexSearcher {
+            haystack,
+            matches: self.find_iter(haystack),
+            last_step_end: 0,
+            next_match: None,
+        }
+    }
+}
+
+/// [`Pattern`] の探索の状態
+#[derive(Debug)]
+pub struct RegexSearcher<'r, 'h> {
+    haystack: &'h str,
+    matches: Matches<'r, 'h>,
+    /// 直前に返した範囲の終了位置
+    last_step_end: usize,
+    /// [`SearchStep::Reject`] の後で返すマッチ
+    next_match: Option<(usize, usize)>,
+}
+
+unsafe impl<'h> Searcher<'h> for RegexSearcher<'_, 'h> {
+    fn haystack(&self) -> &'h str {
+        self.haystack
+    }
+
+    fn next(&mut self) -> SearchStep {
+        if let Some((start, end)) = self.next_match.take() {
+            self.last_step_end = end;
+            return SearchStep::Match(start, end);
+        }
+        match self.matches.next() {
+            Some(found) if found.start() == self.last_step_end => {
+                self.last_step_end = found.end();
+                SearchStep::Match(found.start(), found.end())
+            }
+            Some(found) => {
+                // マッチまでの間を飛ばした範囲として先に返す
+                let rejected: SearchStep = SearchStep::Reject(self.last_step_end, found.start());
+                self.last_step_end = found.start();
+                self.next_match = Some((found.start(), found.end()));
+                rejected
+            }
+            None if self.last_step_end < self.haystack.len() => {
+                let rejected: SearchStep =
+                    SearchStep::Reject(self.last_step_end, self.haystack.len());
+                self.last_step_end = self.haystack.len();
+                rejected
+            }
+            None => SearchStep::Done,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Regex;
+
+    #[test]
+    fn pattern() {
+        let regex = Regex::new(r"(太|一)郎").unwrap();
+        let text: &str = "山田太郎と山田一郎";
+        assert_eq!(text.find(&regex), Some(6));
+        assert!(text.contains(&regex));
+        assert_eq!(
+            text.split(&regex).collect::<Vec<_>>(),
+            vec!["山田", "と山田", ""]
+        );
+        assert_eq!(text.replace(&regex, "*"), "山田*と山田*");
+        assert_eq!(
+            text.match_indices(&regex).collect::<Vec<_>>(),
+            vec![(6, "太郎"), (21, "一郎")]
+        );
+        assert!(!"山田次郎".contains(&regex));
+
+        let regex = Regex::new(r"a*").unwrap();
+        assert_eq!(
+            "baac".split(&regex).collect::<Vec<_>>(),
+            vec!["", "b", "", "c", ""]
+        );
+    }
+}
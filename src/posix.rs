@@ -0,0 +1,286 @@
+//! POSIX の `regcomp`/`regexec` と同じ形の C インターフェース. 宣言は `include/ryota2357_regex_posix.h` にある
+//!
+//! libc の関数と衝突しないよう, 関数名には `ryota2357_` を付けている (ヘッダで `regcomp` などの別名を定義する).
+//! パターンの構文はこの crate のもの (`|`, `*`, `()`, `\`) で, 最左最長でマッチを選ぶ.
+
+#![allow(non_camel_case_types)]
+
+use std::ffi::{CStr, c_char, c_int};
+
+use crate::lexer::{Lexer, Token};
+use crate::parser::Parser;
+use crate::{Regex, RegexBuilder};
+
+/// 構文は常にこの crate のものなので, 指定しても何も変わらない
+pub const REG_EXTENDED: c_int = 1;
+/// 大文字と小文字を区別しない
+pub const REG_ICASE: c_int = 2;
+/// `.` や `^`, `$` がないため, 指定しても何も変わらない
+pub const REG_NEWLINE: c_int = 4;
+/// `regexec` でマッチの位置を求めない
+pub const REG_NOSUB: c_int = 8;
+
+/// `^` がないため, 指定しても何も変わらない
+pub const REG_NOTBOL: c_int = 1;
+/// `$` がないため, 指定しても何も変わらない
+pub const REG_NOTEOL: c_int = 2;
+
+pub const REG_NOMATCH: c_int = 1;
+pub const REG_BADPAT: c_int = 2;
+pub const REG_EESCAPE: c_int = 5;
+pub const REG_EPAREN: c_int = 8;
+pub const REG_BADRPT: c_int = 13;
+
+/// コンパイルしたパターン
+#[repr(C)]
+pub struct regex_t {
+    /// キャプチャグループの数 (マッチ全体は含まない)
+    pub re_nsub: usize,
+    regex: *mut Regex,
+    cflags: c_int,
+}
+
+pub type regoff_t = isize;
+
+/// マッチの位置 (バイトオフセット). 関与しなかったグループは -1
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct regmatch_t {
+    pub rm_so: regoff_t,
+    pub rm_eo: regoff_t,
+}
+
+/// NUL終端のパターン文字列をコンパイルして `*preg` に書き込む. 成功すれば 0, 失敗すればエラーコード
+///
+/// # Safety
+///
+/// `preg` は書き込み可能な `regex_t` を, `pattern` はNUL終端の文字列を指すこと.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ryota2357_regcomp(
+    preg: *mut regex_t,
+    pattern: *const c_char,
+    cflags: c_int,
+) -> c_int {
+    let Ok(pattern) = unsafe { CStr::from_ptr(pattern) }.to_str() else {
+        return REG_BADPAT;
+    };
+    if let Err(code) = check(pattern) {
+        return code;
+    }
+    let regex: Regex = match RegexBuilder::new(pattern)
+        .case_insensitive(cflags & REG_ICASE != 0)
+        .build()
+    {
+        Ok(regex) => regex,
+        Err(_) => return REG_BADPAT,
+    };
+    unsafe {
+        preg.write(regex_t {
+            re_nsub: regex.captures_len() - 1,
+            regex: Box::into_raw(Box::new(regex)),
+            cflags,
+        });
+    }
+    0
+}
+
+/// NUL終端の文字列 `string` の中で最も左にあるマッチを探す. 見つかれば 0, なければ [`REG_NOMATCH`]
+///
+/// `REG_NOSUB` を指定していなければ, `pmatch` の先頭 `nmatch` 個にマッチ全体と各グループの位置を書き込む.
+///
+/// # Safety
+///
+/// `preg` は [`ryota2357_regcomp`] が成功した `regex_t` を, `string` はNUL終端の文字列を指し,
+/// `pmatch` は `nmatch` 個の `regmatch_t` に書き込めること.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ryota2357_regexec(
+    preg: *const regex_t,
+    string: *const c_char,
+    nmatch: usize,
+    pmatch: *mut regmatch_t,
+    _eflags: c_int,
+) -> c_int {
+    let preg: &regex_t = unsafe { &*preg };
+    let regex: &Regex = unsafe { &*preg.regex };
+    let Ok(text) = unsafe { CStr::from_ptr(string) }.to_str() else {
+        return REG_NOMATCH;
+    };
+    if preg.cflags & REG_NOSUB != 0 || nmatch == 0 {
+        return if regex.is_match(text) { 0 } else { REG_NOMATCH };
+    }
+    let Some(caps) = regex.captures(text) else {
+        return REG_NOMATCH;
+    };
+    let pmatch: &mut [regmatch_t] = unsafe { std::slice::from_raw_parts_mut(pmatch, nmatch) };
+    for (i, slot) in pmatch.iter_mut().enumerate() {
+        *slot = match caps.get(i) {
+            Some(m) => regmatch_t {
+                rm_so: m.start() as regoff_t,
+                rm_eo: m.end() as regoff_t,
+            },
+            None => regmatch_t {
+                rm_so: -1,
+                rm_eo: -1,
+            },
+        };
+    }
+    0
+}
+
+/// エラーコードの説明をNUL終端で `errbuf` に (収まる分だけ) 書き込み, 全体に必要なバイト数を返す
+///
+/// # Safety
+///
+/// `errbuf` は `errbuf_size` バイト書き込めること (`errbuf_size` が 0 ならNULLでもよい).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ryota2357_regerror(
+    errcode: c_int,
+    _preg: *const regex_t,
+    errbuf: *mut c_char,
+    errbuf_size: usize,
+) -> usize {
+    let message: &[u8] = match errcode {
+        0 => b"Success",
+        REG_NOMATCH => b"No match",
+        REG_BADPAT => b"Invalid regular expression",
+        REG_EESCAPE => b"Trailing backslash",
+        REG_EPAREN => b"Unmatched ( or )",
+        REG_BADRPT => b"Invalid preceding regular expression",
+        _ => b"Unknown error",
+    };
+    if errbuf_size > 0 {
+        let len: usize = message.len().min(errbuf_size - 1);
+        unsafe {
+            std::ptr::copy_nonoverlapping(message.as_ptr(), errbuf.cast::<u8>(), len);
+            *errbuf.add(len) = 0;
+        }
+    }
+    message.len() + 1
+}
+
+/// [`ryota2357_regcomp`] が確保したものを解放する
+///
+/// # Safety
+///
+/// `preg` は [`ryota2357_regcomp`] が成功した `regex_t` を指し, まだ解放されていないこと.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ryota2357_regfree(preg: *mut regex_t) {
+    let preg: &mut regex_t = unsafe { &mut *preg };
+    if !preg.regex.is_null() {
+        drop(unsafe { Box::from_raw(preg.regex) });
+        preg.regex = std::ptr::null_mut();
+    }
+}
+
+/// 構文の誤りを POSIX のエラーコードに分類する
+fn check(pattern: &str) -> Result<(), c_int> {
+    let backslashes: usize = pattern.chars().rev().take_while(|c| *c == '\\').count();
+    if backslashes % 2 == 1 {
+        return Err(REG_EESCAPE);
+    }
+    let mut depth: usize = 0;
+    let mut lexer: Lexer<'_> = Lexer::new(pattern);
+    loop {
+        match lexer.scan() {
+            Token::LeftParen => depth += 1,
+            Token::RightParen if depth == 0 => return Err(REG_EPAREN),
+            Token::RightParen => depth -= 1,
+            Token::End if depth > 0 => return Err(REG_EPAREN),
+            Token::End => break,
+            Token::Invalid => return Err(REG_BADPAT),
+            _ => {}
+        }
+    }
+    match Parser::new(Lexer::new(pattern)).parse() {
+        Ok(_) => Ok(()),
+        Err(err) if err.actual() == Token::StarOp => Err(REG_BADRPT),
+        Err(_) => Err(REG_BADPAT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(pattern: &std::ffi::CStr, cflags: c_int) -> Result<regex_t, c_int> {
+        let mut preg = std::mem::MaybeUninit::<regex_t>::uninit();
+        match unsafe { ryota2357_regcomp(preg.as_mut_ptr(), pattern.as_ptr(), cflags) } {
+            0 => Ok(unsafe { preg.assume_init() }),
+            code => Err(code),
+        }
+    }
+
+    #[test]
+    fn regexec() {
+        let mut preg = compile(c"(山田|佐藤)(太|一)郎(x)*", REG_EXTENDED).unwrap();
+        assert_eq!(preg.re_nsub, 3);
+        let mut pmatch = [regmatch_t { rm_so: 0, rm_eo: 0 }; 5];
+        let code = unsafe {
+            ryota2357_regexec(&preg, c"私は佐藤一郎".as_ptr(), 5, pmatch.as_mut_ptr(), 0)
+        };
+        assert_eq!(code, 0);
+        let offsets: Vec<(regoff_t, regoff_t)> =
+            pmatch.iter().map(|m| (m.rm_so, m.rm_eo)).collect();
+        assert_eq!(
+            offsets,
+            vec![(6, 18), (6, 12), (12, 15), (-1, -1), (-1, -1)]
+        );
+        let code = unsafe {
+            ryota2357_regexec(&preg, c"山田次郎".as_ptr(), 5, pmatch.as_mut_ptr(), 0)
+        };
+        assert_eq!(code, REG_NOMATCH);
+        unsafe { ryota2357_regfree(&mut preg) };
+
+        let mut preg = compile(c"abc", REG_ICASE | REG_NOSUB).unwrap();
+        let code =
+            unsafe { ryota2357_regexec(&preg, c"xABC".as_ptr(), 1, std::ptr::null_mut(), 0) };
+        assert_eq!(code, 0);
+        unsafe { ryota2357_regfree(&mut preg) };
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(compile(c"a(b", 0).err(), Some(REG_EPAREN));
+        assert_eq!(compile(c"a)", 0).err(), Some(REG_EPAREN));
+        assert_eq!(compile(c"ab\\", 0).err(), Some(REG_EESCAPE));
+        assert_eq!(compile(c"*a", 0).err(), Some(REG_BADRPT));
+        assert!(compile(c"ab\\\\", 0).is_ok());
+
+        let mut buf = [0 as c_char; 8];
+        let len = unsafe {
+            ryota2357_regerror(REG_EPAREN, std::ptr::null(), buf.as_mut_ptr(), buf.len())
+        };
+        assert_eq!(len, "Unmatched ( or )".len() + 1);
+        let written = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(written.to_str().unwrap(), "Unmatch");
+    }
+
+    #[test]
+    fn header() {
+        // フラグの値は glibc の <regex.h> に合わせる
+        assert_eq!(
+            (REG_EXTENDED, REG_ICASE, REG_NEWLINE, REG_NOSUB),
+            (1, 2, 4, 8)
+        );
+        let header: &str = include_str!("../include/ryota2357_regex_posix.h");
+        for name in ["regcomp", "regexec", "regerror", "regfree"] {
+            assert!(header.contains(&format!("ryota2357_{}(", name)));
+            assert!(header.contains(&format!("#define {} ryota2357_{}", name, name)));
+        }
+        for (name, value) in [
+            ("REG_EXTENDED", REG_EXTENDED),
+            ("REG_ICASE", REG_ICASE),
+            ("REG_NEWLINE", REG_NEWLINE),
+            ("REG_NOSUB", REG_NOSUB),
+            ("REG_NOTBOL", REG_NOTBOL),
+            ("REG_NOTEOL", REG_NOTEOL),
+            ("REG_NOMATCH", REG_NOMATCH),
+            ("REG_BADPAT", REG_BADPAT),
+            ("REG_EESCAPE", REG_EESCAPE),
+            ("REG_EPAREN", REG_EPAREN),
+            ("REG_BADRPT", REG_BADRPT),
+        ] {
+            assert!(header.contains(&format!("#define {} {}\n", name, value)));
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+
+use crate::automaton::{DFA, DFAState, NFA};
+use crate::parser::Node;
+
+/// 探索開始位置の候補を高速に見つける. [`RegexBuilder::prefilter`](crate::RegexBuilder::prefilter) で照合に使わせる
+///
+/// `haystack` は [`Regex::find`](crate::Regex::find) や [`Regex::find_iter`](crate::Regex::find_iter) に渡された文字列全体で,
+/// 2つ目以降のマッチを探す場合も切り出さずに渡す. 照合は返された位置より前から始まるマッチを試さない.
+/// 候補に含まれない位置から始まるマッチは見つからなくなるので, マッチが始まりうる位置は必ず返すこと.
+pub trait Prefilter: std::fmt::Debug + Send + Sync {
+    /// `at` 以降で最初の候補位置 (バイトオフセット, 文字の境界). `at` 以降から始まるマッチが無ければ `None`
+    fn find(&self, haystack: &str, at: usize) -> Option<usize>;
+}
+
+/// 文字列 `needle` が現れる位置を候補にする. どのマッチも `needle` で始まる場合に使える
+#[derive(Clone, Debug)]
+pub struct LiteralPrefilter {
+    needle: String,
+}
+
+impl LiteralPrefilter {
+    pub fn new(needle: &str) -> Self {
+        LiteralPrefilter {
+            needle: needle.to_string(),
+        }
+    }
+}
+
+impl Prefilter for LiteralPrefilter {
+    fn find(&self, haystack: &str, at: usize) -> Option<usize> {
+        find_literal(&haystack[at..], &self.needle).map(|pos| at + pos)
+    }
+}
+
+/// 与えた文字のいずれかが現れる位置を候補にする. パターンから作られるフィルタはこれ
+///
+/// ASCII 文字が3種類以下なら `memchr` (`simd` 機能が有効な場合) で探す.
+#[derive(Clone, Debug)]
+pub struct CharSetPrefilter {
+    kind: Kind,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    /// 3種類以下のASCII文字
+    Bytes(Vec<u8>),
+    /// ASCII文字のみからなる集合
+    Table(Box<[bool; 128]>),
+    /// 非ASCII文字を含み, UTF-8 での先頭バイトが3種類以下の集合.
+    /// 先頭バイトを探してから文字を確かめる. 先頭バイトは継続バイトと区別できるので, 見つかった位置は文字の境界
+    LeadBytes(Vec<u8>, HashSet<char>),
+    /// 非ASCII文字を含む集合
+    Chars(HashSet<char>),
+}
+
+impl CharSetPrefilter {
+    /// マッチの先頭になり得る文字集合からフィルタを作る. 空集合なら `None`
+    pub fn new(chars: impl IntoIterator<Item = char>) -> Option<Self> {
+        let chars: HashSet<char> = chars.into_iter().collect();
+        if chars.is_empty() {
+            return None;
+        }
+        let kind = if chars.iter().all(char::is_ascii) {
+            if chars.len() <= 3 {
+                let mut bytes: Vec<u8> = chars.iter().map(|c| *c as u8).collect();
+                bytes.sort();
+                Kind::Bytes(bytes)
+            } else {
+                let mut table = Box::new([false; 128]);
+                for chara in &chars {
+                    table[*chara as usize] = true;
+                }
+                Kind::Table(table)
+            }
+        } else {
+            let mut leads: Vec<u8> = chars
+                .iter()
+                .map(|c| c.encode_utf8(&mut [0; 4]).as_bytes()[0])
+                .collect();
+            leads.sort();
+            leads.dedup();
+            if leads.len() <= 3 {
+                Kind::LeadBytes(leads, chars)
+            } else {
+                Kind::Chars(chars)
+            }
+        };
+        Some(CharSetPrefilter { kind })
+    }
+}
+
+impl Prefilter for CharSetPrefilter {
+    fn find(&self, haystack: &str, at: usize) -> Option<usize> {
+        let bytes = &haystack.as_bytes()[at..];
+        let pos = match &self.kind {
+            Kind::Bytes(set) => find_bytes(set, bytes),
+            Kind::Table(table) => bytes.iter().position(|b| *b < 128 && table[*b as usize]),
+            Kind::LeadBytes(leads, set) => {
+                let mut pos: usize = 0;
+                loop {
+                    pos += find_bytes(leads, &bytes[pos..])?;
+                    let chara: char = haystack[at + pos..].chars().next()?;
+                    if set.contains(&chara) {
+                        break Some(pos);
+                    }
+                    pos += chara.len_utf8();
+                }
+            }
+            Kind::Chars(set) => haystack[at..]
+                .char_indices()
+                .find(|(_, c)| set.contains(c))
+                .map(|(i, _)| i),
+        };
+        pos.map(|pos| at + pos)
+    }
+}
+
+#[cfg(feature = "simd")]
+fn find_bytes(set: &[u8], bytes: &[u8]) -> Option<usize> {
+    match *set {
+        [b1] => memchr::memchr(b1, bytes),
+        [b1, b2] => memchr::memchr2(b1, b2, bytes),
+        [b1, b2, b3] => memchr::memchr3(b1, b2, b3, bytes),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+fn find_bytes(set: &[u8], bytes: &[u8]) -> Option<usize> {
+    match set {
+        [b1] => bytes.iter().position(|b| b == b1),
+        [b1, b2] => bytes.iter().position(|b| b == b1 || b == b2),
+        [b1, b2, b3] => bytes.iter().position(|b| b == b1 || b == b2 || b == b3),
+        _ => unreachable!(),
+    }
+}
+
+/// `haystack` 中で最初に `needle` が現れる位置
+pub(crate) fn find_literal(haystack: &str, needle: &str) -> Option<usize> {
+    #[cfg(feature = "simd")]
+    return memchr::memmem::find(haystack.as_bytes(), needle.as_bytes());
+    #[cfg(not(feature = "simd"))]
+    return haystack.find(needle);
+}
+
+/// どのマッチも同じ文字列で終わる場合に, その文字列を探してから逆向きのDFAで照合し, マッチが無いことを確かめる
+///
+/// 接尾辞が見つからない, または見つかった位置で終わるマッチが無ければ, 前向きの照合をせずに済む.
+#[derive(Clone, Debug)]
+pub(crate) struct ReverseSuffix {
+    literal: String,
+    /// 逆順の文字列を受理するDFA
+    reverse: DFA,
+}
+
+impl ReverseSuffix {
+    /// キャプチャグループを取り除いた構文木から作る. 接尾辞が空文字列なら `None`
+    pub(crate) fn new(node: &Node) -> Option<Self> {
+        let literal: String = node.literal_suffix();
+        if literal.is_empty() {
+            return None;
+        }
+        Some(ReverseSuffix {
+            literal,
+            reverse: DFA::from_nfa(NFA::from_node(node.reverse())),
+        })
+    }
+
+    /// `text` にマッチが無いと確定すれば `true`
+    ///
+    /// 接尾辞より前を逆向きに読んだ文字数が `text` の長さを超えたら, 確定できないとして `false` を返す.
+    /// 接尾辞が何度も現れる場合に, 照合全体が2乗の時間にならないようにするため.
+    pub(crate) fn rules_out(&self, text: &str) -> bool {
+        let mut budget: usize = text.len();
+        let mut from: usize = 0;
+        while let Some(pos) = find_literal(&text[from..], &self.literal) {
+            let start: usize = from + pos;
+            let mut state: Option<DFAState> = Some(self.reverse.start);
+            for chara in self.literal.chars().rev() {
+                state = state.and_then(|state| self.reverse.next_state(state, chara));
+            }
+            if state.is_some_and(|state| self.reverse.is_accept(state)) {
+                return false;
+            }
+            for chara in text[..start].chars().rev() {
+                let Some(current) = state else {
+                    break;
+                };
+                if budget == 0 {
+                    return false;
+                }
+                budget -= 1;
+                state = self.reverse.next_state(current, chara);
+                if state.is_some_and(|state| self.reverse.is_accept(state)) {
+                    return false;
+                }
+            }
+            // 接尾辞の出現が重なっていてもよいので, 1文字だけ進める
+            from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_bytes() {
+        let prefilter = CharSetPrefilter::new(['b', 'c']).unwrap();
+        assert_eq!(prefilter.find("aaabac", 0), Some(3));
+        assert_eq!(prefilter.find("aaabac", 4), Some(5));
+        assert_eq!(prefilter.find("aaaa", 0), None);
+    }
+
+    #[test]
+    fn find_table() {
+        let prefilter = CharSetPrefilter::new(['w', 'x', 'y', 'z']).unwrap();
+        assert_eq!(prefilter.find("あいうz", 0), Some(9));
+        assert_eq!(prefilter.find("abc", 0), None);
+    }
+
+    #[test]
+    fn find_chars() {
+        let prefilter = CharSetPrefilter::new(['山', 'a']).unwrap();
+        assert!(matches!(prefilter.kind, Kind::LeadBytes(..)));
+        assert_eq!(prefilter.find("今日は山田", 0), Some(9));
+        assert_eq!(prefilter.find("今日は山田", 12), None);
+        // 先頭バイトが同じ別の文字 (`峠`) は飛ばす
+        assert_eq!(prefilter.find("峠山", 0), Some(3));
+        assert_eq!(prefilter.find("峠", 0), None);
+
+        let prefilter = CharSetPrefilter::new(['山', 'a', 'é', '😀']).unwrap();
+        assert!(matches!(prefilter.kind, Kind::Chars(..)));
+        assert_eq!(prefilter.find("今日は😀", 0), Some(9));
+    }
+
+    #[test]
+    fn find_literal_prefilter() {
+        let prefilter = LiteralPrefilter::new("山田");
+        assert_eq!(prefilter.find("山と山田", 0), Some(6));
+        assert_eq!(prefilter.find("山と山田", 9), None);
+    }
+
+    #[test]
+    fn literal() {
+        assert_eq!(find_literal("abcabd", "abd"), Some(3));
+        assert_eq!(find_literal("今日は山田", "山田"), Some(9));
+        assert_eq!(find_literal("abc", ""), Some(0));
+        assert_eq!(find_literal("abc", "d"), None);
+    }
+
+    #[test]
+    fn reverse_suffix() {
+        let parse = |pattern: &str| {
+            crate::parser::Parser::new(crate::lexer::Lexer::new(pattern))
+                .parse()
+                .unwrap()
+        };
+        let suffix = ReverseSuffix::new(&parse(r"(a|b)(a|b)*\.json")).unwrap();
+        assert!(suffix.rules_out("xyz"));
+        assert!(suffix.rules_out(".json"));
+        assert!(suffix.rules_out("c.json.json"));
+        assert!(!suffix.rules_out("c.json ab.json"));
+        // 逆向きに読む文字数が上限を超えると, 確定できない
+        let text: String = format!("{}x", "a.json".repeat(10));
+        let suffix = ReverseSuffix::new(&parse(r"x(a|\.|j|s|o|n)*\.json")).unwrap();
+        assert!(!suffix.rules_out(&text));
+        assert!(ReverseSuffix::new(&parse(r"a(b|c)")).is_none());
+    }
+
+    #[test]
+    fn empty_set() {
+        assert!(CharSetPrefilter::new([]).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+//! Python から使うためのモジュール
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::Regex;
+
+/// Python の `Regex` オブジェクト
+#[pyclass(name = "Regex", frozen)]
+struct PyRegex {
+    regex: Regex,
+}
+
+#[pymethods]
+impl PyRegex {
+    /// 元のパターン文字列
+    #[getter]
+    fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// 部分文字列にマッチするか
+    fn search(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    /// `text` の先頭から始まる最長のマッチ. 無ければ `None`
+    #[pyo3(name = "match")]
+    fn match_at_start(&self, text: &str) -> Option<String> {
+        match_at_start(&self.regex, text)
+    }
+
+    /// `text` 全体にマッチするか
+    fn fullmatch(&self, text: &str) -> bool {
+        self.regex.matches(text)
+    }
+
+    /// 重ならないマッチを左から順にすべて返す
+    fn findall(&self, text: &str) -> Vec<String> {
+        findall(&self.regex, text)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Regex({:?})", self.regex.as_str())
+    }
+}
+
+fn compile_regex(pattern: &str) -> PyResult<Regex> {
+    Regex::new(pattern).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn match_at_start(regex: &Regex, text: &str) -> Option<String> {
+    let end: usize = regex.longest_match_at(text, 0)?;
+    Some(text[..end].to_string())
+}
+
+fn findall(regex: &Regex, text: &str) -> Vec<String> {
+    regex
+        .find_iter(text)
+        .map(|m| m.as_str().to_string())
+        .collect()
+}
+
+/// パターンをコンパイルする. 不正なパターンの場合は `ValueError`
+#[pyfunction]
+fn compile(pattern: &str) -> PyResult<PyRegex> {
+    Ok(PyRegex {
+        regex: compile_regex(pattern)?,
+    })
+}
+
+/// `text` の先頭から始まる最長のマッチ. 無ければ `None`
+#[pyfunction(name = "match")]
+fn match_(pattern: &str, text: &str) -> PyResult<Option<String>> {
+    Ok(match_at_start(&compile_regex(pattern)?, text))
+}
+
+/// 重ならないマッチを左から順にすべて返す
+#[pyfunction(name = "findall")]
+fn findall_(pattern: &str, text: &str) -> PyResult<Vec<String>> {
+    Ok(findall(&compile_regex(pattern)?, text))
+}
+
+#[pymodule]
+fn ryota2357_regex(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRegex>()?;
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    m.add_function(wrap_pyfunction!(match_, m)?)?;
+    m.add_function(wrap_pyfunction!(findall_, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_at_start() {
+        let regex = Regex::new(r"ab*").unwrap();
+        assert_eq!(
+            super::match_at_start(&regex, "abbc"),
+            Some("abb".to_string())
+        );
+        assert_eq!(super::match_at_start(&regex, "xab"), None);
+    }
+
+    #[test]
+    fn findall() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        assert_eq!(
+            super::findall(&regex, "山田太郎と山田一郎と山田次郎"),
+            vec!["山田太郎", "山田一郎"]
+        );
+        let regex = Regex::new(r"a*").unwrap();
+        assert_eq!(super::findall(&regex, "baac"), vec!["", "aa", "", ""]);
+    }
+}
@@ -0,0 +1,78 @@
+//! コンパイル済みのDFAを直接操作するための低水準の API
+//!
+//! 状態番号は [`Regex::start_state`] や [`Regex::trace`] と同じもの.
+
+use std::borrow::Cow;
+
+use crate::Regex;
+use crate::automaton::{DFA, DFAState};
+
+/// [`Regex::raw_dfa`] が返す, 照合に使われるDFA
+///
+/// 自前の走査ループの中で `start_state` から `next_state` で1文字ずつ遷移し, `is_accept` で受理を判定する.
+#[derive(Clone, Debug)]
+pub struct Dfa<'r> {
+    dfa: Cow<'r, DFA>,
+}
+
+impl Dfa<'_> {
+    /// 開始状態の番号
+    pub fn start_state(&self) -> u32 {
+        self.dfa.start.0
+    }
+
+    /// `state` から `chara` で遷移した先の状態番号. 遷移できなければ `None` で, その後どう読んでも受理しない
+    pub fn next_state(&self, state: u32, chara: char) -> Option<u32> {
+        self.dfa
+            .next_state(DFAState(state), chara)
+            .map(|state| state.0)
+    }
+
+    /// `state` が受理状態か
+    pub fn is_accept(&self, state: u32) -> bool {
+        self.dfa.is_accept(DFAState(state))
+    }
+
+    /// 開始状態から到達可能な状態の数
+    pub fn state_count(&self) -> usize {
+        self.dfa.states().len()
+    }
+}
+
+impl Regex {
+    /// 照合に使うDFAを直接操作するための [`Dfa`]. オートマトンを使わないパターンでは, 同じ言語のDFAをここで作る
+    pub fn raw_dfa(&self) -> Dfa<'_> {
+        Dfa { dfa: self.dfa() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scanner_loop() {
+        let regex = Regex::new(r"山田(太|一)郎").unwrap();
+        let dfa: Dfa<'_> = regex.raw_dfa();
+        let run = |text: &str| {
+            let mut state: u32 = dfa.start_state();
+            for chara in text.chars() {
+                match dfa.next_state(state, chara) {
+                    Some(next) => state = next,
+                    None => return false,
+                }
+            }
+            dfa.is_accept(state)
+        };
+        for text in ["山田太郎", "山田一郎", "山田", "山田次郎", ""] {
+            assert_eq!(run(text), regex.matches(text), "{:?}", text);
+        }
+        assert!(dfa.state_count() >= 5);
+
+        let regex = Regex::new(&format!("({})*b", "a|".repeat(65))).unwrap();
+        let dfa: Dfa<'_> = regex.raw_dfa();
+        assert_eq!(dfa.start_state(), regex.start_state());
+        let step = regex.trace("ab")[1];
+        assert_eq!(dfa.next_state(step.from(), 'b'), step.to());
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use crate::Regex;
+
+/// コンパイル済みの `Regex` を最大 `capacity` 個まで保持する LRU キャッシュ
+///
+/// 返される `Regex` は内部を共有する複製なので, 複製のコストは小さい.
+#[derive(Clone, Debug)]
+pub struct RegexCache {
+    capacity: usize,
+    /// パターン -> (Regex, 最後に使われた時刻)
+    entries: HashMap<String, (Regex, u64)>,
+    /// 最後に使われた時刻 -> パターン
+    order: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl RegexCache {
+    pub fn new(capacity: usize) -> Self {
+        RegexCache {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// 保持している `Regex` の数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// `pattern` のコンパイル済みの `Regex` を返す. 無ければコンパイルして保持する
+    ///
+    /// 保持数が `capacity` を超える場合は, 最も長く使われていないものを捨てる.
+    /// コンパイルに失敗したパターンは保持しない.
+    pub fn get_or_compile(&mut self, pattern: &str) -> Result<Regex, Box<dyn Error>> {
+        self.clock += 1;
+        if let Some((regex, used)) = self.entries.get_mut(pattern) {
+            self.order.remove(used);
+            *used = self.clock;
+            self.order.insert(self.clock, pattern.to_string());
+            return Ok(regex.clone());
+        }
+        let regex: Regex = Regex::new(pattern)?;
+        if self.capacity == 0 {
+            return Ok(regex);
+        }
+        if self.entries.len() >= self.capacity
+            && let Some((_, oldest)) = self.order.pop_first()
+        {
+            self.entries.remove(&oldest);
+        }
+        self.entries
+            .insert(pattern.to_string(), (regex.clone(), self.clock));
+        self.order.insert(self.clock, pattern.to_string());
+        Ok(regex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_or_compile() {
+        let mut cache = RegexCache::new(2);
+        let r1 = cache.get_or_compile(r"a*").unwrap();
+        let r2 = cache.get_or_compile(r"a*").unwrap();
+        assert!(std::sync::Arc::ptr_eq(&r1.engine, &r2.engine));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get_or_compile(r"a(").is_err());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = RegexCache::new(2);
+        let a = cache.get_or_compile(r"a").unwrap();
+        cache.get_or_compile(r"b").unwrap();
+        cache.get_or_compile(r"a").unwrap();
+        cache.get_or_compile(r"c").unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.entries.contains_key("a"));
+        assert!(!cache.entries.contains_key("b"));
+        let again = cache.get_or_compile(r"a").unwrap();
+        assert!(std::sync::Arc::ptr_eq(&a.engine, &again.engine));
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut cache = RegexCache::new(0);
+        assert!(cache.get_or_compile(r"a").unwrap().matches("a"));
+        assert!(cache.is_empty());
+    }
+}
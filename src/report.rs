@@ -0,0 +1,199 @@
+//! 照合結果をまとめた, シリアライズ可能な構造体
+//!
+//! エディタや CI などのツールが JSON として受け取ることを想定している. フィールドの名前と意味は
+//! [`REPORT_VERSION`] が同じ間は変えない. 位置はすべてバイトオフセット.
+//!
+//! 例えば `Regex::new(r"a(b)").unwrap().report("xab")` は次のようになる.
+//!
+//! ```json
+//! {"version":1,"pattern":"a(b)","matched":false,"matches":[{"start":1,"end":3,"text":"ab","groups":[{"index":1,"name":null,"span":{"start":2,"end":3,"text":"b"}}]}],"failure":{"offset":0,"found":"x","expected":["a"],"expects_other":false,"expects_end":false},"warnings":[]}
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::lint::{Warning, WarningKind};
+use crate::{Match, MatchFailure, Regex};
+
+/// [`MatchReport`] の形式の版. フィールドの意味を変える場合に上げる
+pub const REPORT_VERSION: u32 = 1;
+
+/// [`Regex::report`] が返す, 1つの文字列に対する照合結果
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchReport {
+    /// [`REPORT_VERSION`]
+    pub version: u32,
+    pub pattern: String,
+    /// 文字列全体がマッチしたか
+    pub matched: bool,
+    /// 重ならないマッチを左から順に並べたもの
+    pub matches: Vec<ReportMatch>,
+    /// 文字列全体がマッチしなかった場合の理由
+    pub failure: Option<ReportFailure>,
+    /// パターン中の冗長な構文 ([`lint`](crate::lint))
+    pub warnings: Vec<ReportWarning>,
+}
+
+/// 文字列中の範囲
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportSpan {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// 1つのマッチと, そのキャプチャグループ
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportMatch {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    /// グループ 1 から順に並べたもの
+    pub groups: Vec<ReportGroup>,
+}
+
+/// キャプチャグループ. マッチに関与しなかった場合は `span` が `None`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportGroup {
+    pub index: usize,
+    pub name: Option<String>,
+    pub span: Option<ReportSpan>,
+}
+
+/// [`MatchFailure`] と同じ内容
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportFailure {
+    pub offset: usize,
+    pub found: Option<char>,
+    pub expected: Vec<char>,
+    pub expects_other: bool,
+    pub expects_end: bool,
+}
+
+/// [`Warning`] と同じ内容. `kind` は `"nested_star"`, `"duplicate_branch"`, `"empty_group"`, `"empty_branch"` のいずれか
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportWarning {
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+    pub message: String,
+}
+
+impl From<Match<'_>> for ReportSpan {
+    fn from(found: Match<'_>) -> Self {
+        ReportSpan {
+            start: found.start(),
+            end: found.end(),
+            text: found.as_str().to_string(),
+        }
+    }
+}
+
+impl From<&MatchFailure> for ReportFailure {
+    fn from(failure: &MatchFailure) -> Self {
+        ReportFailure {
+            offset: failure.offset(),
+            found: failure.found(),
+            expected: failure.expected().to_vec(),
+            expects_other: failure.expects_other(),
+            expects_end: failure.expects_end(),
+        }
+    }
+}
+
+impl From<&Warning> for ReportWarning {
+    fn from(warning: &Warning) -> Self {
+        let kind = match warning.kind() {
+            WarningKind::NestedStar => "nested_star",
+            WarningKind::DuplicateBranch => "duplicate_branch",
+            WarningKind::EmptyGroup => "empty_group",
+            WarningKind::EmptyBranch => "empty_branch",
+        };
+        ReportWarning {
+            kind: kind.to_string(),
+            start: warning.span().start,
+            end: warning.span().end,
+            message: warning.to_string(),
+        }
+    }
+}
+
+impl Regex {
+    /// `text` に対するマッチ, キャプチャグループ, マッチしなかった理由, パターンの警告をまとめて返す
+    pub fn report(&self, text: &str) -> MatchReport {
+        let names: Vec<Option<&str>> = self.capture_names().collect();
+        let matches: Vec<ReportMatch> = self
+            .find_iter(text)
+            .map(|found| {
+                let caps = self.captures_at(found);
+                ReportMatch {
+                    start: found.start(),
+                    end: found.end(),
+                    text: found.as_str().to_string(),
+                    groups: (1..caps.len())
+                        .map(|index| ReportGroup {
+                            index,
+                            name: names.get(index).copied().flatten().map(str::to_string),
+                            span: caps.get(index).map(ReportSpan::from),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        let failure: Option<ReportFailure> =
+            self.explain_failure(text).as_ref().map(ReportFailure::from);
+        // negate などで作ったパターン文字列は構文として読めないことがあるので, その場合は警告なし
+        let warnings: Vec<ReportWarning> = crate::lint(self.as_str())
+            .map(|warnings| warnings.iter().map(ReportWarning::from).collect())
+            .unwrap_or_default();
+        MatchReport {
+            version: REPORT_VERSION,
+            pattern: self.as_str().to_string(),
+            matched: failure.is_none(),
+            matches,
+            failure,
+            warnings,
+        }
+    }
+
+    /// [`Regex::report`] の結果を JSON にする
+    pub fn report_json(&self, text: &str) -> String {
+        serde_json::to_string(&self.report(text)).expect("report is always serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema() {
+        let regex = Regex::new(r"a(b)").unwrap();
+        assert_eq!(
+            regex.report_json("xab"),
+            r#"{"version":1,"pattern":"a(b)","matched":false,"matches":[{"start":1,"end":3,"text":"ab","groups":[{"index":1,"name":null,"span":{"start":2,"end":3,"text":"b"}}]}],"failure":{"offset":0,"found":"x","expected":["a"],"expects_other":false,"expects_end":false},"warnings":[]}"#
+        );
+        assert_eq!(
+            Regex::new(r"").unwrap().report_json(""),
+            r#"{"version":1,"pattern":"","matched":true,"matches":[{"start":0,"end":0,"text":"","groups":[]}],"failure":null,"warnings":[]}"#
+        );
+    }
+
+    #[test]
+    fn report() {
+        let regex = Regex::new(r"(?<family>山田|佐藤)(太|)郎|a|a").unwrap();
+        let report: MatchReport = regex.report("山田郎と佐藤太郎");
+        assert!(!report.matched);
+        assert_eq!(report.failure.as_ref().unwrap().offset, 9);
+        assert_eq!(report.matches.len(), 2);
+        let groups: &[ReportGroup] = &report.matches[1].groups;
+        assert_eq!(groups[0].name.as_deref(), Some("family"));
+        assert_eq!(groups[0].span.as_ref().unwrap().text, "佐藤");
+        assert_eq!(groups[1].span.as_ref().unwrap().text, "太");
+        assert_eq!(report.matches[0].groups[1].span.as_ref().unwrap().text, "");
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].kind, "duplicate_branch");
+
+        let json: String = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<MatchReport>(&json).unwrap(), report);
+    }
+}
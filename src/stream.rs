@@ -0,0 +1,604 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use crate::automaton::{DFA, DFAState};
+use crate::prefilter::ReverseSuffix;
+use crate::{Engine, Match, Regex};
+
+/// 文字のイテレータから一度に読む文字数
+const CHARS_CHUNK: usize = 4096;
+
+/// 読み込み済みの入力で, 最も左にあるマッチを探した結果
+enum Scan {
+    /// マッチの開始位置と終了位置
+    Found(usize, usize),
+    /// この位置より前からはマッチが始まらない. ここから先は続きを読まなければ分からない
+    Pending(usize),
+    /// 入力の終わりまでマッチは無い
+    Done,
+}
+
+/// ある位置から始まる照合の途中経過
+struct Thread {
+    start: usize,
+    /// 受理状態に到達できなくなったら `None`
+    state: Option<DFAState>,
+    /// これまでで最長のマッチの終了位置
+    end: Option<usize>,
+}
+
+/// 入力を少しずつ読みながら, マッチを左から順に確定させる
+///
+/// 各位置から始まる照合を同時に進め, 読み込みをまたいでも状態を保つので, 各文字は1度だけ読む.
+/// 照合は開始位置の順に並べ, 同じ状態にある照合は先に始まったものだけを残す.
+/// マッチを持つ照合より後に始まった照合は最も左のマッチになり得ないので捨てる. そのためマッチを持つのは最後の照合のみ.
+/// 位置はすべて呼び出し元の文字列の先頭からのバイトオフセット.
+struct StreamMatcher<'a> {
+    dfa: Cow<'a, DFA>,
+    /// 受理状態に到達できる状態
+    live: HashSet<DFAState>,
+    suffix: Option<&'a ReverseSuffix>,
+    threads: Vec<Thread>,
+    /// 読み終えた位置
+    pos: usize,
+    /// 入力の終わりで, 接尾辞による確認を済ませたか
+    checked: bool,
+    /// 1文字進める間に現れた状態
+    seen: HashSet<DFAState>,
+}
+
+impl<'a> StreamMatcher<'a> {
+    fn new(regex: &'a Regex) -> Self {
+        let dfa: Cow<'a, DFA> = regex.dfa();
+        let live: HashSet<DFAState> = dfa.distances_to_accept().into_keys().collect();
+        let suffix: Option<&ReverseSuffix> = match regex.engine() {
+            Engine::Literal(_) => None,
+            Engine::BitParallel { suffix, .. } | Engine::Automaton { suffix, .. } => {
+                suffix.as_ref()
+            }
+        };
+        StreamMatcher {
+            dfa,
+            live,
+            suffix,
+            threads: Vec::new(),
+            pos: 0,
+            checked: false,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// `text` の読み終えていない部分を読み, 最も左にあるマッチを探す
+    ///
+    /// 前回の呼び出しから `text` は末尾に追加されたか, [`StreamMatcher::shift`] で先頭を捨てたものであること.
+    fn scan(&mut self, text: &str, eof: bool) -> Scan {
+        if eof && !std::mem::replace(&mut self.checked, true) {
+            // 保留している照合も含めて, 残りにマッチが無いと確定すれば読まずに済む
+            let from: usize = self.threads.first().map_or(self.pos, |thread| thread.start);
+            if self
+                .suffix
+                .is_some_and(|suffix| suffix.rules_out(&text[from..]))
+            {
+                self.threads.clear();
+                return Scan::Done;
+            }
+        }
+        let pos: usize = self.pos;
+        for (i, chara) in text[pos..].char_indices() {
+            self.spawn(pos + i);
+            if let Some(found) = self.found() {
+                return found;
+            }
+            self.step(chara, pos + i + chara.len_utf8());
+            self.pos = pos + i + chara.len_utf8();
+        }
+        if let Some(found) = self.found() {
+            return found;
+        }
+        if !eof {
+            let start: usize = self.threads.first().map_or(self.pos, |thread| thread.start);
+            return Scan::Pending(start);
+        }
+        self.spawn(self.pos);
+        match self.threads.last() {
+            Some(Thread {
+                start,
+                end: Some(end),
+                ..
+            }) => Scan::Found(*start, *end),
+            _ => Scan::Done,
+        }
+    }
+
+    /// `at` から始まる照合を加える
+    fn spawn(&mut self, at: usize) {
+        let start: DFAState = self.dfa.start;
+        if !self.live.contains(&start)
+            || self
+                .threads
+                .iter()
+                .any(|thread| thread.end.is_some() || thread.state == Some(start))
+        {
+            return;
+        }
+        self.threads.push(Thread {
+            start: at,
+            state: Some(start),
+            end: self.dfa.is_accept(start).then_some(at),
+        });
+    }
+
+    /// 最も先に始まった照合が終わり, マッチが確定していれば返す
+    fn found(&self) -> Option<Scan> {
+        match self.threads.first()? {
+            Thread {
+                start,
+                state: None,
+                end: Some(end),
+            } => Some(Scan::Found(*start, *end)),
+            _ => None,
+        }
+    }
+
+    /// 全ての照合を1文字進める. `end` はその文字の終了位置
+    fn step(&mut self, chara: char, end: usize) {
+        let (dfa, live, seen) = (&self.dfa, &self.live, &mut self.seen);
+        seen.clear();
+        let mut pruned: bool = false;
+        self.threads.retain_mut(|thread| {
+            if pruned {
+                return false;
+            }
+            let Some(state) = thread.state else {
+                return true;
+            };
+            let next: Option<DFAState> = dfa
+                .next_state(state, chara)
+                .filter(|next| live.contains(next));
+            match next {
+                Some(next) if seen.insert(next) || thread.end.is_some() => {
+                    thread.state = Some(next);
+                    if dfa.is_accept(next) {
+                        thread.end = Some(end);
+                    }
+                }
+                Some(_) => return false,
+                None => thread.state = None,
+            }
+            pruned = thread.end.is_some();
+            pruned || thread.state.is_some()
+        });
+    }
+
+    /// 呼び出し元が文字列の先頭 `len` バイトを捨てたので, 位置をずらす
+    fn shift(&mut self, len: usize) {
+        self.pos -= len;
+        for thread in &mut self.threads {
+            thread.start -= len;
+            thread.end = thread.end.map(|end| end - len);
+        }
+    }
+
+    /// マッチを返した後, `at` から探し直す
+    fn restart(&mut self, at: usize) {
+        self.threads.clear();
+        self.pos = at;
+    }
+}
+
+impl Regex {
+    /// `reader` から読んだ文字列中の重ならないマッチを全て `replacement` で置き換え, `writer` に書き出す
+    ///
+    /// 結果は [`Regex::replace_all`] と同じで, 置き換えた数を返す. 入力は少しずつ読み, マッチしないと確定した部分から書き出すので,
+    /// 保持するのは終わりが確定していないマッチ (またはその候補) の部分だけになる. 入力が UTF-8 でなければ
+    /// [`io::ErrorKind::InvalidData`] のエラーを返す.
+    pub fn replace_all_stream(
+        &self,
+        mut reader: impl Read,
+        mut writer: impl Write,
+        replacement: &str,
+    ) -> io::Result<usize> {
+        let mut matcher: StreamMatcher<'_> = StreamMatcher::new(self);
+        // 読んだ入力. 先頭 `written` バイトは書き出し済み. UTF-8 として不完全な末尾は `bytes` に残す
+        let mut text: String = String::new();
+        let mut written: usize = 0;
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut eof: bool = false;
+        let mut replaced: usize = 0;
+        let mut dst: String = String::new();
+        loop {
+            match matcher.scan(&text, eof) {
+                Scan::Found(start, end) => {
+                    dst.clear();
+                    dst.push_str(&text[written..start]);
+                    let found: Match<'_> = Match {
+                        haystack: &text,
+                        start,
+                        end,
+                    };
+                    self.captures_at(found).expand(replacement, &mut dst);
+                    // 空文字列にマッチした場合は, 次の1文字をそのまま書き出してから探索を続ける
+                    let next: usize = match text[end..].chars().next() {
+                        Some(chara) if start == end => {
+                            dst.push(chara);
+                            end + chara.len_utf8()
+                        }
+                        _ => end,
+                    };
+                    writer.write_all(dst.as_bytes())?;
+                    written = next;
+                    matcher.restart(next);
+                    replaced += 1;
+                    // 入力の末尾での空文字列のマッチが最後のマッチになる
+                    if start == end && next == end {
+                        break;
+                    }
+                }
+                Scan::Pending(start) => {
+                    writer.write_all(&text.as_bytes()[written..start])?;
+                    text.drain(..start);
+                    matcher.shift(start);
+                    written = 0;
+                    eof = fill(&mut reader, &mut bytes, &mut text)?;
+                }
+                Scan::Done => {
+                    writer.write_all(&text.as_bytes()[written..])?;
+                    break;
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(replaced)
+    }
+}
+
+/// [`Regex::find_iter_chars`] が返すマッチ. 入力を保持しないので, マッチした部分文字列を持つ
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharMatch {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+impl CharMatch {
+    /// 開始位置 (入力の先頭からの文字数)
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// 終了位置 (入力の先頭からの文字数)
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// 開始位置から終了位置までの範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+}
+
+/// 文字のイテレータから読みながら, 重ならないマッチを左から順に返すイテレータ
+pub struct CharMatches<'r, I> {
+    matcher: StreamMatcher<'r>,
+    chars: I,
+    /// 読んだが, まだマッチしないと確定していない部分. 先頭 `consumed` バイトは確定済み
+    text: String,
+    consumed: usize,
+    /// `text` の `consumed` の位置の, 入力の先頭からの文字数
+    offset: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<I: Iterator<Item = char>> CharMatches<'_, I> {
+    /// `text` の `consumed` から `to` までを確定済みにする
+    fn consume(&mut self, to: usize) {
+        self.offset += self.text[self.consumed..to].chars().count();
+        self.consumed = to;
+    }
+
+    fn fill(&mut self) {
+        self.text.drain(..self.consumed);
+        self.matcher.shift(self.consumed);
+        self.consumed = 0;
+        let mut read: usize = 0;
+        self.text
+            .extend(self.chars.by_ref().take(CHARS_CHUNK).inspect(|_| read += 1));
+        self.eof = read < CHARS_CHUNK;
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for CharMatches<'_, I> {
+    type Item = CharMatch;
+
+    fn next(&mut self) -> Option<CharMatch> {
+        while !self.done {
+            match self.matcher.scan(&self.text, self.eof) {
+                Scan::Found(start, end) => {
+                    self.consume(start);
+                    let begin: usize = self.offset;
+                    self.consume(end);
+                    let found: CharMatch = CharMatch {
+                        start: begin,
+                        end: self.offset,
+                        text: self.text[start..end].to_string(),
+                    };
+                    // 空文字列にマッチした場合は, 次の1文字を飛ばしてから探索を続ける
+                    let next: usize = match self.text[end..].chars().next() {
+                        Some(chara) if start == end => end + chara.len_utf8(),
+                        _ => end,
+                    };
+                    self.consume(next);
+                    self.matcher.restart(next);
+                    // 入力の末尾での空文字列のマッチが最後のマッチになる
+                    self.done = start == end && next == end;
+                    return Some(found);
+                }
+                Scan::Pending(start) => {
+                    self.consume(start);
+                    self.fill();
+                }
+                Scan::Done => self.done = true,
+            }
+        }
+        None
+    }
+}
+
+impl Regex {
+    /// `chars` 全体がマッチするか. [`Regex::matches`] と同じ
+    pub fn matches_chars(&self, chars: impl IntoIterator<Item = char>) -> bool {
+        let dfa: Cow<'_, DFA> = self.dfa();
+        let mut state: DFAState = dfa.start;
+        for chara in chars {
+            match dfa.next_state(state, chara) {
+                Some(next) => state = next,
+                None => return false,
+            }
+        }
+        dfa.is_accept(state)
+    }
+
+    /// `chars` の一部がマッチするか. [`Regex::is_match`] と同じ
+    ///
+    /// マッチが見つかった時点で読むのをやめる. 各位置から始まる照合のDFAの状態を, 重複を除いて同時に進める.
+    pub fn is_match_chars(&self, chars: impl IntoIterator<Item = char>) -> bool {
+        let dfa: Cow<'_, DFA> = self.dfa();
+        if dfa.is_accept(dfa.start) {
+            return true;
+        }
+        let mut states: Vec<DFAState> = Vec::new();
+        let mut next_states: Vec<DFAState> = Vec::new();
+        for chara in chars {
+            next_states.clear();
+            for state in states.iter().copied().chain([dfa.start]) {
+                let Some(next) = dfa.next_state(state, chara) else {
+                    continue;
+                };
+                if dfa.is_accept(next) {
+                    return true;
+                }
+                if !next_states.contains(&next) {
+                    next_states.push(next);
+                }
+            }
+            std::mem::swap(&mut states, &mut next_states);
+        }
+        false
+    }
+
+    /// `chars` 中の重ならないマッチを左から順に返す. [`Regex::find_iter`] と同じマッチを, 位置を文字数で表して返す
+    ///
+    /// 文字列に集めずに少しずつ読み, 保持するのは終わりが確定していないマッチ (またはその候補) の部分だけになる.
+    /// `encoding_rs` のデコーダなどで変換しながら読む入力に使う.
+    pub fn find_iter_chars<I>(&self, chars: I) -> CharMatches<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = char>,
+    {
+        CharMatches {
+            matcher: StreamMatcher::new(self),
+            chars: chars.into_iter(),
+            text: String::new(),
+            consumed: 0,
+            offset: 0,
+            eof: false,
+            done: false,
+        }
+    }
+}
+
+/// `reader` から読んだ分を UTF-8 として `text` に追加する. 入力の終わりに達したら `true`
+fn fill(reader: &mut impl Read, bytes: &mut Vec<u8>, text: &mut String) -> io::Result<bool> {
+    let mut chunk: [u8; 8192] = [0; 8192];
+    let read: usize = loop {
+        match reader.read(&mut chunk) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            result => break result?,
+        }
+    };
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    };
+    if read == 0 {
+        return if bytes.is_empty() {
+            Ok(true)
+        } else {
+            Err(invalid())
+        };
+    }
+    bytes.extend_from_slice(&chunk[..read]);
+    let valid: usize = match std::str::from_utf8(bytes) {
+        Ok(valid) => valid.len(),
+        // 末尾の文字が途中で切れているだけなら, 続きを読んでから追加する
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => return Err(invalid()),
+    };
+    text.push_str(std::str::from_utf8(&bytes[..valid]).unwrap());
+    bytes.drain(..valid);
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1回の読み込みで `step` バイトずつしか返さない
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len: usize = self.step.min(self.data.len()).min(buf.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    fn replace(regex: &Regex, text: &str, replacement: &str, step: usize) -> (String, usize) {
+        let mut out: Vec<u8> = Vec::new();
+        let reader = Trickle {
+            data: text.as_bytes(),
+            step,
+        };
+        let count: usize = regex
+            .replace_all_stream(reader, &mut out, replacement)
+            .unwrap();
+        (String::from_utf8(out).unwrap(), count)
+    }
+
+    #[test]
+    fn replace_all_stream() {
+        let cases: [(&str, &str, &str); 8] = [
+            (
+                r"(山田|佐藤)(太|一)郎",
+                "山田太郎と佐藤一郎と山田次郎",
+                "$2$1",
+            ),
+            (r"a|a*b", "aaaxaab", "<$0>"),
+            (r"(a|b)*abb|b", "babbxbab", "<$0>"),
+            (r"abc|b", "xabcbab", "<$0>"),
+            (r"a*", "baac", "-"),
+            (r"(a|b)*c", "ababababx ababc", "[$1]"),
+            (r"x", "", "y"),
+            (r"a", "bbb", "y"),
+        ];
+        for (pattern, text, replacement) in cases {
+            let regex = Regex::new(pattern).unwrap();
+            let expected: String = regex.replace_all(text, replacement).into_owned();
+            let count: usize = regex.find_iter(text).count();
+            for step in [1, 2, 5, 8192] {
+                assert_eq!(
+                    replace(&regex, text, replacement, step),
+                    (expected.clone(), count),
+                    "{:?} {:?} {}",
+                    pattern,
+                    text,
+                    step
+                );
+            }
+        }
+        let negated = Regex::new(r"ab").unwrap().negate();
+        assert_eq!(
+            replace(&negated, "xabx", "-", 1).0,
+            negated.replace_all("xabx", "-")
+        );
+    }
+
+    #[test]
+    fn scaling() {
+        // 終わりの確定しない候補が続いても, 読み込みのたびに先頭から読み直さない
+        let long: String = "a".repeat(100_000);
+        let regex = Regex::new(r"a*b").unwrap();
+        assert_eq!(replace(&regex, &long, "-", 16), (long.clone(), 0));
+        assert_eq!(regex.find_iter_chars(long.chars()).count(), 0);
+        let text: String = format!("{}b", long);
+        assert_eq!(replace(&regex, &text, "-", 16), ("-".to_string(), 1));
+        // マッチが多くても, マッチごとに保持している入力を詰め直さない
+        let regex = Regex::new(r"a").unwrap();
+        assert_eq!(
+            replace(&regex, &long, "", 8192),
+            (String::new(), long.len())
+        );
+        let found: Vec<CharMatch> = regex.find_iter_chars(long.chars()).collect();
+        assert_eq!(found.len(), long.len());
+        assert_eq!(found.last().unwrap().range(), long.len() - 1..long.len());
+    }
+
+    #[test]
+    fn find_iter_chars() {
+        let long: String = format!("x{}c", "ab".repeat(3000));
+        let cases: [(&str, &str); 5] = [
+            (r"(山田|佐藤)(太|一)郎", "山田太郎と佐藤一郎と山田次郎"),
+            (r"abc|b", "xabcbab"),
+            (r"a*", "baac"),
+            (r"x", ""),
+            // 一度に読む文字数を越えるマッチ
+            (r"(a|b)*c", &long),
+        ];
+        for (pattern, text) in cases {
+            let regex = Regex::new(pattern).unwrap();
+            let expected: Vec<(usize, usize, &str)> = regex
+                .find_iter(text)
+                .map(|found| {
+                    let start: usize = text[..found.start()].chars().count();
+                    (
+                        start,
+                        start + found.as_str().chars().count(),
+                        found.as_str(),
+                    )
+                })
+                .collect();
+            let found: Vec<CharMatch> = regex.find_iter_chars(text.chars()).collect();
+            let found: Vec<(usize, usize, &str)> = found
+                .iter()
+                .map(|found| (found.start(), found.end(), found.as_str()))
+                .collect();
+            assert_eq!(found, expected, "{:?}", pattern);
+        }
+        // 入力を全て読まずに, 見つかった分だけ返す
+        let regex = Regex::new(r"ab").unwrap();
+        let endless = std::iter::repeat(['a', 'b', 'c']).flatten();
+        let found: Vec<CharMatch> = regex.find_iter_chars(endless).take(2).collect();
+        assert_eq!(found[1].range(), 3..5);
+    }
+
+    #[test]
+    fn is_match_chars() {
+        let regex = Regex::new(r"(a|b)*c").unwrap();
+        for text in ["xxababcx", "c", "ab", "", "山c"] {
+            assert_eq!(regex.is_match_chars(text.chars()), regex.is_match(text));
+            assert_eq!(regex.matches_chars(text.chars()), regex.matches(text));
+        }
+        let endless = "xxac".chars().chain(std::iter::repeat('x'));
+        assert!(regex.is_match_chars(endless));
+        assert!(Regex::new(r"a*").unwrap().is_match_chars("b".chars()));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let regex = Regex::new(r"a").unwrap();
+        for data in [&b"a\xffa"[..], &b"a\xe3\x81"[..]] {
+            let reader = Trickle { data, step: 1 };
+            let err = regex
+                .replace_all_stream(reader, io::sink(), "b")
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
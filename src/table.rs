@@ -0,0 +1,129 @@
+use crate::Regex;
+use crate::automaton::{DFA, DFAState};
+use crate::hash::HashMap;
+
+impl Regex {
+    /// 最小化したDFAの遷移表を CSV にする. 列は `state,symbol,target,accept`
+    ///
+    /// 状態番号は開始状態を 0 として, 遷移を文字順に辿った幅優先探索の順に付け直すので, 同じ形のDFAなら同じ表になる.
+    /// `symbol` は1文字, 同じ遷移先への連続した文字の範囲 (`a-z`), または遷移表に無い文字を表す `other`.
+    /// 遷移の無い状態は `symbol` と `target` を空にした1行になる.
+    pub fn to_csv(&self) -> String {
+        write_table(&self.dfa().minimize(), ',')
+    }
+
+    /// [`Regex::to_csv`] と同じ表をタブ区切りにする
+    pub fn to_tsv(&self) -> String {
+        write_table(&self.dfa().minimize(), '\t')
+    }
+}
+
+fn write_table(dfa: &DFA, delimiter: char) -> String {
+    let numbering: HashMap<DFAState, u32> = dfa.canonical_numbering();
+    let mut states: Vec<(u32, DFAState)> = numbering.iter().map(|(s, n)| (*n, *s)).collect();
+    states.sort();
+    let mut table: String = String::new();
+    let mut write_row = |row: [&str; 4]| {
+        let fields: Vec<String> = row.iter().map(|field| quote(field, delimiter)).collect();
+        table.push_str(&fields.join(&delimiter.to_string()));
+        table.push('\n');
+    };
+    write_row(["state", "symbol", "target", "accept"]);
+    for (number, state) in states {
+        let accept: &str = if dfa.is_accept(state) {
+            "true"
+        } else {
+            "false"
+        };
+        let mut rows: Vec<(String, u32)> = Vec::new();
+        // 同じ遷移先への, 連続した文字の範囲 (始点, 終点, 遷移先)
+        let mut ranges: Vec<(char, char, u32)> = Vec::new();
+        for (chara, next) in dfa.edges(state) {
+            if !dfa.transition.contains_key(&(state, chara)) {
+                // 遷移表に無い文字による遷移 (`edges` の最後)
+                rows.extend(ranges.drain(..).map(|range| (symbol(range), range.2)));
+                rows.push(("other".to_string(), numbering[&next]));
+                continue;
+            }
+            let target: u32 = numbering[&next];
+            match ranges.last_mut() {
+                Some((_, end, to))
+                    if *to == target && char::from_u32(*end as u32 + 1) == Some(chara) =>
+                {
+                    *end = chara;
+                }
+                _ => ranges.push((chara, chara, target)),
+            }
+        }
+        rows.extend(ranges.drain(..).map(|range| (symbol(range), range.2)));
+        let number: String = number.to_string();
+        if rows.is_empty() {
+            write_row([&number, "", "", accept]);
+        }
+        for (symbol, target) in rows {
+            write_row([&number, &symbol, &target.to_string(), accept]);
+        }
+    }
+    table
+}
+
+/// 範囲 (始点, 終点, 遷移先) の `symbol` 列の表記. 制御文字や空白は `\n` のようにエスケープする
+fn symbol((start, end, _): (char, char, u32)) -> String {
+    let escape = |chara: char| {
+        if chara.is_control() || chara.is_whitespace() || chara == '\\' {
+            chara.escape_default().to_string()
+        } else {
+            chara.to_string()
+        }
+    };
+    if start == end {
+        escape(start)
+    } else {
+        format!("{}-{}", escape(start), escape(end))
+    }
+}
+
+/// 区切り文字や `"` を含むフィールドを `"` で囲む (RFC 4180)
+fn quote(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_csv() {
+        let regex = Regex::new("(a|b|c|x)(,|\t)*").unwrap();
+        assert_eq!(
+            regex.to_csv(),
+            "state,symbol,target,accept\n\
+             0,a-c,1,false\n\
+             0,x,1,false\n\
+             1,\\t,1,true\n\
+             1,\",\",1,true\n"
+        );
+        assert_eq!(regex.to_tsv().lines().nth(3), Some("1\t\\t\t1\ttrue"));
+    }
+
+    #[test]
+    fn other_and_terminal() {
+        let regex = Regex::new(r"a").unwrap().negate();
+        let csv: String = regex.to_csv();
+        assert!(csv.contains(",other,"), "{}", csv);
+        assert_eq!(
+            Regex::new(r"a").unwrap().to_csv().lines().last(),
+            Some("1,,,true")
+        );
+        // 状態の内部的な番号によらず, 同じ形のDFAなら同じ表になる
+        let pattern: String = format!("({})*b", "a|".repeat(65));
+        assert_eq!(
+            Regex::new(&pattern).unwrap().to_csv(),
+            Regex::new(&pattern).unwrap().to_csv()
+        );
+    }
+}
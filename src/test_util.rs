@@ -0,0 +1,186 @@
+//! 性質ベースのテストのための道具
+//!
+//! ランダムな構文木 ([`Ast`]) とそのパターン文字列の生成器, および NFA を直接シミュレートする参照実装 ([`ReferenceMatcher`]).
+//! 生成器は proptest の [`Strategy`] として使えるほか, [`Ast::generate`] に乱数を渡せば quickcheck などからも使える.
+
+use std::collections::HashSet;
+use std::error::Error;
+
+use proptest::prelude::*;
+
+use crate::automaton::{NFA, NFAState};
+use crate::lexer::Lexer;
+use crate::parser::{Node, Parser};
+
+/// パターンの構文木
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Ast {
+    Char(char),
+    Empty,
+    Star(Box<Ast>),
+    Union(Box<Ast>, Box<Ast>),
+    Concat(Box<Ast>, Box<Ast>),
+}
+
+impl Ast {
+    /// `choose(n)` で `0..n` の値を選びながら, 深さ `depth` 以下の構文木を作る
+    ///
+    /// `alphabet` は空であってはならない.
+    pub fn generate(alphabet: &[char], depth: u32, choose: &mut impl FnMut(usize) -> usize) -> Ast {
+        let kinds: usize = if depth == 0 { 2 } else { 5 };
+        match choose(kinds) {
+            0 => Ast::Char(alphabet[choose(alphabet.len())]),
+            1 => Ast::Empty,
+            2 => Ast::Star(Box::new(Ast::generate(alphabet, depth - 1, choose))),
+            3 => Ast::Union(
+                Box::new(Ast::generate(alphabet, depth - 1, choose)),
+                Box::new(Ast::generate(alphabet, depth - 1, choose)),
+            ),
+            _ => Ast::Concat(
+                Box::new(Ast::generate(alphabet, depth - 1, choose)),
+                Box::new(Ast::generate(alphabet, depth - 1, choose)),
+            ),
+        }
+    }
+
+    /// パターン文字列として書き出す. メタ文字はエスケープする
+    pub fn to_pattern(&self) -> String {
+        self.to_string()
+    }
+
+    fn to_node(&self) -> Node {
+        match self {
+            Ast::Char(chara) => Node::character(*chara),
+            Ast::Empty => Node::empty(),
+            Ast::Star(ast) => ast.to_node().star(),
+            Ast::Union(a1, a2) => a1.to_node().union(a2.to_node()),
+            Ast::Concat(a1, a2) => a1.to_node().concat(a2.to_node()),
+        }
+    }
+}
+
+impl std::fmt::Display for Ast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_node())
+    }
+}
+
+/// `alphabet` の文字からなる, 深さ `depth` 以下の構文木を生成する
+pub fn ast(alphabet: Vec<char>, depth: u32) -> impl Strategy<Value = Ast> {
+    let leaf = prop_oneof![
+        proptest::sample::select(alphabet).prop_map(Ast::Char),
+        Just(Ast::Empty),
+    ];
+    leaf.prop_recursive(depth, 1 << depth, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|ast| Ast::Star(Box::new(ast))),
+            (inner.clone(), inner.clone())
+                .prop_map(|(a1, a2)| Ast::Union(Box::new(a1), Box::new(a2))),
+            (inner.clone(), inner).prop_map(|(a1, a2)| Ast::Concat(Box::new(a1), Box::new(a2))),
+        ]
+    })
+}
+
+/// [`ast`] で生成した構文木を書き出したパターン文字列を生成する
+pub fn pattern(alphabet: Vec<char>, depth: u32) -> impl Strategy<Value = String> {
+    ast(alphabet, depth).prop_map(|ast| ast.to_pattern())
+}
+
+/// 決定化を行わず, Thompson NFA の状態集合を1文字ずつ更新して照合する参照実装
+///
+/// [`Regex`](crate::Regex) と同じ意味 (`matches` は全体一致, `find` は最左最長) で照合するが, 遅い.
+pub struct ReferenceMatcher {
+    nfa: NFA,
+}
+
+impl ReferenceMatcher {
+    pub fn new(pattern: &str) -> Result<Self, Box<dyn Error>> {
+        let node: Node = Parser::new(Lexer::new(pattern)).parse()?;
+        Ok(ReferenceMatcher {
+            nfa: NFA::from_node(node),
+        })
+    }
+
+    /// `text` 全体にマッチするか
+    pub fn matches(&self, text: &str) -> bool {
+        self.longest_match_at(text, 0) == Some(text.len())
+    }
+
+    /// `text` 中で最も左にあるマッチ (同じ位置からのマッチは最長のもの) の開始位置と終了位置
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let starts = text.char_indices().map(|(i, _)| i).chain([text.len()]);
+        starts
+            .filter_map(|start| Some((start, self.longest_match_at(text, start)?)))
+            .next()
+    }
+
+    fn longest_match_at(&self, text: &str, start: usize) -> Option<usize> {
+        let mut current: HashSet<NFAState> = self.closure([self.nfa.start].into());
+        let mut longest: Option<usize> = None;
+        for (i, chara) in text[start..].char_indices() {
+            if current.iter().any(|s| self.nfa.accepts.contains(s)) {
+                longest = Some(start + i);
+            }
+            let moved: HashSet<NFAState> = current
+                .iter()
+                .flat_map(|s| self.nfa.next_states(*s, Some(chara)))
+                .collect();
+            if moved.is_empty() {
+                return longest;
+            }
+            current = self.closure(moved);
+        }
+        if current.iter().any(|s| self.nfa.accepts.contains(s)) {
+            longest = Some(text.len());
+        }
+        longest
+    }
+
+    /// 空遷移で到達できる状態をすべて加える
+    fn closure(&self, mut states: HashSet<NFAState>) -> HashSet<NFAState> {
+        let mut stack: Vec<NFAState> = states.iter().cloned().collect();
+        while let Some(state) = stack.pop() {
+            for next in self.nfa.next_states(state, None) {
+                if states.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Regex;
+
+    proptest! {
+        #[test]
+        fn agrees_with_regex(
+            ast in ast(vec!['a', 'b', '*', '山'], 4),
+            text in "[ab*山]{0,8}",
+        ) {
+            let pattern: String = ast.to_pattern();
+            let regex = Regex::new(&pattern).unwrap();
+            let reference = ReferenceMatcher::new(&pattern).unwrap();
+            prop_assert_eq!(regex.matches(&text), reference.matches(&text));
+            prop_assert_eq!(regex.find(&text).map(|m| (m.start(), m.end())), reference.find(&text));
+        }
+    }
+
+    #[test]
+    fn generate() {
+        let mut state: usize = 0;
+        let mut choose = |n: usize| {
+            state = state.wrapping_mul(31).wrapping_add(7);
+            state % n
+        };
+        for _ in 0..100 {
+            let ast = Ast::generate(&['a', '|'], 3, &mut choose);
+            assert!(Regex::new(&ast.to_pattern()).is_ok());
+        }
+        let ast = Ast::Concat(Box::new(Ast::Char('|')), Box::new(Ast::Empty));
+        assert_eq!(ast.to_pattern(), r"\|()");
+    }
+}
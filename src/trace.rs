@@ -0,0 +1,342 @@
+use std::collections::BTreeSet;
+
+use crate::automaton::{DFA, DFAState, NFA, NFAState};
+use crate::hash::HashMap;
+
+/// [`Regex::trace`](crate::Regex::trace) が返す, DFAの1回の遷移
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    offset: usize,
+    chara: char,
+    from: u32,
+    to: Option<u32>,
+}
+
+impl Step {
+    /// 読んだ文字の位置 (バイトオフセット)
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 読んだ文字
+    pub fn char(&self) -> char {
+        self.chara
+    }
+
+    /// 遷移元の状態番号
+    pub fn from(&self) -> u32 {
+        self.from
+    }
+
+    /// 遷移先の状態番号. 遷移できなかった場合は `None`
+    pub fn to(&self) -> Option<u32> {
+        self.to
+    }
+}
+
+/// `text` をDFAで先頭から読み, 行った遷移を順に返す. 遷移できなくなった時点で終わる
+pub(crate) fn trace(dfa: &DFA, text: &str) -> Vec<Step> {
+    let mut steps: Vec<Step> = Vec::new();
+    let mut state: DFAState = dfa.start;
+    for (offset, chara) in text.char_indices() {
+        let next: Option<DFAState> = dfa.next_state(state, chara);
+        steps.push(Step {
+            offset,
+            chara,
+            from: state.0,
+            to: next.map(|s| s.0),
+        });
+        match next {
+            Some(next) => state = next,
+            None => break,
+        }
+    }
+    steps
+}
+
+/// [`Regex::find_with_callout`](crate::Regex::find_with_callout) のコールバックに渡される, 照合中の1つの時点
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Callout {
+    start: usize,
+    offset: usize,
+    state: u32,
+    accept: bool,
+}
+
+impl Callout {
+    /// 試している開始位置 (バイトオフセット)
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// ここまで読んだ位置 (バイトオフセット). 開始直後は `start` と同じ
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// DFAの現在の状態番号. [`Regex::trace`](crate::Regex::trace) と同じ番号を使う
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    /// `start..offset` がマッチするか
+    pub fn is_accept(&self) -> bool {
+        self.accept
+    }
+}
+
+/// 開始位置を左から1つずつ試し, 各開始位置での最長のマッチの範囲を返す.
+/// `callout` が `false` を返した時点で, その開始位置ではそれより先を読まず, その時点で終わるマッチも採らない
+pub(crate) fn find_with_callout(
+    dfa: &DFA,
+    text: &str,
+    mut callout: impl FnMut(&Callout) -> bool,
+) -> Option<(usize, usize)> {
+    let starts = text.char_indices().map(|(i, _)| i).chain([text.len()]);
+    for start in starts {
+        let mut state: DFAState = dfa.start;
+        let mut offset: usize = start;
+        let mut end: Option<usize> = None;
+        loop {
+            let event: Callout = Callout {
+                start,
+                offset,
+                state: state.0,
+                accept: dfa.is_accept(state),
+            };
+            if !callout(&event) {
+                break;
+            }
+            if event.accept {
+                end = Some(offset);
+            }
+            let Some(chara) = text[offset..].chars().next() else {
+                break;
+            };
+            let Some(next) = dfa.next_state(state, chara) else {
+                break;
+            };
+            state = next;
+            offset += chara.len_utf8();
+        }
+        if let Some(end) = end {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+/// 部分集合構成法の途中経過.
+/// [`RegexBuilder::determinization_trace`](crate::RegexBuilder::determinization_trace) が返す
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeterminizationTrace {
+    start: u32,
+    subsets: Vec<Subset>,
+    transitions: Vec<SubsetTransition>,
+}
+
+/// DFAの1つの状態と, それに対応するNFAの状態集合
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subset {
+    id: u32,
+    nfa_states: Vec<u32>,
+    accept: bool,
+}
+
+/// DFAの1つの遷移と, 空遷移で閉じる前のNFAの状態集合
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubsetTransition {
+    from: u32,
+    chara: char,
+    moved: Vec<u32>,
+    to: u32,
+}
+
+impl DeterminizationTrace {
+    /// DFAの開始状態の番号
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// 作られたDFAの状態 (番号順)
+    pub fn subsets(&self) -> &[Subset] {
+        &self.subsets
+    }
+
+    /// 作られたDFAの遷移 (遷移元, 文字の順)
+    pub fn transitions(&self) -> &[SubsetTransition] {
+        &self.transitions
+    }
+}
+
+impl Subset {
+    /// DFAの状態番号
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// 対応するNFAの状態集合 (空遷移で閉じたもの, 昇順)
+    pub fn nfa_states(&self) -> &[u32] {
+        &self.nfa_states
+    }
+
+    /// 受理状態か
+    pub fn is_accept(&self) -> bool {
+        self.accept
+    }
+}
+
+impl SubsetTransition {
+    /// 遷移元のDFAの状態番号
+    pub fn from(&self) -> u32 {
+        self.from
+    }
+
+    /// 読む文字
+    pub fn char(&self) -> char {
+        self.chara
+    }
+
+    /// 遷移元のNFAの状態から `char` で直接移れる状態の集合 (空遷移で閉じる前, 昇順)
+    pub fn moved(&self) -> &[u32] {
+        &self.moved
+    }
+
+    /// 遷移先のDFAの状態番号
+    pub fn to(&self) -> u32 {
+        self.to
+    }
+}
+
+/// 部分集合構成法を実行し, 途中経過を記録する
+pub(crate) fn determinize(nfa: &NFA) -> DeterminizationTrace {
+    let (dfa, statemap) = DFA::determinize(nfa);
+    let sets: HashMap<DFAState, &Vec<NFAState>> = statemap.iter().map(|(k, v)| (*v, k)).collect();
+
+    let mut subsets: Vec<Subset> = sets
+        .iter()
+        .map(|(id, nfa_states)| {
+            let mut nfa_states: Vec<u32> = nfa_states.iter().map(|s| s.0).collect();
+            nfa_states.sort();
+            Subset {
+                id: id.0,
+                nfa_states,
+                accept: dfa.is_accept(*id),
+            }
+        })
+        .collect();
+    subsets.sort_by_key(|subset| subset.id);
+
+    let mut transitions: Vec<SubsetTransition> = dfa
+        .transition
+        .iter()
+        .map(|((from, chara), to)| {
+            let moved: BTreeSet<u32> = sets[from]
+                .iter()
+                .flat_map(|s| nfa.next_states(*s, Some(*chara)))
+                .map(|s| s.0)
+                .collect();
+            SubsetTransition {
+                from: from.0,
+                chara: *chara,
+                moved: moved.into_iter().collect(),
+                to: to.0,
+            }
+        })
+        .collect();
+    transitions.sort_by_key(|t| (t.from, t.chara));
+
+    DeterminizationTrace {
+        start: dfa.start.0,
+        subsets,
+        transitions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Construction, Regex, RegexBuilder};
+
+    #[test]
+    fn trace() {
+        let regex = Regex::new(r"a(b|c)*").unwrap();
+        let steps = regex.trace("abcx");
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0].from(), regex.start_state());
+        assert_eq!(
+            steps
+                .iter()
+                .map(|s| (s.offset(), s.char()))
+                .collect::<Vec<_>>(),
+            vec![(0, 'a'), (1, 'b'), (2, 'c'), (3, 'x')]
+        );
+        assert!(steps.windows(2).all(|w| w[0].to() == Some(w[1].from())));
+        assert_eq!(steps[3].to(), None);
+    }
+
+    #[test]
+    fn callout() {
+        let regex = Regex::new(r"(a|b)*c").unwrap();
+        let mut events: Vec<(usize, usize)> = Vec::new();
+        let found = regex.find_with_callout("xabc", |callout| {
+            events.push((callout.start(), callout.offset()));
+            true
+        });
+        assert_eq!(found.unwrap().range(), 1..4);
+        assert_eq!(events, [(0, 0), (1, 1), (1, 2), (1, 3), (1, 4)]);
+
+        // 3文字より長いマッチを採らない
+        let regex = Regex::new(r"a*").unwrap();
+        let found = regex.find_with_callout("aaaaa", |callout| callout.offset() <= 3);
+        assert_eq!(found.unwrap().range(), 0..3);
+        // 開始位置で断ると, 次の位置から試す
+        let regex = Regex::new(r"ab|b").unwrap();
+        let found = regex.find_with_callout("abab", |callout| callout.start() != 0);
+        assert_eq!(found.unwrap().range(), 1..2);
+        assert!(regex.find_with_callout("abab", |_| false).is_none());
+    }
+
+    #[test]
+    fn determinization_trace() {
+        // Glushkov構成: 0 --a--> 1 --b--> 2, 1 --c--> 3
+        let trace = RegexBuilder::new(r"a(b|c)")
+            .construction(Construction::Glushkov)
+            .determinization_trace()
+            .unwrap();
+        let start = &trace.subsets()[trace.start() as usize];
+        assert_eq!(start.nfa_states(), [0]);
+        assert!(!start.is_accept());
+        assert_eq!(trace.subsets().len(), 4);
+        assert_eq!(trace.transitions().len(), 3);
+        let after_a = trace
+            .transitions()
+            .iter()
+            .find(|t| t.char() == 'a')
+            .unwrap();
+        assert_eq!(after_a.from(), trace.start());
+        assert_eq!(after_a.moved(), [1]);
+        assert_eq!(trace.subsets()[after_a.to() as usize].nfa_states(), [1]);
+        assert_eq!(trace.subsets().iter().filter(|s| s.is_accept()).count(), 2);
+    }
+
+    #[test]
+    fn determinization_trace_closure() {
+        // Thompson構成では空遷移で閉じた集合が移った先の集合を含む
+        let trace = RegexBuilder::new(r"a*b").determinization_trace().unwrap();
+        for transition in trace.transitions() {
+            let to = &trace.subsets()[transition.to() as usize];
+            assert!(
+                transition
+                    .moved()
+                    .iter()
+                    .all(|s| to.nfa_states().contains(s))
+            );
+        }
+    }
+
+    #[test]
+    fn trace_empty() {
+        let regex = Regex::new(r"a").unwrap();
+        assert!(regex.trace("").is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Regex;
+
+/// [`Validated`] や [`deserialize_matching`] で使うパターン
+pub trait ValidationPattern {
+    /// 文字列全体がマッチすべきパターン. 通常は `OnceLock` などで一度だけコンパイルしたものを返す
+    fn regex() -> &'static Regex;
+}
+
+/// デシリアライズ時に, 文字列全体が `P` のパターンにマッチすることを確かめた文字列
+pub struct Validated<P> {
+    value: String,
+    pattern: PhantomData<fn() -> P>,
+}
+
+impl<P> Validated<P> {
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> String {
+        self.value
+    }
+}
+
+impl<P: ValidationPattern> Validated<P> {
+    /// `value` 全体がパターンにマッチすれば `Validated` にする
+    pub fn new(value: String) -> Result<Self, String> {
+        check::<P>(&value)?;
+        Ok(Validated {
+            value,
+            pattern: PhantomData,
+        })
+    }
+}
+
+impl<P> std::ops::Deref for Validated<P> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.value
+    }
+}
+
+impl<P> Clone for Validated<P> {
+    fn clone(&self) -> Self {
+        Validated {
+            value: self.value.clone(),
+            pattern: PhantomData,
+        }
+    }
+}
+
+impl<P> fmt::Debug for Validated<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.value, f)
+    }
+}
+
+impl<P> PartialEq for Validated<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<P> Eq for Validated<P> {}
+
+impl<P> Serialize for Validated<P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value)
+    }
+}
+
+impl<'de, P: ValidationPattern> Deserialize<'de> for Validated<P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value: String = deserialize_matching::<D, P>(deserializer)?;
+        Ok(Validated {
+            value,
+            pattern: PhantomData,
+        })
+    }
+}
+
+/// `#[serde(deserialize_with = "deserialize_matching::<_, P>")]` で使う. 文字列全体が `P` のパターンにマッチしなければ失敗する
+pub fn deserialize_matching<'de, D, P>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+    P: ValidationPattern,
+{
+    let value: String = String::deserialize(deserializer)?;
+    check::<P>(&value).map_err(serde::de::Error::custom)?;
+    Ok(value)
+}
+
+fn check<P: ValidationPattern>(value: &str) -> Result<(), String> {
+    P::regex()
+        .validate(value)
+        .map_err(|err| format!("{:?}: {}", value, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    struct Digits;
+
+    impl ValidationPattern for Digits {
+        fn regex() -> &'static Regex {
+            static REGEX: OnceLock<Regex> = OnceLock::new();
+            REGEX
+                .get_or_init(|| Regex::new(r"(0|1|2|3|4|5|6|7|8|9)(0|1|2|3|4|5|6|7|8|9)*").unwrap())
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Form {
+        zip: Validated<Digits>,
+        #[serde(deserialize_with = "deserialize_matching::<_, Digits>")]
+        phone: String,
+    }
+
+    #[test]
+    fn deserialize() {
+        let form: Form = serde_json::from_str(r#"{"zip":"1000001","phone":"0312"}"#).unwrap();
+        assert_eq!(form.zip.as_str(), "1000001");
+        assert_eq!(&*form.zip, "1000001");
+        assert_eq!(form.phone, "0312");
+        assert_eq!(
+            serde_json::to_string(&form).unwrap(),
+            r#"{"zip":"1000001","phone":"0312"}"#
+        );
+
+        let err = serde_json::from_str::<Form>(r#"{"zip":"100-0001","phone":"0312"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with(r#""100-0001": text does not match the pattern"#),
+            "{}",
+            err
+        );
+        assert!(err.contains("found '-' at 3"), "{}", err);
+        assert!(serde_json::from_str::<Form>(r#"{"zip":"1","phone":""}"#).is_err());
+    }
+
+    #[test]
+    fn new() {
+        assert!(Validated::<Digits>::new("42".to_string()).is_ok());
+        assert!(Validated::<Digits>::new("4a".to_string()).is_err());
+    }
+}